@@ -591,6 +591,7 @@ impl Chain {
             chain_genesis.height,
             chain_config.save_trie_changes,
         );
+        store.set_save_account_activity(chain_config.save_account_activity);
         let genesis_chunks = genesis_chunks(
             state_roots.clone(),
             epoch_manager.num_shards(&EpochId::default())?,
@@ -33,16 +33,17 @@ use near_primitives::transaction::{
 use near_primitives::trie_key::{trie_key_parsers, TrieKey};
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{
-    BlockExtra, BlockHeight, BlockHeightDelta, EpochId, NumBlocks, ShardId, StateChanges,
-    StateChangesExt, StateChangesForSplitStates, StateChangesKinds, StateChangesKindsExt,
-    StateChangesRequest,
+    AccountActivityKind, AccountId, BlockExtra, BlockHeight, BlockHeightDelta, EpochId, NumBlocks,
+    ShardId, StateChanges, StateChangesExt, StateChangesForSplitStates, StateChangesKinds,
+    StateChangesKindsExt, StateChangesRequest,
 };
 use near_primitives::utils::{
-    get_block_shard_id, get_outcome_id_block_hash, get_outcome_id_block_hash_rev, index_to_bytes,
-    to_timestamp,
+    get_account_activity_entries, get_account_activity_key, get_account_activity_key_rev,
+    get_account_activity_prefix, get_block_shard_id, get_outcome_id_block_hash,
+    get_outcome_id_block_hash_rev, index_to_bytes, to_timestamp,
 };
 use near_primitives::version::ProtocolVersion;
-use near_primitives::views::LightClientBlockView;
+use near_primitives::views::{AccountActivityCursor, AccountActivityView, LightClientBlockView};
 use near_store::{
     DBCol, KeyForStateChanges, ShardTries, Store, StoreUpdate, WrappedTrieChanges, CHUNK_TAIL_KEY,
     FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY,
@@ -456,6 +457,8 @@ pub struct ChainStore {
     /// - archive is true, cold_store is configured and migration to split_storage is finished - node
     /// working in split storage mode needs trie changes in order to do garbage collection on hot.
     save_trie_changes: bool,
+    /// Whether to maintain the account activity index, see `DBCol::AccountActivity`.
+    save_account_activity: bool,
 }
 
 fn option_to_not_found<T, F>(res: io::Result<Option<T>>, field_name: F) -> Result<T, Error>
@@ -498,9 +501,14 @@ impl ChainStore {
             block_ordinal_to_hash: CellLruCache::new(CACHE_SIZE),
             processed_block_heights: CellLruCache::new(CACHE_SIZE),
             save_trie_changes,
+            save_account_activity: false,
         }
     }
 
+    pub fn set_save_account_activity(&mut self, save_account_activity: bool) {
+        self.save_account_activity = save_account_activity;
+    }
+
     pub fn new_read_only_chunks_store(&self) -> ReadOnlyChunksStore {
         ReadOnlyChunksStore::new(self.store.clone())
     }
@@ -793,6 +801,48 @@ impl ChainStore {
         )?)
     }
 
+    /// Returns transactions and receipts which touched the account at heights
+    /// in `[from_height, to_height]`, ordered by height.  If `after` is given,
+    /// only the entries following the cursor are returned.  At most `limit`
+    /// entries are returned.
+    ///
+    /// The index contains entries of blocks on forks until they are garbage
+    /// collected.
+    pub fn get_account_activity(
+        &self,
+        account_id: &AccountId,
+        from_height: BlockHeight,
+        to_height: BlockHeight,
+        after: Option<&AccountActivityCursor>,
+        limit: usize,
+    ) -> Result<Vec<AccountActivityView>, Error> {
+        let prefix = get_account_activity_prefix(account_id);
+        let mut lower_bound = [prefix.as_slice(), &from_height.to_be_bytes()].concat();
+        if let Some(after) = after {
+            // The smallest key greater than the key the cursor points after.
+            let key = [prefix.as_slice(), after.key(), &[0]].concat();
+            lower_bound = lower_bound.max(key);
+        }
+        let upper_bound = match to_height.checked_add(1) {
+            Some(height) => [prefix.as_slice(), &height.to_be_bytes()].concat(),
+            None => {
+                let mut key = prefix;
+                *key.last_mut().unwrap() += 1;
+                key
+            }
+        };
+        self.store
+            .iter_range(DBCol::AccountActivity, Some(&lower_bound[..]), Some(&upper_bound[..]))
+            .take(limit)
+            .map(|item| {
+                let (key, value) = item?;
+                let (block_height, id) = get_account_activity_key_rev(&key)?;
+                let kind = AccountActivityKind::try_from_slice(&value)?;
+                Ok(AccountActivityView { block_height, id, kind })
+            })
+            .collect()
+    }
+
     /// Returns a vector of Outcome ids for given block and shard id
     pub fn get_outcomes_by_block_hash_and_shard_id(
         &self,
@@ -809,6 +859,16 @@ impl ChainStore {
     pub fn get_block_execution_outcomes(
         &self,
         block_hash: &CryptoHash,
+    ) -> Result<HashMap<ShardId, Vec<ExecutionOutcomeWithIdAndProof>>, Error> {
+        self.get_block_execution_outcomes_for_shards(block_hash, None)
+    }
+
+    /// Same as `get_block_execution_outcomes`, but only reads the outcomes of
+    /// the given shards if `shard_ids` is set.
+    pub fn get_block_execution_outcomes_for_shards(
+        &self,
+        block_hash: &CryptoHash,
+        shard_ids: Option<&[ShardId]>,
     ) -> Result<HashMap<ShardId, Vec<ExecutionOutcomeWithIdAndProof>>, Error> {
         let block = self.get_block(block_hash)?;
         let chunk_headers = block.chunks().iter().cloned().collect::<Vec<_>>();
@@ -816,6 +876,9 @@ impl ChainStore {
         let mut res = HashMap::new();
         for chunk_header in chunk_headers {
             let shard_id = chunk_header.shard_id();
+            if shard_ids.map_or(false, |shard_ids| !shard_ids.contains(&shard_id)) {
+                continue;
+            }
             let outcomes = self
                 .get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?
                 .into_iter()
@@ -2461,6 +2524,7 @@ impl<'a> ChainStoreUpdate<'a> {
         }
         self.gc_col(DBCol::BlockRefCount, block_hash.as_bytes());
         self.gc_outcomes(&block)?;
+        self.gc_account_activity(&block, &gc_mode)?;
         match gc_mode {
             GCMode::StateSync { clear_block_info: false } => {}
             _ => self.gc_col(DBCol::BlockInfo, block_hash.as_bytes()),
@@ -2701,6 +2765,69 @@ impl<'a> ChainStoreUpdate<'a> {
         Ok(())
     }
 
+    /// Lists keys and values of the account activity index for transactions
+    /// and receipts included in the new chunks of the block.  Chunks which
+    /// are not available locally are skipped.  Local receipts are found in the
+    /// outcomes of the transactions, which are saved together with the block.
+    fn get_account_activity_index(
+        &self,
+        block: &Block,
+    ) -> Result<Vec<(Vec<u8>, AccountActivityKind)>, Error> {
+        let height = block.header().height();
+        let receipt_id =
+            |outcome: &ExecutionOutcomeWithProof| outcome.outcome.receipt_ids.first().copied();
+        let local_receipt_id = |tx_hash: &CryptoHash| -> io::Result<Option<CryptoHash>> {
+            if let Some(outcome) =
+                self.chain_store_cache_update.outcomes.get(&(*tx_hash, *block.hash()))
+            {
+                return Ok(receipt_id(outcome));
+            }
+            let outcome: Option<ExecutionOutcomeWithProof> = self.chain_store.store().get_ser(
+                DBCol::TransactionResultForBlock,
+                &get_outcome_id_block_hash(tx_hash, block.hash()),
+            )?;
+            Ok(outcome.as_ref().and_then(receipt_id))
+        };
+        let mut index = vec![];
+        for chunk_header in block.chunks().iter().filter(|h| h.height_included() == height) {
+            let chunk = match self.get_chunk(&chunk_header.chunk_hash()) {
+                Ok(chunk) => chunk,
+                Err(_) => continue,
+            };
+            for (account_id, id, kind) in get_account_activity_entries(&chunk, &local_receipt_id)? {
+                index.push((get_account_activity_key(account_id, height, &id), kind));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Removes the account activity index entries of the block.  This is done
+    /// even if the index is disabled, since it might have been enabled when
+    /// the block was processed.
+    pub fn gc_account_activity(&mut self, block: &Block, gc_mode: &GCMode) -> Result<(), Error> {
+        let mut index = self.get_account_activity_index(block)?;
+        // A block on a fork may include the same chunks as the canonical block
+        // at the same height, so their entries have the same keys and must be
+        // kept.
+        if let GCMode::Fork(_) = gc_mode {
+            let canonical_block = self
+                .get_block_hash_by_height(block.header().height())
+                .and_then(|hash| self.get_block(&hash));
+            if let Ok(canonical_block) = canonical_block {
+                let canonical_index: HashSet<_> = self
+                    .get_account_activity_index(&canonical_block)?
+                    .into_iter()
+                    .map(|(key, _)| key)
+                    .collect();
+                index.retain(|(key, _)| !canonical_index.contains(key));
+            }
+        }
+        for (key, _) in index {
+            self.gc_col(DBCol::AccountActivity, &key);
+        }
+        Ok(())
+    }
+
     fn gc_col(&mut self, col: DBCol, key: &[u8]) {
         let mut store_update = self.store().store_update();
         match col {
@@ -2809,6 +2936,9 @@ impl<'a> ChainStoreUpdate<'a> {
             DBCol::HeaderHashesByHeight => {
                 store_update.delete(col, key);
             }
+            DBCol::AccountActivity => {
+                store_update.delete(col, key);
+            }
             DBCol::DbVersion
             | DBCol::BlockMisc
             | DBCol::_GCCount
@@ -3006,6 +3136,11 @@ impl<'a> ChainStoreUpdate<'a> {
                 .block_hash_per_height
                 .insert(block.header().height(), map);
             store_update.insert_ser(DBCol::Block, hash.as_ref(), block)?;
+            if self.chain_store.save_account_activity {
+                for (key, kind) in self.get_account_activity_index(block)? {
+                    store_update.set_ser(DBCol::AccountActivity, &key, &kind)?;
+                }
+            }
         }
         let mut header_hashes_by_height: HashMap<BlockHeight, HashSet<CryptoHash>> = HashMap::new();
         for (hash, header) in self.chain_store_cache_update.headers.iter() {
@@ -3397,17 +3532,25 @@ mod tests {
     use std::sync::Arc;
 
     use near_chain_configs::{GCConfig, GenesisConfig};
+    use near_crypto::{InMemorySigner, KeyType};
     use near_epoch_manager::shard_tracker::ShardTracker;
     use near_epoch_manager::EpochManagerAdapter;
     use near_primitives::block::{Block, Tip};
     use near_primitives::epoch_manager::block_info::BlockInfo;
     use near_primitives::errors::InvalidTxError;
-    use near_primitives::hash::hash;
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::sharding::{
+        ShardChunk, ShardChunkHeader, ShardChunkHeaderV3, ShardChunkV2,
+    };
     use near_primitives::test_utils::create_test_signer;
     use near_primitives::test_utils::TestBlockBuilder;
-    use near_primitives::types::{BlockHeight, EpochId, NumBlocks};
+    use near_primitives::transaction::{
+        ExecutionOutcome, ExecutionOutcomeWithId, SignedTransaction,
+    };
+    use near_primitives::types::{AccountActivityKind, BlockHeight, EpochId, NumBlocks};
     use near_primitives::utils::index_to_bytes;
     use near_primitives::validator_signer::InMemoryValidatorSigner;
+    use near_primitives::views::{AccountActivityCursor, AccountActivityView};
     use near_store::test_utils::create_test_store;
     use near_store::DBCol;
 
@@ -3925,4 +4068,249 @@ mod tests {
             assert_eq!(store_update.chunk_tail().unwrap(), 0);
         }
     }
+
+    /// Saves a block on top of `prev_block` with a new chunk containing the
+    /// given transactions and the outcomes of applying it.
+    fn add_block_with_transactions(
+        chain: &mut Chain,
+        prev_block: &Block,
+        signer: Arc<InMemoryValidatorSigner>,
+        height: BlockHeight,
+        transactions: Vec<SignedTransaction>,
+        outcomes: Vec<ExecutionOutcomeWithId>,
+    ) -> Block {
+        let mut block = TestBlockBuilder::new(prev_block, signer.clone()).height(height).build();
+        let mut header = ShardChunkHeaderV3::new(
+            *prev_block.hash(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            hash(&height.to_le_bytes()),
+            0,
+            height,
+            0,
+            0,
+            0,
+            0,
+            CryptoHash::default(),
+            CryptoHash::default(),
+            vec![],
+            signer.as_ref(),
+        );
+        header.height_included = height;
+        let header = ShardChunkHeader::V3(header);
+        block.set_chunks(vec![header.clone()]);
+        let chunk = ShardChunk::V2(ShardChunkV2 {
+            chunk_hash: header.chunk_hash(),
+            header,
+            transactions,
+            prev_outgoing_receipts: vec![],
+        });
+        let mut store_update = chain.mut_store().store_update();
+        store_update.save_chunk(chunk);
+        let proofs = vec![vec![]; outcomes.len()];
+        store_update.save_outcomes_with_proofs(block.hash(), 0, outcomes, proofs);
+        store_update.save_block(block.clone());
+        store_update.save_block_header(block.header().clone()).unwrap();
+        store_update.chain_store_cache_update.height_to_hashes.insert(height, Some(*block.hash()));
+        store_update.commit().unwrap();
+        block
+    }
+
+    /// Creates a chain with the account activity index enabled and 3 blocks
+    /// with a transaction from test1 to test2 each.  Returns the blocks and
+    /// the expected index entries of both accounts.
+    fn get_chain_with_account_activity() -> (Chain, Vec<Block>, Vec<AccountActivityView>) {
+        let mut chain = get_chain();
+        chain.mut_store().set_save_account_activity(true);
+        let genesis = chain.get_block_by_height(0).unwrap();
+        let signer = Arc::new(create_test_signer("test1"));
+        let tx_signer =
+            InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+        let mut blocks = vec![genesis];
+        let mut activity = vec![];
+        for height in 1..=3 {
+            let tx = SignedTransaction::send_money(
+                height,
+                "test1".parse().unwrap(),
+                "test2".parse().unwrap(),
+                &tx_signer,
+                1,
+                *blocks[0].hash(),
+            );
+            activity.push(AccountActivityView {
+                block_height: height,
+                id: tx.get_hash(),
+                kind: AccountActivityKind::Transaction,
+            });
+            let block = add_block_with_transactions(
+                &mut chain,
+                blocks.last().unwrap(),
+                signer.clone(),
+                height,
+                vec![tx],
+                vec![],
+            );
+            blocks.push(block);
+        }
+        (chain, blocks, activity)
+    }
+
+    #[test]
+    fn test_account_activity_index() {
+        let (chain, _, activity) = get_chain_with_account_activity();
+        let store = chain.store();
+        for account_id in ["test1", "test2"] {
+            let account_id = account_id.parse().unwrap();
+            assert_eq!(
+                store.get_account_activity(&account_id, 0, BlockHeight::MAX, None, 10).unwrap(),
+                activity
+            );
+            assert_eq!(
+                store.get_account_activity(&account_id, 2, 2, None, 10).unwrap(),
+                activity[1..2]
+            );
+        }
+        let other_account = "test3".parse().unwrap();
+        assert!(store
+            .get_account_activity(&other_account, 0, BlockHeight::MAX, None, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_account_activity_index_disabled() {
+        let mut chain = get_chain();
+        let genesis = chain.get_block_by_height(0).unwrap();
+        let signer = Arc::new(create_test_signer("test1"));
+        let tx_signer =
+            InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+        let tx = SignedTransaction::send_money(
+            1,
+            "test1".parse().unwrap(),
+            "test2".parse().unwrap(),
+            &tx_signer,
+            1,
+            *genesis.hash(),
+        );
+        add_block_with_transactions(&mut chain, &genesis, signer, 1, vec![tx], vec![]);
+        assert!(chain
+            .store()
+            .get_account_activity(&"test1".parse().unwrap(), 0, BlockHeight::MAX, None, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    /// A transaction to the signer itself is converted into a local receipt,
+    /// which is indexed using the outcome of the transaction.
+    #[test]
+    fn test_account_activity_local_receipt() {
+        let mut chain = get_chain();
+        chain.mut_store().set_save_account_activity(true);
+        let genesis = chain.get_block_by_height(0).unwrap();
+        let signer = Arc::new(create_test_signer("test1"));
+        let tx_signer =
+            InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+        let tx = SignedTransaction::send_money(
+            1,
+            "test1".parse().unwrap(),
+            "test1".parse().unwrap(),
+            &tx_signer,
+            1,
+            *genesis.hash(),
+        );
+        let receipt_id = hash(b"local receipt");
+        let outcome = ExecutionOutcomeWithId {
+            id: tx.get_hash(),
+            outcome: ExecutionOutcome { receipt_ids: vec![receipt_id], ..Default::default() },
+        };
+        add_block_with_transactions(
+            &mut chain,
+            &genesis,
+            signer,
+            1,
+            vec![tx.clone()],
+            vec![outcome],
+        );
+        let mut activity = chain
+            .store()
+            .get_account_activity(&"test1".parse().unwrap(), 0, BlockHeight::MAX, None, 10)
+            .unwrap();
+        activity.sort_by_key(|entry| entry.kind == AccountActivityKind::Receipt);
+        assert_eq!(
+            activity,
+            vec![
+                AccountActivityView {
+                    block_height: 1,
+                    id: tx.get_hash(),
+                    kind: AccountActivityKind::Transaction,
+                },
+                AccountActivityView {
+                    block_height: 1,
+                    id: receipt_id,
+                    kind: AccountActivityKind::Receipt,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_account_activity_pagination() {
+        let (chain, _, activity) = get_chain_with_account_activity();
+        let store = chain.store();
+        let account_id = "test1".parse().unwrap();
+        let mut cursor = None;
+        let mut pages = vec![];
+        loop {
+            let page = store
+                .get_account_activity(&account_id, 0, BlockHeight::MAX, cursor.as_ref(), 2)
+                .unwrap();
+            cursor = page.last().map(AccountActivityCursor::after);
+            if page.is_empty() {
+                break;
+            }
+            pages.push(page);
+        }
+        assert_eq!(pages, vec![activity[0..2].to_vec(), activity[2..3].to_vec()]);
+
+        // A cursor before `from_height` doesn't extend the range.
+        let cursor = AccountActivityCursor::after(&activity[0]);
+        assert_eq!(
+            store.get_account_activity(&account_id, 3, 3, Some(&cursor), 10).unwrap(),
+            activity[2..3]
+        );
+        // A cursor after `to_height` returns nothing.
+        let cursor = AccountActivityCursor::after(&activity[2]);
+        assert!(store
+            .get_account_activity(&account_id, 0, 2, Some(&cursor), 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    /// Entries written while the index was enabled are garbage collected even
+    /// after it is disabled.
+    #[test]
+    fn test_account_activity_gc() {
+        let (mut chain, blocks, activity) = get_chain_with_account_activity();
+        chain.mut_store().set_save_account_activity(false);
+        let mut store_update = chain.mut_store().store_update();
+        store_update
+            .gc_account_activity(&blocks[1], &GCMode::StateSync { clear_block_info: true })
+            .unwrap();
+        store_update.commit().unwrap();
+        for account_id in ["test1", "test2"] {
+            assert_eq!(
+                chain
+                    .store()
+                    .get_account_activity(
+                        &account_id.parse().unwrap(),
+                        0,
+                        BlockHeight::MAX,
+                        None,
+                        10
+                    )
+                    .unwrap(),
+                activity[1..]
+            );
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use super::ValidatorSchedule;
 use crate::types::{
     ApplySplitStateResult, ApplyTransactionResult, RuntimeAdapter, RuntimeStorageConfig,
+    SimulateTransactionResult,
 };
 use crate::BlockHeader;
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -1253,6 +1254,7 @@ impl RuntimeAdapter for KeyValueRuntime {
                 kind: QueryResponseKind::ViewState(ViewStateResult {
                     values: Default::default(),
                     proof: vec![],
+                    next_cursor: None,
                 }),
                 block_height,
                 block_hash: *block_hash,
@@ -1265,9 +1267,52 @@ impl RuntimeAdapter for KeyValueRuntime {
                 block_height,
                 block_hash: *block_hash,
             }),
+            QueryRequest::TraceCall { .. } => Ok(QueryResponse {
+                kind: QueryResponseKind::CallTrace(Default::default()),
+                block_height,
+                block_hash: *block_hash,
+            }),
         }
     }
 
+    fn simulate_transaction(
+        &self,
+        shard_id: ShardId,
+        state_root: StateRoot,
+        height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        gas_price: Balance,
+        random_seed: CryptoHash,
+        transaction: &SignedTransaction,
+        _verify_signature: bool,
+    ) -> Result<SimulateTransactionResult, Error> {
+        // Applying only adds a new state, so the state at `state_root` stays intact.
+        let apply_result = self.apply_transactions(
+            shard_id,
+            RuntimeStorageConfig::new(state_root, false),
+            height,
+            block_timestamp,
+            prev_block_hash,
+            &CryptoHash::default(),
+            &[],
+            std::slice::from_ref(transaction),
+            ValidatorStakeIter::empty(),
+            gas_price,
+            Gas::MAX,
+            &ChallengesResult::default(),
+            random_seed,
+            true,
+            false,
+        )?;
+        Ok(SimulateTransactionResult {
+            outcomes: apply_result.outcomes,
+            state_changes: Default::default(),
+            pending_receipts: apply_result.outgoing_receipts,
+            invalid_tx_error: None,
+        })
+    }
+
     fn obtain_state_part(
         &self,
         _shard_id: ShardId,
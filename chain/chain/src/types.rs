@@ -24,7 +24,7 @@ use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction};
 use near_primitives::types::validator_stake::{ValidatorStake, ValidatorStakeIter};
 use near_primitives::types::{
     Balance, BlockHeight, BlockHeightDelta, EpochId, Gas, MerkleHash, NumBlocks, ShardId,
-    StateChangesForSplitStates, StateRoot, StateRootNode,
+    StateChanges, StateChangesForSplitStates, StateRoot, StateRootNode,
 };
 use near_primitives::version::{
     ProtocolVersion, MIN_GAS_PRICE_NEP_92, MIN_GAS_PRICE_NEP_92_FIX, MIN_PROTOCOL_VERSION_NEP_92,
@@ -124,6 +124,21 @@ impl ApplyTransactionResult {
     }
 }
 
+/// Result of applying a transaction without persisting the changes, see
+/// [`RuntimeAdapter::simulate_transaction`].
+#[derive(Debug)]
+pub struct SimulateTransactionResult {
+    /// Outcomes of the transaction and of its receipts in the order of execution.
+    pub outcomes: Vec<ExecutionOutcomeWithId>,
+    /// State changes caused by the transaction and by its receipts.
+    pub state_changes: StateChanges,
+    /// Receipts which were produced but not executed, either because they are
+    /// sent to another shard or because the simulation ran out of blocks.
+    pub pending_receipts: Vec<Receipt>,
+    /// Set if the transaction is invalid, in which case nothing was executed.
+    pub invalid_tx_error: Option<InvalidTxError>,
+}
+
 /// Block economics config taken from genesis config
 pub struct BlockEconomicsConfig {
     gas_price_adjustment_rate: Rational32,
@@ -208,6 +223,8 @@ pub struct ChainGenesis {
 pub struct ChainConfig {
     /// Whether to save `TrieChanges` on disk or not.
     pub save_trie_changes: bool,
+    /// Whether to maintain the account activity index.
+    pub save_account_activity: bool,
     /// Number of threads to execute background migration work.
     /// Currently used for flat storage background creation.
     pub background_migration_threads: usize,
@@ -218,6 +235,7 @@ impl ChainConfig {
     pub fn test() -> Self {
         Self {
             save_trie_changes: true,
+            save_account_activity: false,
             background_migration_threads: 1,
             state_split_config: StateSplitConfig::default(),
         }
@@ -375,6 +393,23 @@ pub trait RuntimeAdapter: Send + Sync {
         request: &QueryRequest,
     ) -> Result<QueryResponse, near_chain_primitives::error::QueryError>;
 
+    /// Applies `transaction` on top of `state_root`, the post state root of
+    /// `prev_block_hash`, as if it was included in the next block. Receipts
+    /// it produces for the same shard are applied in the following blocks.
+    /// Nothing is written to the store.
+    fn simulate_transaction(
+        &self,
+        shard_id: ShardId,
+        state_root: StateRoot,
+        height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        gas_price: Balance,
+        random_seed: CryptoHash,
+        transaction: &SignedTransaction,
+        verify_signature: bool,
+    ) -> Result<SimulateTransactionResult, Error>;
+
     /// Get part of the state corresponding to the given state root.
     /// `prev_hash` is a block whose post state root is `state_root`.
     /// Returns error when storage is inconsistent.
@@ -4,6 +4,7 @@ use actix::Message;
 
 use near_pool::types::PoolIterator;
 use near_pool::{InsertTransactionResult, PoolIteratorWrapper, TransactionPool};
+use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::{account_id_to_shard_uid, ShardLayout, ShardUId};
 use near_primitives::{
     epoch_manager::RngSeed,
@@ -62,6 +63,16 @@ impl ShardedTransactionPool {
         self.pool_for_shard(shard_uid).insert_transaction(tx)
     }
 
+    /// Returns the hashes of the transactions of all the shard pools in no particular order.
+    pub fn transaction_hashes(&self) -> impl Iterator<Item = &CryptoHash> {
+        self.tx_pools.values().flat_map(|pool| pool.transaction_hashes())
+    }
+
+    /// Returns the transaction with the given hash if it is in any of the shard pools.
+    pub fn get_transaction(&self, hash: &CryptoHash) -> Option<&SignedTransaction> {
+        self.tx_pools.values().find_map(|pool| pool.get_transaction(hash))
+    }
+
     pub fn remove_transactions(&mut self, shard_uid: ShardUId, transactions: &[SignedTransaction]) {
         if let Some(pool) = self.tx_pools.get_mut(&shard_uid) {
             pool.remove_transactions(transactions)
@@ -3,17 +3,19 @@ use near_async::{
     messaging::IntoSender,
     test_loop::{
         adhoc::{handle_adhoc_events, AdhocEvent, AdhocEventSender},
-        event_handler::capture_events,
+        event_handler::{capture_events, LoopEventHandler},
     },
     time,
 };
 use near_chain::chunks_store::ReadOnlyChunksStore;
-use near_epoch_manager::EpochManagerAdapter;
+use near_epoch_manager::{test_utils::hash_range, EpochManagerAdapter};
 use near_network::{
-    shards_manager::ShardsManagerRequestFromNetwork, test_loop::SupportsRoutingLookup,
+    shards_manager::ShardsManagerRequestFromNetwork,
+    simnet::{Action, Link, Scenario, SimNetwork},
+    test_loop::SupportsRoutingLookup,
     types::PeerManagerMessageRequest,
 };
-use near_primitives::types::{AccountId, NumShards};
+use near_primitives::types::{AccountId, BlockHeight, NumShards};
 use near_store::test_utils::create_test_store;
 
 use crate::{
@@ -21,8 +23,8 @@ use crate::{
     client::ShardsManagerResponse,
     test_loop::{
         forward_client_request_to_shards_manager, forward_network_request_to_shards_manager,
-        route_shards_manager_network_messages, MockChainForShardsManager,
-        MockChainForShardsManagerConfig,
+        route_shards_manager_network_messages, route_shards_manager_network_messages_over,
+        MockChainForShardsManager, MockChainForShardsManagerConfig,
     },
     test_utils::default_tip,
     ShardsManager,
@@ -59,7 +61,16 @@ struct BasicSetupConfig {
 const NETWORK_DELAY: time::Duration = time::Duration::milliseconds(10);
 
 fn basic_setup(config: BasicSetupConfig) -> ShardsManagerTestLoop {
-    let builder = ShardsManagerTestLoopBuilder::new();
+    setup(ShardsManagerTestLoopBuilder::new(), config, || {
+        route_shards_manager_network_messages(NETWORK_DELAY)
+    })
+}
+
+fn setup(
+    builder: ShardsManagerTestLoopBuilder,
+    config: BasicSetupConfig,
+    route_network_messages: impl Fn() -> LoopEventHandler<Vec<TestData>, (usize, TestEvent)>,
+) -> ShardsManagerTestLoop {
     let all_accounts = config.block_producers.iter().chain(config.chunk_only_producers.iter());
     let data = all_accounts
         .enumerate()
@@ -97,7 +108,7 @@ fn basic_setup(config: BasicSetupConfig) -> ShardsManagerTestLoop {
         test.register_handler(forward_client_request_to_shards_manager().widen().for_index(idx));
         test.register_handler(forward_network_request_to_shards_manager().widen().for_index(idx));
         test.register_handler(capture_events::<ShardsManagerResponse>().widen().for_index(idx));
-        test.register_handler(route_shards_manager_network_messages(NETWORK_DELAY));
+        test.register_handler(route_network_messages());
         // Note that we don't have the periodically resending requests handler, because
         // our forwarding logic means that we don't need to resend requests, unless
         // there is unreliable network, which is tested separately.
@@ -325,3 +336,70 @@ fn test_distribute_chunk_with_chunk_only_producers() {
     }
     test.run_instant();
 }
+
+/// Tests chunk distribution over a simulated network, which is partitioned and
+/// healed when the chain reaches the given heights: while the network is
+/// partitioned, only the nodes in the same group as the chunk producer complete
+/// the chunk, and once it is healed, all of them do again.
+#[test]
+fn test_distribute_chunk_across_partition() {
+    const NUM_VALIDATORS: usize = 10;
+    let builder = ShardsManagerTestLoopBuilder::new();
+    let net = SimNetwork::new(builder.clock(), rand::SeedableRng::seed_from_u64(42));
+    net.set_default_link(Link { latency: NETWORK_DELAY, ..Link::default() });
+    let mut test = setup(
+        builder,
+        BasicSetupConfig {
+            block_producers: (0..NUM_VALIDATORS)
+                .map(|idx| format!("validator_{}", idx).parse().unwrap())
+                .collect(),
+            chunk_only_producers: Vec::new(),
+            epoch_length: 4, // arbitrary
+            num_shards: 3,   // arbitrary
+            track_all_shards: true,
+        },
+        || route_shards_manager_network_messages_over(net.clone()),
+    );
+    let mut scenario = Scenario::new(&net)
+        .at_height(3, Action::Partition(vec![(0..5).collect(), (5..NUM_VALIDATORS).collect()]))
+        .at_height(5, Action::Heal);
+
+    for (i, hash) in hash_range(6).into_iter().enumerate() {
+        let height = i as BlockHeight + 1;
+        for idx in 0..test.data.len() {
+            test.sender().for_index(idx).send_adhoc_event("record block", move |data| {
+                data.chain.record_block(hash, height)
+            });
+        }
+        test.run_instant();
+        // The network conditions follow the chain as seen by the nodes.
+        scenario.update(test.data[0].chain.tip.height);
+
+        let chunk_producer = test.data[0].chain.next_chunk_producer(1);
+        let chunk_producer_idx = test.data.index_for_account(&chunk_producer);
+        for data in test.data.iter_mut() {
+            data.client_events.clear();
+        }
+        test.sender().for_index(chunk_producer_idx).send_adhoc_event("produce chunk", |data| {
+            let chunk = data.chain.produce_chunk(1);
+            data.chain.distribute_chunk(&chunk);
+        });
+        test.run_for(NETWORK_DELAY * 2);
+
+        for idx in 0..test.data.len() {
+            if idx == chunk_producer_idx {
+                continue;
+            }
+            let completed = test.data[idx]
+                .client_events
+                .iter()
+                .any(|event| matches!(event, ShardsManagerResponse::ChunkCompleted { .. }));
+            assert_eq!(
+                completed,
+                net.is_reachable(chunk_producer_idx, idx),
+                "height {height}, chunk producer {chunk_producer_idx}, node {idx}"
+            );
+        }
+    }
+    assert!(scenario.is_done());
+}
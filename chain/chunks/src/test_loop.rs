@@ -11,6 +11,8 @@ use near_epoch_manager::{
     test_utils::{record_block, setup_epoch_manager_with_block_and_chunk_producers},
     EpochManagerAdapter, EpochManagerHandle,
 };
+#[cfg(any(test, feature = "test_features"))]
+use near_network::simnet::SimNetwork;
 use near_network::{
     shards_manager::ShardsManagerRequestFromNetwork,
     test_loop::SupportsRoutingLookup,
@@ -62,6 +64,38 @@ pub fn route_shards_manager_network_messages<
         + From<ShardsManagerRequestFromNetwork>,
 >(
     network_delay: time::Duration,
+) -> LoopEventHandler<Data, (usize, Event)> {
+    route_network_messages(move |_, _| Some(network_delay))
+}
+
+/// Like `route_shards_manager_network_messages`, but the messages travel over
+/// the simulated network `net`, in which every instance is the node with the same
+/// index: messages are delayed by the latency of the link between the nodes, and
+/// dropped if the nodes are partitioned from each other.
+#[cfg(any(test, feature = "test_features"))]
+pub fn route_shards_manager_network_messages_over<
+    Data: SupportsRoutingLookup,
+    Event: TryIntoOrSelf<PeerManagerMessageRequest>
+        + From<PeerManagerMessageRequest>
+        + From<ShardsManagerRequestFromNetwork>,
+>(
+    net: SimNetwork,
+) -> LoopEventHandler<Data, (usize, Event)> {
+    route_network_messages(move |from, to| {
+        net.is_reachable(from, to).then(|| net.link(from, to).latency)
+    })
+}
+
+/// Routes the network messages from the instance `from` to the instance `to`
+/// with the delay returned by `network_delay(from, to)`, or drops them if it
+/// returns None.
+fn route_network_messages<
+    Data: SupportsRoutingLookup,
+    Event: TryIntoOrSelf<PeerManagerMessageRequest>
+        + From<PeerManagerMessageRequest>
+        + From<ShardsManagerRequestFromNetwork>,
+>(
+    network_delay: impl Fn(usize, usize) -> Option<time::Duration> + 'static,
 ) -> LoopEventHandler<Data, (usize, Event)> {
     let mut route_back_lookup: HashMap<CryptoHash, usize> = HashMap::new();
     let mut next_hash: u64 = 0;
@@ -76,6 +110,9 @@ pub fn route_shards_manager_network_messages<
                     match request {
                         NetworkRequests::PartialEncodedChunkRequest { target, request, .. } => {
                             let target_idx = data.index_for_account(&target.account_id.unwrap());
+                            let Some(delay) = network_delay(idx, target_idx) else {
+                                return Ok(());
+                            };
                             let route_back = CryptoHash::hash_borsh(next_hash);
                             route_back_lookup.insert(route_back, idx);
                             next_hash += 1;
@@ -85,20 +122,23 @@ pub fn route_shards_manager_network_messages<
                                 partial_encoded_chunk_request: request,
                                 route_back,
                             }.into()),
-                            network_delay,
+                            delay,
                         );
                             Ok(())
                         }
                         NetworkRequests::PartialEncodedChunkResponse { route_back, response } => {
                             let target_idx =
                                 *route_back_lookup.get(&route_back).expect("Route back not found");
+                            let Some(delay) = network_delay(idx, target_idx) else {
+                                return Ok(());
+                            };
                             context.sender.send_with_delay(
                             (target_idx,
                             ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse {
                                 partial_encoded_chunk_response: response,
                                 received_time: context.clock.now().into(), // TODO: use clock
                             }.into()),
-                            network_delay,
+                            delay,
                         );
                             Ok(())
                         }
@@ -107,6 +147,9 @@ pub fn route_shards_manager_network_messages<
                             partial_encoded_chunk,
                         } => {
                             let target_idx = data.index_for_account(&account_id);
+                            let Some(delay) = network_delay(idx, target_idx) else {
+                                return Ok(());
+                            };
                             context.sender.send_with_delay(
                                 (
                                     target_idx,
@@ -115,18 +158,21 @@ pub fn route_shards_manager_network_messages<
                                     )
                                     .into(),
                                 ),
-                                network_delay,
+                                delay,
                             );
                             Ok(())
                         }
                         NetworkRequests::PartialEncodedChunkForward { account_id, forward } => {
                             let target_idx = data.index_for_account(&account_id);
+                            let Some(delay) = network_delay(idx, target_idx) else {
+                                return Ok(());
+                            };
                             context.sender.send_with_delay(
                             (target_idx,
                             ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkForward(
                                 forward,
                             ).into()),
-                            network_delay,
+                            delay,
                         );
                             Ok(())
                         }
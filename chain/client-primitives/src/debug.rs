@@ -2,7 +2,8 @@
 //! without backwards compatibility of JSON encoding.
 use crate::types::StatusError;
 use chrono::DateTime;
-use near_primitives::types::EpochId;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::types::{EpochId, StoreKey};
 use near_primitives::views::{
     CatchupStatusView, ChainProcessingInfo, EpochValidatorInfo, RequestedStatePartsView,
     SyncStatusView,
@@ -22,6 +23,28 @@ pub struct TrackedShardsView {
     pub shards_tracked_next_epoch: Vec<bool>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct FlatStorageVerificationView {
+    pub shard_uid: ShardUId,
+    pub num_passes: u64,
+    // Batches verified at random positions in the current pass.
+    pub num_sampled_batches: u64,
+    // Where the in-order verification of the current pass got to.
+    pub cursor: Option<StoreKey>,
+    pub num_verified_keys: u64,
+    pub num_mismatches: u64,
+    pub recent_mismatches: Vec<FlatStorageMismatchView>,
+    pub last_flat_head_height: Option<BlockHeight>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct FlatStorageMismatchView {
+    pub key: StoreKey,
+    pub flat_head_height: BlockHeight,
+    pub flat_value_hash: Option<CryptoHash>,
+    pub trie_value_hash: Option<CryptoHash>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct EpochInfoView {
     pub epoch_id: CryptoHash,
@@ -185,6 +208,8 @@ pub enum DebugStatus {
     ChainProcessingStatus,
     // The state parts already requested.
     RequestedStateParts,
+    // Progress of the background verification of flat storage.
+    FlatStorageVerification,
 }
 
 impl actix::Message for DebugStatus {
@@ -206,4 +231,6 @@ pub enum DebugStatusResponse {
     ChainProcessingStatus(ChainProcessingInfo),
     // The state parts already requested.
     RequestedStateParts(Vec<RequestedStatePartsView>),
+    // Progress of the background verification of flat storage, per shard.
+    FlatStorageVerification(Vec<FlatStorageVerificationView>),
 }
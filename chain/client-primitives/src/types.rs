@@ -8,16 +8,19 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::network::PeerId;
 use near_primitives::sharding::ChunkHash;
+use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockReference, EpochId, EpochReference, MaybeBlockId, ShardId,
     TransactionOrReceiptId,
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, DownloadStatusView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    GasPriceView, LightClientBlockLiteView, LightClientBlockView, MaintenanceWindowsView,
-    QueryRequest, QueryResponse, ReceiptView, ShardSyncDownloadView, SplitStorageInfoView,
-    StateChangesKindsView, StateChangesRequestView, StateChangesView, SyncStatusView, TxStatusView,
+    AccountActivityCursor, AccountActivityView, BlockView, ChunkView, DownloadStatusView,
+    EpochValidatorInfo, ExecutionOutcomeWithIdView, GasPriceView, LightClientBlockLiteView,
+    LightClientBlockView, MaintenanceWindowsView, QueryRequest, QueryResponse, ReceiptView,
+    ShardSyncDownloadView, SimulateTransactionView, SplitStorageInfoView,
+    StateChangeWithPrevValueView, StateChangesKindsView, StateChangesRequestView, StateChangesView,
+    SyncStatusView, TxStatusView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
 use std::collections::HashMap;
@@ -634,6 +637,26 @@ impl Message for GetNetworkInfo {
     type Result = Result<NetworkInfoResponse, String>;
 }
 
+/// Lists the hashes of at most `limit` transactions currently waiting in the transaction pool.
+#[derive(Debug)]
+pub struct GetPoolTransactionHashes {
+    pub limit: usize,
+}
+
+impl Message for GetPoolTransactionHashes {
+    type Result = Result<Vec<CryptoHash>, String>;
+}
+
+/// Looks up a transaction waiting in the transaction pool by its hash.
+#[derive(Debug)]
+pub struct GetPoolTransaction {
+    pub tx_hash: CryptoHash,
+}
+
+impl Message for GetPoolTransaction {
+    type Result = Result<Option<SignedTransaction>, String>;
+}
+
 #[derive(Debug)]
 pub struct GetGasPrice {
     pub block_id: MaybeBlockId,
@@ -836,6 +859,20 @@ impl Message for GetStateChangesWithCauseInBlockForTrackedShards {
     type Result = Result<HashMap<ShardId, StateChangesView>, GetStateChangesError>;
 }
 
+/// Same as `GetStateChangesWithCauseInBlockForTrackedShards`, but every change also carries
+/// the value the key had right before it.
+#[derive(Debug)]
+pub struct GetStateChangesWithPrevValueInBlockForTrackedShards {
+    pub block_hash: CryptoHash,
+    pub epoch_id: EpochId,
+    /// If set, only the changes of these shards are returned.
+    pub shard_ids: Option<Vec<ShardId>>,
+}
+
+impl Message for GetStateChangesWithPrevValueInBlockForTrackedShards {
+    type Result = Result<HashMap<ShardId, Vec<StateChangeWithPrevValueView>>, GetStateChangesError>;
+}
+
 #[derive(Debug)]
 pub struct GetExecutionOutcome {
     pub id: TransactionOrReceiptId,
@@ -906,12 +943,89 @@ impl Message for GetExecutionOutcome {
 #[derive(Debug)]
 pub struct GetExecutionOutcomesForBlock {
     pub block_hash: CryptoHash,
+    /// If set, only the outcomes of these shards are returned.
+    pub shard_ids: Option<Vec<ShardId>>,
 }
 
 impl Message for GetExecutionOutcomesForBlock {
     type Result = Result<HashMap<ShardId, Vec<ExecutionOutcomeWithIdView>>, String>;
 }
 
+/// Lists transactions and receipts which touched the account at heights in
+/// `[from_height, to_height]`, following the `after` cursor if it is given.
+#[derive(Debug)]
+pub struct GetAccountActivity {
+    pub account_id: AccountId,
+    pub from_height: Option<BlockHeight>,
+    pub to_height: Option<BlockHeight>,
+    pub after: Option<AccountActivityCursor>,
+    pub limit: usize,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetAccountActivityError {
+    #[error("Account activity index is not enabled on this node")]
+    IndexDisabled,
+    #[error("IO Error: {0}")]
+    IOError(String),
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetAccountActivityError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+impl Message for GetAccountActivity {
+    type Result = Result<Vec<AccountActivityView>, GetAccountActivityError>;
+}
+
+/// Applies a transaction on top of the latest final block without sending it
+/// to the network or persisting any changes.
+#[derive(Debug)]
+pub struct SimulateTransaction {
+    pub transaction: SignedTransaction,
+    pub verify_signature: bool,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SimulateTransactionError {
+    #[error("There are no fully synchronized blocks on the node yet")]
+    NoSyncedBlocks,
+    #[error("The node does not track the shard ID {requested_shard_id}")]
+    UnavailableShard { requested_shard_id: ShardId },
+    #[error("IO Error: {0}")]
+    IOError(String),
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for SimulateTransactionError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+impl Message for SimulateTransaction {
+    type Result = Result<SimulateTransactionView, SimulateTransactionError>;
+}
+
 #[derive(Debug)]
 pub struct GetBlockProof {
     pub block_hash: CryptoHash,
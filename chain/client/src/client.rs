@@ -191,9 +191,17 @@ pub struct Client {
     /// Used for commitments to encoded shards
     /// TODO: feature flag, no option
     commitment_scheme: Option<KzgCommitmentScheme>,
+    /// Hash of the head block, published whenever a new head is accepted.
+    /// Observed by the JSON RPC WebSocket subscriptions, see `SubscribeChainHead`.
+    chain_head_sender: tokio::sync::watch::Sender<Option<CryptoHash>>,
 }
 
 impl Client {
+    /// Returns a receiver notified whenever a new chain head is accepted.
+    pub fn subscribe_chain_head(&self) -> tokio::sync::watch::Receiver<Option<CryptoHash>> {
+        self.chain_head_sender.subscribe()
+    }
+
     pub(crate) fn update_client_config(&self, update_client_config: UpdateableClientConfig) {
         self.config.expected_shutdown.update(update_client_config.expected_shutdown);
     }
@@ -245,6 +253,7 @@ impl Client {
         };
         let chain_config = ChainConfig {
             save_trie_changes: config.save_trie_changes,
+            save_account_activity: config.save_account_activity,
             background_migration_threads: config.client_background_migration_threads,
             state_split_config: config.state_split_config,
         };
@@ -334,6 +343,8 @@ impl Client {
 
         // FIXME: Insecure trusted setup for now
         let kzg_commitment_scheme = KzgCommitmentScheme::insecure_generate(2048);
+        let (chain_head_sender, _) =
+            tokio::sync::watch::channel(chain.head().ok().map(|tip| tip.last_block_hash));
         Ok(Self {
             #[cfg(feature = "test_features")]
             adv_produce_blocks: None,
@@ -376,6 +387,7 @@ impl Client {
             tier1_accounts_cache: None,
             flat_storage_creator,
             commitment_scheme: Some(kzg_commitment_scheme),
+            chain_head_sender,
         })
     }
 
@@ -1436,8 +1448,10 @@ impl Client {
             apply_chunks_done_callback,
         );
         if accepted_blocks.iter().any(|accepted_block| accepted_block.status.is_new_head()) {
+            let head = self.chain.head().unwrap();
+            self.chain_head_sender.send_replace(Some(head.last_block_hash));
             self.shards_manager_adapter.send(ShardsManagerRequestFromClient::UpdateChainHeads {
-                head: self.chain.head().unwrap(),
+                head,
                 header_head: self.chain.header_head().unwrap(),
             });
         }
@@ -19,7 +19,7 @@ use crate::sync::adapter::{SyncMessage, SyncShardInfo};
 use crate::sync::state::{StateSync, StateSyncResult};
 use crate::sync_jobs_actor::{create_sync_job_scheduler, SyncJobsActor};
 use crate::{metrics, StatusResponse, SyncAdapter};
-use actix::{Actor, Addr, Arbiter, AsyncContext, Context, Handler};
+use actix::{Actor, Addr, Arbiter, AsyncContext, Context, Handler, MessageResult};
 use actix_rt::ArbiterHandle;
 use chrono::{DateTime, Utc};
 use near_async::messaging::{CanSend, Sender};
@@ -42,14 +42,16 @@ use near_chunks::adapter::ShardsManagerRequestFromClient;
 use near_chunks::client::ShardsManagerResponse;
 use near_chunks::logic::cares_about_shard_this_or_next_epoch;
 use near_client_primitives::types::{
-    Error, GetClientConfig, GetClientConfigError, GetNetworkInfo, NetworkInfoResponse,
-    StateSyncStatus, Status, StatusError, StatusSyncInfo, SyncStatus,
+    Error, GetClientConfig, GetClientConfigError, GetNetworkInfo, GetPoolTransaction,
+    GetPoolTransactionHashes, NetworkInfoResponse, StateSyncStatus, Status, StatusError,
+    StatusSyncInfo, SyncStatus,
 };
 use near_epoch_manager::shard_tracker::ShardTracker;
 use near_epoch_manager::EpochManagerAdapter;
 use near_network::types::ReasonForBan;
 use near_network::types::{
-    NetworkInfo, NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest,
+    prefer_good_reputation, NetworkInfo, NetworkRequests, PeerManagerAdapter,
+    PeerManagerMessageRequest,
 };
 use near_o11y::{handler_debug_span, OpenTelemetrySpanExt, WithSpanContext, WithSpanContextExt};
 use near_performance_metrics;
@@ -60,6 +62,7 @@ use near_primitives::epoch_manager::RngSeed;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::static_clock::StaticClock;
+use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{BlockHeight, ShardId};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::{from_timestamp, MaybeValidated};
@@ -797,6 +800,54 @@ impl Handler<WithSpanContext<GetNetworkInfo>> for ClientActor {
     }
 }
 
+impl Handler<WithSpanContext<GetPoolTransactionHashes>> for ClientActor {
+    type Result = Result<Vec<CryptoHash>, String>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetPoolTransactionHashes>,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        Ok(self.client.sharded_tx_pool.transaction_hashes().take(msg.limit).copied().collect())
+    }
+}
+
+impl Handler<WithSpanContext<GetPoolTransaction>> for ClientActor {
+    type Result = Result<Option<SignedTransaction>, String>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetPoolTransaction>,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        Ok(self.client.sharded_tx_pool.get_transaction(&msg.tx_hash).cloned())
+    }
+}
+
+/// Subscribes to the changes of the chain head.
+/// The returned receiver holds the hash of the current head and is notified
+/// whenever a new head is accepted.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "tokio::sync::watch::Receiver<Option<CryptoHash>>")]
+pub struct SubscribeChainHead;
+
+impl Handler<WithSpanContext<SubscribeChainHead>> for ClientActor {
+    type Result = MessageResult<WithSpanContext<SubscribeChainHead>>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<SubscribeChainHead>,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, _msg) = handler_debug_span!(target: "client", msg);
+        MessageResult(self.client.subscribe_chain_head())
+    }
+}
+
 /// `ApplyChunksDoneMessage` is a message that signals the finishing of applying chunks of a block.
 /// Upon receiving this message, ClientActors knows that it's time to finish processing the blocks that
 /// just finished applying chunks.
@@ -1406,7 +1457,9 @@ impl ClientActor {
             .collect();
         metrics::PEERS_WITH_INVALID_HASH
             .set(self.network_info.highest_height_peers.len() as i64 - eligible_peers.len() as i64);
-        let peer_info = if let Some(peer_info) = eligible_peers.choose(&mut thread_rng()) {
+        let peer_info = if let Some(peer_info) =
+            prefer_good_reputation(eligible_peers).choose(&mut thread_rng()).copied()
+        {
             peer_info
         } else {
             return Ok(SyncRequirement::NoPeers);
@@ -1725,7 +1778,9 @@ impl ClientActor {
                         StateSyncResult::InProgress => (),
                         StateSyncResult::RequestBlock => {
                             if let Some(peer_info) =
-                                self.network_info.highest_height_peers.choose(&mut thread_rng())
+                                prefer_good_reputation(&self.network_info.highest_height_peers)
+                                    .choose(&mut thread_rng())
+                                    .copied()
                             {
                                 let id = peer_info.peer_info.id.clone();
 
@@ -8,7 +8,8 @@ use near_chain::crypto_hash_timer::CryptoHashTimer;
 use near_chain::{near_chain_primitives, Chain, ChainStoreAccess};
 use near_client_primitives::debug::{
     ApprovalAtHeightStatus, BlockProduction, ChunkCollection, DebugBlockStatusData, DebugStatus,
-    DebugStatusResponse, MissedHeightInfo, ProductionAtHeight, ValidatorStatus,
+    DebugStatusResponse, FlatStorageMismatchView, FlatStorageVerificationView, MissedHeightInfo,
+    ProductionAtHeight, ValidatorStatus,
 };
 use near_client_primitives::types::Error;
 use near_client_primitives::{
@@ -19,13 +20,14 @@ use near_epoch_manager::EpochManagerAdapter;
 use near_o11y::{handler_debug_span, log_assert, OpenTelemetrySpanExt, WithSpanContext};
 use near_performance_metrics_macros::perf;
 use near_primitives::state_sync::get_num_state_parts;
-use near_primitives::types::{AccountId, BlockHeight, ShardId, ValidatorInfoIdentifier};
+use near_primitives::types::{AccountId, BlockHeight, ShardId, StoreKey, ValidatorInfoIdentifier};
 use near_primitives::{
     hash::CryptoHash,
     state_sync::{ShardStateSyncResponseHeader, StateHeaderKey},
     types::EpochId,
     views::ValidatorInfo,
 };
+use near_store::flat::store_helper::get_flat_storage_verification_status;
 use near_store::DBCol;
 use std::cmp::{max, min};
 use std::collections::{HashMap, HashSet};
@@ -179,6 +181,9 @@ impl Handler<WithSpanContext<DebugStatus>> for ClientActor {
             DebugStatus::ChainProcessingStatus => Ok(DebugStatusResponse::ChainProcessingStatus(
                 self.client.chain.get_chain_processing_info(),
             )),
+            DebugStatus::FlatStorageVerification => Ok(
+                DebugStatusResponse::FlatStorageVerification(self.get_flat_storage_verification()?),
+            ),
         }
     }
 }
@@ -330,6 +335,41 @@ impl ClientActor {
         })
     }
 
+    fn get_flat_storage_verification(
+        &self,
+    ) -> Result<Vec<FlatStorageVerificationView>, near_chain_primitives::Error> {
+        let store = self.client.chain.chain_store().store();
+        let mut shard_uids =
+            self.client.runtime_adapter.get_flat_storage_manager().get_shard_uids();
+        shard_uids.sort();
+        shard_uids
+            .into_iter()
+            .map(|shard_uid| {
+                let status = get_flat_storage_verification_status(store, shard_uid)
+                    .map_err(|err| near_chain_primitives::Error::Other(err.to_string()))?;
+                Ok(FlatStorageVerificationView {
+                    shard_uid,
+                    num_passes: status.num_passes,
+                    num_sampled_batches: status.num_sampled_batches,
+                    cursor: status.cursor.map(StoreKey::from),
+                    num_verified_keys: status.num_verified_keys,
+                    num_mismatches: status.num_mismatches,
+                    recent_mismatches: status
+                        .recent_mismatches
+                        .into_iter()
+                        .map(|mismatch| FlatStorageMismatchView {
+                            key: mismatch.key.into(),
+                            flat_head_height: mismatch.flat_head.height,
+                            flat_value_hash: mismatch.flat_value.map(|value| value.hash),
+                            trie_value_hash: mismatch.trie_value.map(|value| value.hash),
+                        })
+                        .collect(),
+                    last_flat_head_height: status.last_flat_head.map(|head| head.height),
+                })
+            })
+            .collect()
+    }
+
     fn get_tracked_shards_view(&self) -> Result<TrackedShardsView, near_chain_primitives::Error> {
         let epoch_id = self.client.chain.header_head()?.epoch_id;
         let fetch_hash = self.client.chain.header_head()?.last_block_hash;
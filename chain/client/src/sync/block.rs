@@ -4,7 +4,9 @@ use near_chain::Chain;
 use near_chain::{check_known, ChainStoreAccess};
 use near_client_primitives::types::SyncStatus;
 use near_network::types::PeerManagerMessageRequest;
-use near_network::types::{HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter};
+use near_network::types::{
+    prefer_good_reputation, HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter,
+};
 use near_primitives::hash::CryptoHash;
 use near_primitives::static_clock::StaticClock;
 use near_primitives::types::{BlockHeight, BlockHeightDelta};
@@ -202,11 +204,12 @@ impl BlockSync {
             let (height, hash) = request;
             let request_from_archival = self.archive && height < gc_stop_height;
             let peer = if request_from_archival {
-                let archival_peer_iter = highest_height_peers.iter().filter(|p| p.archival);
-                archival_peer_iter.choose(&mut rand::thread_rng())
+                let archival_peers =
+                    prefer_good_reputation(highest_height_peers.iter().filter(|p| p.archival));
+                archival_peers.into_iter().choose(&mut rand::thread_rng())
             } else {
-                let peer_iter = highest_height_peers.iter();
-                peer_iter.choose(&mut rand::thread_rng())
+                let peers = prefer_good_reputation(highest_height_peers);
+                peers.into_iter().choose(&mut rand::thread_rng())
             };
 
             if let Some(peer) = peer {
@@ -293,6 +296,7 @@ mod test {
                 highest_block_hash: Default::default(),
                 tracked_shards: vec![],
                 archival: false,
+                low_reputation: false,
             })
             .collect()
     }
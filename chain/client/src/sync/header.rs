@@ -6,7 +6,9 @@ use near_async::messaging::CanSend;
 use near_chain::{Chain, ChainStoreAccess};
 use near_client_primitives::types::SyncStatus;
 use near_network::types::PeerManagerMessageRequest;
-use near_network::types::{HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter};
+use near_network::types::{
+    prefer_good_reputation, HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter,
+};
 use near_primitives::block::Tip;
 use near_primitives::hash::CryptoHash;
 use near_primitives::static_clock::StaticClock;
@@ -95,7 +97,11 @@ impl HeaderSync {
                 highest_height,
             };
             self.syncing_peer = None;
-            if let Some(peer) = highest_height_peers.choose(&mut thread_rng()).cloned() {
+            if let Some(peer) = prefer_good_reputation(highest_height_peers)
+                .choose(&mut thread_rng())
+                .copied()
+                .cloned()
+            {
                 if peer.highest_block_height > header_head.height {
                     self.syncing_peer = self.request_headers(chain, peer);
                 }
@@ -558,6 +564,7 @@ mod test {
                 highest_block_hash: Default::default(),
                 tracked_shards: vec![],
                 archival: false,
+                low_reputation: false,
             });
             header_sync.syncing_peer.as_mut().unwrap().highest_block_height = highest_height;
         };
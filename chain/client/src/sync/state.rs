@@ -40,7 +40,8 @@ use near_client_primitives::types::{
 use near_epoch_manager::EpochManagerAdapter;
 use near_network::types::PeerManagerMessageRequest;
 use near_network::types::{
-    HighestHeightPeerInfo, NetworkRequests, NetworkResponses, PeerManagerAdapter,
+    prefer_good_reputation, HighestHeightPeerInfo, NetworkRequests, NetworkResponses,
+    PeerManagerAdapter,
 };
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
@@ -504,14 +505,17 @@ impl StateSync {
         }
     }
 
-    /// Avoids peers that already have outstanding requests for parts.
+    /// Avoids peers that already have outstanding requests for parts,
+    /// and peers with a low reputation if there are other peers.
     fn select_peers(
         &mut self,
         highest_height_peers: &[HighestHeightPeerInfo],
         shard_id: ShardId,
     ) -> Result<Vec<PeerId>, near_chain::Error> {
-        let peers: Vec<PeerId> =
-            highest_height_peers.iter().map(|peer| peer.peer_info.id.clone()).collect();
+        let peers: Vec<PeerId> = prefer_good_reputation(highest_height_peers)
+            .into_iter()
+            .map(|peer| peer.peer_info.id.clone())
+            .collect();
         let res = match &mut self.inner {
             StateSyncInner::Peers { last_part_id_requested, .. } => {
                 last_part_id_requested.retain(|_, request| !request.expired());
@@ -1324,6 +1328,41 @@ mod test {
     };
     use near_primitives::{test_utils::TestBlockBuilder, types::EpochId};
 
+    #[test]
+    // Peers with a low reputation are asked for state parts only if there are no other peers.
+    fn test_select_peers_by_reputation() {
+        let mut state_sync = StateSync::new(
+            Arc::new(MockPeerManagerAdapter::default()).into(),
+            TimeDuration::from_secs(1),
+            "chain_id",
+            &SyncConfig::Peers,
+            false,
+        );
+        let make_peer = |low_reputation| {
+            let secret_key = SecretKey::from_random(near_crypto::KeyType::ED25519);
+            HighestHeightPeerInfo {
+                peer_info: PeerInfo {
+                    id: PeerId::new(secret_key.public_key()),
+                    addr: None,
+                    account_id: None,
+                },
+                genesis_id: Default::default(),
+                highest_block_height: 10,
+                highest_block_hash: Default::default(),
+                tracked_shards: vec![0],
+                archival: false,
+                low_reputation,
+            }
+        };
+        let good = make_peer(false);
+        let bad = make_peer(true);
+        assert_eq!(
+            state_sync.select_peers(&[bad.clone(), good.clone()], 0).unwrap(),
+            vec![good.peer_info.id.clone()]
+        );
+        assert_eq!(state_sync.select_peers(&[bad.clone()], 0).unwrap(), vec![bad.peer_info.id]);
+    }
+
     #[test]
     // Start a new state sync - and check that it asks for a header.
     fn test_ask_for_header() {
@@ -1382,6 +1421,7 @@ mod test {
             highest_block_hash: Default::default(),
             tracked_shards: vec![0],
             archival: false,
+            low_reputation: false,
         };
 
         run_actix(async {
@@ -113,6 +113,7 @@ pub fn setup(
         doomslug_threshold_mode,
         ChainConfig {
             save_trie_changes: true,
+            save_account_activity: false,
             background_migration_threads: 1,
             state_split_config: StateSplitConfig::default(),
         },
@@ -238,6 +239,7 @@ pub fn setup_only_view(
         doomslug_threshold_mode,
         ChainConfig {
             save_trie_changes: true,
+            save_account_activity: false,
             background_migration_threads: 1,
             state_split_config: StateSplitConfig::default(),
         },
@@ -1010,6 +1012,7 @@ pub fn setup_synchronous_shards_manager(
         DoomslugThresholdMode::TwoThirds, // irrelevant
         ChainConfig {
             save_trie_changes: true,
+            save_account_activity: false,
             background_migration_threads: 1,
             state_split_config: StateSplitConfig::default(),
         }, // irrelevant
@@ -362,6 +362,10 @@ impl TestEnv {
                     account_id,
                     prefix: vec![].into(),
                     include_proof: false,
+                    start_key: None,
+                    end_key: None,
+                    limit: None,
+                    cursor: None,
                 },
             )
             .unwrap();
@@ -193,7 +193,10 @@ fn test_execution_outcome_for_chunk() {
 
             let mut execution_outcomes_in_block = actor_handles
                 .view_client_actor
-                .send(GetExecutionOutcomesForBlock { block_hash }.with_span_context())
+                .send(
+                    GetExecutionOutcomesForBlock { block_hash, shard_ids: None }
+                        .with_span_context(),
+                )
                 .await
                 .unwrap()
                 .unwrap();
@@ -18,14 +18,15 @@ use near_chain::{
 use near_chain_configs::{ClientConfig, ProtocolConfigView};
 use near_chain_primitives::error::EpochErrorResultToChainError;
 use near_client_primitives::types::{
-    Error, GetBlock, GetBlockError, GetBlockProof, GetBlockProofError, GetBlockProofResponse,
-    GetBlockWithMerkleTree, GetChunkError, GetExecutionOutcome, GetExecutionOutcomeError,
-    GetExecutionOutcomesForBlock, GetGasPrice, GetGasPriceError, GetMaintenanceWindows,
-    GetMaintenanceWindowsError, GetNextLightClientBlockError, GetProtocolConfig,
-    GetProtocolConfigError, GetReceipt, GetReceiptError, GetSplitStorageInfo,
-    GetSplitStorageInfoError, GetStateChangesError, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfoError, Query, QueryError,
-    TxStatus, TxStatusError,
+    Error, GetAccountActivity, GetAccountActivityError, GetBlock, GetBlockError, GetBlockProof,
+    GetBlockProofError, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunkError,
+    GetExecutionOutcome, GetExecutionOutcomeError, GetExecutionOutcomesForBlock, GetGasPrice,
+    GetGasPriceError, GetMaintenanceWindows, GetMaintenanceWindowsError,
+    GetNextLightClientBlockError, GetProtocolConfig, GetProtocolConfigError, GetReceipt,
+    GetReceiptError, GetSplitStorageInfo, GetSplitStorageInfoError, GetStateChangesError,
+    GetStateChangesWithCauseInBlock, GetStateChangesWithCauseInBlockForTrackedShards,
+    GetStateChangesWithPrevValueInBlockForTrackedShards, GetValidatorInfoError, Query, QueryError,
+    SimulateTransaction, SimulateTransactionError, TxStatus, TxStatusError,
 };
 use near_epoch_manager::shard_tracker::ShardTracker;
 use near_epoch_manager::EpochManagerAdapter;
@@ -41,25 +42,31 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, PartialMerkleTree};
 use near_primitives::network::AnnounceAccount;
 use near_primitives::receipt::Receipt;
+use near_primitives::shard_layout::account_id_to_shard_id;
 use near_primitives::sharding::ShardChunk;
 use near_primitives::state_sync::{
     ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV3,
 };
 use near_primitives::static_clock::StaticClock;
+use near_primitives::transaction::ExecutionStatus;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockId, BlockReference, EpochReference, Finality, MaybeBlockId,
-    ShardId, SyncCheckpoint, TransactionOrReceiptId, ValidatorInfoIdentifier,
+    ShardId, StateChanges, StateChangesExt, SyncCheckpoint, TransactionOrReceiptId,
+    ValidatorInfoIdentifier,
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView, ExecutionStatusView,
-    FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockView,
-    MaintenanceWindowsView, QueryRequest, QueryResponse, ReceiptView, SplitStorageInfoView,
-    StateChangesKindsView, StateChangesView, TxExecutionStatus, TxStatusView,
+    AccountActivityView, BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
+    ExecutionStatusView, FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, GasPriceView,
+    LightClientBlockView, MaintenanceWindowsView, QueryRequest, QueryResponse, ReceiptView,
+    SimulateTransactionView, SimulatedExecutionOutcomeView, SplitStorageInfoView,
+    StateChangeWithPrevValueView, StateChangesKindsView, StateChangesView, TxExecutionStatus,
+    TxStatusView,
 };
 use near_store::flat::{FlatStorageReadyStatus, FlatStorageStatus};
-use near_store::{DBCol, COLD_HEAD_KEY, FINAL_HEAD_KEY, HEAD_KEY};
+use near_store::{DBCol, KeyForStateChanges, Trie, COLD_HEAD_KEY, FINAL_HEAD_KEY, HEAD_KEY};
 use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
 use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::hash::Hash;
 use std::sync::{Arc, Mutex, RwLock};
@@ -324,6 +331,7 @@ impl ViewClientActor {
             QueryRequest::ViewAccessKey { account_id, .. } => account_id,
             QueryRequest::ViewAccessKeyList { account_id, .. } => account_id,
             QueryRequest::CallFunction { account_id, .. } => account_id,
+            QueryRequest::TraceCall { account_id, .. } => account_id,
             QueryRequest::ViewCode { account_id, .. } => account_id,
         };
         let shard_id = self
@@ -922,6 +930,103 @@ impl Handler<WithSpanContext<GetStateChangesWithCauseInBlockForTrackedShards>> f
     }
 }
 
+impl Handler<WithSpanContext<GetStateChangesWithPrevValueInBlockForTrackedShards>>
+    for ViewClientActor
+{
+    type Result = Result<HashMap<ShardId, Vec<StateChangeWithPrevValueView>>, GetStateChangesError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetStateChangesWithPrevValueInBlockForTrackedShards>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        tracing::debug!(target: "client", ?msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetStateChangesWithPrevValueInBlockForTrackedShards"])
+            .start_timer();
+        let prev_hash = *self.chain.get_block_header(&msg.block_hash)?.prev_hash();
+        let shard_layout = self.epoch_manager.get_shard_layout(&msg.epoch_id).into_chain_error()?;
+        // The previous block may belong to another epoch with a different shard
+        // layout.  There is no previous block, and no previous state, for genesis.
+        let prev_epoch_id = if prev_hash == CryptoHash::default() {
+            None
+        } else {
+            Some(self.epoch_manager.get_epoch_id(&prev_hash).into_chain_error()?)
+        };
+
+        // Values before the block are read from the state the chunks were applied on top of.
+        let mut prev_state_tries: HashMap<ShardId, Option<Trie>> = HashMap::new();
+        let storage_key = KeyForStateChanges::for_block(&msg.block_hash);
+        // Changes of the other shards are skipped before their previous values
+        // are looked up.  Keys without an account are skipped later anyway.
+        let raw_changes = storage_key.find_iter(self.chain.store().store()).filter(|raw_change| {
+            let (Some(shard_ids), Ok(raw_change)) = (&msg.shard_ids, raw_change) else {
+                return true;
+            };
+            raw_change.trie_key.get_account_id().map_or(true, |account_id| {
+                shard_ids.contains(&account_id_to_shard_id(&account_id, &shard_layout))
+            })
+        });
+        let state_changes = StateChanges::from_changes_with_prev_values(
+            raw_changes,
+            |trie_key| -> Result<Option<Vec<u8>>, near_chain::Error> {
+                let (Some(account_id), Some(prev_epoch_id)) =
+                    (trie_key.get_account_id(), &prev_epoch_id)
+                else {
+                    return Ok(None);
+                };
+                let shard_id = self
+                    .epoch_manager
+                    .account_id_to_shard_id(&account_id, prev_epoch_id)
+                    .into_chain_error()?;
+                let trie = match prev_state_tries.entry(shard_id) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let shard_uid = self
+                            .epoch_manager
+                            .shard_id_to_uid(shard_id, prev_epoch_id)
+                            .into_chain_error()?;
+                        let trie = match self.chain.get_chunk_extra(&prev_hash, &shard_uid) {
+                            Ok(chunk_extra) => Some(self.runtime.get_view_trie_for_shard(
+                                shard_id,
+                                &prev_hash,
+                                *chunk_extra.state_root(),
+                            )?),
+                            // There is no previous state for the genesis block.
+                            Err(near_chain::Error::DBNotFoundErr(_)) => None,
+                            Err(err) => return Err(err),
+                        };
+                        entry.insert(trie)
+                    }
+                };
+                match trie {
+                    Some(trie) => Ok(trie.get(&trie_key.to_vec())?),
+                    None => Ok(None),
+                }
+            },
+        )?;
+
+        let mut state_changes_split_by_shard_id: HashMap<
+            ShardId,
+            Vec<StateChangeWithPrevValueView>,
+        > = HashMap::new();
+        for state_change in state_changes {
+            let shard_id = self
+                .epoch_manager
+                .account_id_to_shard_id(
+                    state_change.change.value.affected_account_id(),
+                    &msg.epoch_id,
+                )
+                .map_err(|err| GetStateChangesError::IOError { error_message: err.to_string() })?;
+            state_changes_split_by_shard_id.entry(shard_id).or_default().push(state_change.into());
+        }
+
+        Ok(state_changes_split_by_shard_id)
+    }
+}
+
 /// Returns the next light client block, given the hash of the last block known to the light client.
 /// There are three cases:
 ///  1. The last block known to the light client is in the same epoch as the tip:
@@ -1085,7 +1190,7 @@ impl Handler<WithSpanContext<GetExecutionOutcomesForBlock>> for ViewClientActor
         Ok(self
             .chain
             .store()
-            .get_block_execution_outcomes(&msg.block_hash)
+            .get_block_execution_outcomes_for_shards(&msg.block_hash, msg.shard_ids.as_deref())
             .map_err(|e| e.to_string())?
             .into_iter()
             .map(|(k, v)| (k, v.into_iter().map(Into::into).collect()))
@@ -1093,6 +1198,114 @@ impl Handler<WithSpanContext<GetExecutionOutcomesForBlock>> for ViewClientActor
     }
 }
 
+impl Handler<WithSpanContext<GetAccountActivity>> for ViewClientActor {
+    type Result = Result<Vec<AccountActivityView>, GetAccountActivityError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetAccountActivity>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        tracing::debug!(target: "client", ?msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetAccountActivity"])
+            .start_timer();
+        if !self.config.save_account_activity {
+            return Err(GetAccountActivityError::IndexDisabled);
+        }
+        Ok(self.chain.store().get_account_activity(
+            &msg.account_id,
+            msg.from_height.unwrap_or(0),
+            msg.to_height.unwrap_or(BlockHeight::MAX),
+            msg.after.as_ref(),
+            msg.limit,
+        )?)
+    }
+}
+
+impl Handler<WithSpanContext<SimulateTransaction>> for ViewClientActor {
+    type Result = Result<SimulateTransactionView, SimulateTransactionError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<SimulateTransaction>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        tracing::debug!(target: "client", ?msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["SimulateTransaction"])
+            .start_timer();
+        let final_head = self.chain.final_head().map_err(|err| match err {
+            near_chain::near_chain_primitives::Error::DBNotFoundErr(_) => {
+                SimulateTransactionError::NoSyncedBlocks
+            }
+            err => err.into(),
+        })?;
+        let header = self.chain.get_block_header(&final_head.last_block_hash)?;
+        // The transaction is applied as if it was included in the next block.
+        let epoch_id =
+            self.epoch_manager.get_epoch_id_from_prev_block(header.hash()).into_chain_error()?;
+        let shard_id = self
+            .epoch_manager
+            .account_id_to_shard_id(&msg.transaction.transaction.signer_id, &epoch_id)
+            .into_chain_error()?;
+        let shard_uid =
+            self.epoch_manager.shard_id_to_uid(shard_id, &epoch_id).into_chain_error()?;
+        let chunk_extra =
+            self.chain.get_chunk_extra(header.hash(), &shard_uid).map_err(|err| match err {
+                near_chain::near_chain_primitives::Error::DBNotFoundErr(_) => {
+                    SimulateTransactionError::UnavailableShard { requested_shard_id: shard_id }
+                }
+                err => err.into(),
+            })?;
+
+        let result = self.runtime.simulate_transaction(
+            shard_id,
+            *chunk_extra.state_root(),
+            header.height() + 1,
+            header.raw_timestamp(),
+            header.hash(),
+            header.next_gas_price(),
+            *header.random_value(),
+            &msg.transaction,
+            msg.verify_signature,
+        )?;
+        let failure = match result.invalid_tx_error {
+            Some(err) => Some(err.into()),
+            None => result.outcomes.iter().find_map(|outcome_with_id| {
+                match &outcome_with_id.outcome.status {
+                    ExecutionStatus::Failure(err) => Some(err.clone()),
+                    _ => None,
+                }
+            }),
+        };
+        Ok(SimulateTransactionView {
+            block_hash: *header.hash(),
+            block_height: header.height(),
+            failure,
+            gas_burnt: result
+                .outcomes
+                .iter()
+                .map(|outcome_with_id| outcome_with_id.outcome.gas_burnt)
+                .sum(),
+            outcomes: result
+                .outcomes
+                .into_iter()
+                .map(|outcome_with_id| SimulatedExecutionOutcomeView {
+                    id: outcome_with_id.id,
+                    outcome: outcome_with_id.outcome.into(),
+                })
+                .collect(),
+            pending_receipts: result.pending_receipts.into_iter().map(Into::into).collect(),
+            state_changes: result.state_changes.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
 impl Handler<WithSpanContext<GetReceipt>> for ViewClientActor {
     type Result = Result<Option<ReceiptView>, GetReceiptError>;
 
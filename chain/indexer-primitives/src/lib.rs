@@ -2,6 +2,11 @@ pub use near_primitives::hash::CryptoHash;
 pub use near_primitives::{self, types, views};
 
 /// Resulting struct represents block with chunks
+///
+/// Only final blocks are streamed, so `block.header.height` (or equivalently
+/// `block.header.hash`) uniquely identifies a message. Delivery is at least once:
+/// messages sent after the last committed height are streamed again after a restart,
+/// so consumers should use the block height as the deduplication key.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct StreamerMessage {
     pub block: views::BlockView,
@@ -32,6 +37,10 @@ pub struct IndexerExecutionOutcomeWithOptionalReceipt {
 pub struct IndexerExecutionOutcomeWithReceipt {
     pub execution_outcome: views::ExecutionOutcomeWithIdView,
     pub receipt: views::ReceiptView,
+    /// State changes caused by the receipt, each with the value the key had before the change.
+    /// Allows building reversible diffs. Also included into `IndexerShard::state_changes`.
+    #[serde(default)]
+    pub state_changes: Vec<views::StateChangeWithPrevValueView>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -0,0 +1,180 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use rocksdb::{WriteOptions, DB};
+
+use near_primitives::types::BlockHeight;
+
+/// Height of the last block handed over to the consumer by the streamer.
+const LAST_SYNCED_BLOCK_HEIGHT_KEY: &[u8] = b"last_synced_block_height";
+/// Height of the last block the consumer acknowledged as processed.
+const LAST_COMMITTED_BLOCK_HEIGHT_KEY: &[u8] = b"last_committed_block_height";
+
+/// Durable record of the streaming progress stored in the indexer database.
+///
+/// The streamer only records which blocks it has sent, while the consumer
+/// acknowledges which blocks it has actually processed via [`Checkpoint::commit`].
+/// `SyncModeEnum::FromInterruption` resumes right after the last committed
+/// height, so every block is delivered at least once.
+///
+/// The handle is cheap to clone and can be moved into the task that drains
+/// the stream returned by `Indexer::streamer`.
+#[derive(Clone)]
+pub struct Checkpoint {
+    inner: Arc<CheckpointInner>,
+}
+
+struct CheckpointInner {
+    db: DB,
+    /// Cached value of the committed height, also serializes commits.
+    last_committed_block_height: Mutex<Option<BlockHeight>>,
+}
+
+impl Checkpoint {
+    pub(crate) fn open(path: &Path) -> anyhow::Result<Self> {
+        let db = DB::open_default(path)
+            .with_context(|| format!("failed to open indexer database at {}", path.display()))?;
+        let last_committed_block_height = read_height(&db, LAST_COMMITTED_BLOCK_HEIGHT_KEY)?;
+        Ok(Self {
+            inner: Arc::new(CheckpointInner {
+                db,
+                last_committed_block_height: Mutex::new(last_committed_block_height),
+            }),
+        })
+    }
+
+    /// Marks all the blocks up to and including `block_height` as processed.
+    ///
+    /// The height is written with a synchronous write, so once this returns
+    /// it survives a crash of the process or the machine. Blocks must be
+    /// committed in order: after a restart, blocks below the committed height
+    /// are not delivered again even if they were never processed. Consumers
+    /// which process blocks concurrently have to commit a height up to which
+    /// all blocks are done. Committing a height which is not above the
+    /// committed one is a no-op.
+    pub fn commit(&self, block_height: BlockHeight) -> anyhow::Result<()> {
+        let mut last_committed = self.inner.last_committed_block_height.lock().unwrap();
+        if last_committed.map_or(false, |last_committed| last_committed >= block_height) {
+            return Ok(());
+        }
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(true);
+        self.inner
+            .db
+            .put_opt(LAST_COMMITTED_BLOCK_HEIGHT_KEY, block_height.to_string(), &write_options)
+            .context("failed to persist the committed block height")?;
+        *last_committed = Some(block_height);
+        Ok(())
+    }
+
+    /// Returns the height of the last block acknowledged via [`Checkpoint::commit`].
+    pub fn last_committed_block_height(&self) -> Option<BlockHeight> {
+        *self.inner.last_committed_block_height.lock().unwrap()
+    }
+
+    /// Returns the height the streamer should start from when resuming after
+    /// an interruption.
+    ///
+    /// Databases written by older versions of the indexer only contain the
+    /// last synced height, in which case that block is streamed again.
+    pub(crate) fn resume_block_height(&self) -> anyhow::Result<Option<BlockHeight>> {
+        if let Some(last_committed) = self.last_committed_block_height() {
+            return Ok(Some(last_committed + 1));
+        }
+        read_height(&self.inner.db, LAST_SYNCED_BLOCK_HEIGHT_KEY)
+    }
+
+    pub(crate) fn set_last_synced_block_height(
+        &self,
+        block_height: BlockHeight,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .db
+            .put(LAST_SYNCED_BLOCK_HEIGHT_KEY, block_height.to_string())
+            .context("failed to persist the synced block height")
+    }
+}
+
+fn read_height(db: &DB, key: &[u8]) -> anyhow::Result<Option<BlockHeight>> {
+    let Some(value) = db.get(key)? else {
+        return Ok(None);
+    };
+    let value = String::from_utf8(value).context("block height is not a valid UTF-8 string")?;
+    Ok(Some(value.parse().with_context(|| format!("invalid block height {:?}", value))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::open(dir.path()).unwrap();
+        assert_eq!(checkpoint.last_committed_block_height(), None);
+        assert_eq!(checkpoint.resume_block_height().unwrap(), None);
+    }
+
+    #[test]
+    fn test_commit_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let checkpoint = Checkpoint::open(dir.path()).unwrap();
+            checkpoint.set_last_synced_block_height(12).unwrap();
+            checkpoint.commit(10).unwrap();
+            assert_eq!(checkpoint.last_committed_block_height(), Some(10));
+        }
+        let checkpoint = Checkpoint::open(dir.path()).unwrap();
+        assert_eq!(checkpoint.last_committed_block_height(), Some(10));
+        // Blocks which were sent but not committed are streamed again.
+        assert_eq!(checkpoint.resume_block_height().unwrap(), Some(11));
+    }
+
+    #[test]
+    fn test_out_of_order_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let checkpoint = Checkpoint::open(dir.path()).unwrap();
+            checkpoint.set_last_synced_block_height(12).unwrap();
+            checkpoint.commit(11).unwrap();
+            // A lower commit doesn't move the checkpoint back.
+            checkpoint.commit(10).unwrap();
+            assert_eq!(checkpoint.last_committed_block_height(), Some(11));
+        }
+        // Committing 11 acknowledged everything up to it, so block 10 isn't
+        // streamed again even though it was committed afterwards.
+        let checkpoint = Checkpoint::open(dir.path()).unwrap();
+        assert_eq!(checkpoint.last_committed_block_height(), Some(11));
+        assert_eq!(checkpoint.resume_block_height().unwrap(), Some(12));
+    }
+
+    #[test]
+    fn test_resume_from_legacy_database() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let db = DB::open_default(dir.path()).unwrap();
+            db.put(LAST_SYNCED_BLOCK_HEIGHT_KEY, "42").unwrap();
+        }
+        let checkpoint = Checkpoint::open(dir.path()).unwrap();
+        assert_eq!(checkpoint.last_committed_block_height(), None);
+        assert_eq!(checkpoint.resume_block_height().unwrap(), Some(42));
+    }
+
+    #[test]
+    fn test_invalid_height() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let db = DB::open_default(dir.path()).unwrap();
+            db.put(LAST_SYNCED_BLOCK_HEIGHT_KEY, "not a height").unwrap();
+        }
+        let checkpoint = Checkpoint::open(dir.path()).unwrap();
+        assert!(checkpoint.resume_block_height().is_err());
+        drop(checkpoint);
+        {
+            let db = DB::open_default(dir.path()).unwrap();
+            db.put(LAST_COMMITTED_BLOCK_HEIGHT_KEY, "not a height").unwrap();
+        }
+        assert!(Checkpoint::open(dir.path()).is_err());
+    }
+}
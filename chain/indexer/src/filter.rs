@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+
+use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout};
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::views;
+
+/// Matches an account either by its exact id or by a prefix of the id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountFilter {
+    Exact(AccountId),
+    Prefix(String),
+}
+
+impl AccountFilter {
+    fn matches(&self, account_id: &AccountId) -> bool {
+        match self {
+            AccountFilter::Exact(expected) => expected == account_id,
+            AccountFilter::Prefix(prefix) => account_id.as_str().starts_with(prefix.as_str()),
+        }
+    }
+}
+
+/// Kind of an `Action`, used to select transactions and receipts by what they do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    CreateAccount,
+    DeployContract,
+    FunctionCall,
+    Transfer,
+    Stake,
+    AddKey,
+    DeleteKey,
+    DeleteAccount,
+    Delegate,
+}
+
+impl From<&views::ActionView> for ActionKind {
+    fn from(action: &views::ActionView) -> Self {
+        match action {
+            views::ActionView::CreateAccount => ActionKind::CreateAccount,
+            views::ActionView::DeployContract { .. } => ActionKind::DeployContract,
+            views::ActionView::FunctionCall { .. } => ActionKind::FunctionCall,
+            views::ActionView::Transfer { .. } => ActionKind::Transfer,
+            views::ActionView::Stake { .. } => ActionKind::Stake,
+            views::ActionView::AddKey { .. } => ActionKind::AddKey,
+            views::ActionView::DeleteKey { .. } => ActionKind::DeleteKey,
+            views::ActionView::DeleteAccount { .. } => ActionKind::DeleteAccount,
+            views::ActionView::Delegate { .. } => ActionKind::Delegate,
+        }
+    }
+}
+
+/// Selects which parts of the blocks end up in `StreamerMessage`.
+///
+/// Every empty list means "no restriction", so the default filter streams
+/// everything. Chunks, outcomes and state changes of the shards nobody is
+/// interested in are not fetched at all, the rest of the filtered out data is
+/// dropped before the message is assembled.
+#[derive(Debug, Clone, Default)]
+pub struct IndexerFilter {
+    /// Keep only transactions and receipts where the signer, predecessor or
+    /// receiver matches one of the filters, and state changes of such accounts.
+    ///
+    /// If all the filters are `AccountFilter::Exact`, only the shards of these
+    /// accounts are streamed. Transactions are included into the chunks of
+    /// the signer's shard, so transactions to these accounts signed on other
+    /// shards are then only seen through the receipts they produce.
+    pub accounts: Vec<AccountFilter>,
+    /// Keep only these shards in `StreamerMessage::shards`.
+    pub shard_ids: Vec<ShardId>,
+    /// Keep only transactions and receipts containing at least one of these actions.
+    /// Data receipts never match a non-empty list.
+    pub action_kinds: Vec<ActionKind>,
+    /// Keep only transactions and receipt execution outcomes that failed.
+    /// Receipts included into chunks carry no outcome and are not affected.
+    pub failed_outcomes_only: bool,
+}
+
+impl IndexerFilter {
+    pub(crate) fn matches_shard(&self, shard_id: ShardId) -> bool {
+        self.shard_ids.is_empty() || self.shard_ids.contains(&shard_id)
+    }
+
+    /// Returns the shards of `shard_layout` which have to be fetched to build
+    /// the filtered `StreamerMessage`.
+    pub(crate) fn shards_to_fetch(&self, shard_layout: &ShardLayout) -> Vec<ShardId> {
+        let account_shards = self.exact_accounts().map(|accounts| {
+            accounts
+                .map(|account_id| account_id_to_shard_id(account_id, shard_layout))
+                .collect::<HashSet<_>>()
+        });
+        (0..shard_layout.num_shards())
+            .filter(|shard_id| self.matches_shard(*shard_id))
+            .filter(|shard_id| account_shards.as_ref().map_or(true, |s| s.contains(shard_id)))
+            .collect()
+    }
+
+    /// Returns the accounts of the filter if it only selects exact accounts.
+    fn exact_accounts(&self) -> Option<impl Iterator<Item = &AccountId>> {
+        let all_exact =
+            self.accounts.iter().all(|filter| matches!(filter, AccountFilter::Exact(_)));
+        (!self.accounts.is_empty() && all_exact).then(|| {
+            self.accounts.iter().filter_map(|filter| match filter {
+                AccountFilter::Exact(account_id) => Some(account_id),
+                AccountFilter::Prefix(_) => None,
+            })
+        })
+    }
+
+    pub(crate) fn matches_outcome(&self, outcome: &views::ExecutionOutcomeView) -> bool {
+        !self.failed_outcomes_only
+            || matches!(outcome.status, views::ExecutionStatusView::Failure(_))
+    }
+
+    pub(crate) fn matches_transaction(&self, transaction: &views::SignedTransactionView) -> bool {
+        self.matches_accounts([&transaction.signer_id, &transaction.receiver_id])
+            && self.matches_actions(&transaction.actions)
+    }
+
+    pub(crate) fn matches_receipt(&self, receipt: &views::ReceiptView) -> bool {
+        match &receipt.receipt {
+            views::ReceiptEnumView::Action { signer_id, actions, .. } => {
+                self.matches_accounts([&receipt.predecessor_id, &receipt.receiver_id, signer_id])
+                    && self.matches_actions(actions)
+            }
+            views::ReceiptEnumView::Data { .. } => {
+                self.matches_accounts([&receipt.predecessor_id, &receipt.receiver_id])
+                    && self.action_kinds.is_empty()
+            }
+        }
+    }
+
+    pub(crate) fn matches_state_change(&self, change: &views::StateChangeWithCauseView) -> bool {
+        let account_id = match &change.value {
+            views::StateChangeValueView::AccountUpdate { account_id, .. }
+            | views::StateChangeValueView::AccountDeletion { account_id }
+            | views::StateChangeValueView::AccessKeyUpdate { account_id, .. }
+            | views::StateChangeValueView::AccessKeyDeletion { account_id, .. }
+            | views::StateChangeValueView::DataUpdate { account_id, .. }
+            | views::StateChangeValueView::DataDeletion { account_id, .. }
+            | views::StateChangeValueView::ContractCodeUpdate { account_id, .. }
+            | views::StateChangeValueView::ContractCodeDeletion { account_id } => account_id,
+        };
+        self.matches_accounts([account_id])
+    }
+
+    fn matches_accounts<'a>(&self, account_ids: impl IntoIterator<Item = &'a AccountId>) -> bool {
+        if self.accounts.is_empty() {
+            return true;
+        }
+        account_ids
+            .into_iter()
+            .any(|account_id| self.accounts.iter().any(|filter| filter.matches(account_id)))
+    }
+
+    fn matches_actions(&self, actions: &[views::ActionView]) -> bool {
+        self.action_kinds.is_empty()
+            || actions.iter().any(|action| self.action_kinds.contains(&ActionKind::from(action)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(signer_id: &str, receiver_id: &str) -> views::SignedTransactionView {
+        views::SignedTransactionView {
+            signer_id: signer_id.parse().unwrap(),
+            public_key: near_crypto::PublicKey::empty(near_crypto::KeyType::ED25519),
+            nonce: 0,
+            receiver_id: receiver_id.parse().unwrap(),
+            actions: vec![views::ActionView::Transfer { deposit: 1 }],
+            signature: near_crypto::Signature::empty(near_crypto::KeyType::ED25519),
+            hash: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_default_filter_matches_everything() {
+        let filter = IndexerFilter::default();
+        assert!(filter.matches_shard(3));
+        assert!(filter.matches_transaction(&transaction("alice.near", "bob.near")));
+    }
+
+    #[test]
+    fn test_account_filter() {
+        let filter = IndexerFilter {
+            accounts: vec![
+                AccountFilter::Exact("bob.near".parse().unwrap()),
+                AccountFilter::Prefix("app.".to_string()),
+            ],
+            ..Default::default()
+        };
+        assert!(filter.matches_transaction(&transaction("alice.near", "bob.near")));
+        assert!(filter.matches_transaction(&transaction("app.alice.near", "carol.near")));
+        assert!(!filter.matches_transaction(&transaction("alice.near", "carol.near")));
+    }
+
+    #[test]
+    fn test_shards_to_fetch() {
+        let shard_layout =
+            ShardLayout::v1(vec!["b".parse().unwrap(), "d".parse().unwrap()], None, 1);
+        assert_eq!(IndexerFilter::default().shards_to_fetch(&shard_layout), vec![0, 1, 2]);
+
+        let filter = IndexerFilter { shard_ids: vec![0, 2], ..Default::default() };
+        assert_eq!(filter.shards_to_fetch(&shard_layout), vec![0, 2]);
+
+        let filter = IndexerFilter {
+            accounts: vec![
+                AccountFilter::Exact("a.near".parse().unwrap()),
+                AccountFilter::Exact("c.near".parse().unwrap()),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(filter.shards_to_fetch(&shard_layout), vec![0, 1]);
+        let filter = IndexerFilter { shard_ids: vec![1, 2], ..filter };
+        assert_eq!(filter.shards_to_fetch(&shard_layout), vec![1]);
+
+        // Prefixes may match accounts on any shard.
+        let filter = IndexerFilter {
+            accounts: vec![
+                AccountFilter::Exact("a.near".parse().unwrap()),
+                AccountFilter::Prefix("app.".to_string()),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(filter.shards_to_fetch(&shard_layout), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_action_kind_filter() {
+        let filter =
+            IndexerFilter { action_kinds: vec![ActionKind::FunctionCall], ..Default::default() };
+        assert!(!filter.matches_transaction(&transaction("alice.near", "bob.near")));
+        let filter =
+            IndexerFilter { action_kinds: vec![ActionKind::Transfer], ..Default::default() };
+        assert!(filter.matches_transaction(&transaction("alice.near", "bob.near")));
+    }
+}
@@ -5,7 +5,7 @@ use tokio::sync::mpsc;
 
 use near_chain_configs::GenesisValidationMode;
 pub use near_primitives;
-use near_primitives::types::Gas;
+use near_primitives::types::{BlockHeight, Gas};
 pub use nearcore::{get_default_home, init_configs, NearConfig};
 
 pub use near_indexer_primitives::{
@@ -14,6 +14,11 @@ pub use near_indexer_primitives::{
     StreamerMessage,
 };
 
+pub use self::checkpoint::Checkpoint;
+pub use self::filter::{AccountFilter, ActionKind, IndexerFilter};
+
+mod checkpoint;
+mod filter;
 mod streamer;
 
 pub const INDEXER: &str = "indexer";
@@ -55,7 +60,8 @@ pub struct InitConfigArgs {
 pub enum SyncModeEnum {
     /// Real-time syncing, always taking the latest finalized block to stream
     LatestSynced,
-    /// Starts syncing from the block NEAR Indexer was interrupted last time
+    /// Starts syncing from the block following the last one committed via `Indexer::commit`.
+    /// Falls back to the last block sent to the consumer if nothing was ever committed.
     FromInterruption,
     /// Specific block height to start syncing from
     BlockHeight(u64),
@@ -81,6 +87,8 @@ pub struct IndexerConfig {
     pub await_for_node_synced: AwaitForNodeSyncedEnum,
     /// Tells whether to validate the genesis file before starting
     pub validate_genesis: bool,
+    /// Selects which shards, transactions, receipts and outcomes are streamed
+    pub filter: IndexerFilter,
 }
 
 /// This is the core component, which handles `nearcore` and internal `streamer`.
@@ -89,6 +97,7 @@ pub struct Indexer {
     near_config: nearcore::NearConfig,
     view_client: actix::Addr<near_client::ViewClientActor>,
     client: actix::Addr<near_client::ClientActor>,
+    checkpoint: Checkpoint,
 }
 
 impl Indexer {
@@ -116,26 +125,61 @@ impl Indexer {
             ",
             indexer_config.home_dir.join("config.json").display()
         );
+        let indexer_db_path = near_store::NodeStorage::opener(
+            &indexer_config.home_dir,
+            near_config.config.archive,
+            &near_config.config.store,
+            None,
+        )
+        .path()
+        .join("indexer");
+        let checkpoint = Checkpoint::open(&indexer_db_path)?;
         let nearcore::NearNode { client, view_client, .. } =
             nearcore::start_with_config(&indexer_config.home_dir, near_config.clone())
                 .with_context(|| "start_with_config")?;
-        Ok(Self { view_client, client, near_config, indexer_config })
+        Ok(Self { view_client, client, near_config, indexer_config, checkpoint })
     }
 
     /// Boots up `near_indexer::streamer`, so it monitors the new blocks with chunks, transactions, receipts, and execution outcomes inside. The returned stream handler should be drained and handled on the user side.
+    ///
+    /// The stream ends if the streamer fails to read or persist its progress in the indexer database.
     pub fn streamer(&self) -> mpsc::Receiver<StreamerMessage> {
         let (sender, receiver) = mpsc::channel(100);
-        actix::spawn(streamer::start(
+        let streamer = streamer::start(
             self.view_client.clone(),
             self.client.clone(),
             self.indexer_config.clone(),
-            self.near_config.config.store.clone(),
-            self.near_config.config.archive,
+            self.checkpoint.clone(),
             sender,
-        ));
+        );
+        actix::spawn(async move {
+            if let Err(err) = streamer.await {
+                tracing::error!(target: INDEXER, "Streamer failed, terminating the stream: {:?}", err);
+            }
+        });
         receiver
     }
 
+    /// Acknowledges that all the blocks up to and including `block_height` were processed.
+    ///
+    /// The height is persisted before returning and `SyncModeEnum::FromInterruption` resumes
+    /// streaming from the next block after a restart. Messages which were sent but not yet
+    /// committed are delivered again, see `StreamerMessage` for how to deduplicate them.
+    /// Blocks must be committed in order, see `Checkpoint::commit`.
+    pub fn commit(&self, block_height: BlockHeight) -> Result<(), anyhow::Error> {
+        self.checkpoint.commit(block_height)
+    }
+
+    /// Height of the last block acknowledged via `Indexer::commit`.
+    pub fn last_committed_block_height(&self) -> Option<BlockHeight> {
+        self.checkpoint.last_committed_block_height()
+    }
+
+    /// Handle to commit processed heights from the task that drains the stream.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.checkpoint.clone()
+    }
+
     /// Expose neard config
     pub fn near_config(&self) -> &nearcore::NearConfig {
         &self.near_config
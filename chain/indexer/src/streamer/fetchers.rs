@@ -67,15 +67,25 @@ pub(crate) async fn fetch_block(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
+/// Fetches the state changes of the block for tracked shards along with the previous values.
+/// If `shard_ids` is set, only the changes of these shards are fetched.
 pub(crate) async fn fetch_state_changes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
     epoch_id: near_primitives::types::EpochId,
-) -> Result<HashMap<near_primitives::types::ShardId, views::StateChangesView>, FailedToFetchData> {
+    shard_ids: Option<&[types::ShardId]>,
+) -> Result<
+    HashMap<near_primitives::types::ShardId, Vec<views::StateChangeWithPrevValueView>>,
+    FailedToFetchData,
+> {
     client
         .send(
-            near_client::GetStateChangesWithCauseInBlockForTrackedShards { block_hash, epoch_id }
-                .with_span_context(),
+            near_client::GetStateChangesWithPrevValueInBlockForTrackedShards {
+                block_hash,
+                epoch_id,
+                shard_ids: shard_ids.map(<[_]>::to_vec),
+            }
+            .with_span_context(),
         )
         .await?
         .map_err(|err| FailedToFetchData::String(err.to_string()))
@@ -83,15 +93,23 @@ pub(crate) async fn fetch_state_changes(
 
 /// Fetch all ExecutionOutcomeWithId for current block
 /// Returns a HashMap where the key is shard id IndexerExecutionOutcomeWithOptionalReceipt
+/// If `shard_ids` is set, only the outcomes of these shards are fetched.
 pub(crate) async fn fetch_outcomes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
+    shard_ids: Option<&[types::ShardId]>,
 ) -> Result<
     HashMap<near_primitives::types::ShardId, Vec<IndexerExecutionOutcomeWithOptionalReceipt>>,
     FailedToFetchData,
 > {
     let outcomes = client
-        .send(near_client::GetExecutionOutcomesForBlock { block_hash }.with_span_context())
+        .send(
+            near_client::GetExecutionOutcomesForBlock {
+                block_hash,
+                shard_ids: shard_ids.map(<[_]>::to_vec),
+            }
+            .with_span_context(),
+        )
         .await?
         .map_err(FailedToFetchData::String)?;
 
@@ -149,14 +167,19 @@ async fn fetch_single_chunk(
 
 /// Fetches all chunks belonging to given block.
 /// Includes transactions and receipts in custom struct (to provide more info).
+/// If `shard_ids` is set, only the chunks of these shards are fetched.
 pub(crate) async fn fetch_block_chunks(
     client: &Addr<near_client::ViewClientActor>,
     block: &views::BlockView,
+    shard_ids: Option<&[types::ShardId]>,
 ) -> Result<Vec<views::ChunkView>, FailedToFetchData> {
     let mut futures: futures::stream::FuturesUnordered<_> = block
         .chunks
         .iter()
-        .filter(|chunk| chunk.height_included == block.header.height)
+        .filter(|chunk| {
+            chunk.height_included == block.header.height
+                && shard_ids.map_or(true, |shard_ids| shard_ids.contains(&chunk.shard_id))
+        })
         .map(|chunk| fetch_single_chunk(&client, chunk.chunk_hash))
         .collect();
     let mut chunks = Vec::<views::ChunkView>::with_capacity(futures.len());
@@ -1,9 +1,9 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use actix::Addr;
 use async_recursion::async_recursion;
 use node_runtime::config::RuntimeConfig;
-use rocksdb::DB;
 use tokio::sync::mpsc;
 use tokio::time;
 use tracing::{debug, info};
@@ -14,9 +14,10 @@ use near_indexer_primitives::{
     StreamerMessage,
 };
 use near_primitives::hash::CryptoHash;
+use near_primitives::types::ShardId;
 use near_primitives::views;
 
-use crate::{AwaitForNodeSyncedEnum, IndexerConfig};
+use crate::{AwaitForNodeSyncedEnum, Checkpoint, IndexerConfig, IndexerFilter};
 
 use self::errors::FailedToFetchData;
 use self::fetchers::{
@@ -66,36 +67,48 @@ fn test_problematic_blocks_hash() {
 
 /// This function supposed to return the entire `StreamerMessage`.
 /// It fetches the block and all related parts (chunks, outcomes, state changes etc.)
-/// and returns everything together in one struct
+/// and returns everything together in one struct.
+/// Data of the shards rejected by the `filter` is not fetched, the rest of the
+/// rejected data is dropped as early as possible.
 #[async_recursion]
 async fn build_streamer_message(
     client: &Addr<near_client::ViewClientActor>,
     block: views::BlockView,
+    filter: &IndexerFilter,
 ) -> Result<StreamerMessage, FailedToFetchData> {
     let _timer = metrics::BUILD_STREAMER_MESSAGE_TIME.start_timer();
-    let chunks = fetch_block_chunks(&client, &block).await?;
-
     let protocol_config_view = fetch_protocol_config(&client, block.header.hash).await?;
     let num_shards = protocol_config_view.num_block_producer_seats_per_shard.len()
         as near_primitives::types::NumShards;
+    let shard_ids = filter.shards_to_fetch(&protocol_config_view.shard_layout);
+
+    let chunks = fetch_block_chunks(&client, &block, Some(&shard_ids)).await?;
 
     let runtime_config_store =
         near_primitives::runtime::config_store::RuntimeConfigStore::new(None);
     let runtime_config = runtime_config_store.get_config(protocol_config_view.protocol_version);
 
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash, Some(&shard_ids)).await?;
     let mut state_changes = fetch_state_changes(
         &client,
         block.header.hash,
         near_primitives::types::EpochId(block.header.epoch_id),
+        Some(&shard_ids),
     )
     .await?;
+    let mut receipts_state_changes = split_state_changes_by_receipt(&state_changes, filter);
     let mut indexer_shards = (0..num_shards)
         .map(|shard_id| IndexerShard {
             shard_id,
             chunk: None,
             receipt_execution_outcomes: vec![],
-            state_changes: state_changes.remove(&shard_id).unwrap_or_default(),
+            state_changes: state_changes
+                .remove(&shard_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|change| change.change)
+                .filter(|change| filter.matches_state_change(change))
+                .collect(),
         })
         .collect::<Vec<_>>();
 
@@ -111,6 +124,8 @@ async fn build_streamer_message(
 
         // Take execution outcomes for receipts from the vec and keep only the ones for transactions
         let mut receipt_outcomes = outcomes.split_off(transactions.len());
+        receipt_outcomes
+            .retain(|outcome| filter.matches_outcome(&outcome.execution_outcome.outcome));
 
         let indexer_transactions = transactions
             .into_iter()
@@ -184,8 +199,13 @@ async fn build_streamer_message(
                     prev_block_tried += 1;
                 }
             };
-            receipt_execution_outcomes
-                .push(IndexerExecutionOutcomeWithReceipt { execution_outcome, receipt });
+            let state_changes =
+                receipts_state_changes.remove(&execution_outcome.id).unwrap_or_default();
+            receipt_execution_outcomes.push(IndexerExecutionOutcomeWithReceipt {
+                execution_outcome,
+                receipt,
+                state_changes,
+            });
         }
 
         // Blocks #47317863 and #47317864
@@ -212,6 +232,15 @@ async fn build_streamer_message(
         }
 
         chunk_receipts.extend(chunk_non_local_receipts);
+        chunk_receipts.retain(|receipt| filter.matches_receipt(receipt));
+        receipt_execution_outcomes.retain(|outcome| filter.matches_receipt(&outcome.receipt));
+        let indexer_transactions = indexer_transactions
+            .into_iter()
+            .filter(|tx| {
+                filter.matches_transaction(&tx.transaction)
+                    && filter.matches_outcome(&tx.outcome.execution_outcome.outcome)
+            })
+            .collect();
 
         indexer_shards[shard_id].receipt_execution_outcomes = receipt_execution_outcomes;
         // Put the chunk into corresponding indexer shard
@@ -228,16 +257,48 @@ async fn build_streamer_message(
     // That might happen before the fix https://github.com/near/nearcore/pull/4228
     for (shard_id, outcomes) in shards_outcomes {
         indexer_shards[shard_id as usize].receipt_execution_outcomes.extend(
-            outcomes.into_iter().map(|outcome| IndexerExecutionOutcomeWithReceipt {
-                execution_outcome: outcome.execution_outcome,
-                receipt: outcome.receipt.expect("`receipt` must be present at this moment"),
-            }),
+            outcomes
+                .into_iter()
+                .map(|outcome| IndexerExecutionOutcomeWithReceipt {
+                    state_changes: receipts_state_changes
+                        .remove(&outcome.execution_outcome.id)
+                        .unwrap_or_default(),
+                    execution_outcome: outcome.execution_outcome,
+                    receipt: outcome.receipt.expect("`receipt` must be present at this moment"),
+                })
+                .filter(|outcome| {
+                    filter.matches_outcome(&outcome.execution_outcome.outcome)
+                        && filter.matches_receipt(&outcome.receipt)
+                }),
         )
     }
+    indexer_shards.retain(|shard| shard_ids.contains(&shard.shard_id));
 
     Ok(StreamerMessage { block, shards: indexer_shards })
 }
 
+/// Groups the state changes of the block by the receipt which caused them.
+/// Changes caused by transactions or by the protocol itself are skipped.
+fn split_state_changes_by_receipt(
+    state_changes: &HashMap<ShardId, Vec<views::StateChangeWithPrevValueView>>,
+    filter: &IndexerFilter,
+) -> HashMap<CryptoHash, Vec<views::StateChangeWithPrevValueView>> {
+    let mut receipts_state_changes: HashMap<CryptoHash, Vec<_>> = HashMap::new();
+    for state_change in state_changes.values().flatten() {
+        let receipt_hash = match state_change.change.cause {
+            views::StateChangeCauseView::ActionReceiptProcessingStarted { receipt_hash }
+            | views::StateChangeCauseView::ActionReceiptGasReward { receipt_hash }
+            | views::StateChangeCauseView::ReceiptProcessing { receipt_hash }
+            | views::StateChangeCauseView::PostponedReceipt { receipt_hash } => receipt_hash,
+            _ => continue,
+        };
+        if filter.matches_state_change(&state_change.change) {
+            receipts_state_changes.entry(receipt_hash).or_default().push(state_change.clone());
+        }
+    }
+    receipts_state_changes
+}
+
 /// Function that tries to find specific local receipt by it's ID and returns it
 /// otherwise returns None
 async fn find_local_receipt_by_id_in_block(
@@ -246,9 +307,9 @@ async fn find_local_receipt_by_id_in_block(
     block: views::BlockView,
     receipt_id: near_primitives::hash::CryptoHash,
 ) -> Result<Option<views::ReceiptView>, FailedToFetchData> {
-    let chunks = fetch_block_chunks(&client, &block).await?;
+    let chunks = fetch_block_chunks(&client, &block, None).await?;
 
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash, None).await?;
 
     for chunk in chunks {
         let views::ChunkView { header, transactions, .. } = chunk;
@@ -287,22 +348,17 @@ async fn find_local_receipt_by_id_in_block(
 /// compares to already fetched block height and in case it differs fetches new block of given height.
 ///
 /// We have to pass `client: Addr<near_client::ClientActor>` and `view_client: Addr<near_client::ViewClientActor>`.
+///
+/// Returns an error if the streaming progress can't be read from or persisted
+/// to the indexer database.
 pub(crate) async fn start(
     view_client: Addr<near_client::ViewClientActor>,
     client: Addr<near_client::ClientActor>,
     indexer_config: IndexerConfig,
-    store_config: near_store::StoreConfig,
-    archive: bool,
+    checkpoint: Checkpoint,
     blocks_sink: mpsc::Sender<StreamerMessage>,
-) {
+) -> anyhow::Result<()> {
     info!(target: INDEXER, "Starting Streamer...");
-    let indexer_db_path =
-        near_store::NodeStorage::opener(&indexer_config.home_dir, archive, &store_config, None)
-            .path()
-            .join("indexer");
-
-    // TODO: implement proper error handling
-    let db = DB::open_default(indexer_db_path).unwrap();
     let mut last_synced_block_height: Option<near_primitives::types::BlockHeight> = None;
 
     'main: loop {
@@ -326,22 +382,18 @@ pub(crate) async fn start(
         };
 
         let latest_block_height = block.header.height;
-        let start_syncing_block_height = if let Some(last_synced_block_height) =
-            last_synced_block_height
-        {
-            last_synced_block_height + 1
-        } else {
-            match indexer_config.sync_mode {
-                crate::SyncModeEnum::FromInterruption => {
-                    match db.get(b"last_synced_block_height").unwrap() {
-                        Some(value) => String::from_utf8(value).unwrap().parse::<u64>().unwrap(),
-                        None => latest_block_height,
+        let start_syncing_block_height =
+            if let Some(last_synced_block_height) = last_synced_block_height {
+                last_synced_block_height + 1
+            } else {
+                match indexer_config.sync_mode {
+                    crate::SyncModeEnum::FromInterruption => {
+                        checkpoint.resume_block_height()?.unwrap_or(latest_block_height)
                     }
+                    crate::SyncModeEnum::LatestSynced => latest_block_height,
+                    crate::SyncModeEnum::BlockHeight(height) => height,
                 }
-                crate::SyncModeEnum::LatestSynced => latest_block_height,
-                crate::SyncModeEnum::BlockHeight(height) => height,
-            }
-        };
+            };
 
         debug!(
             target: INDEXER,
@@ -354,7 +406,8 @@ pub(crate) async fn start(
         for block_height in start_syncing_block_height..=latest_block_height {
             metrics::CURRENT_BLOCK_HEIGHT.set(block_height as i64);
             if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block).await;
+                let response =
+                    build_streamer_message(&view_client, block, &indexer_config.filter).await;
 
                 match response {
                     Ok(streamer_message) => {
@@ -378,8 +431,9 @@ pub(crate) async fn start(
                     }
                 }
             }
-            db.put(b"last_synced_block_height", &block_height.to_string()).unwrap();
+            checkpoint.set_last_synced_block_height(block_height)?;
             last_synced_block_height = Some(block_height);
         }
     }
+    Ok(())
 }
@@ -0,0 +1,55 @@
+use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::views::{AccountActivityCursor, AccountActivityView};
+
+/// Number of entries returned when the request doesn't set `limit`.
+pub const DEFAULT_ACCOUNT_ACTIVITY_LIMIT: usize = 100;
+/// Maximal number of entries returned in a single response.
+pub const MAX_ACCOUNT_ACTIVITY_LIMIT: usize = 1000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcAccountActivityRequest {
+    pub account_id: AccountId,
+    /// Lowest block height to return entries for, inclusive.
+    pub from_height: Option<BlockHeight>,
+    /// Highest block height to return entries for, inclusive.
+    pub to_height: Option<BlockHeight>,
+    /// `next_cursor` of the previous response.
+    pub cursor: Option<AccountActivityCursor>,
+    /// Maximal number of entries to return, capped at
+    /// [`MAX_ACCOUNT_ACTIVITY_LIMIT`].
+    pub limit: Option<usize>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcAccountActivityResponse {
+    pub account_id: AccountId,
+    /// Transactions and receipts which touched the account, ordered by height.
+    pub activity: Vec<AccountActivityView>,
+    /// Set if there may be more entries in the requested range.  Pass it as
+    /// `cursor` to get them.
+    pub next_cursor: Option<AccountActivityCursor>,
+}
+
+#[derive(thiserror::Error, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcAccountActivityError {
+    #[error("Account activity index is not enabled on this node")]
+    IndexDisabled,
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcAccountActivityError> for crate::errors::RpcError {
+    fn from(error: RpcAccountActivityError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcAccountActivityError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
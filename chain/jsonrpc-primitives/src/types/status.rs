@@ -1,11 +1,13 @@
 #[cfg(feature = "debug_types")]
 use near_client_primitives::debug::{
-    DebugBlockStatusData, EpochInfoView, TrackedShardsView, ValidatorStatus,
+    DebugBlockStatusData, EpochInfoView, FlatStorageVerificationView, TrackedShardsView,
+    ValidatorStatus,
 };
 #[cfg(feature = "debug_types")]
 use near_primitives::views::{
-    CatchupStatusView, ChainProcessingInfo, NetworkGraphView, NetworkRoutesView, PeerStoreView,
-    RecentOutboundConnectionsView, RequestedStatePartsView, SnapshotHostsView, SyncStatusView,
+    CatchupStatusView, ChainProcessingInfo, NetworkGraphView, NetworkRoutesView,
+    PeerReputationView, PeerStoreView, RecentOutboundConnectionsView, RequestedStatePartsView,
+    SnapshotHostsView, SyncStatusView,
 };
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -34,6 +36,8 @@ pub enum DebugStatusResponse {
     RecentOutboundConnections(RecentOutboundConnectionsView),
     Routes(NetworkRoutesView),
     SnapshotHosts(SnapshotHostsView),
+    PeerReputation(PeerReputationView),
+    FlatStorageVerification(Vec<FlatStorageVerificationView>),
 }
 
 #[cfg(feature = "debug_types")]
@@ -0,0 +1,96 @@
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Finality, ShardId};
+use serde_json::Value;
+
+/// Identifier of a subscription, unique within a single WebSocket connection.
+pub type RpcSubscriptionId = u64;
+
+/// Kinds of events a WebSocket client can subscribe to.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RpcSubscriptionKind {
+    /// New chain heads.  `optimistic` finality pushes every new head, `final`
+    /// finality pushes the latest final block whenever it changes.
+    NewHeads {
+        #[serde(default)]
+        finality: Finality,
+    },
+    /// New chunks included into blocks for the given shard.
+    NewChunks {
+        shard_id: ShardId,
+        #[serde(default)]
+        finality: Finality,
+    },
+    /// Changes of the execution status of a transaction.  The subscription is
+    /// closed by the server once the `Final` status has been pushed.
+    TxStatus { tx_hash: CryptoHash, sender_account_id: AccountId },
+}
+
+impl RpcSubscriptionKind {
+    /// Name used as metric label and in diagnostic messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NewHeads { .. } => "new_heads",
+            Self::NewChunks { .. } => "new_chunks",
+            Self::TxStatus { .. } => "tx_status",
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RpcSubscribeRequest {
+    #[serde(flatten)]
+    pub kind: RpcSubscriptionKind,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RpcSubscribeResponse {
+    pub subscription_id: RpcSubscriptionId,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RpcUnsubscribeRequest {
+    pub subscription_id: RpcSubscriptionId,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RpcUnsubscribeResponse {}
+
+/// Payload of the `subscription` notification pushed to WebSocket clients.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RpcSubscriptionNotification {
+    pub subscription_id: RpcSubscriptionId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// Set when the server terminated the subscription because of an error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<crate::errors::RpcError>,
+}
+
+#[derive(thiserror::Error, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSubscriptionError {
+    #[error("Subscriptions are only available over the WebSocket endpoint")]
+    NotSupported,
+    #[error("Too many subscriptions on this connection, the limit is {limit}")]
+    TooManySubscriptions { limit: usize },
+    #[error("Subscription {subscription_id} does not exist")]
+    UnknownSubscription { subscription_id: RpcSubscriptionId },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcSubscriptionError> for crate::errors::RpcError {
+    fn from(error: RpcSubscriptionError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSubscriptionError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
@@ -0,0 +1,17 @@
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcTraceCallRequest {
+    #[serde(flatten)]
+    pub block_reference: near_primitives::types::BlockReference,
+    pub account_id: near_primitives::types::AccountId,
+    pub method_name: String,
+    #[serde(rename = "args_base64")]
+    pub args: near_primitives::types::FunctionArgs,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcTraceCallResponse {
+    #[serde(flatten)]
+    pub trace: near_primitives::views::CallTraceView,
+    pub block_height: near_primitives::types::BlockHeight,
+    pub block_hash: near_primitives::hash::CryptoHash,
+}
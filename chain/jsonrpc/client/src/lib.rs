@@ -220,6 +220,15 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "tx", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_account_activity(
+        &self,
+        request: near_jsonrpc_primitives::types::account_activity::RpcAccountActivityRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::account_activity::RpcAccountActivityResponse>
+    {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_account_activity", request)
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_changes(
         &self,
@@ -261,6 +270,24 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_split_storage_info", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_simulate_tx(
+        &self,
+        request: near_jsonrpc_primitives::types::simulate_transaction::RpcSimulateTransactionRequest,
+    ) -> RpcRequest<
+        near_jsonrpc_primitives::types::simulate_transaction::RpcSimulateTransactionResponse,
+    > {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_simulate_tx", request)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_trace_call(
+        &self,
+        request: near_jsonrpc_primitives::types::trace_call::RpcTraceCallRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::trace_call::RpcTraceCallResponse> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_trace_call", request)
+    }
+
     pub fn validators(
         &self,
         epoch_id_or_block_id: Option<EpochReference>,
@@ -8,7 +8,9 @@ use serde_json::json;
 use near_actix_test_utils::run_actix;
 use near_crypto::{KeyType, PublicKey, Signature};
 use near_jsonrpc::client::{new_client, ChunkId};
+use near_jsonrpc_primitives::types::account_activity::RpcAccountActivityRequest;
 use near_jsonrpc_primitives::types::query::QueryResponseKind;
+use near_jsonrpc_primitives::types::trace_call::RpcTraceCallRequest;
 use near_jsonrpc_primitives::types::validator::RpcValidatorsOrderedRequest;
 use near_network::test_utils::wait_or_timeout;
 use near_o11y::testonly::init_test_logger;
@@ -299,6 +301,10 @@ fn test_query_state() {
                     account_id: "test".parse().unwrap(),
                     prefix: vec![].into(),
                     include_proof: false,
+                    start_key: None,
+                    end_key: None,
+                    limit: None,
+                    cursor: None,
                 },
             })
             .await
@@ -342,6 +348,38 @@ fn test_query_call_function() {
     });
 }
 
+/// Connect to json rpc and trace a function call
+#[test]
+fn test_trace_call() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let trace_response = client
+            .EXPERIMENTAL_trace_call(RpcTraceCallRequest {
+                block_reference: BlockReference::latest(),
+                account_id: "test".parse().unwrap(),
+                method_name: "method".to_string(),
+                args: vec![].into(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(trace_response.block_height, 0);
+        assert_eq!(trace_response.trace.result.len(), 0);
+        assert_eq!(trace_response.trace.storage_reads.len(), 0);
+
+        // Traces are only served by the dedicated method.
+        let query_response = client
+            .query(near_jsonrpc_primitives::types::query::RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::TraceCall {
+                    account_id: "test".parse().unwrap(),
+                    method_name: "method".to_string(),
+                    args: vec![].into(),
+                },
+            })
+            .await;
+        assert!(query_response.is_err());
+    });
+}
+
 /// query contract code
 #[test]
 fn test_query_contract_code() {
@@ -504,6 +542,23 @@ fn test_gas_price() {
     });
 }
 
+/// The account activity index is disabled by default.
+#[test]
+fn test_account_activity_index_disabled() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let request = RpcAccountActivityRequest {
+            account_id: "test1".parse().unwrap(),
+            from_height: None,
+            to_height: None,
+            cursor: None,
+            limit: None,
+        };
+        let error = client.EXPERIMENTAL_account_activity(request).await.unwrap_err();
+        let error = serde_json::to_value(error).unwrap();
+        assert_eq!(error["cause"]["name"], json!("INDEX_DISABLED"));
+    });
+}
+
 #[test]
 fn test_invalid_methods() {
     test_with_client!(test_utils::NodeType::NonValidator, client, async move {
@@ -582,3 +637,95 @@ fn test_get_chunk_with_object_in_params() {
         assert_eq!(chunk.header.chunk_hash, same_chunk.header.chunk_hash);
     });
 }
+
+#[test]
+fn test_batch_requests() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "block", "params": {"block_id": 0}},
+            {"jsonrpc": "2.0", "id": 2, "method": "no_such_method", "params": []},
+            {"jsonrpc": "2.0", "id": 3, "method": "chunk", "params": {"block_id": 0, "shard_id": 0}},
+            true,
+        ]);
+        let response = &mut client
+            .client
+            .post(&client.server_addr)
+            .insert_header(("Content-Type", "application/json"))
+            .send_json(&batch)
+            .await
+            .unwrap();
+        let response: serde_json::Value = response.json().await.unwrap();
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["id"], json!(1));
+        assert_eq!(responses[0]["result"]["header"]["height"], json!(0));
+        assert_eq!(responses[1]["id"], json!(2));
+        assert_eq!(responses[1]["error"]["code"], json!(-32_601));
+        assert_eq!(responses[2]["id"], json!(3));
+        assert_eq!(responses[2]["result"]["header"]["shard_id"], json!(0));
+        assert_eq!(responses[3]["id"], json!(null));
+        assert_eq!(responses[3]["error"]["code"], json!(-32_600));
+    });
+}
+
+/// Notifications of a batch are processed, but not answered.
+#[test]
+fn test_batch_requests_with_notifications() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let batch = json!([
+            {"jsonrpc": "2.0", "method": "status", "params": []},
+            {"jsonrpc": "2.0", "id": 1, "method": "block", "params": {"block_id": 0}},
+            {"jsonrpc": "2.0", "method": "no_such_method", "params": []},
+        ]);
+        let response = &mut client
+            .client
+            .post(&client.server_addr)
+            .insert_header(("Content-Type", "application/json"))
+            .send_json(&batch)
+            .await
+            .unwrap();
+        let response: serde_json::Value = response.json().await.unwrap();
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["id"], json!(1));
+        assert_eq!(responses[0]["result"]["header"]["height"], json!(0));
+    });
+}
+
+/// Neither a notification nor a batch of notifications only is answered.
+#[test]
+fn test_notifications_only() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let notification = json!({"jsonrpc": "2.0", "method": "status", "params": []});
+        for message in [notification.clone(), json!([notification.clone(), notification])] {
+            let response = &mut client
+                .client
+                .post(&client.server_addr)
+                .insert_header(("Content-Type", "application/json"))
+                .send_json(&message)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), awc::http::StatusCode::NO_CONTENT);
+            assert!(response.body().await.unwrap().is_empty());
+        }
+    });
+}
+
+#[test]
+fn test_batch_requests_limits() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": "status", "params": []});
+        let default_limit = near_jsonrpc::RpcLimitsConfig::default().batch_max_size;
+        for batch in [json!([]), json!(vec![request; default_limit + 1])] {
+            let response = &mut client
+                .client
+                .post(&client.server_addr)
+                .insert_header(("Content-Type", "application/json"))
+                .send_json(&batch)
+                .await
+                .unwrap();
+            let response: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(response["error"]["code"], json!(-32_600));
+        }
+    });
+}
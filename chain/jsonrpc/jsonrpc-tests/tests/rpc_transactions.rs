@@ -7,6 +7,7 @@ use futures::{future, FutureExt, TryFutureExt};
 use near_actix_test_utils::run_actix;
 use near_crypto::{InMemorySigner, KeyType};
 use near_jsonrpc::client::new_client;
+use near_jsonrpc_primitives::types::simulate_transaction::RpcSimulateTransactionRequest;
 use near_jsonrpc_primitives::types::transactions::{RpcTransactionStatusRequest, TransactionInfo};
 use near_network::test_utils::WaitOrTimeoutActor;
 use near_o11y::testonly::{init_integration_logger, init_test_logger};
@@ -114,6 +115,33 @@ fn test_send_tx_commit() {
     });
 }
 
+/// Test simulating a transaction without sending it.
+#[test]
+fn test_simulate_tx() {
+    test_with_client!(test_utils::NodeType::Validator, client, async move {
+        let block_hash = client.block(BlockReference::latest()).await.unwrap().header.hash;
+        let signer = InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+        let tx = SignedTransaction::send_money(
+            1,
+            "test1".parse().unwrap(),
+            "test2".parse().unwrap(),
+            &signer,
+            100,
+            block_hash,
+        );
+        let tx_hash = tx.get_hash();
+        let response = client
+            .EXPERIMENTAL_simulate_tx(RpcSimulateTransactionRequest {
+                signed_transaction: tx,
+                skip_signature_check: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.simulation.failure, None);
+        assert_eq!(response.simulation.outcomes[0].id, tx_hash);
+    });
+}
+
 /// Test that expired transaction should be rejected
 #[test]
 fn test_expired_tx() {
@@ -0,0 +1,297 @@
+use actix::System;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+
+use near_actix_test_utils::run_actix;
+use near_crypto::{InMemorySigner, KeyType};
+use near_jsonrpc_primitives::message::{from_slice, Message};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::serialize::to_base64;
+use near_primitives::transaction::SignedTransaction;
+
+use near_jsonrpc_tests as test_utils;
+
+async fn connect(
+    addr: &near_network::tcp::ListenerAddr,
+) -> impl Sink<awc::ws::Message, Error = awc::error::WsProtocolError>
+       + Stream<Item = Result<awc::ws::Frame, awc::error::WsProtocolError>>
+       + Unpin {
+    let (_response, connection) =
+        awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+    connection
+}
+
+async fn send_request(
+    connection: &mut (impl Sink<awc::ws::Message, Error = awc::error::WsProtocolError> + Unpin),
+    id: u64,
+    method: &str,
+    params: Value,
+) {
+    let request = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+    connection.send(awc::ws::Message::Text(request.to_string().into())).await.unwrap();
+}
+
+async fn next_message(
+    connection: &mut (impl Stream<Item = Result<awc::ws::Frame, awc::error::WsProtocolError>> + Unpin),
+) -> Message {
+    loop {
+        match connection.next().await.unwrap().unwrap() {
+            awc::ws::Frame::Text(bytes) => return from_slice(&bytes).unwrap(),
+            awc::ws::Frame::Ping(_) | awc::ws::Frame::Pong(_) => continue,
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+    }
+}
+
+fn unwrap_response(message: Message, id: u64) -> Result<Value, Value> {
+    match message {
+        Message::Response(response) => {
+            assert_eq!(response.id, json!(id));
+            response.result.map_err(|err| serde_json::to_value(err).unwrap())
+        }
+        message => panic!("expected a response, got {:?}", message),
+    }
+}
+
+/// Subscribe to new optimistic heads and check that consecutive blocks are pushed.
+#[test]
+fn test_ws_subscribe_new_heads() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let mut connection = connect(&addr).await;
+            send_request(
+                &mut connection,
+                1,
+                "subscribe",
+                json!({"type": "new_heads", "finality": "optimistic"}),
+            )
+            .await;
+            let subscription = unwrap_response(next_message(&mut connection).await, 1).unwrap();
+            let subscription_id = subscription["subscription_id"].clone();
+
+            let mut last_height = None;
+            for _ in 0..3 {
+                let notification = match next_message(&mut connection).await {
+                    Message::Notification(notification) => notification,
+                    message => panic!("expected a notification, got {:?}", message),
+                };
+                assert_eq!(notification.method, "subscription");
+                assert_eq!(notification.params["subscription_id"], subscription_id);
+                let height = notification.params["result"]["header"]["height"].as_u64().unwrap();
+                if let Some(last_height) = last_height {
+                    assert!(height > last_height);
+                }
+                last_height = Some(height);
+            }
+
+            send_request(&mut connection, 2, "unsubscribe", json!([subscription_id])).await;
+            loop {
+                // Notifications sent before the unsubscribe was processed may still arrive.
+                if let Message::Response(_) = &next_message(&mut connection).await {
+                    break;
+                }
+            }
+            System::current().stop();
+        });
+    });
+}
+
+fn unwrap_notification(message: Message, subscription_id: &Value) -> Value {
+    match message {
+        Message::Notification(notification) => {
+            assert_eq!(notification.method, "subscription");
+            assert_eq!(&notification.params["subscription_id"], subscription_id);
+            notification.params
+        }
+        message => panic!("expected a notification, got {:?}", message),
+    }
+}
+
+/// Subscribe to new chunks of a shard and check that chunks of consecutive blocks are pushed.
+#[test]
+fn test_ws_subscribe_new_chunks() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let mut connection = connect(&addr).await;
+            send_request(
+                &mut connection,
+                1,
+                "subscribe",
+                json!({"type": "new_chunks", "shard_id": 0, "finality": "optimistic"}),
+            )
+            .await;
+            let subscription = unwrap_response(next_message(&mut connection).await, 1).unwrap();
+            let subscription_id = subscription["subscription_id"].clone();
+
+            let mut last_height = None;
+            for _ in 0..3 {
+                let params =
+                    unwrap_notification(next_message(&mut connection).await, &subscription_id);
+                let header = &params["result"]["header"];
+                assert_eq!(header["shard_id"], json!(0));
+                let height = header["height_included"].as_u64().unwrap();
+                if let Some(last_height) = last_height {
+                    assert!(height > last_height);
+                }
+                last_height = Some(height);
+            }
+            System::current().stop();
+        });
+    });
+}
+
+/// Subscribing to chunks of a non-existent shard pushes an error.
+#[test]
+fn test_ws_subscribe_new_chunks_invalid_shard() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let mut connection = connect(&addr).await;
+            send_request(
+                &mut connection,
+                1,
+                "subscribe",
+                json!({"type": "new_chunks", "shard_id": 42, "finality": "optimistic"}),
+            )
+            .await;
+            let subscription = unwrap_response(next_message(&mut connection).await, 1).unwrap();
+            let subscription_id = subscription["subscription_id"].clone();
+
+            let params = unwrap_notification(next_message(&mut connection).await, &subscription_id);
+            assert_eq!(params["error"]["cause"]["name"], json!("INVALID_SHARD_ID"));
+
+            // The subscription has ended.
+            send_request(&mut connection, 2, "unsubscribe", json!([subscription_id])).await;
+            let error = unwrap_response(next_message(&mut connection).await, 2).unwrap_err();
+            assert_eq!(error["cause"]["name"], json!("UNKNOWN_SUBSCRIPTION"));
+            System::current().stop();
+        });
+    });
+}
+
+/// Subscribe to the status of a transaction and check that its changes are pushed
+/// until the transaction becomes final.
+#[test]
+fn test_ws_subscribe_tx_status() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let mut connection = connect(&addr).await;
+            send_request(&mut connection, 1, "block", json!({"finality": "final"})).await;
+            let block = unwrap_response(next_message(&mut connection).await, 1).unwrap();
+            let block_hash = serde_json::from_value(block["header"]["hash"].clone()).unwrap();
+            let signer =
+                InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+            let tx = SignedTransaction::send_money(
+                1,
+                "test1".parse().unwrap(),
+                "test2".parse().unwrap(),
+                &signer,
+                100,
+                block_hash,
+            );
+
+            send_request(
+                &mut connection,
+                2,
+                "subscribe",
+                json!({
+                    "type": "tx_status",
+                    "tx_hash": tx.get_hash(),
+                    "sender_account_id": "test1",
+                }),
+            )
+            .await;
+            let subscription = unwrap_response(next_message(&mut connection).await, 2).unwrap();
+            let subscription_id = subscription["subscription_id"].clone();
+            send_request(
+                &mut connection,
+                3,
+                "broadcast_tx_async",
+                json!([to_base64(&borsh::to_vec(&tx).unwrap())]),
+            )
+            .await;
+
+            let mut statuses = vec![];
+            while statuses.last() != Some(&json!("FINAL")) {
+                match next_message(&mut connection).await {
+                    Message::Response(response) => {
+                        assert_eq!(response.id, json!(3));
+                        assert_eq!(response.result.unwrap(), json!(tx.get_hash()));
+                    }
+                    message => {
+                        let params = unwrap_notification(message, &subscription_id);
+                        let status = params["result"]["final_execution_status"].clone();
+                        assert!(!statuses.contains(&status), "{status} pushed twice");
+                        statuses.push(status);
+                    }
+                }
+            }
+            // The subscription has ended once the transaction became final.
+            send_request(&mut connection, 4, "unsubscribe", json!([subscription_id])).await;
+            let error = unwrap_response(next_message(&mut connection).await, 4).unwrap_err();
+            assert_eq!(error["cause"]["name"], json!("UNKNOWN_SUBSCRIPTION"));
+            System::current().stop();
+        });
+    });
+}
+
+/// Regular requests are served over the WebSocket and unknown subscriptions are rejected.
+#[test]
+fn test_ws_requests_and_unknown_subscription() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        actix::spawn(async move {
+            let mut connection = connect(&addr).await;
+            send_request(&mut connection, 1, "block", json!({"block_id": 0})).await;
+            let block = unwrap_response(next_message(&mut connection).await, 1).unwrap();
+            assert_eq!(block["header"]["height"], json!(0));
+
+            send_request(&mut connection, 2, "unsubscribe", json!({"subscription_id": 42})).await;
+            let error = unwrap_response(next_message(&mut connection).await, 2).unwrap_err();
+            assert_eq!(error["cause"]["name"], json!("UNKNOWN_SUBSCRIPTION"));
+            System::current().stop();
+        });
+    });
+}
+
+/// Subscriptions are rejected over HTTP.
+#[test]
+fn test_subscribe_over_http() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        actix::spawn(async move {
+            let client = awc::Client::new();
+            let result = test_utils::call_method::<Value>(
+                &client,
+                &format!("http://{}", addr),
+                "subscribe",
+                json!({"type": "new_heads"}),
+            )
+            .await;
+            let error = serde_json::to_value(result.unwrap_err()).unwrap();
+            assert_eq!(error["cause"]["name"], json!("NOT_SUPPORTED"));
+            System::current().stop();
+        });
+    });
+}
@@ -0,0 +1,37 @@
+use super::{Params, RpcFrom, RpcRequest};
+use near_client_primitives::types::GetAccountActivityError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::account_activity::{
+    RpcAccountActivityError, RpcAccountActivityRequest,
+};
+use serde_json::Value;
+
+impl RpcRequest for RpcAccountActivityRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcAccountActivityError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetAccountActivityError> for RpcAccountActivityError {
+    fn rpc_from(error: GetAccountActivityError) -> Self {
+        match error {
+            GetAccountActivityError::IndexDisabled => Self::IndexDisabled,
+            GetAccountActivityError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetAccountActivityError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcAccountActivityError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
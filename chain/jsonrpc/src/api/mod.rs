@@ -3,6 +3,7 @@ use serde_json::Value;
 use near_jsonrpc_primitives::errors::RpcParseError;
 use near_jsonrpc_primitives::errors::{RpcError, ServerError};
 
+mod account_activity;
 mod blocks;
 mod changes;
 mod chunks;
@@ -15,8 +16,11 @@ mod network_info;
 mod query;
 mod receipts;
 mod sandbox;
+mod simulate_transaction;
 mod split_storage;
 mod status;
+mod subscriptions;
+mod trace_call;
 mod transactions;
 mod validator;
 
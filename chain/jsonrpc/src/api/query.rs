@@ -71,6 +71,10 @@ fn parse_path_data(path: String, data: String) -> Result<RpcQueryRequest, RpcPar
             account_id,
             prefix: parse_data()?.into(),
             include_proof: false,
+            start_key: None,
+            end_key: None,
+            limit: None,
+            cursor: None,
         },
         "call" => match maybe_extra_arg {
             Some(method_name) => QueryRequest::CallFunction {
@@ -160,6 +164,9 @@ impl RpcFrom<near_primitives::views::QueryResponseKind>
             near_primitives::views::QueryResponseKind::CallResult(call_result) => {
                 Self::CallResult(call_result)
             }
+            near_primitives::views::QueryResponseKind::CallTrace(call_trace) => {
+                Self::CallTrace(call_trace)
+            }
             near_primitives::views::QueryResponseKind::AccessKey(access_key_view) => {
                 Self::AccessKey(access_key_view)
             }
@@ -0,0 +1,114 @@
+use super::{Params, RpcFrom, RpcRequest};
+use near_client_primitives::types::SimulateTransactionError;
+use near_crypto::Signature;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::simulate_transaction::{
+    RpcSimulateTransactionError, RpcSimulateTransactionRequest,
+};
+use near_primitives::borsh::BorshDeserialize;
+use near_primitives::transaction::{SignedTransaction, Transaction};
+use serde_json::Value;
+
+/// Besides a signed transaction the request may carry a borsh encoded
+/// `Transaction` without a signature, in which case the signature is not
+/// checked.
+#[derive(serde::Deserialize)]
+struct SimulateTransactionParams {
+    signed_tx_base64: Option<SignedTransaction>,
+    tx_base64: Option<String>,
+    #[serde(default)]
+    skip_signature_check: bool,
+}
+
+impl RpcRequest for RpcSimulateTransactionRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        let params: SimulateTransactionParams = Params::parse(value)?;
+        match (params.signed_tx_base64, params.tx_base64) {
+            (Some(signed_transaction), None) => {
+                Ok(Self { signed_transaction, skip_signature_check: params.skip_signature_check })
+            }
+            (None, Some(tx_base64)) => {
+                let bytes = near_primitives::serialize::from_base64(&tx_base64).map_err(|err| {
+                    RpcParseError(format!("Failed to decode transaction: {}", err))
+                })?;
+                let transaction = Transaction::try_from_slice(&bytes).map_err(|err| {
+                    RpcParseError(format!("Failed to decode transaction: {}", err))
+                })?;
+                let signature = Signature::empty(transaction.public_key.key_type());
+                Ok(Self {
+                    signed_transaction: SignedTransaction::new(signature, transaction),
+                    skip_signature_check: true,
+                })
+            }
+            _ => Err(RpcParseError(
+                "Exactly one of `signed_tx_base64` and `tx_base64` must be set".to_string(),
+            )),
+        }
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcSimulateTransactionError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<SimulateTransactionError> for RpcSimulateTransactionError {
+    fn rpc_from(error: SimulateTransactionError) -> Self {
+        match error {
+            SimulateTransactionError::NoSyncedBlocks => Self::NoSyncedBlocks,
+            SimulateTransactionError::UnavailableShard { requested_shard_id } => {
+                Self::UnavailableShard { requested_shard_id }
+            }
+            SimulateTransactionError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            SimulateTransactionError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcSimulateTransactionError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::RpcRequest;
+    use near_jsonrpc_primitives::types::simulate_transaction::RpcSimulateTransactionRequest;
+    use near_primitives::borsh;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::serialize::to_base64;
+    use near_primitives::transaction::SignedTransaction;
+
+    #[test]
+    fn test_parse_signed_tx() {
+        let tx = SignedTransaction::empty(CryptoHash::new());
+        let str_tx = to_base64(&borsh::to_vec(&tx).unwrap());
+        let params = serde_json::json!({"signed_tx_base64": str_tx});
+        let request = RpcSimulateTransactionRequest::parse(params).unwrap();
+        assert_eq!(request.signed_transaction.get_hash(), tx.get_hash());
+        assert!(!request.skip_signature_check);
+    }
+
+    #[test]
+    fn test_parse_unsigned_tx() {
+        let tx = SignedTransaction::empty(CryptoHash::new());
+        let str_tx = to_base64(&borsh::to_vec(&tx.transaction).unwrap());
+        let params = serde_json::json!({"tx_base64": str_tx});
+        let request = RpcSimulateTransactionRequest::parse(params).unwrap();
+        assert_eq!(request.signed_transaction.get_hash(), tx.get_hash());
+        assert!(request.skip_signature_check);
+    }
+
+    #[test]
+    fn test_parse_requires_one_tx() {
+        let tx = SignedTransaction::empty(CryptoHash::new());
+        let str_tx = to_base64(&borsh::to_vec(&tx).unwrap());
+        let params = serde_json::json!({"signed_tx_base64": str_tx, "tx_base64": str_tx});
+        assert!(RpcSimulateTransactionRequest::parse(params).is_err());
+        assert!(RpcSimulateTransactionRequest::parse(serde_json::json!({})).is_err());
+    }
+}
@@ -49,6 +49,11 @@ impl RpcFrom<near_client_primitives::debug::DebugStatusResponse>
                     x,
                 )
             }
+            near_client_primitives::debug::DebugStatusResponse::FlatStorageVerification(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::FlatStorageVerification(
+                    x,
+                )
+            }
         }
     }
 }
@@ -73,6 +78,9 @@ impl RpcFrom<near_network::debug::DebugStatus>
             near_network::debug::DebugStatus::SnapshotHosts(x) => {
                 near_jsonrpc_primitives::types::status::DebugStatusResponse::SnapshotHosts(x)
             }
+            near_network::debug::DebugStatus::PeerReputation(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::PeerReputation(x)
+            }
         }
     }
 }
@@ -0,0 +1,10 @@
+use super::{Params, RpcRequest};
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::trace_call::RpcTraceCallRequest;
+use serde_json::Value;
+
+impl RpcRequest for RpcTraceCallRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
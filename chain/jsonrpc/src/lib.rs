@@ -11,16 +11,19 @@ use futures::Future;
 use futures::FutureExt;
 use near_chain_configs::GenesisConfig;
 use near_client::{
-    ClientActor, DebugStatus, GetBlock, GetBlockProof, GetChunk, GetClientConfig,
-    GetExecutionOutcome, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
+    ClientActor, DebugStatus, GetAccountActivity, GetBlock, GetBlockProof, GetChunk,
+    GetClientConfig, GetExecutionOutcome, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
     GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
     GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, ProcessTxRequest,
-    ProcessTxResponse, Query, Status, TxStatus, ViewClientActor,
+    ProcessTxResponse, Query, SimulateTransaction, Status, TxStatus, ViewClientActor,
 };
 use near_client_primitives::types::GetSplitStorageInfo;
 pub use near_jsonrpc_client as client;
-use near_jsonrpc_primitives::errors::RpcError;
-use near_jsonrpc_primitives::message::{Message, Request};
+use near_jsonrpc_primitives::errors::{RpcError, RpcParseError};
+use near_jsonrpc_primitives::message::{Message, Notification, Request};
+use near_jsonrpc_primitives::types::account_activity::{
+    DEFAULT_ACCOUNT_ACTIVITY_LIMIT, MAX_ACCOUNT_ACTIVITY_LIMIT,
+};
 use near_jsonrpc_primitives::types::config::RpcProtocolConfigResponse;
 use near_jsonrpc_primitives::types::entity_debug::{EntityDebugHandler, EntityQuery};
 use near_jsonrpc_primitives::types::query::RpcQueryRequest;
@@ -35,7 +38,9 @@ use near_o11y::{WithSpanContext, WithSpanContextExt};
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{AccountId, BlockHeight};
-use near_primitives::views::{QueryRequest, TxExecutionStatus};
+use near_primitives::views::{
+    AccountActivityCursor, QueryRequest, QueryResponseKind, TxExecutionStatus,
+};
 use serde_json::{json, Value};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -45,6 +50,7 @@ use tracing::{error, info};
 
 mod api;
 mod metrics;
+mod ws;
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug)]
 pub struct RpcPollingConfig {
@@ -65,11 +71,37 @@ impl Default for RpcPollingConfig {
 pub struct RpcLimitsConfig {
     /// Maximum byte size of the json payload.
     pub json_payload_max_size: usize,
+    /// Maximum number of requests in a single JSON RPC batch.
+    #[serde(default = "default_batch_max_size")]
+    pub batch_max_size: usize,
+    /// Maximum number of simultaneously open WebSocket connections.
+    #[serde(default = "default_ws_max_connections")]
+    pub ws_max_connections: usize,
+    /// Maximum number of active subscriptions on a single WebSocket connection.
+    #[serde(default = "default_ws_max_subscriptions_per_connection")]
+    pub ws_max_subscriptions_per_connection: usize,
+}
+
+fn default_batch_max_size() -> usize {
+    100
+}
+
+fn default_ws_max_connections() -> usize {
+    100
+}
+
+fn default_ws_max_subscriptions_per_connection() -> usize {
+    32
 }
 
 impl Default for RpcLimitsConfig {
     fn default() -> Self {
-        Self { json_payload_max_size: 10 * 1024 * 1024 }
+        Self {
+            json_payload_max_size: 10 * 1024 * 1024,
+            batch_max_size: default_batch_max_size(),
+            ws_max_connections: default_ws_max_connections(),
+            ws_max_subscriptions_per_connection: default_ws_max_subscriptions_per_connection(),
+        }
     }
 }
 
@@ -222,6 +254,7 @@ struct JsonRpcHandler {
     view_client_addr: Addr<ViewClientActor>,
     peer_manager_addr: Option<Addr<PeerManagerActor>>,
     polling_config: RpcPollingConfig,
+    batch_max_size: usize,
     genesis_config: GenesisConfig,
     enable_debug_rpc: bool,
     debug_pages_src_path: Option<PathBuf>,
@@ -229,16 +262,70 @@ struct JsonRpcHandler {
 }
 
 impl JsonRpcHandler {
-    pub async fn process(&self, message: Message) -> Result<Message, HttpError> {
+    /// Processes a message.  Returns `None` if no response is due, i.e. the
+    /// message was a notification or a batch of notifications only.
+    pub async fn process(&self, message: Message) -> Result<Option<Message>, HttpError> {
         let id = message.id();
         match message {
             Message::Request(request) => {
-                Ok(Message::response(id, self.process_request(request).await))
+                Ok(Some(Message::response(id, self.process_request(request).await)))
             }
-            _ => Ok(Message::error(RpcError::parse_error(
+            Message::Notification(notification) => {
+                self.process_notification(notification).await;
+                Ok(None)
+            }
+            Message::Batch(messages) => Ok(self.process_batch(messages).await),
+            _ => Ok(Some(Message::error(RpcError::parse_error(
                 "JSON RPC Request format was expected".to_owned(),
-            ))),
+            )))),
+        }
+    }
+
+    /// Processes a notification.  As required by the JSON RPC 2.0 spec, the
+    /// client is not answered, not even with an error.
+    async fn process_notification(&self, notification: Notification) {
+        if let Err(err) = self.process_request(notification.into_request()).await {
+            tracing::debug!(target: "jsonrpc", ?err, "Failed to process notification");
+        }
+    }
+
+    /// Processes a batch of requests as defined by the JSON RPC 2.0 spec.
+    ///
+    /// Entries of the batch are processed concurrently and the responses are
+    /// returned in the order of the requests.  Notifications are processed,
+    /// but not answered, so a batch of notifications only yields no response.
+    /// Entries which are neither requests nor notifications (including nested
+    /// batches) are answered with an invalid request error.
+    async fn process_batch(&self, messages: Vec<Message>) -> Option<Message> {
+        if messages.is_empty() {
+            return Some(Message::error(RpcError::invalid_request("Empty batch".to_owned())));
+        }
+        if messages.len() > self.batch_max_size {
+            return Some(Message::error(RpcError::invalid_request(format!(
+                "Batch of {} requests exceeds the limit of {} requests",
+                messages.len(),
+                self.batch_max_size
+            ))));
         }
+        metrics::RPC_BATCH_SIZE.observe(messages.len() as f64);
+        let responses = futures::future::join_all(messages.into_iter().map(|message| async move {
+            match message {
+                Message::Request(request) => {
+                    let id = request.id.clone();
+                    Some(Message::response(id, self.process_request(request).await))
+                }
+                Message::Notification(notification) => {
+                    self.process_notification(notification).await;
+                    None
+                }
+                _ => Some(Message::error(RpcError::invalid_request(
+                    "JSON RPC Request format was expected".to_owned(),
+                ))),
+            }
+        }))
+        .await;
+        let responses: Vec<_> = responses.into_iter().flatten().collect();
+        (!responses.is_empty()).then_some(Message::Batch(responses))
     }
 
     // `process_request` increments affected metrics but the request processing is done by
@@ -298,6 +385,12 @@ impl JsonRpcHandler {
                     QueryRequest::ViewAccessKey { .. } => "query_view_access_key",
                     QueryRequest::ViewAccessKeyList { .. } => "query_view_access_key_list",
                     QueryRequest::CallFunction { .. } => "query_call_function",
+                    QueryRequest::TraceCall { .. } => {
+                        let err = RpcParseError(
+                            "Use EXPERIMENTAL_trace_call method to trace calls".to_string(),
+                        );
+                        return (method_name, Err(RpcError::from(err)));
+                    }
                 };
                 (metrics_name.to_string(), process_query_response(self.query(params).await))
             }
@@ -339,6 +432,10 @@ impl JsonRpcHandler {
             "network_info" => process_method_call(request, |_params: ()| self.network_info()).await,
             "send_tx" => process_method_call(request, |params| self.send_tx(params)).await,
             "status" => process_method_call(request, |_params: ()| self.status()).await,
+            "subscribe" | "unsubscribe" => Err(
+                near_jsonrpc_primitives::types::subscriptions::RpcSubscriptionError::NotSupported
+                    .into(),
+            ),
             "tx" => {
                 process_method_call(request, |params| self.tx_status_common(params, false)).await
             }
@@ -346,6 +443,9 @@ impl JsonRpcHandler {
             "client_config" => {
                 process_method_call(request, |_params: ()| self.client_config()).await
             }
+            "EXPERIMENTAL_account_activity" => {
+                process_method_call(request, |params| self.account_activity(params)).await
+            }
             "EXPERIMENTAL_changes" => {
                 process_method_call(request, |params| self.changes_in_block_by_type(params)).await
             }
@@ -370,6 +470,12 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_receipt" => {
                 process_method_call(request, |params| self.receipt(params)).await
             }
+            "EXPERIMENTAL_simulate_tx" => {
+                process_method_call(request, |params| self.simulate_transaction(params)).await
+            }
+            "EXPERIMENTAL_trace_call" => {
+                process_method_call(request, |params| self.trace_call(params)).await
+            }
             "EXPERIMENTAL_tx_status" => {
                 process_method_call(request, |params| self.tx_status_common(params, true)).await
             }
@@ -749,6 +855,9 @@ impl JsonRpcHandler {
                     "/debug/api/requested_state_parts" => {
                         self.client_send(DebugStatus::RequestedStateParts).await?.rpc_into()
                     }
+                    "/debug/api/flat_storage_verification" => {
+                        self.client_send(DebugStatus::FlatStorageVerification).await?.rpc_into()
+                    }
                     "/debug/api/peer_store" => self
                         .peer_manager_send(near_network::debug::GetDebugStatus::PeerStore)
                         .await?
@@ -771,6 +880,10 @@ impl JsonRpcHandler {
                         .peer_manager_send(near_network::debug::GetDebugStatus::SnapshotHosts)
                         .await?
                         .rpc_into(),
+                    "/debug/api/peer_reputation" => self
+                        .peer_manager_send(near_network::debug::GetDebugStatus::PeerReputation)
+                        .await?
+                        .rpc_into(),
                     _ => return Ok(None),
                 };
             Ok(Some(near_jsonrpc_primitives::types::status::RpcDebugStatusResponse {
@@ -824,6 +937,55 @@ impl JsonRpcHandler {
         Ok(query_response.rpc_into())
     }
 
+    async fn trace_call(
+        &self,
+        request_data: near_jsonrpc_primitives::types::trace_call::RpcTraceCallRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::trace_call::RpcTraceCallResponse,
+        near_jsonrpc_primitives::types::query::RpcQueryError,
+    > {
+        let query_response = self
+            .view_client_send(Query::new(
+                request_data.block_reference,
+                QueryRequest::TraceCall {
+                    account_id: request_data.account_id,
+                    method_name: request_data.method_name,
+                    args: request_data.args,
+                },
+            ))
+            .await?;
+        match query_response.kind {
+            QueryResponseKind::CallTrace(trace) => {
+                Ok(near_jsonrpc_primitives::types::trace_call::RpcTraceCallResponse {
+                    trace,
+                    block_height: query_response.block_height,
+                    block_hash: query_response.block_hash,
+                })
+            }
+            kind => Err(near_jsonrpc_primitives::types::query::RpcQueryError::InternalError {
+                error_message: format!("Unexpected response to trace call: {:?}", kind),
+            }),
+        }
+    }
+
+    async fn simulate_transaction(
+        &self,
+        request_data: near_jsonrpc_primitives::types::simulate_transaction::RpcSimulateTransactionRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::simulate_transaction::RpcSimulateTransactionResponse,
+        near_jsonrpc_primitives::types::simulate_transaction::RpcSimulateTransactionError,
+    > {
+        let simulation = self
+            .view_client_send(SimulateTransaction {
+                transaction: request_data.signed_transaction,
+                verify_signature: !request_data.skip_signature_check,
+            })
+            .await?;
+        Ok(near_jsonrpc_primitives::types::simulate_transaction::RpcSimulateTransactionResponse {
+            simulation,
+        })
+    }
+
     async fn tx_status_common(
         &self,
         request_data: near_jsonrpc_primitives::types::transactions::RpcTransactionStatusRequest,
@@ -861,6 +1023,38 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::chunks::RpcChunkResponse { chunk_view })
     }
 
+    async fn account_activity(
+        &self,
+        request_data: near_jsonrpc_primitives::types::account_activity::RpcAccountActivityRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::account_activity::RpcAccountActivityResponse,
+        near_jsonrpc_primitives::types::account_activity::RpcAccountActivityError,
+    > {
+        let limit = request_data
+            .limit
+            .unwrap_or(DEFAULT_ACCOUNT_ACTIVITY_LIMIT)
+            .min(MAX_ACCOUNT_ACTIVITY_LIMIT);
+        let activity = self
+            .view_client_send(GetAccountActivity {
+                account_id: request_data.account_id.clone(),
+                from_height: request_data.from_height,
+                to_height: request_data.to_height,
+                after: request_data.cursor,
+                limit,
+            })
+            .await?;
+        // A full page means there may be more entries.
+        let next_cursor = match activity.last() {
+            Some(last) if activity.len() == limit => Some(AccountActivityCursor::after(last)),
+            _ => None,
+        };
+        Ok(near_jsonrpc_primitives::types::account_activity::RpcAccountActivityResponse {
+            account_id: request_data.account_id,
+            activity,
+            next_cursor,
+        })
+    }
+
     async fn receipt(
         &self,
         request_data: near_jsonrpc_primitives::types::receipts::RpcReceiptRequest,
@@ -1284,8 +1478,11 @@ fn rpc_handler(
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
     let response = async move {
-        let message = handler.process(message.0).await?;
-        Ok(HttpResponse::Ok().json(&message))
+        match handler.process(message.0).await? {
+            Some(message) => Ok(HttpResponse::Ok().json(&message)),
+            // Notifications are not answered.
+            None => Ok(HttpResponse::NoContent().finish()),
+        }
     };
     response.boxed()
 }
@@ -1444,6 +1641,9 @@ async fn display_debug_html(
         "sync.css" => Some(debug_page_string!("sync.css", handler)),
         "validator" => Some(debug_page_string!("validator.html", handler)),
         "validator.css" => Some(debug_page_string!("validator.css", handler)),
+        "flat_storage_verification" => {
+            Some(debug_page_string!("flat_storage_verification.html", handler))
+        }
         _ => None,
     };
 
@@ -1485,8 +1685,12 @@ pub fn start_http(
     } = config;
     let prometheus_addr = prometheus_addr.filter(|it| it != &addr.to_string());
     let cors_allowed_origins_clone = cors_allowed_origins.clone();
+    let subscription_hub = Arc::new(ws::SubscriptionHub::new(&limits_config));
     info!(target:"network", "Starting http server at {}", addr);
     let mut servers = Vec::new();
+    let hub_client_addr = client_addr.clone();
+    let hub_view_client_addr = view_client_addr.clone();
+    let hub = subscription_hub.clone();
     let listener = HttpServer::new(move || {
         App::new()
             .wrap(get_cors(&cors_allowed_origins))
@@ -1495,14 +1699,17 @@ pub fn start_http(
                 view_client_addr: view_client_addr.clone(),
                 peer_manager_addr: peer_manager_addr.clone(),
                 polling_config,
+                batch_max_size: limits_config.batch_max_size,
                 genesis_config: genesis_config.clone(),
                 enable_debug_rpc,
                 debug_pages_src_path: debug_pages_src_path.clone().map(Into::into),
                 entity_debug_handler: entity_debug_handler.clone(),
             }))
+            .app_data(web::Data::from(hub.clone()))
             .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to(rpc_handler)))
+            .service(web::resource("/ws").route(web::get().to(ws::ws_handler)))
             .service(
                 web::resource("/status")
                     .route(web::get().to(status_handler))
@@ -1533,6 +1740,7 @@ pub fn start_http(
             let server = s.workers(4).shutdown_timeout(5).disable_signals().run();
             servers.push(("JSON RPC", server.handle()));
             tokio::spawn(server);
+            tokio::spawn(subscription_hub.run(hub_client_addr, hub_view_client_addr));
         }
         std::result::Result::Err(e) => {
             error!(
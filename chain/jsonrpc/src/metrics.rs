@@ -1,4 +1,6 @@
-use near_o11y::metrics::{exponential_buckets, HistogramVec, IntCounter, IntCounterVec};
+use near_o11y::metrics::{
+    exponential_buckets, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
 use once_cell::sync::Lazy;
 
 pub static RPC_PROCESSING_TIME: Lazy<HistogramVec> = Lazy::new(|| {
@@ -10,6 +12,14 @@ pub static RPC_PROCESSING_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static RPC_BATCH_SIZE: Lazy<Histogram> = Lazy::new(|| {
+    near_o11y::metrics::try_create_histogram_with_buckets(
+        "near_rpc_batch_size",
+        "Number of requests in JSON RPC batches",
+        exponential_buckets(1.0, 2.0, 12).unwrap(),
+    )
+    .unwrap()
+});
 pub static RPC_TIMEOUT_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
     near_o11y::metrics::try_create_int_counter(
         "near_rpc_timeout_total",
@@ -55,3 +65,18 @@ pub static RPC_UNREACHABLE_ERROR_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static RPC_WS_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_gauge(
+        "near_rpc_ws_connections",
+        "Number of open WebSocket connections",
+    )
+    .unwrap()
+});
+pub static RPC_WS_SUBSCRIPTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_gauge_vec(
+        "near_rpc_ws_subscriptions",
+        "Number of active WebSocket subscriptions, by kind",
+        &["kind"],
+    )
+    .unwrap()
+});
@@ -0,0 +1,543 @@
+//! WebSocket transport for the JSON RPC server.
+//!
+//! Regular JSON RPC requests sent over a WebSocket connection are processed
+//! exactly like the ones sent over HTTP.  On top of that, WebSocket clients
+//! may `subscribe` to new heads, new chunks of a shard and transaction status
+//! changes, and `unsubscribe` from them.  Events are pushed to the client as
+//! `subscription` notifications:
+//!
+//! ```json
+//! {"jsonrpc": "2.0", "method": "subscription", "params": {"subscription_id": 0, "result": {...}}}
+//! ```
+//!
+//! Events are pushed by the `ClientActor`, which notifies a single
+//! [`SubscriptionHub`] task shared by all connections whenever a new chain
+//! head is accepted.  The hub fetches the new blocks from the
+//! `ViewClientActor` once for all the subscribers, so the cost of following
+//! the chain does not grow with the number of subscribers.  Transaction status
+//! subscriptions re-check the status on every new head.  Number of
+//! connections and of subscriptions per connection is bounded by
+//! [`RpcLimitsConfig`].
+
+use crate::{metrics, JsonRpcHandler, RpcLimitsConfig};
+use actix::{
+    Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, Handler, SpawnHandle, StreamHandler,
+    WrapFuture,
+};
+use actix_web::{web, Error as HttpError, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use near_client::{ClientActor, GetBlock, GetChunk, SubscribeChainHead, TxStatus, ViewClientActor};
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{self, Message, Request};
+use near_jsonrpc_primitives::types::chunks::RpcChunkError;
+use near_jsonrpc_primitives::types::subscriptions::{
+    RpcSubscribeRequest, RpcSubscribeResponse, RpcSubscriptionError, RpcSubscriptionId,
+    RpcSubscriptionKind, RpcSubscriptionNotification, RpcUnsubscribeRequest,
+    RpcUnsubscribeResponse,
+};
+use near_jsonrpc_primitives::types::transactions::{RpcTransactionError, RpcTransactionResponse};
+use near_o11y::WithSpanContextExt;
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::types::{AccountId, BlockHeight, BlockId, BlockReference, Finality, ShardId};
+use near_primitives::views::{BlockView, TxExecutionStatus};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
+
+/// How many heads may be buffered for a subscriber before it starts missing
+/// them.
+const HEADS_CHANNEL_CAPACITY: usize = 64;
+
+/// Maximum number of blocks fetched to fill a gap between two consecutive
+/// heads of a finality level.  Larger gaps (e.g. after the node was syncing) are not
+/// backfilled.
+const MAX_HEADS_BACKFILL: usize = 16;
+
+/// Name of the JSON RPC notification carrying subscription events.
+const SUBSCRIPTION_NOTIFICATION_METHOD: &str = "subscription";
+
+/// State shared by all WebSocket connections of a JSON RPC server.
+pub(crate) struct SubscriptionHub {
+    optimistic_heads: broadcast::Sender<Arc<BlockView>>,
+    doomslug_heads: broadcast::Sender<Arc<BlockView>>,
+    final_heads: broadcast::Sender<Arc<BlockView>>,
+    /// Hash of the latest chain head, updated once the heads above have been
+    /// published.
+    chain_head: watch::Sender<Option<CryptoHash>>,
+    connections: AtomicUsize,
+    max_connections: usize,
+    max_subscriptions_per_connection: usize,
+    max_frame_size: usize,
+}
+
+impl SubscriptionHub {
+    pub(crate) fn new(limits_config: &RpcLimitsConfig) -> Self {
+        Self {
+            optimistic_heads: broadcast::channel(HEADS_CHANNEL_CAPACITY).0,
+            doomslug_heads: broadcast::channel(HEADS_CHANNEL_CAPACITY).0,
+            final_heads: broadcast::channel(HEADS_CHANNEL_CAPACITY).0,
+            chain_head: watch::channel(None).0,
+            connections: AtomicUsize::new(0),
+            max_connections: limits_config.ws_max_connections,
+            max_subscriptions_per_connection: limits_config.ws_max_subscriptions_per_connection,
+            max_frame_size: limits_config.json_payload_max_size,
+        }
+    }
+
+    fn heads(&self, finality: &Finality) -> &broadcast::Sender<Arc<BlockView>> {
+        match finality {
+            Finality::None => &self.optimistic_heads,
+            Finality::DoomSlug => &self.doomslug_heads,
+            Finality::Final => &self.final_heads,
+        }
+    }
+
+    /// Reserves a connection slot.  Returns `false` if the limit of
+    /// connections has been reached.
+    fn try_acquire_connection(&self) -> bool {
+        self.connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |connections| {
+                (connections < self.max_connections).then_some(connections + 1)
+            })
+            .is_ok()
+    }
+
+    fn release_connection(&self) {
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Waits for the client to accept a new chain head, then publishes the new
+    /// blocks of every finality level which has at least one subscriber.
+    ///
+    /// Runs until the client or the view client actor goes away.
+    pub(crate) async fn run(
+        self: Arc<Self>,
+        client_addr: Addr<ClientActor>,
+        view_client_addr: Addr<ViewClientActor>,
+    ) {
+        let Ok(mut client_chain_head) =
+            client_addr.send(SubscribeChainHead.with_span_context()).await
+        else {
+            return;
+        };
+        let mut last_heads: [Option<(CryptoHash, BlockHeight)>; 3] = [None; 3];
+        let finalities = [Finality::None, Finality::DoomSlug, Finality::Final];
+        loop {
+            if client_chain_head.changed().await.is_err() {
+                return;
+            }
+            let chain_head = *client_chain_head.borrow_and_update();
+            for (finality, last_head) in finalities.iter().zip(last_heads.iter_mut()) {
+                let sender = self.heads(finality);
+                if sender.receiver_count() == 0 {
+                    *last_head = None;
+                    continue;
+                }
+                let head = match view_client_addr
+                    .send(GetBlock(BlockReference::Finality(finality.clone())).with_span_context())
+                    .await
+                {
+                    Ok(Ok(head)) => head,
+                    Ok(Err(err)) => {
+                        tracing::debug!(target: "jsonrpc", ?finality, ?err, "Failed to fetch head");
+                        continue;
+                    }
+                    Err(_) => return,
+                };
+                if last_head.map(|(hash, _)| hash) == Some(head.header.hash) {
+                    continue;
+                }
+                let new_head = (head.header.hash, head.header.height);
+                for block in fetch_new_blocks(&view_client_addr, head, *last_head).await {
+                    // An error only means that all subscribers are gone.
+                    let _ = sender.send(Arc::new(block));
+                }
+                *last_head = Some(new_head);
+            }
+            self.chain_head.send_replace(chain_head);
+        }
+    }
+}
+
+/// Returns `head` preceded by its ancestors which were produced since
+/// `last_head`, oldest first.
+async fn fetch_new_blocks(
+    view_client_addr: &Addr<ViewClientActor>,
+    head: BlockView,
+    last_head: Option<(CryptoHash, BlockHeight)>,
+) -> Vec<BlockView> {
+    let mut blocks = vec![head];
+    if let Some((last_hash, last_height)) = last_head {
+        while blocks.len() < MAX_HEADS_BACKFILL {
+            let oldest = &blocks.last().unwrap().header;
+            if oldest.prev_hash == last_hash || oldest.height <= last_height + 1 {
+                break;
+            }
+            let block_reference = BlockReference::BlockId(BlockId::Hash(oldest.prev_hash));
+            match view_client_addr.send(GetBlock(block_reference).with_span_context()).await {
+                Ok(Ok(block)) => blocks.push(block),
+                _ => break,
+            }
+        }
+    }
+    blocks.reverse();
+    blocks
+}
+
+/// Handles `GET /ws` by upgrading the connection to a WebSocket.
+pub(crate) async fn ws_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+    hub: web::Data<SubscriptionHub>,
+) -> Result<HttpResponse, HttpError> {
+    let max_frame_size = hub.max_frame_size;
+    let session = WsSession {
+        handler,
+        hub: hub.into_inner(),
+        has_connection_slot: false,
+        next_subscription_id: 0,
+        subscriptions: HashMap::new(),
+    };
+    ws::WsResponseBuilder::new(session, &req, stream).frame_size(max_frame_size).start()
+}
+
+/// Pushes an event of a subscription to the WebSocket client.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+struct Notify(RpcSubscriptionNotification);
+
+/// A single WebSocket connection.
+struct WsSession {
+    handler: web::Data<JsonRpcHandler>,
+    hub: Arc<SubscriptionHub>,
+    /// Whether the session holds one of the connection slots of the hub and
+    /// needs to release it once stopped.
+    has_connection_slot: bool,
+    next_subscription_id: RpcSubscriptionId,
+    subscriptions: HashMap<RpcSubscriptionId, (&'static str, SpawnHandle)>,
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if !self.hub.try_acquire_connection() {
+            tracing::debug!(target: "jsonrpc", "Too many WebSocket connections, rejecting");
+            ctx.close(Some(ws::CloseReason {
+                code: ws::CloseCode::Again,
+                description: Some("too many connections".to_string()),
+            }));
+            ctx.stop();
+            return;
+        }
+        self.has_connection_slot = true;
+        metrics::RPC_WS_CONNECTIONS.inc();
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        for (name, _) in self.subscriptions.values() {
+            metrics::RPC_WS_SUBSCRIPTIONS.with_label_values(&[name]).dec();
+        }
+        if self.has_connection_slot {
+            self.hub.release_connection();
+            metrics::RPC_WS_CONNECTIONS.dec();
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Text(text)) => self.handle_payload(text.as_bytes(), ctx),
+            Ok(ws::Message::Binary(bytes)) => self.handle_payload(&bytes, ctx),
+            Ok(ws::Message::Ping(payload)) => ctx.pong(&payload),
+            Ok(ws::Message::Pong(_)) | Ok(ws::Message::Nop) => {}
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(ws::Message::Continuation(_)) => {
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Unsupported,
+                    description: Some("fragmented messages are not supported".to_string()),
+                }));
+                ctx.stop();
+            }
+            Err(err) => {
+                tracing::debug!(target: "jsonrpc", ?err, "WebSocket protocol error");
+                ctx.stop();
+            }
+        }
+    }
+}
+
+impl Handler<Notify> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: Notify, ctx: &mut Self::Context) {
+        match serde_json::to_value(msg.0) {
+            Ok(params) => send_message(
+                ctx,
+                &Message::notification(SUBSCRIPTION_NOTIFICATION_METHOD.to_string(), params),
+            ),
+            Err(err) => {
+                tracing::warn!(target: "jsonrpc", ?err, "Failed to serialize subscription event")
+            }
+        }
+    }
+}
+
+fn send_message(ctx: &mut ws::WebsocketContext<WsSession>, message: &Message) {
+    match serde_json::to_string(message) {
+        Ok(text) => ctx.text(text),
+        Err(err) => tracing::warn!(target: "jsonrpc", ?err, "Failed to serialize message"),
+    }
+}
+
+impl WsSession {
+    fn handle_payload(&mut self, payload: &[u8], ctx: &mut ws::WebsocketContext<Self>) {
+        let message = match message::from_slice(payload) {
+            Ok(message) => message,
+            Err(broken) => return send_message(ctx, &broken.reply()),
+        };
+        match message {
+            Message::Request(request)
+                if request.method == "subscribe" || request.method == "unsubscribe" =>
+            {
+                let response = self.process_subscription_request(&request, ctx);
+                send_message(ctx, &Message::response(request.id, response));
+            }
+            message => {
+                let handler = self.handler.clone();
+                ctx.spawn(async move { handler.process(message).await }.into_actor(self).map(
+                    |response, _act, ctx| match response {
+                        Ok(Some(message)) => send_message(ctx, &message),
+                        Ok(None) => {}
+                        Err(err) => {
+                            tracing::debug!(target: "jsonrpc", ?err, "Failed to process request")
+                        }
+                    },
+                ));
+            }
+        }
+    }
+
+    fn process_subscription_request(
+        &mut self,
+        request: &Request,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<Value, RpcError> {
+        let response = match request.method.as_str() {
+            "subscribe" => {
+                let request: RpcSubscribeRequest =
+                    crate::api::RpcRequest::parse(request.params.clone())?;
+                serde_json::to_value(self.subscribe(request.kind, ctx)?)
+            }
+            _ => {
+                let request: RpcUnsubscribeRequest =
+                    crate::api::RpcRequest::parse(request.params.clone())?;
+                serde_json::to_value(self.unsubscribe(request.subscription_id, ctx)?)
+            }
+        };
+        response.map_err(|err| RpcError::serialization_error(err.to_string()))
+    }
+
+    fn subscribe(
+        &mut self,
+        kind: RpcSubscriptionKind,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<RpcSubscribeResponse, RpcSubscriptionError> {
+        let limit = self.hub.max_subscriptions_per_connection;
+        if self.subscriptions.len() >= limit {
+            return Err(RpcSubscriptionError::TooManySubscriptions { limit });
+        }
+        let subscription_id = self.next_subscription_id;
+        self.next_subscription_id += 1;
+
+        let name = kind.name();
+        let events = SubscriptionEvents { subscription_id, session: ctx.address() };
+        let task: LocalBoxFuture<'static, ()> = match kind {
+            RpcSubscriptionKind::NewHeads { finality } => {
+                events.stream_heads(self.hub.heads(&finality).subscribe()).boxed_local()
+            }
+            RpcSubscriptionKind::NewChunks { shard_id, finality } => events
+                .stream_chunks(
+                    self.handler.clone(),
+                    self.hub.heads(&finality).subscribe(),
+                    shard_id,
+                )
+                .boxed_local(),
+            RpcSubscriptionKind::TxStatus { tx_hash, sender_account_id } => events
+                .stream_tx_status(
+                    self.handler.clone(),
+                    self.hub.chain_head.subscribe(),
+                    tx_hash,
+                    sender_account_id,
+                )
+                .boxed_local(),
+        };
+        // Tasks end on their own only when there will be no more events, e.g.
+        // a transaction reached its final status.
+        let handle = ctx.spawn(task.into_actor(self).map(move |(), act, _ctx| {
+            act.remove_subscription(subscription_id);
+        }));
+        self.subscriptions.insert(subscription_id, (name, handle));
+        metrics::RPC_WS_SUBSCRIPTIONS.with_label_values(&[name]).inc();
+        Ok(RpcSubscribeResponse { subscription_id })
+    }
+
+    fn unsubscribe(
+        &mut self,
+        subscription_id: RpcSubscriptionId,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<RpcUnsubscribeResponse, RpcSubscriptionError> {
+        let handle = self
+            .remove_subscription(subscription_id)
+            .ok_or(RpcSubscriptionError::UnknownSubscription { subscription_id })?;
+        ctx.cancel_future(handle);
+        Ok(RpcUnsubscribeResponse {})
+    }
+
+    fn remove_subscription(&mut self, subscription_id: RpcSubscriptionId) -> Option<SpawnHandle> {
+        let (name, handle) = self.subscriptions.remove(&subscription_id)?;
+        metrics::RPC_WS_SUBSCRIPTIONS.with_label_values(&[name]).dec();
+        Some(handle)
+    }
+}
+
+/// Producer side of a single subscription.
+struct SubscriptionEvents {
+    subscription_id: RpcSubscriptionId,
+    session: Addr<WsSession>,
+}
+
+impl SubscriptionEvents {
+    /// Pushes an event to the client.  Returns `false` if the connection is
+    /// gone and the subscription should end.
+    async fn push(&self, result: impl serde::Serialize) -> bool {
+        let subscription_id = self.subscription_id;
+        let notification = match serde_json::to_value(result) {
+            Ok(result) => {
+                RpcSubscriptionNotification { subscription_id, result: Some(result), error: None }
+            }
+            Err(err) => RpcSubscriptionNotification {
+                subscription_id,
+                result: None,
+                error: Some(RpcError::serialization_error(err.to_string())),
+            },
+        };
+        self.notify(notification).await
+    }
+
+    /// Pushes an error to the client.  The subscription ends afterwards.
+    async fn push_error(&self, error: RpcError) {
+        self.notify(RpcSubscriptionNotification {
+            subscription_id: self.subscription_id,
+            result: None,
+            error: Some(error),
+        })
+        .await;
+    }
+
+    async fn notify(&self, notification: RpcSubscriptionNotification) -> bool {
+        self.session.send(Notify(notification)).await.is_ok()
+    }
+
+    async fn stream_heads(self, mut heads: broadcast::Receiver<Arc<BlockView>>) {
+        while let Some(block) = recv_head(&mut heads).await {
+            if !self.push(block.as_ref()).await {
+                break;
+            }
+        }
+    }
+
+    async fn stream_chunks(
+        self,
+        handler: web::Data<JsonRpcHandler>,
+        mut heads: broadcast::Receiver<Arc<BlockView>>,
+        shard_id: ShardId,
+    ) {
+        while let Some(block) = recv_head(&mut heads).await {
+            let Some(chunk) = block.chunks.iter().find(|chunk| chunk.shard_id == shard_id) else {
+                let error = RpcChunkError::InvalidShardId { shard_id };
+                return self.push_error(error.into()).await;
+            };
+            // Blocks without a new chunk for the shard carry the old header.
+            if chunk.height_included != block.header.height {
+                continue;
+            }
+            let chunk_view = handler
+                .view_client_send::<_, _, RpcChunkError, _>(GetChunk::ChunkHash(ChunkHash(
+                    chunk.chunk_hash,
+                )))
+                .await;
+            let pushed = match chunk_view {
+                Ok(chunk_view) => self.push(chunk_view).await,
+                Err(err) => {
+                    tracing::debug!(target: "jsonrpc", ?err, "Failed to fetch chunk");
+                    continue;
+                }
+            };
+            if !pushed {
+                break;
+            }
+        }
+    }
+
+    /// Pushes the status of the transaction and then its changes, checking it
+    /// again on every new chain head.
+    async fn stream_tx_status(
+        self,
+        handler: web::Data<JsonRpcHandler>,
+        mut chain_head: watch::Receiver<Option<CryptoHash>>,
+        tx_hash: CryptoHash,
+        signer_account_id: AccountId,
+    ) {
+        let mut last_status = None;
+        loop {
+            let tx_status = handler
+                .view_client_send::<_, _, RpcTransactionError, _>(TxStatus {
+                    tx_hash,
+                    signer_account_id: signer_account_id.clone(),
+                    fetch_receipt: false,
+                })
+                .await;
+            match tx_status {
+                Ok(tx_status) if Some(&tx_status.status) != last_status.as_ref() => {
+                    let status = tx_status.status.clone();
+                    if !self.push(RpcTransactionResponse::from(tx_status)).await
+                        || status == TxExecutionStatus::Final
+                    {
+                        break;
+                    }
+                    last_status = Some(status);
+                }
+                Ok(_) | Err(RpcTransactionError::UnknownTransaction { .. }) => {}
+                Err(err) => return self.push_error(err.into()).await,
+            }
+            if chain_head.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Receives the next head, skipping over the ones missed because the
+/// subscriber was too slow.
+async fn recv_head(heads: &mut broadcast::Receiver<Arc<BlockView>>) -> Option<Arc<BlockView>> {
+    loop {
+        match heads.recv().await {
+            Ok(block) => return Some(block),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::debug!(target: "jsonrpc", skipped, "WebSocket subscriber lagged");
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
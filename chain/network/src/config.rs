@@ -1,5 +1,7 @@
 use crate::blacklist;
 use crate::concurrency::rate;
+use crate::config_json::TransportEncryption;
+use crate::network_protocol::MessageClass;
 use crate::network_protocol::PeerAddr;
 use crate::network_protocol::PeerInfo;
 use crate::peer_manager::peer_manager_actor::Event;
@@ -89,6 +91,28 @@ pub struct Tier1 {
     pub enable_outbound: bool,
 }
 
+/// Limits of the rate at which messages of each `MessageClass` are sent to a single peer,
+/// with `qps` in bytes per second and `burst` in bytes.
+/// Messages of a class without a limit are sent as fast as the connection allows.
+#[derive(Clone, Copy, Default)]
+pub struct SendRateLimits {
+    pub consensus: Option<rate::Limit>,
+    pub chunk_distribution: Option<rate::Limit>,
+    pub sync: Option<rate::Limit>,
+    pub gossip: Option<rate::Limit>,
+}
+
+impl SendRateLimits {
+    pub fn get(&self, class: MessageClass) -> Option<rate::Limit> {
+        match class {
+            MessageClass::Consensus => self.consensus,
+            MessageClass::ChunkDistribution => self.chunk_distribution,
+            MessageClass::Sync => self.sync,
+            MessageClass::Gossip => self.gossip,
+        }
+    }
+}
+
 /// Validated configuration for the peer-to-peer manager.
 #[derive(Clone)]
 pub struct NetworkConfig {
@@ -155,6 +179,9 @@ pub struct NetworkConfig {
     pub snapshot_hosts_broadcast_rate_limit: rate::Limit,
     /// Maximal rate at which RoutingTable can be recomputed.
     pub routing_table_update_rate_limit: rate::Limit,
+    /// Maximal rate at which messages of each class are sent to a single peer.
+    /// Regardless of the limits, messages are sent in the order of the priority of their class.
+    pub send_rate_limits: SendRateLimits,
     /// Config of the TIER1 network.
     pub tier1: Option<Tier1>,
 
@@ -165,6 +192,13 @@ pub struct NetworkConfig {
     //   * ignoring received deleted edges as well
     pub skip_tombstones: Option<time::Duration>,
 
+    /// Whether the connections with peers are encrypted.
+    pub transport_encryption: TransportEncryption,
+
+    /// Establishes the outbound connections and opens the listener at `node_addr`.
+    /// Always TCP, except for the tests running over a simulated network.
+    pub dialer: tcp::Dialer,
+
     /// TEST-ONLY
     /// TODO(gprusak): make it pub(crate), once all integration tests
     /// are merged into near_network.
@@ -211,6 +245,30 @@ impl NetworkConfig {
         ) {
             self.routing_table_update_rate_limit = rate::Limit { qps, burst }
         }
+        if let (Some(qps), Some(burst)) = (
+            overrides.consensus_send_rate_limit_bytes_per_sec,
+            overrides.consensus_send_rate_limit_burst_bytes,
+        ) {
+            self.send_rate_limits.consensus = Some(rate::Limit { qps, burst })
+        }
+        if let (Some(qps), Some(burst)) = (
+            overrides.chunk_distribution_send_rate_limit_bytes_per_sec,
+            overrides.chunk_distribution_send_rate_limit_burst_bytes,
+        ) {
+            self.send_rate_limits.chunk_distribution = Some(rate::Limit { qps, burst })
+        }
+        if let (Some(qps), Some(burst)) = (
+            overrides.sync_send_rate_limit_bytes_per_sec,
+            overrides.sync_send_rate_limit_burst_bytes,
+        ) {
+            self.send_rate_limits.sync = Some(rate::Limit { qps, burst })
+        }
+        if let (Some(qps), Some(burst)) = (
+            overrides.gossip_send_rate_limit_bytes_per_sec,
+            overrides.gossip_send_rate_limit_burst_bytes,
+        ) {
+            self.send_rate_limits.gossip = Some(rate::Limit { qps, burst })
+        }
     }
 
     pub fn new(
@@ -286,6 +344,7 @@ impl NetworkConfig {
                 connect_only_to_boot_nodes: cfg.experimental.connect_only_to_boot_nodes,
                 ban_window: cfg.ban_window.try_into()?,
                 peer_expiration_duration: cfg.peer_expiration_duration.try_into()?,
+                reputation_half_life: cfg.peer_reputation_half_life.try_into()?,
             },
             snapshot_hosts: snapshot_hosts::Config {
                 snapshot_hosts_cache_size: cfg.snapshot_hosts_cache_size,
@@ -327,6 +386,7 @@ impl NetworkConfig {
             accounts_data_broadcast_rate_limit: rate::Limit { qps: 0.1, burst: 1 },
             snapshot_hosts_broadcast_rate_limit: rate::Limit { qps: 0.1, burst: 1 },
             routing_table_update_rate_limit: rate::Limit { qps: 1., burst: 1 },
+            send_rate_limits: SendRateLimits::default(),
             tier1: Some(Tier1 {
                 connect_interval: cfg.experimental.tier1_connect_interval.try_into()?,
                 new_connections_per_attempt: cfg.experimental.tier1_new_connections_per_attempt,
@@ -340,6 +400,8 @@ impl NetworkConfig {
             } else {
                 None
             },
+            transport_encryption: cfg.transport_encryption,
+            dialer: tcp::Dialer::Tcp,
             event_sink: Sink::null(),
         };
         this.override_config(cfg.experimental.network_config_overrides);
@@ -370,6 +432,7 @@ impl NetworkConfig {
                 peer_states_cache_size: 1000,
                 ban_window: time::Duration::seconds(1),
                 peer_expiration_duration: time::Duration::seconds(60 * 60),
+                reputation_half_life: time::Duration::hours(6),
                 connect_only_to_boot_nodes: false,
             },
             snapshot_hosts: snapshot_hosts::Config { snapshot_hosts_cache_size: 1000 },
@@ -397,6 +460,7 @@ impl NetworkConfig {
             accounts_data_broadcast_rate_limit: rate::Limit { qps: 100., burst: 1000000 },
             snapshot_hosts_broadcast_rate_limit: rate::Limit { qps: 100., burst: 1000000 },
             routing_table_update_rate_limit: rate::Limit { qps: 10., burst: 1 },
+            send_rate_limits: SendRateLimits::default(),
             tier1: Some(Tier1 {
                 // Interval is very large, so that it doesn't happen spontaneously in tests.
                 // It should rather be triggered manually in tests.
@@ -407,6 +471,8 @@ impl NetworkConfig {
                 enable_outbound: true,
             }),
             skip_tombstones: None,
+            transport_encryption: TransportEncryption::Optional,
+            dialer: tcp::Dialer::Tcp,
             event_sink: Sink::null(),
         }
     }
@@ -450,12 +516,24 @@ impl NetworkConfig {
             );
         }
 
+        if !self.peer_store.reputation_half_life.is_positive() {
+            anyhow::bail!(
+                "peer_reputation_half_life({}) must be positive",
+                self.peer_store.reputation_half_life
+            );
+        }
+
         self.accounts_data_broadcast_rate_limit
             .validate()
             .context("accounts_Data_broadcast_rate_limit")?;
         self.routing_table_update_rate_limit
             .validate()
             .context("routing_table_update_rate_limit")?;
+        for class in MessageClass::ALL {
+            if let Some(limit) = self.send_rate_limits.get(class) {
+                limit.validate().with_context(|| format!("send_rate_limits: {class:?}"))?;
+            }
+        }
         Ok(VerifiedConfig { node_id: self.node_id(), inner: self })
     }
 }
@@ -583,6 +661,16 @@ mod test {
                 &after.accounts_data_broadcast_rate_limit.qps,
                 &overrides.accounts_data_broadcast_rate_limit_qps
             ));
+            assert!(check_override_field(
+                &before.send_rate_limits.sync.map(|l| l.burst),
+                &after.send_rate_limits.sync.map(|l| l.burst),
+                &overrides.sync_send_rate_limit_burst_bytes.map(Some)
+            ));
+            assert!(check_override_field(
+                &before.send_rate_limits.sync.map(|l| l.qps),
+                &after.send_rate_limits.sync.map(|l| l.qps),
+                &overrides.sync_send_rate_limit_bytes_per_sec.map(Some)
+            ));
         };
         let no_overrides = NetworkConfigOverrides::default();
         let mut overrides = NetworkConfigOverrides::default();
@@ -591,6 +679,8 @@ mod test {
         overrides.routed_message_ttl = Some(43);
         overrides.accounts_data_broadcast_rate_limit_burst = Some(44);
         overrides.accounts_data_broadcast_rate_limit_qps = Some(45.0);
+        overrides.sync_send_rate_limit_burst_bytes = Some(46);
+        overrides.sync_send_rate_limit_bytes_per_sec = Some(47.0);
 
         let nc_before =
             config::NetworkConfig::from_seed("123", tcp::ListenerAddr::reserve_for_test());
@@ -59,6 +59,10 @@ fn default_snapshot_hosts_cache_size() -> u32 {
 fn default_peer_expiration_duration() -> Duration {
     Duration::from_secs(7 * 24 * 60 * 60)
 }
+/// Time after which the reputation score of a peer decays to half of its value.
+fn default_peer_reputation_half_life() -> Duration {
+    Duration::from_secs(6 * 60 * 60)
+}
 
 /// If non-zero - we'll skip sending tombstones during initial sync and for that many seconds after start.
 fn default_skip_tombstones() -> i64 {
@@ -80,6 +84,20 @@ fn default_trusted_stun_servers() -> Vec<stun::ServerAddr> {
     ]
 }
 
+/// Whether the connections with peers are encrypted.
+/// Encryption is negotiated during the handshake, see `peer::noise`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransportEncryption {
+    /// Connections are encrypted if the peer supports it, peers which don't
+    /// (i.e. running an older neard) are connected to in cleartext.
+    #[default]
+    Optional,
+    /// Connections with peers which don't support encryption are rejected.
+    Required,
+    /// Connections are never encrypted.
+    Disabled,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct Config {
     /// Local address to listen for incoming connections.
@@ -149,6 +167,9 @@ pub struct Config {
     // Remove peers that were not active for this amount of time.
     #[serde(default = "default_peer_expiration_duration")]
     pub peer_expiration_duration: Duration,
+    /// Time after which the reputation score of a peer decays to half of its value.
+    #[serde(default = "default_peer_reputation_half_life")]
+    pub peer_reputation_half_life: Duration,
 
     /// List of the public addresses (in the format "<node public key>@<IP>:<port>") of trusted nodes,
     /// which are willing to route messages to this node. Useful only if this node is a validator.
@@ -195,6 +216,11 @@ pub struct Config {
     /// such a case.
     #[serde(default = "default_trusted_stun_servers")]
     pub trusted_stun_servers: Vec<stun::ServerAddr>,
+    /// Whether the connections with peers are encrypted.
+    /// `Optional` accepts the peers which don't support encryption yet, switch to
+    /// `Required` once all the peers you care about support it.
+    #[serde(default)]
+    pub transport_encryption: TransportEncryption,
     // Experimental part of the JSON config. Regular users/validators should not have to set any values there.
     // Field names in here can change/disappear at any moment without warning.
     #[serde(default)]
@@ -270,6 +296,14 @@ pub struct NetworkConfigOverrides {
     pub accounts_data_broadcast_rate_limit_qps: Option<f64>,
     pub routing_table_update_rate_limit_burst: Option<u64>,
     pub routing_table_update_rate_limit_qps: Option<f64>,
+    pub consensus_send_rate_limit_burst_bytes: Option<u64>,
+    pub consensus_send_rate_limit_bytes_per_sec: Option<f64>,
+    pub chunk_distribution_send_rate_limit_burst_bytes: Option<u64>,
+    pub chunk_distribution_send_rate_limit_bytes_per_sec: Option<f64>,
+    pub sync_send_rate_limit_burst_bytes: Option<u64>,
+    pub sync_send_rate_limit_bytes_per_sec: Option<f64>,
+    pub gossip_send_rate_limit_burst_bytes: Option<u64>,
+    pub gossip_send_rate_limit_bytes_per_sec: Option<f64>,
 }
 
 impl Default for ExperimentalConfig {
@@ -310,9 +344,11 @@ impl Default for Config {
             peer_stats_period: default_peer_stats_period(),
             monitor_peers_max_period: default_monitor_peers_max_period(),
             peer_expiration_duration: default_peer_expiration_duration(),
+            peer_reputation_half_life: default_peer_reputation_half_life(),
             public_addrs: vec![],
             allow_private_ip_in_public_addrs: false,
             trusted_stun_servers: default_trusted_stun_servers(),
+            transport_encryption: Default::default(),
             experimental: Default::default(),
         }
     }
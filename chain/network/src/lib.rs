@@ -30,6 +30,9 @@ pub mod types;
 #[cfg(test)]
 pub(crate) mod testonly;
 
+#[cfg(any(test, feature = "test_features"))]
+pub mod simnet;
+
 // TODO(gprusak): these should be testonly, once all network integration tests are moved to near_network.
 pub mod broadcast;
 pub mod sink;
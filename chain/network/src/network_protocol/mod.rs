@@ -335,6 +335,23 @@ pub struct Handshake {
     pub(crate) partial_edge_info: PartialEdgeInfo,
     /// Account owned by the sender.
     pub(crate) owned_account: Option<SignedOwnedAccount>,
+    /// Noise handshake message, present if the sender wants the connection to be encrypted.
+    /// Not supported in Borsh encoding.
+    pub(crate) noise: Option<NoiseHandshake>,
+    /// Compression algorithms which the sender can decompress.
+    /// Not supported in Borsh encoding.
+    pub(crate) compression: Vec<Compression>,
+    /// Whether the sender can receive frames split into chunks.
+    /// Not supported in Borsh encoding.
+    pub(crate) chunked_frames: bool,
+}
+
+/// See NoiseHandshake in network_protocol/network.proto.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct NoiseHandshake {
+    pub(crate) message: Vec<u8>,
+    /// Signature of the Noise handshake hash by the sender's node key.
+    pub(crate) signature: Signature,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, strum::IntoStaticStr)]
@@ -422,12 +439,68 @@ impl fmt::Display for PeerMessage {
     }
 }
 
+/// Class of a message, which determines the priority with which it is sent to a peer
+/// and the rate limit applied to it, see `NetworkConfig::send_rate_limits`.
+/// Classes are listed in the order of decreasing priority.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, strum::IntoStaticStr)]
+pub enum MessageClass {
+    /// Connection management and block approvals.
+    Consensus,
+    /// New blocks and chunks.
+    ChunkDistribution,
+    /// Block headers and state requested by syncing nodes.
+    Sync,
+    /// Routing, peer discovery, transactions and everything else.
+    Gossip,
+}
+
+impl MessageClass {
+    pub(crate) const COUNT: usize = 4;
+    /// All the classes, in the order of decreasing priority.
+    pub(crate) const ALL: [MessageClass; Self::COUNT] = [
+        MessageClass::Consensus,
+        MessageClass::ChunkDistribution,
+        MessageClass::Sync,
+        MessageClass::Gossip,
+    ];
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, strum::IntoStaticStr)]
 pub enum Encoding {
     Borsh,
     Proto,
 }
 
+/// Compression algorithm of the messages sent to a peer, negotiated in the Handshake.
+/// See CompressedPeerMessage in network_protocol/network.proto.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Compression {
+    Zstd,
+}
+
+impl Compression {
+    /// Algorithms supported by this node, in the order of preference.
+    pub(crate) const SUPPORTED: &'static [Compression] = &[Compression::Zstd];
+
+    /// Selects the algorithm to compress the messages sent to a peer,
+    /// which supports the `peer` algorithms.
+    pub(crate) fn negotiate(peer: &[Compression]) -> Option<Compression> {
+        Self::SUPPORTED.iter().find(|c| peer.contains(c)).copied()
+    }
+}
+
+/// Messages smaller than that are sent uncompressed, since compressing them saves little.
+const COMPRESSION_THRESHOLD_BYTES: usize = 4 * bytesize::KIB as usize;
+/// Low zstd levels compress fast enough not to slow down the sending of large messages.
+const ZSTD_COMPRESSION_LEVEL: i32 = 3;
+/// Upper bound on the size of a decompressed message,
+/// equal to the maximal size of a frame in peer/stream.rs.
+const MAX_UNCOMPRESSED_SIZE_BYTES: usize = 512 * bytesize::MIB as usize;
+/// Upper bound on the ratio of the size of a decompressed message to the size of its compressed data,
+/// so that a small message can't make us allocate and parse a much larger one.
+/// Messages which compress better are sent uncompressed.
+const MAX_COMPRESSION_RATIO: usize = 32;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParsePeerMessageError {
     #[error("BorshDecode")]
@@ -438,6 +511,16 @@ pub enum ParsePeerMessageError {
     ProtoDecode(#[source] protobuf::Error),
     #[error("ProtoConv")]
     ProtoConv(#[source] proto_conv::ParsePeerMessageError),
+    #[error("Compression")]
+    Compression(#[source] proto_conv::ParseCompressionError),
+    #[error("UnexpectedCompression: {0:?} was not advertised")]
+    UnexpectedCompression(Compression),
+    #[error("UncompressedSize: got {got_bytes}B, want <={want_max_bytes}B")]
+    UncompressedSize { got_bytes: usize, want_max_bytes: usize },
+    #[error("Decompress")]
+    Decompress(#[source] std::io::Error),
+    #[error("NotCompressible: {0} messages are never compressed")]
+    NotCompressible(&'static str),
 }
 
 impl PeerMessage {
@@ -455,27 +538,142 @@ impl PeerMessage {
         }
     }
 
+    /// Serializes a message like `serialize()`, and compresses it with `compression`,
+    /// if the message is large enough and of a variant which is worth compressing.
+    /// Compression is supported only in `Proto` encoding.
+    /// Returns the serialized message and its size before compression.
+    pub(crate) fn serialize_compressed(
+        &self,
+        enc: Encoding,
+        compression: Option<Compression>,
+    ) -> (Vec<u8>, usize) {
+        let data = self.serialize(enc);
+        let raw_len = data.len();
+        let compression = match (enc, compression) {
+            (Encoding::Proto, Some(compression))
+                if raw_len >= COMPRESSION_THRESHOLD_BYTES && self.is_compressible() =>
+            {
+                compression
+            }
+            _ => return (data, raw_len),
+        };
+        let _span = tracing::trace_span!(target: "network", "compress", raw_len).entered();
+        let compressed = match compression {
+            Compression::Zstd => zstd::bulk::compress(&data, ZSTD_COMPRESSION_LEVEL).unwrap(),
+        };
+        // Payloads which don't compress well are sent as they are,
+        // and so are the ones which compress too well for the receiver to accept them.
+        if compressed.len() >= raw_len || raw_len > compressed.len() * MAX_COMPRESSION_RATIO {
+            return (data, raw_len);
+        }
+        let msg = proto::PeerMessage {
+            message_type: Some(proto::peer_message::Message_type::Compressed(
+                proto::CompressedPeerMessage {
+                    algorithm: proto::compressed_peer_message::Algorithm::from(&compression).into(),
+                    uncompressed_size: raw_len as u32,
+                    data: compressed,
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        };
+        (msg.write_to_bytes().unwrap(), raw_len)
+    }
+
+    /// Deserializes a message in the given encoding. Compressed messages are rejected.
     pub(crate) fn deserialize(
         enc: Encoding,
         data: &[u8],
     ) -> Result<PeerMessage, ParsePeerMessageError> {
+        Ok(Self::deserialize_compressed(enc, data, &[])?.0)
+    }
+
+    /// Deserializes a message like `deserialize()`, decompressing it if it is compressed
+    /// with one of the `accepted` algorithms, i.e. the ones advertised to the sender.
+    /// Returns the message and its size before compression.
+    pub(crate) fn deserialize_compressed(
+        enc: Encoding,
+        data: &[u8],
+        accepted: &[Compression],
+    ) -> Result<(PeerMessage, usize), ParsePeerMessageError> {
         let span = tracing::trace_span!(target: "network", "deserialize").entered();
         Ok(match enc {
-            Encoding::Borsh => (&borsh_::PeerMessage::try_from_slice(data)
-                .map_err(ParsePeerMessageError::BorshDecode)?)
-                .try_into()
-                .map_err(ParsePeerMessageError::BorshConv)?,
+            Encoding::Borsh => (
+                (&borsh_::PeerMessage::try_from_slice(data)
+                    .map_err(ParsePeerMessageError::BorshDecode)?)
+                    .try_into()
+                    .map_err(ParsePeerMessageError::BorshConv)?,
+                data.len(),
+            ),
             Encoding::Proto => {
-                let proto_msg: proto::PeerMessage = proto::PeerMessage::parse_from_bytes(data)
+                let mut proto_msg: proto::PeerMessage = proto::PeerMessage::parse_from_bytes(data)
                     .map_err(ParsePeerMessageError::ProtoDecode)?;
+                let mut raw_len = data.len();
+                let mut compressed = false;
+                if let Some(proto::peer_message::Message_type::Compressed(msg)) =
+                    &proto_msg.message_type
+                {
+                    let data = decompress(msg, accepted)?;
+                    raw_len = data.len();
+                    compressed = true;
+                    proto_msg = proto::PeerMessage::parse_from_bytes(&data)
+                        .map_err(ParsePeerMessageError::ProtoDecode)?;
+                }
                 if let Ok(extracted_span_context) = extract_span_context(&proto_msg.trace_context) {
                     span.clone().or_current().add_link(extracted_span_context);
                 }
-                (&proto_msg).try_into().map_err(|err| ParsePeerMessageError::ProtoConv(err))?
+                let msg: PeerMessage =
+                    (&proto_msg).try_into().map_err(|err| ParsePeerMessageError::ProtoConv(err))?;
+                if compressed && !msg.is_compressible() {
+                    return Err(ParsePeerMessageError::NotCompressible(msg.msg_variant()));
+                }
+                (msg, raw_len)
             }
         })
     }
 
+    pub(crate) fn class(&self) -> MessageClass {
+        match self {
+            PeerMessage::Tier1Handshake(_)
+            | PeerMessage::Tier2Handshake(_)
+            | PeerMessage::HandshakeFailure(..)
+            | PeerMessage::LastEdge(_)
+            | PeerMessage::Disconnect(_) => MessageClass::Consensus,
+            // Blocks are sent both as responses to BlockRequests and as broadcasts of new blocks,
+            // which shouldn't wait behind the sync traffic.
+            PeerMessage::Block(_) => MessageClass::ChunkDistribution,
+            PeerMessage::BlockHeadersRequest(_)
+            | PeerMessage::BlockHeaders(_)
+            | PeerMessage::BlockRequest(_)
+            | PeerMessage::StateRequestHeader(..)
+            | PeerMessage::StateRequestPart(..)
+            | PeerMessage::VersionedStateResponse(_) => MessageClass::Sync,
+            PeerMessage::SyncRoutingTable(_)
+            | PeerMessage::DistanceVector(_)
+            | PeerMessage::RequestUpdateNonce(_)
+            | PeerMessage::SyncAccountsData(_)
+            | PeerMessage::PeersRequest(_)
+            | PeerMessage::PeersResponse(_)
+            | PeerMessage::Transaction(_)
+            | PeerMessage::Challenge(_)
+            | PeerMessage::SyncSnapshotHosts(_) => MessageClass::Gossip,
+            PeerMessage::Routed(msg) => msg.msg.body.class(),
+        }
+    }
+
+    /// Whether the message is of a variant carrying a large payload, worth compressing.
+    fn is_compressible(&self) -> bool {
+        match self {
+            PeerMessage::Block(_)
+            | PeerMessage::BlockHeaders(_)
+            | PeerMessage::VersionedStateResponse(_) => true,
+            PeerMessage::Routed(msg) => {
+                matches!(msg.msg.body, RoutedMessageBody::VersionedPartialEncodedChunk(_))
+            }
+            _ => false,
+        }
+    }
+
     pub(crate) fn msg_variant(&self) -> &'static str {
         match self {
             PeerMessage::Routed(routed_msg) => routed_msg.body_variant(),
@@ -484,6 +682,33 @@ impl PeerMessage {
     }
 }
 
+fn decompress(
+    msg: &proto::CompressedPeerMessage,
+    accepted: &[Compression],
+) -> Result<Vec<u8>, ParsePeerMessageError> {
+    let compression =
+        Compression::try_from(&msg.algorithm).map_err(ParsePeerMessageError::Compression)?;
+    if !accepted.contains(&compression) {
+        return Err(ParsePeerMessageError::UnexpectedCompression(compression));
+    }
+    let size = msg.uncompressed_size as usize;
+    // The declared size bounds the size of the decompressed data,
+    // so that a malicious peer can't make us allocate arbitrary amounts of memory.
+    let max_size =
+        MAX_UNCOMPRESSED_SIZE_BYTES.min(msg.data.len().saturating_mul(MAX_COMPRESSION_RATIO));
+    if size > max_size {
+        return Err(ParsePeerMessageError::UncompressedSize {
+            got_bytes: size,
+            want_max_bytes: max_size,
+        });
+    }
+    match compression {
+        Compression::Zstd => {
+            zstd::bulk::decompress(&msg.data, size).map_err(ParsePeerMessageError::Decompress)
+        }
+    }
+}
+
 // TODO(#1313): Use Box
 #[derive(
     borsh::BorshSerialize, borsh::BorshDeserialize, PartialEq, Eq, Clone, strum::IntoStaticStr,
@@ -530,6 +755,30 @@ pub enum RoutedMessageBody {
 }
 
 impl RoutedMessageBody {
+    pub(crate) fn class(&self) -> MessageClass {
+        match self {
+            RoutedMessageBody::BlockApproval(_) => MessageClass::Consensus,
+            RoutedMessageBody::PartialEncodedChunkRequest(_)
+            | RoutedMessageBody::PartialEncodedChunkResponse(_)
+            | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+            | RoutedMessageBody::PartialEncodedChunkForward(_) => MessageClass::ChunkDistribution,
+            RoutedMessageBody::StateResponse(_) => MessageClass::Sync,
+            RoutedMessageBody::ForwardTx(_)
+            | RoutedMessageBody::TxStatusRequest(..)
+            | RoutedMessageBody::TxStatusResponse(_)
+            | RoutedMessageBody::_UnusedQueryRequest
+            | RoutedMessageBody::_UnusedQueryResponse
+            | RoutedMessageBody::ReceiptOutcomeRequest(_)
+            | RoutedMessageBody::_UnusedReceiptOutcomeResponse
+            | RoutedMessageBody::_UnusedStateRequestHeader
+            | RoutedMessageBody::_UnusedStateRequestPart
+            | RoutedMessageBody::_UnusedPartialEncodedChunk
+            | RoutedMessageBody::Ping(_)
+            | RoutedMessageBody::Pong(_)
+            | RoutedMessageBody::_UnusedVersionedStateResponse => MessageClass::Gossip,
+        }
+    }
+
     // Return whether this message is important.
     // In routing logics, we send important messages multiple times to minimize the risk that they are
     // lost
@@ -796,6 +1045,13 @@ impl StateResponseInfo {
         }
     }
 
+    pub fn part_id(&self) -> Option<u64> {
+        match self {
+            Self::V1(info) => info.state_response.part_id(),
+            Self::V2(info) => info.state_response.part_id(),
+        }
+    }
+
     pub fn take_state_response(self) -> ShardStateSyncResponse {
         match self {
             Self::V1(info) => ShardStateSyncResponse::V1(info.state_response),
@@ -2,7 +2,7 @@
 use super::*;
 
 use crate::network_protocol::proto;
-use crate::network_protocol::{Handshake, HandshakeFailureReason};
+use crate::network_protocol::{Compression, Handshake, HandshakeFailureReason, NoiseHandshake};
 use crate::network_protocol::{PeerChainInfoV2, PeerInfo};
 use near_primitives::block::GenesisId;
 use protobuf::MessageField as MF;
@@ -63,6 +63,34 @@ impl TryFrom<&proto::PeerChainInfo> for PeerChainInfoV2 {
 
 //////////////////////////////////////////
 
+#[derive(thiserror::Error, Debug)]
+pub enum ParseNoiseHandshakeError {
+    #[error("signature {0}")]
+    Signature(ParseRequiredError<ParseSignatureError>),
+}
+
+impl From<&NoiseHandshake> for proto::NoiseHandshake {
+    fn from(x: &NoiseHandshake) -> Self {
+        Self {
+            message: x.message.clone(),
+            signature: MF::some((&x.signature).into()),
+            ..Self::default()
+        }
+    }
+}
+
+impl TryFrom<&proto::NoiseHandshake> for NoiseHandshake {
+    type Error = ParseNoiseHandshakeError;
+    fn try_from(p: &proto::NoiseHandshake) -> Result<Self, Self::Error> {
+        Ok(Self {
+            message: p.message.clone(),
+            signature: try_from_required(&p.signature).map_err(Self::Error::Signature)?,
+        })
+    }
+}
+
+//////////////////////////////////////////
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseHandshakeError {
     #[error("sender_peer_id {0}")]
@@ -77,6 +105,8 @@ pub enum ParseHandshakeError {
     PartialEdgeInfo(ParseRequiredError<ParsePartialEdgeInfoError>),
     #[error("owned_account {0}")]
     OwnedAccount(ParseSignedOwnedAccountError),
+    #[error("noise {0}")]
+    Noise(ParseNoiseHandshakeError),
 }
 
 impl From<&Handshake> for proto::Handshake {
@@ -90,6 +120,13 @@ impl From<&Handshake> for proto::Handshake {
             sender_chain_info: MF::some((&x.sender_chain_info).into()),
             partial_edge_info: MF::some((&x.partial_edge_info).into()),
             owned_account: x.owned_account.as_ref().map(Into::into).into(),
+            noise: x.noise.as_ref().map(Into::into).into(),
+            compression: x
+                .compression
+                .iter()
+                .map(|c| proto::compressed_peer_message::Algorithm::from(c).into())
+                .collect(),
+            chunked_frames: x.chunked_frames,
             ..Self::default()
         }
     }
@@ -120,6 +157,14 @@ impl TryFrom<&proto::Handshake> for Handshake {
                 .map_err(Self::Error::PartialEdgeInfo)?,
             owned_account: try_from_optional(&p.owned_account)
                 .map_err(Self::Error::OwnedAccount)?,
+            noise: try_from_optional(&p.noise).map_err(Self::Error::Noise)?,
+            // Algorithms unknown to this node are not supported anyway.
+            compression: p
+                .compression
+                .iter()
+                .filter_map(|c| Compression::try_from(c).ok())
+                .collect(),
+            chunked_frames: p.chunked_frames,
         })
     }
 }
@@ -5,8 +5,8 @@ use crate::network_protocol::proto::peer_message::Message_type as ProtoMT;
 use crate::network_protocol::proto::{self};
 use crate::network_protocol::state_sync::{SnapshotHostInfo, SyncSnapshotHosts};
 use crate::network_protocol::{
-    AdvertisedPeerDistance, Disconnect, DistanceVector, PeerMessage, PeersRequest, PeersResponse,
-    RoutingTableUpdate, SyncAccountsData,
+    AdvertisedPeerDistance, Compression, Disconnect, DistanceVector, PeerMessage, PeersRequest,
+    PeersResponse, RoutingTableUpdate, SyncAccountsData,
 };
 use crate::network_protocol::{RoutedMessage, RoutedMessageV2};
 use crate::types::StateResponseInfo;
@@ -18,6 +18,32 @@ use near_primitives::transaction::SignedTransaction;
 use protobuf::MessageField as MF;
 use std::sync::Arc;
 
+impl From<&Compression> for proto::compressed_peer_message::Algorithm {
+    fn from(x: &Compression) -> Self {
+        match x {
+            Compression::Zstd => Self::ZSTD,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("unknown compression algorithm")]
+pub struct ParseCompressionError;
+
+impl TryFrom<&protobuf::EnumOrUnknown<proto::compressed_peer_message::Algorithm>> for Compression {
+    type Error = ParseCompressionError;
+    fn try_from(
+        x: &protobuf::EnumOrUnknown<proto::compressed_peer_message::Algorithm>,
+    ) -> Result<Self, Self::Error> {
+        match x.enum_value() {
+            Ok(proto::compressed_peer_message::Algorithm::ZSTD) => Ok(Compression::Zstd),
+            _ => Err(ParseCompressionError),
+        }
+    }
+}
+
+//////////////////////////////////////////
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseRoutingTableUpdateError {
     #[error("edges {0}")]
@@ -386,6 +412,10 @@ pub enum ParsePeerMessageError {
     StateResponse(ParseRequiredError<ParseStateInfoError>),
     #[error("sync_snapshot_hosts: {0}")]
     SyncSnapshotHosts(ParseSyncSnapshotHostsError),
+    /// Compressed messages are decompressed before the conversion,
+    /// so a compressed message inside of a compressed message is unexpected.
+    #[error("compressed: unexpected compressed message")]
+    Compressed,
 }
 
 impl TryFrom<&proto::PeerMessage> for PeerMessage {
@@ -487,6 +517,7 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
             ProtoMT::SyncSnapshotHosts(srh) => PeerMessage::SyncSnapshotHosts(
                 srh.try_into().map_err(Self::Error::SyncSnapshotHosts)?,
             ),
+            ProtoMT::Compressed(_) => return Err(Self::Error::Compressed),
         })
     }
 }
@@ -346,6 +346,9 @@ pub fn make_handshake<R: Rng>(rng: &mut R, chain: &Chain) -> Handshake {
         sender_chain_info: chain.get_peer_chain_info(),
         partial_edge_info: make_partial_edge(rng),
         owned_account: None,
+        noise: None,
+        compression: vec![],
+        chunked_frames: false,
     }
 }
 
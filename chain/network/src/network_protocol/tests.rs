@@ -5,6 +5,7 @@ use crate::testonly::make_rng;
 use crate::types::{Disconnect, HandshakeFailureReason, PeerMessage};
 use crate::types::{PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg};
 use anyhow::{bail, Context as _};
+use assert_matches::assert_matches;
 use itertools::Itertools as _;
 use near_async::time;
 use rand::Rng as _;
@@ -63,6 +64,14 @@ fn serialize_deserialize_protobuf_only() {
     let chain = data::Chain::make(&mut clock, &mut rng, 12);
     let msgs = [
         PeerMessage::Tier1Handshake(data::make_handshake(&mut rng, &chain)),
+        PeerMessage::Tier2Handshake(Handshake {
+            noise: Some(NoiseHandshake {
+                message: (0..32).map(|_| rng.gen()).collect(),
+                signature: data::make_secret_key(&mut rng).sign(b"noise handshake hash"),
+            }),
+            compression: vec![Compression::Zstd],
+            ..data::make_handshake(&mut rng, &chain)
+        }),
         PeerMessage::SyncAccountsData(SyncAccountsData {
             accounts_data: (0..4)
                 .map(|_| Arc::new(data::make_signed_account_data(&mut rng, &clock.clock())))
@@ -79,6 +88,103 @@ fn serialize_deserialize_protobuf_only() {
     }
 }
 
+#[test]
+fn serialize_deserialize_compressed() {
+    let mut rng = make_rng(39521947542);
+    let mut clock = time::FakeClock::default();
+    let chain = data::Chain::make(&mut clock, &mut rng, 12);
+    let compression = Some(Compression::Zstd);
+
+    // Large messages of the compressible variants are compressed.
+    let headers =
+        PeerMessage::BlockHeaders((0..10).flat_map(|_| chain.get_block_headers()).collect());
+    let headers_raw = headers.serialize(Encoding::Proto);
+    let (compressed, raw_len) = headers.serialize_compressed(Encoding::Proto, compression);
+    assert_eq!(raw_len, headers_raw.len());
+    assert!(compressed.len() < headers_raw.len());
+    assert_eq!(
+        (headers.clone(), headers_raw.len()),
+        PeerMessage::deserialize_compressed(Encoding::Proto, &compressed, Compression::SUPPORTED)
+            .unwrap()
+    );
+    // Compressed messages are only accepted with the advertised algorithms.
+    assert_matches!(
+        PeerMessage::deserialize(Encoding::Proto, &compressed),
+        Err(ParsePeerMessageError::UnexpectedCompression(Compression::Zstd))
+    );
+
+    // Other messages are sent uncompressed.
+    let small = PeerMessage::BlockRequest(*chain.blocks[5].hash());
+    let routing_table = PeerMessage::SyncRoutingTable(RoutingTableUpdate {
+        edges: vec![],
+        accounts: (0..100).map(|_| data::make_announce_account(&mut rng)).collect(),
+    });
+    for (m, enc, compression) in [
+        (&headers, Encoding::Proto, None),
+        (&headers, Encoding::Borsh, compression),
+        (&small, Encoding::Proto, compression),
+        (&routing_table, Encoding::Proto, compression),
+    ] {
+        let raw = m.serialize(enc);
+        assert_eq!((raw.clone(), raw.len()), m.serialize_compressed(enc, compression));
+        assert_eq!(
+            (m.clone(), raw.len()),
+            PeerMessage::deserialize_compressed(enc, &raw, Compression::SUPPORTED).unwrap()
+        );
+    }
+
+    let compressed_msg = |raw: &[u8], uncompressed_size: u32| {
+        proto::PeerMessage {
+            message_type: Some(proto::peer_message::Message_type::Compressed(
+                proto::CompressedPeerMessage {
+                    algorithm: proto::compressed_peer_message::Algorithm::ZSTD.into(),
+                    uncompressed_size,
+                    data: zstd::bulk::compress(raw, ZSTD_COMPRESSION_LEVEL).unwrap(),
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap()
+    };
+    let deserialize = |data: &[u8]| {
+        PeerMessage::deserialize_compressed(Encoding::Proto, data, &[Compression::Zstd])
+    };
+
+    // The declared uncompressed size is enforced.
+    assert_eq!(
+        headers,
+        deserialize(&compressed_msg(&headers_raw, headers_raw.len() as u32)).unwrap().0
+    );
+    assert_matches!(
+        deserialize(&compressed_msg(&headers_raw, headers_raw.len() as u32 - 1)),
+        Err(ParsePeerMessageError::Decompress(_))
+    );
+    assert_matches!(
+        deserialize(&compressed_msg(&headers_raw, u32::MAX)),
+        Err(ParsePeerMessageError::UncompressedSize { .. })
+    );
+
+    // Payloads decompressing to much more than their compressed size are rejected.
+    let zeros = vec![0; 1024 * 1024];
+    assert_matches!(
+        deserialize(&compressed_msg(&zeros, zeros.len() as u32)),
+        Err(ParsePeerMessageError::UncompressedSize { .. })
+    );
+    let (bytes, raw_len) =
+        PeerMessage::BlockHeaders(vec![chain.get_block_headers()[0].clone(); 10_000])
+            .serialize_compressed(Encoding::Proto, compression);
+    assert_eq!(bytes.len(), raw_len);
+
+    // Only the variants which are sent compressed are accepted compressed.
+    let routing_table_raw = routing_table.serialize(Encoding::Proto);
+    assert_matches!(
+        deserialize(&compressed_msg(&routing_table_raw, routing_table_raw.len() as u32)),
+        Err(ParsePeerMessageError::NotCompressible(_))
+    );
+}
+
 #[test]
 fn serialize_deserialize() -> anyhow::Result<()> {
     let mut rng = make_rng(89028037453);
@@ -1,4 +1,6 @@
+mod noise;
 pub(crate) mod peer_actor;
+mod qos;
 mod stream;
 mod tracker;
 mod transfer_stats;
@@ -0,0 +1,266 @@
+//! Encryption of the peer connections with the Noise protocol framework
+//! (https://noiseprotocol.org/noise.html).
+//!
+//! The Noise handshake is carried by the Tier1Handshake/Tier2Handshake messages:
+//! the outbound peer puts the first Noise message into its Handshake and the inbound
+//! peer puts the second one into its response. The NN pattern only exchanges ephemeral
+//! keys, so each peer additionally signs the Noise handshake hash with its node key.
+//! The hash covers the ephemeral keys the session keys are derived from, which
+//! authenticates the session against the PeerIds exchanged in the Handshake.
+//!
+//! Once the handshake is complete, every frame is encrypted as a sequence of Noise
+//! transport messages. The length prefix of the frame stays in cleartext.
+use crate::network_protocol::NoiseHandshake;
+use crate::tcp;
+use near_crypto::{SecretKey, Signature};
+use near_primitives::network::PeerId;
+use std::sync::Arc;
+
+const NOISE_PARAMS: &str = "Noise_NN_25519_ChaChaPoly_SHA256";
+/// Identifies the protocol in the Noise prologue.
+const PROLOGUE_PREFIX: &[u8] = b"near-network-noise-v1";
+/// Maximal size of a single Noise message.
+const MAX_MESSAGE_LEN: usize = 65535;
+/// Size of the authentication tag of a Noise transport message.
+const TAG_LEN: usize = 16;
+/// Maximal size of the payload of a Noise transport message.
+const MAX_PAYLOAD_LEN: usize = MAX_MESSAGE_LEN - TAG_LEN;
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum Error {
+    #[error("noise: {0}")]
+    Noise(#[from] snow::Error),
+    #[error("invalid signature of the noise handshake hash")]
+    InvalidSignature,
+}
+
+/// Size of a frame of `len` bytes once encrypted.
+pub(crate) fn encrypted_len(len: usize) -> usize {
+    len + num_messages(len) * TAG_LEN
+}
+
+/// Number of Noise transport messages a frame of `len` bytes is encrypted into.
+/// An empty frame is encrypted into a single empty message, so that it is authenticated too.
+fn num_messages(len: usize) -> usize {
+    len.div_ceil(MAX_PAYLOAD_LEN).max(1)
+}
+
+/// Starts a Noise handshake on a connection of the given TIER between the peers.
+/// The prologue binds the handshake to the peers and the TIER of the connection.
+fn new_handshake(
+    initiator: &PeerId,
+    responder: &PeerId,
+    tier: tcp::Tier,
+    is_initiator: bool,
+) -> Result<snow::HandshakeState, snow::Error> {
+    let mut prologue = PROLOGUE_PREFIX.to_vec();
+    prologue.extend(borsh::to_vec(initiator).unwrap());
+    prologue.extend(borsh::to_vec(responder).unwrap());
+    prologue.push(match tier {
+        tcp::Tier::T1 => 1,
+        tcp::Tier::T2 => 2,
+    });
+    let builder = snow::Builder::new(NOISE_PARAMS.parse().unwrap()).prologue(&prologue);
+    match is_initiator {
+        true => builder.build_initiator(),
+        false => builder.build_responder(),
+    }
+}
+
+fn sign_handshake_hash(state: &snow::HandshakeState, node_key: &SecretKey) -> Signature {
+    node_key.sign(state.get_handshake_hash())
+}
+
+fn verify_handshake_hash(
+    state: &snow::HandshakeState,
+    peer_id: &PeerId,
+    signature: &Signature,
+) -> Result<(), Error> {
+    match signature.verify(state.get_handshake_hash(), peer_id.public_key()) {
+        true => Ok(()),
+        false => Err(Error::InvalidSignature),
+    }
+}
+
+/// Noise handshake started by the outbound peer, waiting for the response.
+pub(crate) struct Initiator {
+    state: snow::HandshakeState,
+}
+
+impl Initiator {
+    /// Starts a Noise handshake with `responder`.
+    /// Returns the message to send in the Handshake.
+    pub fn start(
+        node_key: &SecretKey,
+        responder: &PeerId,
+        tier: tcp::Tier,
+    ) -> Result<(Self, NoiseHandshake), Error> {
+        let initiator = PeerId::new(node_key.public_key());
+        let mut state = new_handshake(&initiator, responder, tier, true)?;
+        let mut message = vec![0; MAX_MESSAGE_LEN];
+        let n = state.write_message(&[], &mut message)?;
+        message.truncate(n);
+        let signature = sign_handshake_hash(&state, node_key);
+        Ok((Self { state }, NoiseHandshake { message, signature }))
+    }
+
+    /// Completes the handshake with the response of `responder`.
+    pub fn finish(
+        mut self,
+        responder: &PeerId,
+        response: &NoiseHandshake,
+    ) -> Result<Transport, Error> {
+        self.state.read_message(&response.message, &mut vec![0; MAX_MESSAGE_LEN])?;
+        verify_handshake_hash(&self.state, responder, &response.signature)?;
+        Transport::new(self.state)
+    }
+}
+
+/// Responds to the Noise handshake started by `initiator`.
+/// Returns the established session and the message to send in the Handshake response.
+pub(crate) fn respond(
+    node_key: &SecretKey,
+    initiator: &PeerId,
+    tier: tcp::Tier,
+    msg: &NoiseHandshake,
+) -> Result<(Transport, NoiseHandshake), Error> {
+    let responder = PeerId::new(node_key.public_key());
+    let mut state = new_handshake(initiator, &responder, tier, false)?;
+    let mut message = vec![0; MAX_MESSAGE_LEN];
+    state.read_message(&msg.message, &mut message)?;
+    verify_handshake_hash(&state, initiator, &msg.signature)?;
+    let n = state.write_message(&[], &mut message)?;
+    message.truncate(n);
+    let signature = sign_handshake_hash(&state, node_key);
+    Ok((Transport::new(state)?, NoiseHandshake { message, signature }))
+}
+
+/// Established Noise session.
+pub(crate) struct Transport(Arc<snow::StatelessTransportState>);
+
+impl Transport {
+    fn new(state: snow::HandshakeState) -> Result<Self, Error> {
+        Ok(Self(Arc::new(state.into_stateless_transport_mode()?)))
+    }
+
+    /// Splits the session into the halves encrypting the sent frames and decrypting
+    /// the received ones, so that they can be used by the send and recv loops independently.
+    pub fn split(self) -> (Encryptor, Decryptor) {
+        (Encryptor { state: self.0.clone(), nonce: 0 }, Decryptor { state: self.0, nonce: 0 })
+    }
+}
+
+pub(crate) struct Encryptor {
+    state: Arc<snow::StatelessTransportState>,
+    nonce: u64,
+}
+
+impl Encryptor {
+    pub fn encrypt(&mut self, frame: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut out = vec![0; encrypted_len(frame.len())];
+        let mut pos = 0;
+        for i in 0..num_messages(frame.len()) {
+            let payload = &frame[i * MAX_PAYLOAD_LEN..frame.len().min((i + 1) * MAX_PAYLOAD_LEN)];
+            pos += self.state.write_message(self.nonce, payload, &mut out[pos..])?;
+            self.nonce += 1;
+        }
+        debug_assert_eq!(pos, out.len());
+        Ok(out)
+    }
+}
+
+pub(crate) struct Decryptor {
+    state: Arc<snow::StatelessTransportState>,
+    nonce: u64,
+}
+
+impl Decryptor {
+    pub fn decrypt(&mut self, frame: &[u8]) -> Result<Vec<u8>, Error> {
+        if frame.is_empty() {
+            return Err(snow::Error::Decrypt.into());
+        }
+        let mut out = vec![0; frame.len()];
+        let mut pos = 0;
+        for message in frame.chunks(MAX_MESSAGE_LEN) {
+            pos += self.state.read_message(self.nonce, message, &mut out[pos..])?;
+            self.nonce += 1;
+        }
+        out.truncate(pos);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_protocol::testonly as data;
+    use crate::testonly::make_rng;
+
+    fn handshake(tier: tcp::Tier) -> ((Encryptor, Decryptor), (Encryptor, Decryptor)) {
+        let mut rng = make_rng(89028037453);
+        let a = data::make_secret_key(&mut rng);
+        let b = data::make_secret_key(&mut rng);
+        let a_id = PeerId::new(a.public_key());
+        let b_id = PeerId::new(b.public_key());
+        let (initiator, msg) = Initiator::start(&a, &b_id, tier).unwrap();
+        let (b_transport, response) = respond(&b, &a_id, tier, &msg).unwrap();
+        let a_transport = initiator.finish(&b_id, &response).unwrap();
+        (a_transport.split(), b_transport.split())
+    }
+
+    #[test]
+    fn encrypt_decrypt() {
+        let ((mut a_enc, mut a_dec), (mut b_enc, mut b_dec)) = handshake(tcp::Tier::T2);
+        for len in [0, 1, 1000, MAX_PAYLOAD_LEN, MAX_PAYLOAD_LEN + 1, 3 * MAX_MESSAGE_LEN] {
+            let frame: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let encrypted = a_enc.encrypt(&frame).unwrap();
+            assert_eq!(encrypted.len(), encrypted_len(len));
+            assert_eq!(b_dec.decrypt(&encrypted).unwrap(), frame);
+            let encrypted = b_enc.encrypt(&frame).unwrap();
+            assert_eq!(a_dec.decrypt(&encrypted).unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn tampered_frames() {
+        let ((mut a_enc, _), (_, mut b_dec)) = handshake(tcp::Tier::T1);
+        let first = a_enc.encrypt(b"first").unwrap();
+        let second = a_enc.encrypt(b"second").unwrap();
+        let mut tampered = first.clone();
+        tampered[0] ^= 1;
+        assert!(b_dec.decrypt(&tampered).is_err());
+        // Frames can't be reordered or replayed.
+        assert!(b_dec.decrypt(&second).is_err());
+        assert_eq!(b_dec.decrypt(&first).unwrap(), b"first");
+        assert!(b_dec.decrypt(&first).is_err());
+        assert_eq!(b_dec.decrypt(&second).unwrap(), b"second");
+    }
+
+    #[test]
+    fn handshake_authentication() {
+        let mut rng = make_rng(89028037453);
+        let a = data::make_secret_key(&mut rng);
+        let b = data::make_secret_key(&mut rng);
+        let mallory = data::make_secret_key(&mut rng);
+        let a_id = PeerId::new(a.public_key());
+        let b_id = PeerId::new(b.public_key());
+        let tier = tcp::Tier::T2;
+
+        // The handshake has to be signed by the peer it claims to come from.
+        let (_, msg) = Initiator::start(&mallory, &b_id, tier).unwrap();
+        assert!(respond(&b, &a_id, tier, &msg).is_err());
+        let (initiator, msg) = Initiator::start(&a, &b_id, tier).unwrap();
+        let (_, response) = respond(&mallory, &a_id, tier, &msg).unwrap();
+        assert!(initiator.finish(&b_id, &response).is_err());
+
+        // A handshake message can't be reused with another ephemeral key.
+        let (_, msg) = Initiator::start(&a, &b_id, tier).unwrap();
+        let (_, mut other_msg) = Initiator::start(&a, &b_id, tier).unwrap();
+        other_msg.signature = msg.signature;
+        assert!(respond(&b, &a_id, tier, &other_msg).is_err());
+
+        // The handshake is bound to the TIER of the connection.
+        let (_, msg) = Initiator::start(&a, &b_id, tcp::Tier::T1).unwrap();
+        assert!(respond(&b, &a_id, tcp::Tier::T2, &msg).is_err());
+    }
+}
@@ -2,18 +2,21 @@ use crate::accounts_data::AccountDataError;
 use crate::concurrency::atomic_cell::AtomicCell;
 use crate::concurrency::demux;
 use crate::config::PEERS_RESPONSE_MAX_PEERS;
+use crate::config_json::TransportEncryption;
 use crate::network_protocol::{
-    DistanceVector, Edge, EdgeState, Encoding, OwnedAccount, ParsePeerMessageError,
-    PartialEdgeInfo, PeerChainInfoV2, PeerIdOrHash, PeerInfo, PeersRequest, PeersResponse,
-    RawRoutedMessage, RoutedMessageBody, RoutingTableUpdate, StateResponseInfo, SyncAccountsData,
-    SyncSnapshotHosts,
+    Compression, DistanceVector, Edge, EdgeState, Encoding, NoiseHandshake, OwnedAccount,
+    ParsePeerMessageError, PartialEdgeInfo, PeerChainInfoV2, PeerIdOrHash, PeerInfo, PeersRequest,
+    PeersResponse, RawRoutedMessage, RoutedMessageBody, RoutingTableUpdate, StateResponseInfo,
+    SyncAccountsData, SyncSnapshotHosts,
 };
+use crate::peer::noise;
 use crate::peer::stream;
 use crate::peer::tracker::Tracker;
 use crate::peer_manager::connection;
 use crate::peer_manager::network_state::{NetworkState, PRUNE_EDGES_AFTER};
 use crate::peer_manager::peer_manager_actor::Event;
 use crate::peer_manager::peer_manager_actor::MAX_TIER2_PEERS;
+use crate::peer_manager::peer_store::reputation;
 use crate::private_actix::{RegisterPeerError, SendMessage};
 use crate::routing::edge::verify_nonce;
 use crate::routing::NetworkTopologyChange;
@@ -113,6 +116,8 @@ pub(crate) enum ClosingReason {
     TooLargeClockSkew,
     #[error("owned_account.peer_id doesn't match handshake.sender_peer_id")]
     OwnedAccountMismatch,
+    #[error("transport encryption is required, but not supported by the peer")]
+    EncryptionRequired,
     #[error("PeerActor stopped NOT via PeerActor::stop()")]
     Unknown,
 }
@@ -134,6 +139,7 @@ impl ClosingReason {
             ClosingReason::DisconnectMessage => false, // graceful disconnect
             ClosingReason::TooLargeClockSkew => true, // reconnect will fail for the same reason
             ClosingReason::OwnedAccountMismatch => true, // misbehaving peer
+            ClosingReason::EncryptionRequired => true, // reconnect will fail for the same reason
             ClosingReason::Unknown => false,        // only happens in tests
         }
     }
@@ -156,6 +162,13 @@ pub(crate) struct PeerActor {
 
     /// Framed wrapper to send messages through the TCP connection.
     framed: stream::FramedStream<PeerActor>,
+    /// Noise handshake sent in the outbound Handshake, waiting for the response.
+    noise_initiator: Option<noise::Initiator>,
+    /// Compression of the sent messages, negotiated in the Handshake.
+    compression: Option<Compression>,
+    /// Compression algorithms advertised in the Handshake sent to the peer,
+    /// which are the only ones it may compress the received messages with.
+    accepted_compression: &'static [Compression],
 
     /// Tracker for requests and responses.
     tracker: Arc<Mutex<Tracker>>,
@@ -302,7 +315,13 @@ impl PeerActor {
                 let peer_addr = stream.peer_addr;
                 let stream_type = stream.type_.clone();
                 let stats = Arc::new(connection::Stats::default());
-                let framed = stream::FramedStream::spawn(ctx, stream, stats.clone());
+                let framed = stream::FramedStream::spawn(
+                    ctx,
+                    clock.clone(),
+                    stream,
+                    stats.clone(),
+                    network_state.config.send_rate_limits,
+                );
                 Self {
                     closing_reason: None,
                     clock,
@@ -315,6 +334,9 @@ impl PeerActor {
                     },
                     peer_status: PeerStatus::Connecting(send, connecting_status),
                     framed,
+                    noise_initiator: None,
+                    compression: None,
+                    accepted_compression: &[],
                     tracker: Default::default(),
                     stats,
                     routed_message_cache: LruCache::new(ROUTED_MESSAGE_CACHE_SIZE),
@@ -353,15 +375,22 @@ impl PeerActor {
         }
     }
 
-    fn parse_message(&mut self, msg: &[u8]) -> Result<PeerMessage, ParsePeerMessageError> {
+    /// Parses a message, returning it together with its size before compression.
+    fn parse_message(&mut self, msg: &[u8]) -> Result<(PeerMessage, usize), ParsePeerMessageError> {
         if let Some(e) = self.encoding() {
-            return PeerMessage::deserialize(e, msg);
+            return PeerMessage::deserialize_compressed(e, msg, self.accepted_compression);
         }
-        if let Ok(msg) = PeerMessage::deserialize(Encoding::Proto, msg) {
+        if let Ok(msg) =
+            PeerMessage::deserialize_compressed(Encoding::Proto, msg, self.accepted_compression)
+        {
             self.protocol_buffers_supported = true;
             return Ok(msg);
         }
-        return PeerMessage::deserialize(Encoding::Borsh, msg);
+        return PeerMessage::deserialize_compressed(
+            Encoding::Borsh,
+            msg,
+            self.accepted_compression,
+        );
     }
 
     fn send_message_or_log(&self, msg: &PeerMessage) {
@@ -407,19 +436,41 @@ impl PeerActor {
             _ => (),
         };
 
-        let bytes = msg.serialize(enc);
-        self.tracker.lock().increment_sent(&self.clock, bytes.len() as u64);
+        let (bytes, raw_len) = msg.serialize_compressed(enc, self.compression);
         let bytes_len = bytes.len();
-        tracing::trace!(target: "network", msg_len = bytes_len);
-        self.framed.send(stream::Frame(bytes));
+        self.tracker.lock().increment_sent(&self.clock, bytes_len as u64, raw_len as u64);
+        tracing::trace!(target: "network", msg_len = bytes_len, raw_len);
+        self.framed.send(msg.class(), stream::Frame(bytes));
         metrics::PEER_DATA_SENT_BYTES.inc_by(bytes_len as u64);
         metrics::PEER_MESSAGE_SENT_BY_TYPE_TOTAL.with_label_values(&[msg_type]).inc();
         metrics::PEER_MESSAGE_SENT_BY_TYPE_BYTES
             .with_label_values(&[msg_type])
             .inc_by(bytes_len as u64);
+        metrics::PEER_MESSAGE_SENT_BY_TYPE_RAW_BYTES
+            .with_label_values(&[msg_type])
+            .inc_by(raw_len as u64);
+    }
+
+    /// Starts a Noise handshake to be sent in the outbound Handshake,
+    /// unless transport encryption is disabled.
+    fn start_noise_handshake(&mut self, spec: &HandshakeSpec) -> Option<NoiseHandshake> {
+        if self.network_state.config.transport_encryption == TransportEncryption::Disabled {
+            return None;
+        }
+        match noise::Initiator::start(&self.network_state.config.node_key, &spec.peer_id, spec.tier)
+        {
+            Ok((initiator, msg)) => {
+                self.noise_initiator = Some(initiator);
+                Some(msg)
+            }
+            Err(err) => {
+                tracing::warn!(target: "network", "Failed to start the noise handshake: {err}");
+                None
+            }
+        }
     }
 
-    fn send_handshake(&self, spec: HandshakeSpec) {
+    fn send_handshake(&mut self, spec: HandshakeSpec, noise: Option<NoiseHandshake>) {
         let (height, tracked_shards) =
             if let Some(chain_info) = self.network_state.chain_info.load().as_ref() {
                 (chain_info.block.header().height(), chain_info.tracked_shards.clone())
@@ -448,7 +499,11 @@ impl PeerActor {
                 }
                 .sign(vc.signer.as_ref())
             }),
+            noise,
+            compression: Compression::SUPPORTED.to_vec(),
+            chunked_frames: true,
         };
+        self.accepted_compression = Compression::SUPPORTED;
         let msg = match spec.tier {
             tcp::Tier::T1 => PeerMessage::Tier1Handshake(handshake),
             tcp::Tier::T2 => PeerMessage::Tier2Handshake(handshake),
@@ -587,6 +642,66 @@ impl PeerActor {
             }
         }
 
+        // Negotiate transport encryption, see `noise` module.
+        let encryption = self.network_state.config.transport_encryption;
+        let noise_result = match (cs, &handshake.noise) {
+            (ConnectingStatus::Outbound { .. }, Some(response)) => {
+                self.noise_initiator.take().map(|initiator| {
+                    initiator.finish(&handshake.sender_peer_id, response).map(|t| (t, None))
+                })
+            }
+            (ConnectingStatus::Inbound { .. }, Some(msg))
+                if encryption != TransportEncryption::Disabled =>
+            {
+                let node_key = &self.network_state.config.node_key;
+                Some(
+                    noise::respond(node_key, &handshake.sender_peer_id, tier, msg)
+                        .map(|(t, response)| (t, Some(response))),
+                )
+            }
+            _ => None,
+        };
+        let transport = match noise_result {
+            Some(Ok(it)) => Some(it),
+            Some(Err(noise::Error::InvalidSignature)) => {
+                tracing::debug!(target: "network", peer_id = ?handshake.sender_peer_id, "Invalid signature of the noise handshake");
+                self.stop(ctx, ClosingReason::Ban(ReasonForBan::InvalidSignature));
+                return;
+            }
+            Some(Err(err)) => {
+                tracing::debug!(target: "network", peer_id = ?handshake.sender_peer_id, "Noise handshake failed: {err}");
+                self.stop(ctx, ClosingReason::HandshakeFailed);
+                return;
+            }
+            None if encryption == TransportEncryption::Required => {
+                tracing::debug!(target: "network", peer_id = ?handshake.sender_peer_id, "Peer doesn't support transport encryption");
+                self.stop(ctx, ClosingReason::EncryptionRequired);
+                return;
+            }
+            None => None,
+        };
+        let encrypted = transport.is_some();
+        let mut noise_response = None;
+        if let Some((transport, response)) = transport {
+            let (encryptor, decryptor) = transport.split();
+            match response {
+                // Outbound peer has just received the last cleartext message.
+                None => {
+                    self.framed.start_encryption(encryptor);
+                    self.framed.start_decryption(decryptor);
+                }
+                // Inbound peer starts encryption once its Handshake is sent.
+                Some(response) => noise_response = Some((response, encryptor, decryptor)),
+            }
+        }
+
+        // Compress the sent messages with an algorithm which the peer can decompress.
+        self.compression = Compression::negotiate(&handshake.compression);
+        // Send large frames in chunks, if the peer can reassemble them.
+        if handshake.chunked_frames {
+            self.framed.start_chunking();
+        }
+
         // Merge partial edges.
         let nonce = handshake.partial_edge_info.nonce;
         let partial_edge_info = match cs {
@@ -624,6 +739,7 @@ impl PeerActor {
             addr: ctx.address(),
             peer_info: peer_info.clone(),
             owned_account: handshake.owned_account.clone(),
+            encrypted,
             genesis_id: handshake.sender_chain_info.genesis_id.clone(),
             tracked_shards: handshake.sender_chain_info.tracked_shards.clone(),
             archival: handshake.sender_chain_info.archival,
@@ -661,6 +777,12 @@ impl PeerActor {
                         .received_bytes_per_sec
                         .store(received.bytes_per_min / 60, Ordering::Relaxed);
                     conn.stats.sent_bytes_per_sec.store(sent.bytes_per_min / 60, Ordering::Relaxed);
+                    conn.stats
+                        .received_raw_bytes_per_sec
+                        .store(received.raw_bytes_per_min / 60, Ordering::Relaxed);
+                    conn.stats
+                        .sent_raw_bytes_per_sec
+                        .store(sent.raw_bytes_per_min / 60, Ordering::Relaxed);
                 }
             })
         });
@@ -688,12 +810,23 @@ impl PeerActor {
                         act.peer_status = PeerStatus::Ready(conn.clone());
                         // Respond to handshake if it's inbound and connection was consolidated.
                         if act.peer_type == PeerType::Inbound {
+                            let (noise, encryptor) = match noise_response {
+                                Some((response, encryptor, decryptor)) => {
+                                    // Outbound peer starts encryption once it receives the Handshake.
+                                    act.framed.start_decryption(decryptor);
+                                    (Some(response), Some(encryptor))
+                                }
+                                None => (None, None),
+                            };
                             act.send_handshake(HandshakeSpec{
                                 peer_id: handshake.sender_peer_id.clone(),
                                 tier,
                                 protocol_version: handshake.protocol_version,
                                 partial_edge_info: partial_edge_info,
-                            });
+                            }, noise);
+                            if let Some(encryptor) = encryptor {
+                                act.framed.start_encryption(encryptor);
+                            }
                         }
                         // TIER1 is strictly reserved for BFT consensensus messages,
                         // so all kinds of periodical syncs happen only on TIER2 connections.
@@ -853,7 +986,8 @@ impl PeerActor {
                         handshake_spec.protocol_version = common_version;
                         let spec = handshake_spec.clone();
                         ctx.wait(actix::fut::ready(()).then(move |_, act: &mut Self, _| {
-                            act.send_handshake(spec);
+                            let noise = act.start_noise_handshake(&spec);
+                            act.send_handshake(spec, noise);
                             actix::fut::ready(())
                         }));
                     }
@@ -899,7 +1033,8 @@ impl PeerActor {
                 );
                 let spec = handshake_spec.clone();
                 ctx.wait(actix::fut::ready(()).then(move |_, act: &mut Self, _| {
-                    act.send_handshake(spec);
+                    let noise = act.start_noise_handshake(&spec);
+                    act.send_handshake(spec, noise);
                     actix::fut::ready(())
                 }));
             }
@@ -1020,10 +1155,29 @@ impl PeerActor {
                         last_block.clone()
                     }
                 });
+                self.network_state.peer_store.response_received(
+                    &self.clock,
+                    &conn.peer_info.id,
+                    reputation::Request::Block(hash),
+                );
                 let mut tracker = self.tracker.lock();
                 tracker.push_received(hash);
                 tracker.has_request(&hash)
             }
+            PeerMessage::VersionedStateResponse(info) => {
+                if let Some(part_id) = info.part_id() {
+                    self.network_state.peer_store.response_received(
+                        &self.clock,
+                        &conn.peer_info.id,
+                        reputation::Request::StatePart {
+                            shard_id: info.shard_id(),
+                            sync_hash: info.sync_hash(),
+                            part_id,
+                        },
+                    );
+                }
+                false
+            }
             _ => false,
         };
         let clock = self.clock.clone();
@@ -1470,7 +1624,9 @@ impl actix::Actor for PeerActor {
         if let PeerStatus::Connecting(_, ConnectingStatus::Outbound { handshake_spec, .. }) =
             &self.peer_status
         {
-            self.send_handshake(handshake_spec.clone());
+            let spec = handshake_spec.clone();
+            let noise = self.start_noise_handshake(&spec);
+            self.send_handshake(spec, noise);
         }
         self.network_state
             .config
@@ -1549,6 +1705,9 @@ impl actix::Handler<stream::Error> for PeerActor {
             }
             // It is expected in a sense that the peer might be just slow.
             stream::Error::Send(stream::SendError::QueueOverflow { .. }) => true,
+            // Frames might have been tampered with on the way.
+            stream::Error::Recv(stream::RecvError::Decryption(_)) => true,
+            stream::Error::Send(stream::SendError::Encryption(_)) => false,
             stream::Error::Recv(stream::RecvError::IO(err))
             | stream::Error::Send(stream::SendError::IO(err)) => match err.kind() {
                 // Connection has been closed.
@@ -1594,16 +1753,18 @@ impl actix::Handler<stream::Frame> for PeerActor {
         {
             metrics::PEER_DATA_RECEIVED_BYTES.inc_by(msg.len() as u64);
             tracing::trace!(target: "network", msg_len=msg.len());
-            self.tracker.lock().increment_received(&self.clock, msg.len() as u64);
         }
 
-        let mut peer_msg = match self.parse_message(&msg) {
+        let (mut peer_msg, raw_len) = match self.parse_message(&msg) {
             Ok(msg) => msg,
             Err(err) => {
                 tracing::debug!(target: "network", "Received invalid data {} from {}: {}", near_fmt::AbbrBytes(&msg), self.peer_info, err);
+                let len = msg.len() as u64;
+                self.tracker.lock().increment_received(&self.clock, len, len);
                 return;
             }
         };
+        self.tracker.lock().increment_received(&self.clock, msg.len() as u64, raw_len as u64);
 
         tracing::trace!(target: "network", "Received message: {}", peer_msg);
 
@@ -1613,6 +1774,9 @@ impl actix::Handler<stream::Frame> for PeerActor {
             metrics::PEER_MESSAGE_RECEIVED_BY_TYPE_BYTES
                 .with_label_values(&labels)
                 .inc_by(msg.len() as u64);
+            metrics::PEER_MESSAGE_RECEIVED_BY_TYPE_RAW_BYTES
+                .with_label_values(&labels)
+                .inc_by(raw_len as u64);
         }
         match &self.peer_status {
             PeerStatus::Connecting { .. } => self.handle_msg_connecting(ctx, peer_msg),
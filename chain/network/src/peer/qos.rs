@@ -0,0 +1,251 @@
+//! Scheduling of the messages sent to a peer.
+//!
+//! Every message belongs to a `MessageClass`. Messages are sent in the order of the
+//! priority of their class, and in the order they were queued within a class,
+//! so that e.g. a burst of state parts doesn't delay block approvals.
+//! Additionally, the rate at which the messages of a class are sent can be limited
+//! with `NetworkConfig::send_rate_limits`.
+//!
+//! Large frames are sent in chunks (see `peer::stream`), so that frames of higher
+//! priority classes can be sent before the rest of the frame.
+use crate::concurrency::rate;
+use crate::config::SendRateLimits;
+use crate::network_protocol::MessageClass;
+use crate::stats::metrics;
+use bytesize::MIB;
+use near_async::time;
+use std::collections::VecDeque;
+
+/// Maximal number of bytes of messages of a single class waiting to be sent to a peer.
+/// Messages of a class which has that many bytes queued are dropped.
+const MAX_QUEUED_BYTES_PER_CLASS: usize = 256 * MIB as usize;
+
+/// Token bucket implementing a `rate::Limit` of bytes.
+/// The bucket is allowed to go into debt, so that messages larger than the burst
+/// can be sent too: the class just waits until the debt is paid off.
+struct TokenBucket {
+    limit: rate::Limit,
+    tokens: f64,
+    updated: time::Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: rate::Limit, now: time::Instant) -> Self {
+        Self { limit, tokens: limit.burst as f64, updated: now }
+    }
+
+    fn refill(&mut self, now: time::Instant) {
+        if now <= self.updated {
+            return;
+        }
+        let elapsed = (now - self.updated).as_seconds_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.qps).min(self.limit.burst as f64);
+        self.updated = now;
+    }
+
+    /// Time since which a message can be sent.
+    fn ready_at(&self) -> time::Instant {
+        if self.tokens >= 0. {
+            return self.updated;
+        }
+        self.updated + time::Duration::seconds_f64(-self.tokens / self.limit.qps)
+    }
+}
+
+struct ClassQueue {
+    class: MessageClass,
+    frames: VecDeque<Vec<u8>>,
+    bytes: usize,
+    bucket: Option<TokenBucket>,
+}
+
+/// Result of `SendQueues::pop`.
+pub(crate) enum Pop {
+    Frame(MessageClass, Vec<u8>),
+    /// All the classes with queued messages are rate limited until the given time.
+    NotBefore(time::Instant),
+    Empty,
+}
+
+/// Messages waiting to be sent to a peer, by class.
+pub(crate) struct SendQueues {
+    /// Queues in the order of decreasing priority, see `MessageClass::ALL`.
+    queues: [ClassQueue; MessageClass::COUNT],
+}
+
+impl SendQueues {
+    pub fn new(now: time::Instant, limits: &SendRateLimits) -> Self {
+        Self {
+            queues: MessageClass::ALL.map(|class| ClassQueue {
+                class,
+                frames: VecDeque::new(),
+                bytes: 0,
+                bucket: limits.get(class).map(|limit| TokenBucket::new(limit, now)),
+            }),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(|q| q.frames.is_empty())
+    }
+
+    /// Queues a frame of the given class.
+    /// Returns the frame back if it has been dropped, because its class queue is full.
+    pub fn push(&mut self, class: MessageClass, frame: Vec<u8>) -> Result<(), Vec<u8>> {
+        let labels = [<&str>::from(class)];
+        let q = &mut self.queues[class as usize];
+        if q.bytes + frame.len() > MAX_QUEUED_BYTES_PER_CLASS && !q.frames.is_empty() {
+            metrics::PEER_SEND_QUEUE_DROPPED.with_label_values(&labels).inc();
+            return Err(frame);
+        }
+        q.bytes += frame.len();
+        q.frames.push_back(frame);
+        metrics::PEER_SEND_QUEUE_DEPTH.with_label_values(&labels).inc();
+        Ok(())
+    }
+
+    /// Pops the oldest frame of the highest priority class which is not rate limited at `now`.
+    /// If `above` is set, only the classes of higher priority than `above` are considered.
+    pub fn pop(&mut self, now: time::Instant, above: Option<MessageClass>) -> Pop {
+        let mut not_before: Option<time::Instant> = None;
+        let classes = above.map_or(MessageClass::COUNT, |class| class as usize);
+        for q in &mut self.queues[..classes] {
+            if q.frames.is_empty() {
+                continue;
+            }
+            if let Some(bucket) = &mut q.bucket {
+                bucket.refill(now);
+                let ready_at = bucket.ready_at();
+                if ready_at > now {
+                    not_before = Some(not_before.map_or(ready_at, |t| t.min(ready_at)));
+                    continue;
+                }
+            }
+            let frame = q.frames.pop_front().unwrap();
+            q.bytes -= frame.len();
+            if let Some(bucket) = &mut q.bucket {
+                bucket.tokens -= frame.len() as f64;
+            }
+            metrics::PEER_SEND_QUEUE_DEPTH.with_label_values(&[<&str>::from(q.class)]).dec();
+            return Pop::Frame(q.class, frame);
+        }
+        match not_before {
+            Some(t) => Pop::NotBefore(t),
+            None => Pop::Empty,
+        }
+    }
+}
+
+impl Drop for SendQueues {
+    fn drop(&mut self) {
+        for q in &self.queues {
+            metrics::PEER_SEND_QUEUE_DEPTH
+                .with_label_values(&[<&str>::from(q.class)])
+                .sub(q.frames.len() as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pop_frame(queues: &mut SendQueues, now: time::Instant) -> Option<Vec<u8>> {
+        match queues.pop(now, None) {
+            Pop::Frame(_, frame) => Some(frame),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn priorities() {
+        let clock = time::FakeClock::default();
+        let mut queues = SendQueues::new(clock.now(), &SendRateLimits::default());
+        queues.push(MessageClass::Gossip, vec![4]).unwrap();
+        queues.push(MessageClass::Sync, vec![3]).unwrap();
+        queues.push(MessageClass::Sync, vec![3, 3]).unwrap();
+        queues.push(MessageClass::Consensus, vec![1]).unwrap();
+        queues.push(MessageClass::ChunkDistribution, vec![2]).unwrap();
+        let mut got = vec![];
+        while let Some(frame) = pop_frame(&mut queues, clock.now()) {
+            got.push(frame);
+        }
+        assert_eq!(got, vec![vec![1], vec![2], vec![3], vec![3, 3], vec![4]]);
+        assert!(queues.is_empty());
+        assert!(matches!(queues.pop(clock.now(), None), Pop::Empty));
+    }
+
+    #[test]
+    fn pop_above() {
+        let clock = time::FakeClock::default();
+        let limits = SendRateLimits {
+            consensus: Some(rate::Limit { qps: 1., burst: 1 }),
+            ..Default::default()
+        };
+        let mut queues = SendQueues::new(clock.now(), &limits);
+        queues.push(MessageClass::Sync, vec![3]).unwrap();
+        assert!(matches!(queues.pop(clock.now(), Some(MessageClass::Sync)), Pop::Empty));
+        queues.push(MessageClass::Consensus, vec![1, 1]).unwrap();
+        queues.push(MessageClass::Consensus, vec![1]).unwrap();
+        queues.push(MessageClass::ChunkDistribution, vec![2]).unwrap();
+        assert!(matches!(
+            queues.pop(clock.now(), Some(MessageClass::ChunkDistribution)),
+            Pop::Frame(MessageClass::Consensus, f) if f == vec![1, 1]
+        ));
+        // Only the rate limited class has higher priority.
+        let ready_at = clock.now() + time::Duration::seconds(1);
+        assert!(matches!(
+            queues.pop(clock.now(), Some(MessageClass::ChunkDistribution)),
+            Pop::NotBefore(t) if t == ready_at
+        ));
+        assert!(matches!(
+            queues.pop(clock.now(), Some(MessageClass::Sync)),
+            Pop::Frame(MessageClass::ChunkDistribution, f) if f == vec![2]
+        ));
+    }
+
+    #[test]
+    fn rate_limits() {
+        let clock = time::FakeClock::default();
+        let limits = SendRateLimits {
+            sync: Some(rate::Limit { qps: 100., burst: 100 }),
+            ..Default::default()
+        };
+        let mut queues = SendQueues::new(clock.now(), &limits);
+        for _ in 0..3 {
+            queues.push(MessageClass::Sync, vec![0; 150]).unwrap();
+        }
+        queues.push(MessageClass::Gossip, vec![1]).unwrap();
+
+        // The burst lets the first message through, putting the bucket into debt.
+        assert_eq!(pop_frame(&mut queues, clock.now()), Some(vec![0; 150]));
+        // Lower priority classes are not blocked by a rate limited class.
+        assert_eq!(pop_frame(&mut queues, clock.now()), Some(vec![1]));
+        // The debt of 50 bytes is paid off after 0.5s.
+        let ready_at = clock.now() + time::Duration::milliseconds(500);
+        assert!(matches!(queues.pop(clock.now(), None), Pop::NotBefore(t) if t == ready_at));
+        clock.advance(time::Duration::milliseconds(499));
+        assert_eq!(pop_frame(&mut queues, clock.now()), None);
+        clock.advance(time::Duration::milliseconds(1));
+        assert_eq!(pop_frame(&mut queues, clock.now()), Some(vec![0; 150]));
+        clock.advance(time::Duration::seconds(10));
+        assert_eq!(pop_frame(&mut queues, clock.now()), Some(vec![0; 150]));
+        assert!(queues.is_empty());
+    }
+
+    #[test]
+    fn drop_when_full() {
+        let clock = time::FakeClock::default();
+        let mut queues = SendQueues::new(clock.now(), &SendRateLimits::default());
+        queues.push(MessageClass::Sync, vec![0; MAX_QUEUED_BYTES_PER_CLASS]).unwrap();
+        assert_eq!(queues.push(MessageClass::Sync, vec![1]), Err(vec![1]));
+        // Other classes have separate queues.
+        queues.push(MessageClass::Consensus, vec![2]).unwrap();
+        assert_eq!(pop_frame(&mut queues, clock.now()), Some(vec![2]));
+        assert_eq!(
+            pop_frame(&mut queues, clock.now()).map(|f| f.len()),
+            Some(MAX_QUEUED_BYTES_PER_CLASS)
+        );
+        queues.push(MessageClass::Sync, vec![1]).unwrap();
+    }
+}
@@ -1,9 +1,15 @@
+use crate::config::SendRateLimits;
+use crate::network_protocol::MessageClass;
+use crate::peer::noise;
+use crate::peer::qos;
 use crate::peer_manager::connection;
 use crate::stats::metrics;
 use crate::tcp;
 use actix::fut::future::wrap_future;
 use actix::AsyncContext as _;
-use bytesize::{GIB, MIB};
+use bytesize::{GIB, KIB, MIB};
+use near_async::time;
+use parking_lot::Mutex;
 use std::io;
 use std::net::SocketAddr;
 use std::sync::atomic::Ordering;
@@ -16,9 +22,18 @@ use tokio::io::AsyncWriteExt as _;
 const NETWORK_MESSAGE_MAX_SIZE_BYTES: usize = 512 * MIB as usize;
 /// Maximum capacity of write buffer in bytes.
 const MAX_WRITE_BUFFER_CAPACITY_BYTES: usize = GIB as usize;
+/// Frames larger than that are sent in chunks of that size, once the peer has declared
+/// that it can reassemble them, so that a large frame doesn't delay the frames of
+/// higher priority classes queued after it.
+const FRAME_CHUNK_SIZE: usize = 64 * KIB as usize;
+/// Bit of the length prefix marking a chunk of a frame.
+/// The payload of a chunk starts with a byte which is 1 if more chunks of the frame follow,
+/// and 0 if it is the last chunk. Frames of other classes may be sent between the chunks,
+/// but only a single frame is sent in chunks at a time.
+pub(crate) const CHUNK_FLAG: u32 = 1 << 31;
 
-type ReadHalf = tokio::io::ReadHalf<tokio::net::TcpStream>;
-type WriteHalf = tokio::io::WriteHalf<tokio::net::TcpStream>;
+type ReadHalf = tokio::io::ReadHalf<tcp::Transport>;
+type WriteHalf = tokio::io::WriteHalf<tcp::Transport>;
 
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum SendError {
@@ -26,6 +41,8 @@ pub(crate) enum SendError {
     IO(#[source] io::Error),
     #[error("queue is full, got {got_bytes}B, max capacity is {want_max_bytes}")]
     QueueOverflow { got_bytes: usize, want_max_bytes: usize },
+    #[error("encryption: {0}")]
+    Encryption(#[source] noise::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -34,12 +51,25 @@ pub(crate) enum RecvError {
     IO(#[source] io::Error),
     #[error("message too large: got {got_bytes}B, want <={want_max_bytes}B")]
     MessageTooLarge { got_bytes: usize, want_max_bytes: usize },
+    #[error("decryption: {0}")]
+    Decryption(#[source] noise::Error),
+    #[error("invalid chunk of a frame")]
+    InvalidChunk,
 }
 
 #[derive(actix::Message, PartialEq, Eq, Clone, Debug)]
 #[rtype(result = "()")]
 pub(crate) struct Frame(pub Vec<u8>);
 
+/// Entry of the send queue.
+enum Outgoing {
+    Frame(MessageClass, Frame),
+    /// Frames after this entry are encrypted.
+    StartEncryption(noise::Encryptor),
+    /// Large frames are sent in chunks from now on.
+    StartChunking,
+}
+
 /// Stream critical error.
 /// Actor is responsible for calling ctx.stop() after receiving stream::Error.
 /// Actor might receive more than 1 stream::Error, but should call ctx.stop() just after the
@@ -57,7 +87,9 @@ pub(crate) enum Error {
 }
 
 pub(crate) struct FramedStream<Actor: actix::Actor> {
-    queue_send: tokio::sync::mpsc::UnboundedSender<Frame>,
+    queue_send: tokio::sync::mpsc::UnboundedSender<Outgoing>,
+    /// Decryptor to be picked up by run_recv_loop.
+    decryptor: Arc<Mutex<Option<noise::Decryptor>>>,
     stats: Arc<connection::Stats>,
     send_buf_size_metric: Arc<metrics::IntGaugeGuard>,
     addr: actix::Addr<Actor>,
@@ -71,11 +103,14 @@ where
 {
     pub fn spawn(
         ctx: &mut actix::Context<Actor>,
+        clock: time::Clock,
         stream: tcp::Stream,
         stats: Arc<connection::Stats>,
+        send_rate_limits: SendRateLimits,
     ) -> Self {
         let (tcp_recv, tcp_send) = tokio::io::split(stream.stream);
         let (queue_send, queue_recv) = tokio::sync::mpsc::unbounded_channel();
+        let decryptor = Arc::new(Mutex::new(None));
         let send_buf_size_metric = Arc::new(metrics::MetricGuard::new(
             &*metrics::PEER_DATA_WRITE_BUFFER_SIZE,
             vec![stream.peer_addr.to_string()],
@@ -85,30 +120,36 @@ where
             let stats = stats.clone();
             let m = send_buf_size_metric.clone();
             async move {
-                if let Err(err) = Self::run_send_loop(tcp_send, queue_recv, stats, m).await {
-                    addr.do_send(Error::Send(SendError::IO(err)));
+                if let Err(err) =
+                    Self::run_send_loop(clock, tcp_send, queue_recv, stats, m, send_rate_limits)
+                        .await
+                {
+                    addr.do_send(Error::Send(err));
                 }
             }
         }));
         ctx.spawn(wrap_future({
             let addr = ctx.address();
             let stats = stats.clone();
+            let decryptor = decryptor.clone();
             async move {
                 if let Err(err) =
-                    Self::run_recv_loop(stream.peer_addr, tcp_recv, addr.clone(), stats).await
+                    Self::run_recv_loop(stream.peer_addr, tcp_recv, addr.clone(), stats, decryptor)
+                        .await
                 {
                     addr.do_send(Error::Recv(err));
                 }
             }
         }));
-        Self { queue_send, stats, send_buf_size_metric, addr: ctx.address() }
+        Self { queue_send, decryptor, stats, send_buf_size_metric, addr: ctx.address() }
     }
 
-    /// Pushes `msg` to the send queue.
+    /// Pushes `msg` of the given class to the send queue.
     /// Silently drops message if the connection has been closed.
-    /// If the message is too large, it will be silently dropped inside run_send_loop.
+    /// If the message is too large, or too many messages of its class are waiting
+    /// to be sent, it will be silently dropped inside run_send_loop.
     /// Emits a critical error to Actor if send queue is full.
-    pub fn send(&self, frame: Frame) {
+    pub fn send(&self, class: MessageClass, frame: Frame) {
         let msg = &frame.0;
         let mut buf_size =
             self.stats.bytes_to_send.fetch_add(msg.len() as u64, Ordering::Acquire) as usize;
@@ -125,7 +166,30 @@ where
                 want_max_bytes: MAX_WRITE_BUFFER_CAPACITY_BYTES,
             }));
         }
-        let _ = self.queue_send.send(frame);
+        let _ = self.queue_send.send(Outgoing::Frame(class, frame));
+    }
+
+    /// Frames pushed to the send queue after this call are encrypted.
+    pub fn start_encryption(&self, encryptor: noise::Encryptor) {
+        let _ = self.queue_send.send(Outgoing::StartEncryption(encryptor));
+    }
+
+    /// Large frames are sent in chunks after this call, see `FRAME_CHUNK_SIZE`.
+    /// Should be called only once the peer has declared that it can receive chunks.
+    pub fn start_chunking(&self) {
+        let _ = self.queue_send.send(Outgoing::StartChunking);
+    }
+
+    /// Frames received after this call are decrypted.
+    /// The peer is expected to start encryption only after it has received
+    /// a message sent after this call, so there is no race with run_recv_loop.
+    ///
+    /// An outbound peer which doesn't know the encoding of the inbound peer yet sends
+    /// a borsh copy of its Handshake, which doesn't support the noise handshake. The copy
+    /// reaches the inbound peer only after it has started decryption, so a single frame
+    /// which can't be decrypted is skipped, unless some frame has been decrypted already.
+    pub fn start_decryption(&self, decryptor: noise::Decryptor) {
+        *self.decryptor.lock() = Some(decryptor);
     }
 
     /// Event loop receiving and processing messages.
@@ -141,6 +205,7 @@ where
         read: ReadHalf,
         addr: actix::Addr<Actor>,
         stats: Arc<connection::Stats>,
+        pending_decryptor: Arc<Mutex<Option<noise::Decryptor>>>,
     ) -> Result<(), RecvError> {
         const READ_BUFFER_CAPACITY: usize = 8 * 1024;
         let mut read = tokio::io::BufReader::with_capacity(READ_BUFFER_CAPACITY, read);
@@ -151,13 +216,26 @@ where
             &metrics::PEER_DATA_READ_BUFFER_SIZE,
             vec![peer_addr.to_string()],
         );
+        let mut decryptor = None;
+        // See start_decryption().
+        let mut may_skip_frame = true;
+        // Frame received in chunks so far, see `CHUNK_FLAG`.
+        let mut chunked_frame: Option<Vec<u8>> = None;
         loop {
-            let n = read.read_u32_le().await.map_err(RecvError::IO)? as usize;
-            if n > NETWORK_MESSAGE_MAX_SIZE_BYTES {
-                return Err(RecvError::MessageTooLarge {
-                    got_bytes: n,
-                    want_max_bytes: NETWORK_MESSAGE_MAX_SIZE_BYTES,
-                });
+            let prefix = read.read_u32_le().await.map_err(RecvError::IO)?;
+            let is_chunk = prefix & CHUNK_FLAG != 0;
+            let n = (prefix & !CHUNK_FLAG) as usize;
+            // The frame has been sent by the peer after it started encryption,
+            // so start_decryption() has been already called for it, if at all.
+            if decryptor.is_none() {
+                decryptor = pending_decryptor.lock().take();
+            }
+            let max_size = match decryptor {
+                Some(_) => noise::encrypted_len(NETWORK_MESSAGE_MAX_SIZE_BYTES),
+                None => NETWORK_MESSAGE_MAX_SIZE_BYTES,
+            };
+            if n > max_size {
+                return Err(RecvError::MessageTooLarge { got_bytes: n, want_max_bytes: max_size });
             }
             msg_size_metric.observe(n as f64);
             buf_size_metric.set(n as i64);
@@ -166,8 +244,38 @@ where
             read.read_exact(&mut buf[..]).await.map_err(RecvError::IO)?;
             t.observe_duration();
             buf_size_metric.set(0);
-            stats.received_messages.fetch_add(1, Ordering::Relaxed);
             stats.received_bytes.fetch_add(n as u64, Ordering::Relaxed);
+            if let Some(decryptor) = &mut decryptor {
+                match decryptor.decrypt(&buf) {
+                    Ok(frame) => buf = frame,
+                    Err(_) if may_skip_frame => {
+                        may_skip_frame = false;
+                        continue;
+                    }
+                    Err(err) => return Err(RecvError::Decryption(err)),
+                }
+                may_skip_frame = false;
+            }
+            if is_chunk {
+                let Some((&more, chunk)) = buf.split_first() else {
+                    return Err(RecvError::InvalidChunk);
+                };
+                let frame = chunked_frame.get_or_insert_with(Vec::new);
+                let got_bytes = frame.len() + chunk.len();
+                if got_bytes > NETWORK_MESSAGE_MAX_SIZE_BYTES {
+                    return Err(RecvError::MessageTooLarge {
+                        got_bytes,
+                        want_max_bytes: NETWORK_MESSAGE_MAX_SIZE_BYTES,
+                    });
+                }
+                frame.extend_from_slice(chunk);
+                match more {
+                    0 => buf = chunked_frame.take().unwrap(),
+                    1 => continue,
+                    _ => return Err(RecvError::InvalidChunk),
+                }
+            }
+            stats.received_messages.fetch_add(1, Ordering::Relaxed);
             if let Err(_) = addr.send(Frame(buf)).await {
                 // We got mailbox error, which means that Actor has stopped,
                 // so we should just close the stream.
@@ -175,41 +283,136 @@ where
             }
         }
     }
+    /// Event loop sending the queued messages.
+    /// Messages are sent in the order of the priority of their class, see `qos` module.
+    /// A large frame is sent in chunks, and the frames of higher priority classes
+    /// queued in the meantime are sent between its chunks.
+    /// Messages are written in batches, with a flush whenever there is nothing more
+    /// to be sent immediately.
     async fn run_send_loop(
+        clock: time::Clock,
         tcp_send: WriteHalf,
-        mut queue_recv: tokio::sync::mpsc::UnboundedReceiver<Frame>,
+        mut queue_recv: tokio::sync::mpsc::UnboundedReceiver<Outgoing>,
         stats: Arc<connection::Stats>,
         buf_size_metric: Arc<metrics::IntGaugeGuard>,
-    ) -> io::Result<()> {
+        send_rate_limits: SendRateLimits,
+    ) -> Result<(), SendError> {
         const WRITE_BUFFER_CAPACITY: usize = 8 * 1024;
         let mut writer = tokio::io::BufWriter::with_capacity(WRITE_BUFFER_CAPACITY, tcp_send);
-        while let Some(Frame(mut msg)) = queue_recv.recv().await {
-            // Try writing a batch of messages and flush once at the end.
-            loop {
-                // TODO(gprusak): sending a too large message should probably be treated as a bug,
-                // since dropping messages may lead to hard-to-debug high-level issues.
-                if msg.len() > NETWORK_MESSAGE_MAX_SIZE_BYTES {
-                    metrics::MessageDropped::InputTooLong.inc_unknown_msg();
-                } else {
-                    writer.write_u32_le(msg.len() as u32).await?;
-                    writer.write_all(&msg[..]).await?;
+        let mut queues = qos::SendQueues::new(clock.now(), &send_rate_limits);
+        let mut encryptor: Option<noise::Encryptor> = None;
+        // Encryption applies to the frames queued after it was started, so the frames
+        // queued after it wait in queue_recv until all the frames before it are sent.
+        let mut pending_encryptor: Option<noise::Encryptor> = None;
+        let mut chunking = false;
+        // Frame being sent in chunks: its class, the frame and the number of bytes sent.
+        let mut chunked_frame: Option<(MessageClass, Vec<u8>, usize)> = None;
+        // Updates the stats once a frame is sent or dropped.
+        let dequeued = |len: usize| {
+            stats.messages_to_send.fetch_sub(1, Ordering::Release);
+            stats.bytes_to_send.fetch_sub(len as u64, Ordering::Release);
+            buf_size_metric.sub(len as i64);
+        };
+        let queue = |queues: &mut qos::SendQueues,
+                     pending_encryptor: &mut Option<noise::Encryptor>,
+                     chunking: &mut bool,
+                     item: Outgoing| match item {
+            Outgoing::StartEncryption(it) => *pending_encryptor = Some(it),
+            Outgoing::StartChunking => *chunking = true,
+            Outgoing::Frame(class, Frame(msg)) => {
+                if let Err(msg) = queues.push(class, msg) {
+                    dequeued(msg.len());
                 }
-                stats.messages_to_send.fetch_sub(1, Ordering::Release);
-                stats.bytes_to_send.fetch_sub(msg.len() as u64, Ordering::Release);
-                buf_size_metric.sub(msg.len() as i64);
-                msg = match queue_recv.try_recv() {
-                    Ok(Frame(it)) => it,
+            }
+        };
+        loop {
+            if pending_encryptor.is_some() && queues.is_empty() && chunked_frame.is_none() {
+                encryptor = pending_encryptor.take();
+            }
+            while pending_encryptor.is_none() {
+                match queue_recv.try_recv() {
+                    Ok(item) => queue(&mut queues, &mut pending_encryptor, &mut chunking, item),
                     Err(_) => break,
-                };
+                }
+            }
+            // While a frame is sent in chunks, only the frames of higher priority classes
+            // are sent between them.
+            let above = chunked_frame.as_ref().map(|(class, ..)| *class);
+            let not_before = match queues.pop(clock.now(), above) {
+                qos::Pop::Frame(class, msg) => {
+                    // TODO(gprusak): sending a too large message should probably be treated as a bug,
+                    // since dropping messages may lead to hard-to-debug high-level issues.
+                    if msg.len() > NETWORK_MESSAGE_MAX_SIZE_BYTES {
+                        metrics::MessageDropped::InputTooLong.inc_unknown_msg();
+                    } else if chunking && chunked_frame.is_none() && msg.len() > FRAME_CHUNK_SIZE {
+                        chunked_frame = Some((class, msg, 0));
+                        continue;
+                    } else {
+                        write_frame(&mut writer, &mut encryptor, &msg, None).await?;
+                    }
+                    dequeued(msg.len());
+                    continue;
+                }
+                _ if chunked_frame.is_some() => {
+                    let (_, msg, sent) = chunked_frame.as_mut().unwrap();
+                    let end = msg.len().min(*sent + FRAME_CHUNK_SIZE);
+                    let more = end < msg.len();
+                    write_frame(&mut writer, &mut encryptor, &msg[*sent..end], Some(more)).await?;
+                    *sent = end;
+                    if !more {
+                        dequeued(msg.len());
+                        chunked_frame = None;
+                    }
+                    continue;
+                }
+                // All the frames queued before the start of encryption have been sent.
+                qos::Pop::Empty if pending_encryptor.is_some() => continue,
+                qos::Pop::Empty => None,
+                qos::Pop::NotBefore(t) => Some(t),
+            };
+            // Nothing can be sent right now, so flush the batch written so far and wait
+            // for a new message or until a rate limited class can be sent again.
+            writer.flush().await.map_err(SendError::IO)?;
+            let item = match (not_before, &pending_encryptor) {
+                (Some(t), Some(_)) => {
+                    clock.sleep_until(t).await;
+                    continue;
+                }
+                (Some(t), None) => tokio::select! {
+                    item = queue_recv.recv() => item,
+                    () = clock.sleep_until(t) => continue,
+                },
+                (None, _) => queue_recv.recv().await,
+            };
+            match item {
+                Some(item) => queue(&mut queues, &mut pending_encryptor, &mut chunking, item),
+                None => return Ok(()),
             }
-            // This is an unconditional flush, which means that even if new messages
-            // will be added to the queue in the meantime, we will wait for the buffer
-            // to be flushed before sending them. This is suboptimal in case messages are small
-            // and added to the queue at a rate similar to flush latency. To fix that
-            // we would need to put writer.flush() and queue_recv.recv() into a tokio::select
-            // and make sure that both are cancellation-safe.
-            writer.flush().await?;
         }
-        Ok(())
     }
 }
+
+/// Writes a frame with its length prefix, or a chunk of a frame if `more` is set,
+/// see `CHUNK_FLAG`.
+async fn write_frame(
+    writer: &mut tokio::io::BufWriter<WriteHalf>,
+    encryptor: &mut Option<noise::Encryptor>,
+    data: &[u8],
+    more: Option<bool>,
+) -> Result<(), SendError> {
+    let chunk;
+    let encrypted;
+    let mut data = data;
+    let mut flag = 0;
+    if let Some(more) = more {
+        chunk = [&[more as u8][..], data].concat();
+        data = &chunk;
+        flag = CHUNK_FLAG;
+    }
+    if let Some(encryptor) = encryptor {
+        encrypted = encryptor.encrypt(data).map_err(SendError::Encryption)?;
+        data = &encrypted;
+    }
+    writer.write_u32_le(flag | data.len() as u32).await.map_err(SendError::IO)?;
+    writer.write_all(data).await.map_err(SendError::IO)
+}
@@ -1,17 +1,26 @@
 use crate::actix::ActixSystem;
+use crate::config::SendRateLimits;
 use crate::network_protocol::testonly as data;
+use crate::network_protocol::MessageClass;
+use crate::peer::noise;
 use crate::peer::stream;
 use crate::tcp;
 use crate::testonly::make_rng;
 use actix::Actor as _;
 use actix::ActorContext as _;
-use rand::Rng as _;
+use bytesize::MIB;
+use near_async::time;
+use near_primitives::network::PeerId;
+use rand::Rng;
 use std::sync::Arc;
+use tokio::io::AsyncReadExt as _;
 use tokio::sync::mpsc;
 
 struct Actor {
     stream: stream::FramedStream<Actor>,
     queue_send: mpsc::UnboundedSender<stream::Frame>,
+    /// Decryption is started once the first frame is received.
+    decryptor: Option<noise::Decryptor>,
 }
 
 impl actix::Actor for Actor {
@@ -20,18 +29,32 @@ impl actix::Actor for Actor {
 
 #[derive(actix::Message)]
 #[rtype("()")]
-struct SendFrame(stream::Frame);
+struct SendFrame(MessageClass, stream::Frame);
 
 impl actix::Handler<SendFrame> for Actor {
     type Result = ();
-    fn handle(&mut self, SendFrame(frame): SendFrame, _ctx: &mut Self::Context) {
-        self.stream.send(frame);
+    fn handle(&mut self, SendFrame(class, frame): SendFrame, _ctx: &mut Self::Context) {
+        self.stream.send(class, frame);
+    }
+}
+
+#[derive(actix::Message)]
+#[rtype("()")]
+struct StartEncryption(noise::Encryptor);
+
+impl actix::Handler<StartEncryption> for Actor {
+    type Result = ();
+    fn handle(&mut self, StartEncryption(encryptor): StartEncryption, _ctx: &mut Self::Context) {
+        self.stream.start_encryption(encryptor);
     }
 }
 
 impl actix::Handler<stream::Frame> for Actor {
     type Result = ();
     fn handle(&mut self, frame: stream::Frame, _ctx: &mut Self::Context) {
+        if let Some(decryptor) = self.decryptor.take() {
+            self.stream.start_decryption(decryptor);
+        }
         self.queue_send.send(frame).ok().unwrap();
     }
 }
@@ -49,14 +72,21 @@ struct Handler {
 }
 
 impl Actor {
-    async fn spawn(s: tcp::Stream) -> Handler {
+    async fn spawn(s: tcp::Stream, decryptor: Option<noise::Decryptor>) -> Handler {
         let (queue_send, queue_recv) = mpsc::unbounded_channel();
         Handler {
             queue_recv,
             system: ActixSystem::spawn(|| {
                 Actor::create(|ctx| {
-                    let stream = stream::FramedStream::spawn(ctx, s, Arc::default());
-                    Self { stream, queue_send }
+                    let stream = stream::FramedStream::spawn(
+                        ctx,
+                        time::Clock::real(),
+                        s,
+                        Arc::default(),
+                        SendRateLimits::default(),
+                    );
+                    stream.start_chunking();
+                    Self { stream, queue_send, decryptor }
                 })
             })
             .await,
@@ -64,12 +94,47 @@ impl Actor {
     }
 }
 
+fn make_frame(rng: &mut impl Rng, size: usize) -> stream::Frame {
+    let mut msg = vec![0; size];
+    rng.fill(&mut msg[..]);
+    stream::Frame(msg)
+}
+
+/// Encryptor of one end of a connection and the matching decryptor of the other end.
+fn make_encryption(rng: &mut impl Rng) -> (noise::Encryptor, noise::Decryptor) {
+    let a = data::make_secret_key(rng);
+    let b = data::make_secret_key(rng);
+    let a_id = PeerId::new(a.public_key());
+    let b_id = PeerId::new(b.public_key());
+    let (initiator, msg) = noise::Initiator::start(&a, &b_id, tcp::Tier::T2).unwrap();
+    let (b_transport, response) = noise::respond(&b, &a_id, tcp::Tier::T2, &msg).unwrap();
+    let a_transport = initiator.finish(&b_id, &response).unwrap();
+    (a_transport.split().0, b_transport.split().1)
+}
+
+/// Size of a frame which doesn't fit into the socket buffers, so that its sending
+/// is blocked until the other end starts reading.
+const LARGE_FRAME_SIZE: usize = 32 * MIB as usize;
+
+/// Reads the first chunk of a frame directly from the stream, before an actor is spawned
+/// for it, and returns its payload. Once it returns, the send loop of the other end is
+/// in the middle of sending the frame in chunks.
+async fn read_first_chunk(s: &mut tcp::Stream) -> Vec<u8> {
+    let prefix = s.stream.read_u32_le().await.unwrap();
+    assert_ne!(prefix & stream::CHUNK_FLAG, 0);
+    let mut buf = vec![0; (prefix & !stream::CHUNK_FLAG) as usize];
+    s.stream.read_exact(&mut buf[..]).await.unwrap();
+    // More chunks of the frame follow.
+    assert_eq!(buf[0], 1);
+    buf.split_off(1)
+}
+
 #[tokio::test]
 async fn send_recv() {
     let mut rng = make_rng(98324532);
     let (s1, s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
-    let a1 = Actor::spawn(s1).await;
-    let mut a2 = Actor::spawn(s2).await;
+    let a1 = Actor::spawn(s1, None).await;
+    let mut a2 = Actor::spawn(s2, None).await;
 
     for _ in 0..5 {
         let n = rng.gen_range(1..10);
@@ -82,7 +147,33 @@ async fn send_recv() {
             })
             .collect();
         for msg in &msgs {
-            a1.system.addr.send(SendFrame(msg.clone())).await.unwrap();
+            a1.system.addr.send(SendFrame(MessageClass::Gossip, msg.clone())).await.unwrap();
+        }
+        for want in &msgs {
+            let got = a2.queue_recv.recv().await.unwrap();
+            assert_eq!(&got, want);
+        }
+    }
+}
+
+// Frames larger than a chunk are received whole, also when interleaved with small ones.
+#[tokio::test]
+async fn send_recv_chunked() {
+    let mut rng = make_rng(43298012);
+    let (s1, s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
+    let a1 = Actor::spawn(s1, None).await;
+    let mut a2 = Actor::spawn(s2, None).await;
+
+    for _ in 0..5 {
+        let n = rng.gen_range(1..10);
+        let msgs: Vec<_> = (0..n)
+            .map(|_| {
+                let size = rng.gen_range(0..200000);
+                make_frame(&mut rng, size)
+            })
+            .collect();
+        for msg in &msgs {
+            a1.system.addr.send(SendFrame(MessageClass::Gossip, msg.clone())).await.unwrap();
         }
         for want in &msgs {
             let got = a2.queue_recv.recv().await.unwrap();
@@ -90,3 +181,53 @@ async fn send_recv() {
         }
     }
 }
+
+#[tokio::test]
+async fn higher_class_preempts_chunked_frame() {
+    let mut rng = make_rng(7645234);
+    let (s1, mut s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
+    let a1 = Actor::spawn(s1, None).await;
+
+    let large = make_frame(&mut rng, LARGE_FRAME_SIZE);
+    let gossip = make_frame(&mut rng, 100);
+    let consensus = make_frame(&mut rng, 100);
+    a1.system.addr.send(SendFrame(MessageClass::Sync, large.clone())).await.unwrap();
+    let first_chunk = read_first_chunk(&mut s2).await;
+    assert_eq!(first_chunk[..], large.0[..first_chunk.len()]);
+    a1.system.addr.send(SendFrame(MessageClass::Gossip, gossip.clone())).await.unwrap();
+    a1.system.addr.send(SendFrame(MessageClass::Consensus, consensus.clone())).await.unwrap();
+
+    // The consensus frame is sent between the chunks of the large frame,
+    // while the gossip frame has to wait for the large frame to be sent.
+    // The receiver starts reading after the first chunk, so it gets the rest of the frame.
+    let mut a2 = Actor::spawn(s2, None).await;
+    assert_eq!(a2.queue_recv.recv().await.unwrap(), consensus);
+    assert_eq!(a2.queue_recv.recv().await.unwrap().0, large.0[first_chunk.len()..]);
+    assert_eq!(a2.queue_recv.recv().await.unwrap(), gossip);
+}
+
+#[tokio::test]
+async fn chunked_frame_before_encryption() {
+    let mut rng = make_rng(2398471);
+    let (s1, mut s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
+    let a1 = Actor::spawn(s1, None).await;
+    let (encryptor, decryptor) = make_encryption(&mut rng);
+
+    let large = make_frame(&mut rng, LARGE_FRAME_SIZE);
+    let small = make_frame(&mut rng, 100);
+    a1.system.addr.send(SendFrame(MessageClass::Sync, large.clone())).await.unwrap();
+    let first_chunk = read_first_chunk(&mut s2).await;
+    a1.system.addr.send(StartEncryption(encryptor)).await.unwrap();
+    // Frames queued after the start of encryption are sent only after the whole large frame
+    // has been sent in cleartext, even if they are of a higher priority class.
+    // Were they sent in cleartext, the receiver would skip the first one
+    // and fail to decrypt the second one.
+    for _ in 0..2 {
+        a1.system.addr.send(SendFrame(MessageClass::Consensus, small.clone())).await.unwrap();
+    }
+
+    let mut a2 = Actor::spawn(s2, Some(decryptor)).await;
+    assert_eq!(a2.queue_recv.recv().await.unwrap().0, large.0[first_chunk.len()..]);
+    assert_eq!(a2.queue_recv.recv().await.unwrap(), small);
+    assert_eq!(a2.queue_recv.recv().await.unwrap(), small);
+}
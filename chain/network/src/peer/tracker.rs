@@ -63,12 +63,12 @@ impl Default for Tracker {
 }
 
 impl Tracker {
-    pub(crate) fn increment_received(&mut self, clock: &time::Clock, size: u64) {
-        self.received_bytes.record(clock, size);
+    pub(crate) fn increment_received(&mut self, clock: &time::Clock, size: u64, raw_size: u64) {
+        self.received_bytes.record(clock, size, raw_size);
     }
 
-    pub(crate) fn increment_sent(&mut self, clock: &time::Clock, size: u64) {
-        self.sent_bytes.record(clock, size);
+    pub(crate) fn increment_sent(&mut self, clock: &time::Clock, size: u64, raw_size: u64) {
+        self.sent_bytes.record(clock, size, raw_size);
     }
 
     // TODO: uncomment this once we add a new message type to sync block height
@@ -1,6 +1,7 @@
 use near_async::time;
 /// The purpose of `TransferStats` is to keep track of transfer sizes in done in a period of 1 minute.
 /// And then; to provide a summary, the count and total size in bytes when requested.
+/// Sizes are tracked both as sent over the wire and before compression.
 ///
 /// ```rust,ignore
 /// use crate::peer::transfer_stats::TransferStats;
@@ -9,7 +10,7 @@ use near_async::time;
 /// let ts = TransferStats::new();
 /// let start = time::Instant::now();
 ///
-/// ts.record(1234, start);
+/// ts.record(1234, 5678, start);
 ///
 /// let later = time::Instant::now();
 /// println!("{}", ts.minute_stats(later));
@@ -25,6 +26,8 @@ struct Event {
     instant: time::Instant,
     /// Number of bytes
     bytes: u64,
+    /// Number of bytes before compression.
+    raw_bytes: u64,
 }
 
 /// Represents all events which happened in last minute.
@@ -35,6 +38,8 @@ pub(crate) struct TransferStats {
     events: VecDeque<Event>,
     /// Sum of bytes for all entries.
     total_bytes_in_events: u64,
+    /// Sum of bytes before compression for all entries.
+    total_raw_bytes_in_events: u64,
 }
 
 /// Represents cumulative stats per minute.
@@ -42,24 +47,31 @@ pub(crate) struct TransferStats {
 pub(crate) struct MinuteStats {
     /// Bytes per minute.
     pub(crate) bytes_per_min: u64,
+    /// Bytes before compression per minute.
+    pub(crate) raw_bytes_per_min: u64,
     /// Messages per minute.
     pub(crate) count_per_min: usize,
 }
 
 impl TransferStats {
-    /// Record event at current time `now` with `bytes` bytes.
-    /// Time in `now` should be monotonically increasing.
-    pub(crate) fn record(&mut self, clock: &time::Clock, bytes: u64) {
+    /// Record event at current time `now` with `bytes` bytes, which were `raw_bytes`
+    /// bytes before compression. Time in `now` should be monotonically increasing.
+    pub(crate) fn record(&mut self, clock: &time::Clock, bytes: u64, raw_bytes: u64) {
         let now = clock.now();
         self.remove_old_entries(now);
         self.total_bytes_in_events += bytes;
-        self.events.push_back(Event { instant: now, bytes });
+        self.total_raw_bytes_in_events += raw_bytes;
+        self.events.push_back(Event { instant: now, bytes, raw_bytes });
     }
 
     /// Get stats stored in `MinuteStats` struct.
     pub(crate) fn minute_stats(&mut self, clock: &time::Clock) -> MinuteStats {
         self.remove_old_entries(clock.now());
-        MinuteStats { bytes_per_min: self.total_bytes_in_events, count_per_min: self.events.len() }
+        MinuteStats {
+            bytes_per_min: self.total_bytes_in_events,
+            raw_bytes_per_min: self.total_raw_bytes_in_events,
+            count_per_min: self.events.len(),
+        }
     }
 
     /// Remove entries older than 1m.
@@ -67,6 +79,7 @@ impl TransferStats {
         while let Some(event) = self.events.pop_front() {
             if now - event.instant > TRANSFER_STATS_INTERVAL {
                 self.total_bytes_in_events -= event.bytes;
+                self.total_raw_bytes_in_events -= event.raw_bytes;
             } else {
                 // add the event back
                 self.events.push_front(event);
@@ -85,40 +98,40 @@ mod tests {
         let clock = time::FakeClock::default();
         assert_eq!(
             ts.minute_stats(&clock.clock()),
-            MinuteStats { bytes_per_min: 0, count_per_min: 0 }
+            MinuteStats { bytes_per_min: 0, raw_bytes_per_min: 0, count_per_min: 0 }
         );
 
-        ts.record(&clock.clock(), 10);
+        ts.record(&clock.clock(), 10, 10);
 
         assert_eq!(
             ts.minute_stats(&clock.clock()),
-            MinuteStats { bytes_per_min: 10, count_per_min: 1 }
+            MinuteStats { bytes_per_min: 10, raw_bytes_per_min: 10, count_per_min: 1 }
         );
 
         clock.advance(time::Duration::seconds(45));
-        ts.record(&clock.clock(), 100);
+        ts.record(&clock.clock(), 100, 300);
         assert_eq!(
             ts.minute_stats(&clock.clock()),
-            MinuteStats { bytes_per_min: 110, count_per_min: 2 }
+            MinuteStats { bytes_per_min: 110, raw_bytes_per_min: 310, count_per_min: 2 }
         );
 
         clock.advance(time::Duration::seconds(14));
-        ts.record(&clock.clock(), 1000);
+        ts.record(&clock.clock(), 1000, 1000);
         assert_eq!(
             ts.minute_stats(&clock.clock()),
-            MinuteStats { bytes_per_min: 1110, count_per_min: 3 }
+            MinuteStats { bytes_per_min: 1110, raw_bytes_per_min: 1310, count_per_min: 3 }
         );
 
         clock.advance(time::Duration::seconds(2));
         assert_eq!(
             ts.minute_stats(&clock.clock()),
-            MinuteStats { bytes_per_min: 1100, count_per_min: 2 }
+            MinuteStats { bytes_per_min: 1100, raw_bytes_per_min: 1300, count_per_min: 2 }
         );
 
         clock.advance(time::Duration::seconds(60));
         assert_eq!(
             ts.minute_stats(&clock.clock()),
-            MinuteStats { bytes_per_min: 0, count_per_min: 0 }
+            MinuteStats { bytes_per_min: 0, raw_bytes_per_min: 0, count_per_min: 0 }
         );
     }
 }
@@ -62,6 +62,10 @@ pub(crate) struct Stats {
     pub received_bytes_per_sec: AtomicU64,
     /// Avg sent bytes/s, based on the last few minutes of traffic.
     pub sent_bytes_per_sec: AtomicU64,
+    /// Like `received_bytes_per_sec`, but counting the size of the messages before compression.
+    pub received_raw_bytes_per_sec: AtomicU64,
+    /// Like `sent_bytes_per_sec`, but counting the size of the messages before compression.
+    pub sent_raw_bytes_per_sec: AtomicU64,
 
     /// Number of messages in the buffer to send.
     pub messages_to_send: AtomicU64,
@@ -80,6 +84,8 @@ pub(crate) struct Connection {
     pub peer_info: PeerInfo,
     /// AccountKey ownership proof.
     pub owned_account: Option<SignedOwnedAccount>,
+    /// Whether the connection is encrypted.
+    pub encrypted: bool,
     /// Chain Id and hash of genesis block.
     pub genesis_id: GenesisId,
     /// Shards that the peer is tracking.
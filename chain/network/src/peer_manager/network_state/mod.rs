@@ -424,9 +424,12 @@ impl NetworkState {
             interval.tick(&clock).await;
 
             let result = async {
-                let stream = tcp::Stream::connect(&peer_info, tcp::Tier::T2)
+                let stream = self
+                    .config
+                    .dialer
+                    .connect(&peer_info, tcp::Tier::T2)
                     .await
-                    .context("tcp::Stream::connect()")?;
+                    .context("Dialer::connect()")?;
                 PeerActor::spawn_and_handshake(clock.clone(), stream, None, self.clone())
                     .await
                     .context("PeerActor::spawn()")?;
@@ -232,6 +232,7 @@ impl NetworkState {
             .call(event, |events: Vec<NetworkTopologyChange>| async move {
                 let (to_broadcast, oks) =
                     this.graph_v2.batch_process_network_changes(&clock, events).await;
+                this.config.event_sink.push(Event::RoutesUpdated);
 
                 if let Some(my_distance_vector) = to_broadcast {
                     this.broadcast_distance_vector(my_distance_vector);
@@ -28,8 +28,9 @@ use near_performance_metrics_macros::perf;
 use near_primitives::block::GenesisId;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::views::{
-    ConnectionInfoView, EdgeView, KnownPeerStateView, NetworkGraphView, PeerStoreView,
-    RecentOutboundConnectionsView, SnapshotHostInfoView, SnapshotHostsView,
+    ConnectionInfoView, EdgeView, KnownPeerStateView, NetworkGraphView, PeerReputationInfoView,
+    PeerReputationView, PeerStoreView, RecentOutboundConnectionsView, SnapshotHostInfoView,
+    SnapshotHostsView,
 };
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
@@ -106,6 +107,8 @@ pub enum Event {
     RoutedMessageDropped,
     AccountsAdded(Vec<AnnounceAccount>),
     EdgesAdded(Vec<Edge>),
+    // Reported once the V2 routing table has processed a batch of network topology changes.
+    RoutesUpdated,
     Ping(Ping),
     Pong(Pong),
     // Reported once a message has been processed.
@@ -208,8 +211,9 @@ impl PeerManagerActor {
     ) -> anyhow::Result<actix::Addr<Self>> {
         let config = config.verify().context("config")?;
         let store = store::Store::from(store);
-        let peer_store = peer_store::PeerStore::new(&clock, config.peer_store.clone())
-            .context("PeerStore::new")?;
+        let peer_store =
+            peer_store::PeerStore::new(&clock, config.peer_store.clone(), store.clone())
+                .context("PeerStore::new")?;
         tracing::debug!(target: "network",
                len = peer_store.len(),
                boot_nodes = config.peer_store.boot_nodes.len(),
@@ -244,7 +248,7 @@ impl PeerManagerActor {
                 // Start server if address provided.
                 if let Some(server_addr) = &state.config.node_addr {
                     tracing::debug!(target: "network", at = ?server_addr, "starting public server");
-                    let mut listener = match server_addr.listener() {
+                    let mut listener = match state.config.dialer.listener(server_addr) {
                         Ok(it) => it,
                         Err(e) => {
                             panic!("failed to start listening on server_addr={server_addr:?} e={e:?}")
@@ -399,7 +403,7 @@ impl PeerManagerActor {
             && !self.state.config.outbound_disabled
     }
 
-    /// Returns peers close to the highest height
+    /// Returns peers close to the highest height, marking the ones with a low reputation.
     fn highest_height_peers(&self) -> Vec<HighestHeightPeerInfo> {
         let infos: Vec<HighestHeightPeerInfo> = self
             .state
@@ -422,6 +426,11 @@ impl PeerManagerActor {
                 i.highest_block_height.saturating_add(self.state.config.highest_peer_horizon)
                     >= max_height
             })
+            .map(|mut i| {
+                i.low_reputation =
+                    self.state.peer_store.has_low_reputation(&self.clock, &i.peer_info.id);
+                i
+            })
             .collect()
     }
 
@@ -573,6 +582,7 @@ impl PeerManagerActor {
             let prefer_previously_connected_peer =
                 thread_rng().gen_bool(PREFER_PREVIOUSLY_CONNECTED_PEER);
             if let Some(peer_info) = self.state.peer_store.unconnected_peer(
+                &self.clock,
                 |peer_state| {
                     // Ignore connecting to ourself
                     self.my_peer_id == peer_state.peer_info.id
@@ -592,7 +602,7 @@ impl PeerManagerActor {
                     let clock = self.clock.clone();
                     async move {
                         let result = async {
-                            let stream = tcp::Stream::connect(&peer_info, tcp::Tier::T2).await.context("tcp::Stream::connect()")?;
+                            let stream = state.config.dialer.connect(&peer_info, tcp::Tier::T2).await.context("Dialer::connect()")?;
                             PeerActor::spawn_and_handshake(clock.clone(),stream,None,state.clone()).await.context("PeerActor::spawn()")?;
                             anyhow::Ok(())
                         }.await;
@@ -746,8 +756,16 @@ impl PeerManagerActor {
                 NetworkResponses::NoResponse
             }
             NetworkRequests::BlockRequest { hash, peer_id } => {
-                if self.state.tier2.send_message(peer_id, Arc::new(PeerMessage::BlockRequest(hash)))
+                if self
+                    .state
+                    .tier2
+                    .send_message(peer_id.clone(), Arc::new(PeerMessage::BlockRequest(hash)))
                 {
+                    self.state.peer_store.request_sent(
+                        &self.clock,
+                        peer_id,
+                        peer_store::reputation::Request::Block(hash),
+                    );
                     NetworkResponses::NoResponse
                 } else {
                     NetworkResponses::RouteNotFound
@@ -776,9 +794,14 @@ impl PeerManagerActor {
             }
             NetworkRequests::StateRequestPart { shard_id, sync_hash, part_id, peer_id } => {
                 if self.state.tier2.send_message(
-                    peer_id,
+                    peer_id.clone(),
                     Arc::new(PeerMessage::StateRequestPart(shard_id, sync_hash, part_id)),
                 ) {
+                    self.state.peer_store.request_sent(
+                        &self.clock,
+                        peer_id,
+                        peer_store::reputation::Request::StatePart { shard_id, sync_hash, part_id },
+                    );
                     NetworkResponses::NoResponse
                 } else {
                     NetworkResponses::RouteNotFound
@@ -1103,6 +1126,22 @@ impl actix::Handler<GetDebugStatus> for PeerManagerActor {
                     })
                     .collect::<Vec<_>>(),
             }),
+            GetDebugStatus::PeerReputation => {
+                let mut peers = self
+                    .state
+                    .peer_store
+                    .load_reputation(&self.clock)
+                    .into_iter()
+                    .map(|(peer_id, score)| PeerReputationInfoView {
+                        low_reputation: score < peer_store::reputation::LOW_SCORE,
+                        peer_id,
+                        score,
+                    })
+                    .collect::<Vec<_>>();
+                // Show the peers with the lowest scores first.
+                peers.sort_by(|a, b| a.score.total_cmp(&b.score));
+                DebugStatus::PeerReputation(PeerReputationView { peers })
+            }
         }
     }
 }
@@ -1,5 +1,6 @@
 use crate::blacklist;
 use crate::network_protocol::PeerInfo;
+use crate::store;
 use crate::types::{KnownPeerState, KnownPeerStatus, ReasonForBan};
 use anyhow::bail;
 use im::hashmap::Entry;
@@ -13,6 +14,7 @@ use rand::thread_rng;
 use std::net::SocketAddr;
 use std::ops::Not;
 
+pub(crate) mod reputation;
 #[cfg(test)]
 mod testonly;
 #[cfg(test)]
@@ -23,9 +25,10 @@ mod tests;
 ///       by connecting to them directly or by learning about them from other peers.
 ///     - Respond to requests from other peers for known peers (see PeerStore::healthy_peers).
 ///     - Select peers to which we may try to connect directly (see PeerStore::unconnected_peer).
+///     - Keep track of the reputation of the peers (see reputation module).
 ///
-/// Contents of the PeerStore are not persisted to the database. Upon starting a node,
-/// the PeerStore is initialized from the boot nodes in its config.
+/// Contents of the PeerStore, except for the reputation scores, are not persisted to the database.
+/// Upon starting a node, the PeerStore is initialized from the boot nodes in its config.
 
 /// Level of trust we have about a new (PeerId, Addr) pair.
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
@@ -71,11 +74,14 @@ pub struct Config {
     pub peer_expiration_duration: time::Duration,
     /// Duration of the ban for misbehaving peers.
     pub ban_window: time::Duration,
+    /// Time after which the reputation score of a peer decays to half of its value.
+    pub reputation_half_life: time::Duration,
 }
 
 /// Known peers store, maintaining cache of known peers
 struct Inner {
     config: Config,
+    store: store::Store,
     boot_nodes: HashSet<PeerId>,
     // LruCache of the known peer states. Be sure to use peek/peek_mut to access information.
     // Using the get/put methods modifies the cache order.
@@ -84,6 +90,7 @@ struct Inner {
     // It can happens that some peers don't have known address, so
     // they will not be present in this list, otherwise they will be present.
     addr_peers: HashMap<SocketAddr, VerifiedPeer>,
+    reputation: reputation::Reputation,
 }
 
 impl Inner {
@@ -271,23 +278,74 @@ impl Inner {
         }
     }
 
+    /// Persists the reputation scores, if they have changed.
+    fn save_reputation(&mut self) {
+        if let Some(scores) = self.reputation.take_changed() {
+            if let Err(err) = self.store.set_peer_reputation(&scores) {
+                tracing::error!(target: "network", ?err, "Failed to save peer reputation");
+            }
+        }
+    }
+
     /// Cleans up the state of the PeerStore, due to passing time.
     /// * it unbans a peer if config.ban_window has passed
     /// * it updates KnownPeerStatus.last_seen of the connected peers
     /// * it removes peers which were not seen for config.peer_expiration_duration
+    /// * it records timeouts of the requests sent to peers and persists the reputation scores
     /// This function should be called periodically.
     pub fn update(&mut self, clock: &time::Clock) {
         let now = clock.now_utc();
         self.unban(now);
         self.update_last_seen(now);
         self.remove_expired(now);
+        self.reputation.update(clock);
+        self.save_reputation();
+    }
+
+    fn unconnected_peer(
+        &self,
+        ignore_fn: impl Fn(&KnownPeerState) -> bool,
+        prefer_previously_connected_peer: bool,
+    ) -> Option<PeerInfo> {
+        if prefer_previously_connected_peer {
+            let preferred_peer = self
+                .find_peers(
+                    |p| {
+                        (p.status == KnownPeerStatus::NotConnected)
+                        && !ignore_fn(p)
+                        && p.peer_info.addr.is_some()
+                        // if we're connecting only to the boot nodes - filter out the nodes that are not bootnodes.
+                        && (!self.config.connect_only_to_boot_nodes || self.boot_nodes.contains(&p.peer_info.id))
+                    },
+                    1,
+                )
+                .get(0)
+                .cloned();
+            // If we found a preferred peer - return it.
+            if preferred_peer.is_some() {
+                return preferred_peer;
+            };
+            // otherwise, pick a peer from the wider pool below.
+        }
+        self.find_peers(
+            |p| {
+                (p.status == KnownPeerStatus::NotConnected || p.status == KnownPeerStatus::Unknown)
+                    && !ignore_fn(p)
+                    && p.peer_info.addr.is_some()
+                    // If we're connecting only to the boot nodes - filter out the nodes that are not boot nodes.
+                    && (!self.config.connect_only_to_boot_nodes || self.boot_nodes.contains(&p.peer_info.id))
+            },
+            1,
+        )
+        .get(0)
+        .cloned()
     }
 }
 
 pub(crate) struct PeerStore(Mutex<Inner>);
 
 impl PeerStore {
-    pub fn new(clock: &time::Clock, config: Config) -> anyhow::Result<Self> {
+    pub fn new(clock: &time::Clock, config: Config, store: store::Store) -> anyhow::Result<Self> {
         let boot_nodes: HashSet<_> = config.boot_nodes.iter().map(|p| p.id.clone()).collect();
         // A mapping from `PeerId` to `KnownPeerState`.
         let mut peerid_2_state = LruCache::new(config.peer_states_cache_size as usize);
@@ -334,8 +392,16 @@ impl PeerStore {
             }
         }
 
-        let inner =
-            Inner { config, boot_nodes, peer_states: peerid_2_state, addr_peers: addr_2_peer };
+        let reputation =
+            reputation::Reputation::new(config.reputation_half_life, store.get_peer_reputation());
+        let inner = Inner {
+            config,
+            store,
+            boot_nodes,
+            peer_states: peerid_2_state,
+            addr_peers: addr_2_peer,
+            reputation,
+        };
         Ok(PeerStore(Mutex::new(inner)))
     }
 
@@ -416,6 +482,8 @@ impl PeerStore {
     ) -> anyhow::Result<()> {
         tracing::warn!(target: "network", "Banning peer {} for {:?}", peer_id, ban_reason);
         let mut inner = self.0.lock();
+        let event = reputation::Event::from_ban_reason(ban_reason);
+        inner.reputation.record(clock.now_utc(), peer_id, event);
         if let Some(peer_state) = inner.peer_states.get_mut(peer_id) {
             let now = clock.now_utc();
             peer_state.last_seen = now;
@@ -428,43 +496,24 @@ impl PeerStore {
 
     /// Return unconnected or peers with unknown status that we can try to connect to.
     /// Peers with unknown addresses are filtered out.
+    /// Peers with a low reputation are returned only if there are no other candidates.
     pub fn unconnected_peer(
         &self,
+        clock: &time::Clock,
         ignore_fn: impl Fn(&KnownPeerState) -> bool,
         prefer_previously_connected_peer: bool,
     ) -> Option<PeerInfo> {
         let inner = self.0.lock();
-        if prefer_previously_connected_peer {
-            let preferred_peer = inner.find_peers(
-                |p| {
-                    (p.status == KnownPeerStatus::NotConnected)
-                        && !ignore_fn(p)
-                        && p.peer_info.addr.is_some()
-                        // if we're connecting only to the boot nodes - filter out the nodes that are not bootnodes.
-                        && (!inner.config.connect_only_to_boot_nodes || inner.boot_nodes.contains(&p.peer_info.id))
-                },
-                1,
+        let now = clock.now_utc();
+        let has_low_reputation = |p: &KnownPeerState| {
+            inner.reputation.score(now, &p.peer_info.id) < reputation::LOW_SCORE
+        };
+        inner
+            .unconnected_peer(
+                |p| ignore_fn(p) || has_low_reputation(p),
+                prefer_previously_connected_peer,
             )
-            .get(0)
-            .cloned();
-            // If we found a preferred peer - return it.
-            if preferred_peer.is_some() {
-                return preferred_peer;
-            };
-            // otherwise, pick a peer from the wider pool below.
-        }
-        inner.find_peers(
-            |p| {
-                (p.status == KnownPeerStatus::NotConnected || p.status == KnownPeerStatus::Unknown)
-                    && !ignore_fn(p)
-                    && p.peer_info.addr.is_some()
-                    // If we're connecting only to the boot nodes - filter out the nodes that are not boot nodes.
-                    && (!inner.config.connect_only_to_boot_nodes || inner.boot_nodes.contains(&p.peer_info.id))
-            },
-            1,
-        )
-        .get(0)
-        .cloned()
+            .or_else(|| inner.unconnected_peer(&ignore_fn, prefer_previously_connected_peer))
     }
 
     /// Return healthy known peers up to given amount.
@@ -515,4 +564,39 @@ impl PeerStore {
     pub fn load(&self) -> HashMap<PeerId, KnownPeerState> {
         self.0.lock().peer_states.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
     }
+
+    /// Current reputation scores of the peers, see `reputation` module.
+    /// Peers without a score have score 0.
+    pub fn load_reputation(&self, clock: &time::Clock) -> std::collections::HashMap<PeerId, f64> {
+        self.0.lock().reputation.scores(clock.now_utc())
+    }
+
+    /// Whether the peer should be avoided if there are other peers to choose from.
+    pub fn has_low_reputation(&self, clock: &time::Clock, peer_id: &PeerId) -> bool {
+        self.0.lock().reputation.score(clock.now_utc(), peer_id) < reputation::LOW_SCORE
+    }
+
+    pub fn reputation_event(
+        &self,
+        clock: &time::Clock,
+        peer_id: &PeerId,
+        event: reputation::Event,
+    ) {
+        self.0.lock().reputation.record(clock.now_utc(), peer_id, event)
+    }
+
+    /// Records a request sent to the peer, so that the response time is taken into account
+    /// in its reputation. See `Self::response_received`.
+    pub fn request_sent(&self, clock: &time::Clock, peer_id: PeerId, request: reputation::Request) {
+        self.0.lock().reputation.request_sent(clock.now(), peer_id, request)
+    }
+
+    pub fn response_received(
+        &self,
+        clock: &time::Clock,
+        peer_id: &PeerId,
+        request: reputation::Request,
+    ) {
+        self.0.lock().reputation.response_received(clock, peer_id, request)
+    }
 }
@@ -0,0 +1,205 @@
+//! Reputation of the peers, learned from their behaviour.
+//!
+//! Every peer has a score, which increases when the peer serves our requests well and
+//! decreases when it misbehaves: sends invalid blocks, doesn't respond to our requests
+//! or serves state parts slowly.
+//! The score decays exponentially towards 0, so that old events are eventually forgotten.
+//! Peers with a low score are avoided when choosing outbound connections and sync peers.
+use crate::stats::metrics;
+use crate::types::{PeerScore, ReasonForBan};
+use lru::LruCache;
+use near_async::time;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::types::ShardId;
+use std::collections::HashMap;
+
+/// Scores are kept within [-MAX_SCORE, MAX_SCORE].
+const MAX_SCORE: f64 = 100.;
+/// Peers with a score below this threshold are chosen only if there are no other candidates.
+pub(crate) const LOW_SCORE: f64 = -20.;
+/// Scores which have decayed below this threshold (in absolute value) are forgotten.
+const NEGLIGIBLE_SCORE: f64 = 0.1;
+/// BlockRequests which haven't been responded within this time are considered timed out.
+pub(crate) const BLOCK_REQUEST_TIMEOUT: time::Duration = time::Duration::seconds(10);
+/// StateRequestParts which haven't been responded within this time are considered timed out.
+pub(crate) const STATE_PART_REQUEST_TIMEOUT: time::Duration = time::Duration::seconds(60);
+/// State parts received later than this after the request are considered slow.
+pub(crate) const SLOW_STATE_PART: time::Duration = time::Duration::seconds(10);
+/// Maximal number of requests awaiting a response tracked at the same time.
+const MAX_PENDING_REQUESTS: usize = 10_000;
+
+/// Behaviour of a peer affecting its score.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::IntoStaticStr)]
+pub(crate) enum Event {
+    /// Peer has been banned for sending an invalid block or block header.
+    InvalidBlock,
+    /// Peer has been banned for any other reason.
+    Banned,
+    BlockDelivered,
+    BlockRequestTimeout,
+    StatePartDelivered,
+    SlowStatePart,
+    StatePartRequestTimeout,
+}
+
+impl Event {
+    pub fn from_ban_reason(reason: ReasonForBan) -> Self {
+        match reason {
+            ReasonForBan::BadBlock | ReasonForBan::BadBlockHeader => Event::InvalidBlock,
+            _ => Event::Banned,
+        }
+    }
+
+    fn weight(self) -> f64 {
+        match self {
+            Event::InvalidBlock => -50.,
+            Event::Banned => -30.,
+            Event::BlockDelivered => 1.,
+            Event::BlockRequestTimeout => -5.,
+            Event::StatePartDelivered => 2.,
+            Event::SlowStatePart => -2.,
+            Event::StatePartRequestTimeout => -10.,
+        }
+    }
+}
+
+/// Request sent to a peer, which the peer is expected to respond to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Request {
+    Block(CryptoHash),
+    StatePart { shard_id: ShardId, sync_hash: CryptoHash, part_id: u64 },
+}
+
+impl Request {
+    fn timeout(&self) -> time::Duration {
+        match self {
+            Request::Block(_) => BLOCK_REQUEST_TIMEOUT,
+            Request::StatePart { .. } => STATE_PART_REQUEST_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Score {
+    value: f64,
+    updated: time::Utc,
+}
+
+impl Score {
+    /// Value of the score at `now`, decayed since the last update.
+    fn value_at(&self, now: time::Utc, half_life: time::Duration) -> f64 {
+        if now <= self.updated {
+            return self.value;
+        }
+        let elapsed = (now - self.updated).as_seconds_f64();
+        self.value * 0.5f64.powf(elapsed / half_life.as_seconds_f64())
+    }
+}
+
+pub(crate) struct Reputation {
+    /// Time after which a score decays to half of its value.
+    half_life: time::Duration,
+    scores: HashMap<PeerId, Score>,
+    /// Requests sent to the peers, awaiting a response.
+    pending: LruCache<(PeerId, Request), time::Instant>,
+    /// Whether `scores` have changed since they were last persisted.
+    dirty: bool,
+}
+
+impl Reputation {
+    pub fn new(half_life: time::Duration, scores: Vec<PeerScore>) -> Self {
+        let scores: HashMap<_, _> = scores
+            .into_iter()
+            .map(|s| (s.peer_id, Score { value: s.value, updated: s.updated }))
+            .collect();
+        metrics::PEER_REPUTATION_SCORED.set(scores.len() as i64);
+        Self { half_life, scores, pending: LruCache::new(MAX_PENDING_REQUESTS), dirty: false }
+    }
+
+    /// Current score of the peer. Peers we know nothing about have score 0.
+    pub fn score(&self, now: time::Utc, peer_id: &PeerId) -> f64 {
+        self.scores.get(peer_id).map_or(0., |s| s.value_at(now, self.half_life))
+    }
+
+    pub fn scores(&self, now: time::Utc) -> HashMap<PeerId, f64> {
+        self.scores.iter().map(|(id, s)| (id.clone(), s.value_at(now, self.half_life))).collect()
+    }
+
+    pub fn record(&mut self, now: time::Utc, peer_id: &PeerId, event: Event) {
+        metrics::PEER_REPUTATION_EVENTS.with_label_values(&[event.into()]).inc();
+        let value = (self.score(now, peer_id) + event.weight()).clamp(-MAX_SCORE, MAX_SCORE);
+        self.scores.insert(peer_id.clone(), Score { value, updated: now });
+        metrics::PEER_REPUTATION_SCORED.set(self.scores.len() as i64);
+        self.dirty = true;
+    }
+
+    pub fn request_sent(&mut self, now: time::Instant, peer_id: PeerId, request: Request) {
+        self.pending.put((peer_id, request), now);
+    }
+
+    /// Records the response of the peer to a request.
+    /// Responses to requests which were not sent, or have already timed out, are ignored.
+    pub fn response_received(&mut self, clock: &time::Clock, peer_id: &PeerId, request: Request) {
+        let key = (peer_id.clone(), request);
+        let sent = match self.pending.pop(&key) {
+            Some(sent) => sent,
+            None => return,
+        };
+        let event = match key.1 {
+            Request::Block(_) => Event::BlockDelivered,
+            Request::StatePart { .. } if clock.now() - sent > SLOW_STATE_PART => {
+                Event::SlowStatePart
+            }
+            Request::StatePart { .. } => Event::StatePartDelivered,
+        };
+        self.record(clock.now_utc(), peer_id, event);
+    }
+
+    /// Records the timeouts of the pending requests and forgets the negligible scores.
+    /// This function should be called periodically.
+    pub fn update(&mut self, clock: &time::Clock) {
+        let now = clock.now();
+        let timed_out: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(key, sent)| now - **sent > key.1.timeout())
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in timed_out {
+            self.pending.pop(&key);
+            let (peer_id, request) = key;
+            let event = match request {
+                Request::Block(_) => Event::BlockRequestTimeout,
+                Request::StatePart { .. } => Event::StatePartRequestTimeout,
+            };
+            self.record(clock.now_utc(), &peer_id, event);
+        }
+
+        let now = clock.now_utc();
+        let half_life = self.half_life;
+        let len = self.scores.len();
+        self.scores.retain(|_, s| s.value_at(now, half_life).abs() >= NEGLIGIBLE_SCORE);
+        if self.scores.len() != len {
+            metrics::PEER_REPUTATION_SCORED.set(self.scores.len() as i64);
+            self.dirty = true;
+        }
+    }
+
+    /// Returns the scores to persist, if they have changed since the last call.
+    pub fn take_changed(&mut self) -> Option<Vec<PeerScore>> {
+        if !std::mem::take(&mut self.dirty) {
+            return None;
+        }
+        Some(
+            self.scores
+                .iter()
+                .map(|(peer_id, s)| PeerScore {
+                    peer_id: peer_id.clone(),
+                    value: s.value,
+                    updated: s.updated,
+                })
+                .collect(),
+        )
+    }
+}
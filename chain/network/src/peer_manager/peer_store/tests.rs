@@ -2,6 +2,7 @@ use super::*;
 use crate::blacklist::Blacklist;
 use near_async::time;
 use near_crypto::{KeyType, SecretKey};
+use near_primitives::hash::CryptoHash;
 use std::collections::HashSet;
 use std::net::{Ipv4Addr, SocketAddrV4};
 
@@ -25,6 +26,10 @@ fn gen_peer_info(port: u16) -> PeerInfo {
     }
 }
 
+fn make_store() -> store::Store {
+    store::Store::from(near_store::db::TestDB::new())
+}
+
 fn make_config(
     boot_nodes: &[PeerInfo],
     blacklist: blacklist::Blacklist,
@@ -37,6 +42,7 @@ fn make_config(
         connect_only_to_boot_nodes,
         ban_window: time::Duration::seconds(1),
         peer_expiration_duration: time::Duration::days(1000),
+        reputation_half_life: time::Duration::hours(1),
     }
 }
 
@@ -47,9 +53,12 @@ fn ban_store() {
     let peer_info_to_ban = gen_peer_info(1);
     let boot_nodes = vec![peer_info_a, peer_info_to_ban.clone()];
 
-    let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&boot_nodes, Blacklist::default(), false))
-            .unwrap();
+    let peer_store = PeerStore::new(
+        &clock.clock(),
+        make_config(&boot_nodes, Blacklist::default(), false),
+        make_store(),
+    )
+    .unwrap();
     assert_eq!(peer_store.healthy_peers(3).len(), 2);
     peer_store.peer_ban(&clock.clock(), &peer_info_to_ban.id, ReasonForBan::Abusive).unwrap();
     assert_eq!(peer_store.healthy_peers(3).len(), 1);
@@ -62,12 +71,15 @@ fn test_unconnected_peer() {
     let peer_info_to_ban = gen_peer_info(1);
     let boot_nodes = vec![peer_info_a, peer_info_to_ban];
 
-    let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&boot_nodes, Blacklist::default(), false))
-            .unwrap();
+    let peer_store = PeerStore::new(
+        &clock.clock(),
+        make_config(&boot_nodes, Blacklist::default(), false),
+        make_store(),
+    )
+    .unwrap();
 
-    assert!(peer_store.unconnected_peer(|_| false, false).is_some());
-    assert!(peer_store.unconnected_peer(|_| true, false).is_none());
+    assert!(peer_store.unconnected_peer(&clock.clock(), |_| false, false).is_some());
+    assert!(peer_store.unconnected_peer(&clock.clock(), |_| true, false).is_none());
 }
 
 #[test]
@@ -85,9 +97,12 @@ fn test_unknown_vs_not_connected() {
         nodes.map(|peer| peer_store.get_peer_state(&peer.id).map(|known_state| known_state.status))
     };
 
-    let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&boot_nodes, Blacklist::default(), false))
-            .unwrap();
+    let peer_store = PeerStore::new(
+        &clock.clock(),
+        make_config(&boot_nodes, Blacklist::default(), false),
+        make_store(),
+    )
+    .unwrap();
 
     // Check the status of the in-memory store.
     // Boot node should be marked as not-connected, as we've verified it.
@@ -120,7 +135,7 @@ fn test_unknown_vs_not_connected() {
     // if we prefer 'previously connected' peers - we should keep picking 'b'.
     assert_eq!(
         (0..10)
-            .map(|_| peer_store.unconnected_peer(|_| false, true).unwrap().id)
+            .map(|_| peer_store.unconnected_peer(&clock.clock(), |_| false, true).unwrap().id)
             .collect::<HashSet<PeerId>>(),
         [peer_info_b.id.clone()].into_iter().collect::<HashSet<_>>()
     );
@@ -128,7 +143,7 @@ fn test_unknown_vs_not_connected() {
     // if we don't care, we should pick either 'b' or 'boot'.
     assert_eq!(
         (0..100)
-            .map(|_| peer_store.unconnected_peer(|_| false, false).unwrap().id)
+            .map(|_| peer_store.unconnected_peer(&clock.clock(), |_| false, false).unwrap().id)
             .collect::<HashSet<PeerId>>(),
         [peer_info_b.id.clone(), peer_info_boot_node.id.clone()]
             .into_iter()
@@ -159,24 +174,33 @@ fn test_unconnected_peer_only_boot_nodes() {
     // 1 non-boot (peer_in_store) node peer that is in the store.
     // we should connect to peer_in_store
     {
-        let peer_store =
-            PeerStore::new(&clock.clock(), make_config(&boot_nodes, Blacklist::default(), false))
-                .unwrap();
+        let peer_store = PeerStore::new(
+            &clock.clock(),
+            make_config(&boot_nodes, Blacklist::default(), false),
+            make_store(),
+        )
+        .unwrap();
         peer_store.add_direct_peer(&clock.clock(), peer_in_store.clone());
         peer_store.peer_connected(&clock.clock(), &peer_info_a);
-        assert_eq!(peer_store.unconnected_peer(|_| false, false), Some(peer_in_store.clone()));
+        assert_eq!(
+            peer_store.unconnected_peer(&clock.clock(), |_| false, false),
+            Some(peer_in_store.clone())
+        );
     }
 
     // 1 boot node (peer_info_a) that we're already connected to.
     // 1 non-boot (peer_in_store) node peer that is in the store.
     // connect to only boot nodes is enabled - we should not find any peer to connect to.
     {
-        let peer_store =
-            PeerStore::new(&clock.clock(), make_config(&boot_nodes, Default::default(), true))
-                .unwrap();
+        let peer_store = PeerStore::new(
+            &clock.clock(),
+            make_config(&boot_nodes, Default::default(), true),
+            make_store(),
+        )
+        .unwrap();
         peer_store.add_direct_peer(&clock.clock(), peer_in_store);
         peer_store.peer_connected(&clock.clock(), &peer_info_a);
-        assert_eq!(peer_store.unconnected_peer(|_| false, false), None);
+        assert_eq!(peer_store.unconnected_peer(&clock.clock(), |_| false, false), None);
     }
 
     // 1 boot node (peer_info_a) is in the store.
@@ -185,10 +209,14 @@ fn test_unconnected_peer_only_boot_nodes() {
         let peer_store = PeerStore::new(
             &clock.clock(),
             make_config(&boot_nodes, Default::default(), connect_to_boot_nodes),
+            make_store(),
         )
         .unwrap();
         peer_store.add_direct_peer(&clock.clock(), peer_info_a.clone());
-        assert_eq!(peer_store.unconnected_peer(|_| false, false), Some(peer_info_a.clone()));
+        assert_eq!(
+            peer_store.unconnected_peer(&clock.clock(), |_| false, false),
+            Some(peer_info_a.clone())
+        );
     }
 }
 
@@ -237,7 +265,8 @@ fn check_integrity(peer_store: &PeerStore) -> bool {
 fn handle_peer_id_change() {
     let clock = time::FakeClock::default();
     let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false)).unwrap();
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
 
     let peers_id = (0..2).map(|ix| get_peer_id(format!("node{}", ix))).collect::<Vec<_>>();
     let addr = get_addr(0);
@@ -261,7 +290,8 @@ fn handle_peer_id_change() {
 fn dont_handle_address_change() {
     let clock = time::FakeClock::default();
     let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false)).unwrap();
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
 
     let peers_id = (0..1).map(|ix| get_peer_id(format!("node{}", ix))).collect::<Vec<_>>();
     let addrs = (0..2).map(get_addr).collect::<Vec<_>>();
@@ -280,7 +310,8 @@ fn dont_handle_address_change() {
 fn check_add_peers_overriding() {
     let clock = time::FakeClock::default();
     let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false)).unwrap();
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
 
     // Five peers: A, B, C, D, X, T
     let peers_id = (0..6).map(|ix| get_peer_id(format!("node{}", ix))).collect::<Vec<_>>();
@@ -372,7 +403,8 @@ fn check_ignore_blacklisted_peers() {
     let blacklist: blacklist::Blacklist =
         ["127.0.0.1:1"].iter().map(|e| e.parse().unwrap()).collect();
 
-    let peer_store = PeerStore::new(&clock.clock(), make_config(&[], blacklist, false)).unwrap();
+    let peer_store =
+        PeerStore::new(&clock.clock(), make_config(&[], blacklist, false), make_store()).unwrap();
 
     peer_store.add_indirect_peers(
         &clock.clock(),
@@ -417,7 +449,8 @@ fn test_delete_peers() {
     let peer_addresses = peer_infos.iter().map(|info| info.addr.unwrap()).collect::<Vec<_>>();
 
     let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false)).unwrap();
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
 
     peer_store.add_indirect_peers(&clock.clock(), peer_infos.into_iter());
     assert_peers_in_cache(&peer_store, &peer_ids, &peer_addresses);
@@ -431,7 +464,7 @@ fn test_lru_eviction() {
     let clock = time::FakeClock::default();
     let mut config = make_config(&[], Default::default(), false);
     config.peer_states_cache_size = 10;
-    let peer_store = PeerStore::new(&clock.clock(), config).unwrap();
+    let peer_store = PeerStore::new(&clock.clock(), config, make_store()).unwrap();
 
     let (peer_ids, peer_infos): (Vec<_>, Vec<_>) = (0..15)
         .map(|i| {
@@ -462,7 +495,7 @@ fn test_lru_ignore_duplicate_peers() {
     let clock = time::FakeClock::default();
     let mut config = make_config(&[], Default::default(), false);
     config.peer_states_cache_size = 10;
-    let peer_store = PeerStore::new(&clock.clock(), config).unwrap();
+    let peer_store = PeerStore::new(&clock.clock(), config, make_store()).unwrap();
 
     let (peer_ids, peer_infos): (Vec<_>, Vec<_>) = (0..15)
         .map(|i| {
@@ -485,3 +518,93 @@ fn test_lru_ignore_duplicate_peers() {
     peer_store.add_indirect_peers(&clock.clock(), peer_infos[10..].iter().cloned());
     assert_peers_in_cache(&peer_store, &peer_ids[5..], &peer_addresses[5..]);
 }
+
+#[test]
+fn reputation_decays_and_is_persisted() {
+    let clock = time::FakeClock::default();
+    let store = make_store();
+    let config = make_config(&[], Default::default(), false);
+    let peer_id = get_peer_id("node".to_string());
+
+    let peer_store = PeerStore::new(&clock.clock(), config.clone(), store.clone()).unwrap();
+    peer_store.reputation_event(&clock.clock(), &peer_id, reputation::Event::InvalidBlock);
+    assert_eq!(peer_store.load_reputation(&clock.clock())[&peer_id], -50.);
+    assert!(peer_store.has_low_reputation(&clock.clock(), &peer_id));
+
+    clock.advance(config.reputation_half_life);
+    assert_eq!(peer_store.load_reputation(&clock.clock())[&peer_id], -25.);
+    assert!(peer_store.has_low_reputation(&clock.clock(), &peer_id));
+    clock.advance(config.reputation_half_life);
+    assert!(!peer_store.has_low_reputation(&clock.clock(), &peer_id));
+
+    // Scores are persisted on update() and loaded by a new PeerStore.
+    peer_store.update(&clock.clock());
+    let peer_store = PeerStore::new(&clock.clock(), config.clone(), store).unwrap();
+    assert_eq!(peer_store.load_reputation(&clock.clock())[&peer_id], -12.5);
+
+    // Negligible scores are forgotten.
+    clock.advance(config.reputation_half_life * 10);
+    peer_store.update(&clock.clock());
+    assert!(peer_store.load_reputation(&clock.clock()).is_empty());
+}
+
+#[test]
+fn reputation_request_timeouts() {
+    let clock = time::FakeClock::default();
+    let peer_store =
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
+    let peer_id = get_peer_id("node".to_string());
+    let block = reputation::Request::Block(CryptoHash::hash_bytes(b"block"));
+    let part = reputation::Request::StatePart {
+        shard_id: 0,
+        sync_hash: CryptoHash::hash_bytes(b"sync"),
+        part_id: 3,
+    };
+    let score = || peer_store.load_reputation(&clock.clock()).get(&peer_id).copied();
+
+    // Unsolicited responses don't affect the score.
+    peer_store.response_received(&clock.clock(), &peer_id, block.clone());
+    assert_eq!(score(), None);
+
+    peer_store.request_sent(&clock.clock(), peer_id.clone(), block.clone());
+    peer_store.request_sent(&clock.clock(), peer_id.clone(), part.clone());
+    clock.advance(reputation::BLOCK_REQUEST_TIMEOUT + time::Duration::seconds(1));
+    peer_store.update(&clock.clock());
+    assert_eq!(score(), Some(-5.));
+    // The block arrived too late, while the state part arrived in time, but slowly.
+    peer_store.response_received(&clock.clock(), &peer_id, block);
+    peer_store.response_received(&clock.clock(), &peer_id, part.clone());
+    assert_eq!(score(), Some(-7.));
+
+    peer_store.request_sent(&clock.clock(), peer_id.clone(), part.clone());
+    clock.advance(reputation::STATE_PART_REQUEST_TIMEOUT + time::Duration::seconds(1));
+    peer_store.update(&clock.clock());
+    // The score has decayed a bit since, so compare approximately.
+    assert!((score().unwrap() + 17.).abs() < 0.1);
+}
+
+#[test]
+fn unconnected_peer_avoids_low_reputation() {
+    let clock = time::FakeClock::default();
+    let peer_store =
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
+    let good = gen_peer_info(0);
+    let bad = gen_peer_info(1);
+    peer_store.add_direct_peer(&clock.clock(), good.clone());
+    peer_store.add_direct_peer(&clock.clock(), bad.clone());
+    peer_store.reputation_event(&clock.clock(), &bad.id, reputation::Event::Banned);
+
+    for _ in 0..10 {
+        assert_eq!(
+            peer_store.unconnected_peer(&clock.clock(), |_| false, false),
+            Some(good.clone())
+        );
+    }
+    // A peer with a low reputation is chosen if there are no other candidates.
+    assert_eq!(
+        peer_store.unconnected_peer(&clock.clock(), |p| p.peer_info.id == good.id, false),
+        Some(bad)
+    );
+}
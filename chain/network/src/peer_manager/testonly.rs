@@ -146,7 +146,7 @@ impl ActorHandler {
     pub async fn send_outbound_connect(&self, peer_info: &PeerInfo, tier: tcp::Tier) {
         let addr = self.actix.addr.clone();
         let peer_info = peer_info.clone();
-        let stream = tcp::Stream::connect(&peer_info, tier).await.unwrap();
+        let stream = self.cfg.dialer.connect(&peer_info, tier).await.unwrap();
         addr.do_send(PeerManagerMessageRequest::OutboundTcpConnect(stream).with_span_context());
     }
 
@@ -158,8 +158,9 @@ impl ActorHandler {
         let addr = self.actix.addr.clone();
         let events = self.events.clone();
         let peer_info = peer_info.clone();
+        let dialer = self.cfg.dialer.clone();
         async move {
-            let stream = tcp::Stream::connect(&peer_info, tier).await.unwrap();
+            let stream = dialer.connect(&peer_info, tier).await.unwrap();
             let mut events = events.from_now();
             let stream_id = stream.id();
             addr.do_send(PeerManagerMessageRequest::OutboundTcpConnect(stream).with_span_context());
@@ -446,6 +447,24 @@ impl ActorHandler {
         }
     }
 
+    // Awaits until the V2 routing table matches `want`.
+    pub async fn wait_for_routing_table_v2(&self, want: &[(PeerId, Vec<PeerId>)]) {
+        let mut events = self.events.from_now();
+        loop {
+            let got =
+                self.with_state(|s| async move { s.graph_v2.routing_table.info().next_hops }).await;
+            if test_utils::expected_routing_tables(&got, want) {
+                return;
+            }
+            events
+                .recv_until(|ev| match ev {
+                    Event::PeerManager(PME::RoutesUpdated) => Some(()),
+                    _ => None,
+                })
+                .await;
+        }
+    }
+
     pub async fn wait_for_account_owner(&self, account: &AccountId) -> PeerId {
         let mut events = self.events.from_now();
         loop {
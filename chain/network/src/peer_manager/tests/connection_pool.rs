@@ -103,6 +103,9 @@ async fn loop_connection() {
                 &pm.cfg.node_key,
             ),
             owned_account: None,
+            noise: None,
+            compression: vec![],
+            chunked_frames: false,
         }))
         .await;
     let reason = events
@@ -171,6 +174,9 @@ async fn owned_account_mismatch() {
                 }
                 .sign(vc.signer.as_ref()),
             ),
+            noise: None,
+            compression: vec![],
+            chunked_frames: false,
         }))
         .await;
     let reason = events
@@ -284,6 +290,9 @@ async fn invalid_edge() {
                     }
                     .sign(vc.signer.as_ref()),
                 ),
+                noise: None,
+                compression: vec![],
+                chunked_frames: false,
             };
             let handshake = match tier {
                 tcp::Tier::T1 => PeerMessage::Tier1Handshake(handshake),
@@ -2,5 +2,7 @@ mod accounts_data;
 mod connection_pool;
 mod nonce;
 mod routing;
+mod simnet;
 mod tier1;
 mod tier2;
+mod transport_encryption;
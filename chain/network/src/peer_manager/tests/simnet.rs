@@ -0,0 +1,96 @@
+use crate::network_protocol::testonly as data;
+use crate::peer_manager::connection_store::STORED_CONNECTIONS_MIN_DURATION;
+use crate::peer_manager::network_state::RECONNECT_ATTEMPT_INTERVAL;
+use crate::peer_manager::peer_manager_actor::Event as PME;
+use crate::peer_manager::peer_manager_actor::POLL_CONNECTION_STORE_INTERVAL;
+use crate::peer_manager::testonly::start as start_pm;
+use crate::peer_manager::testonly::{ActorHandler, Event};
+use crate::simnet::{Action, Scenario, SimNetwork};
+use crate::tcp;
+use crate::testonly::{abort_on_panic, make_rng};
+use near_async::time;
+use near_primitives::network::PeerId;
+use near_store::db::TestDB;
+use std::sync::Arc;
+
+/// Expected routing table of node `i` in a ring of nodes `ids`.
+fn ring_routing_table(ids: &[PeerId], i: usize) -> Vec<(PeerId, Vec<PeerId>)> {
+    let n = ids.len();
+    let (next, prev) = (&ids[(i + 1) % n], &ids[(i + n - 1) % n]);
+    vec![
+        (next.clone(), vec![next.clone()]),
+        (prev.clone(), vec![prev.clone()]),
+        (ids[(i + 2) % n].clone(), vec![next.clone(), prev.clone()]),
+    ]
+}
+
+async fn wait_for_routing_tables(pm: &ActorHandler, want: &[(PeerId, Vec<PeerId>)]) {
+    pm.wait_for_routing_table(want).await;
+    pm.wait_for_routing_table_v2(want).await;
+}
+
+// Partition a ring of 4 nodes into 2 halves and heal it afterwards.
+// Nodes listen and dial over the simulated network, so once the partition is healed
+// they re-establish the broken outbound connections on their own.
+// Both routing tables should converge after every change of the topology.
+#[tokio::test]
+async fn partition_and_heal() {
+    abort_on_panic();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+    let net = SimNetwork::new(clock.clock(), make_rng(3489237));
+    // The chain doesn't progress in this test, so the scenario is driven by time.
+    let mut scenario = Scenario::new(&net)
+        .after(STORED_CONNECTIONS_MIN_DURATION, Action::Partition(vec![vec![0, 1], vec![2, 3]]))
+        .after(STORED_CONNECTIONS_MIN_DURATION + time::Duration::seconds(1), Action::Heal);
+
+    tracing::info!(target:"test", "connect 4 nodes in a ring");
+    let mut pms = vec![];
+    for i in 0..4 {
+        let mut cfg = chain.make_config(rng);
+        cfg.node_addr = Some(tcp::ListenerAddr::new(SimNetwork::addr(i)));
+        cfg.dialer = tcp::Dialer::Simulated(net.clone(), i);
+        pms.push(start_pm(clock.clock(), TestDB::new(), cfg, chain.clone()).await);
+    }
+    let ids: Vec<_> = pms.iter().map(|pm| pm.cfg.node_id()).collect();
+    for i in 0..4 {
+        pms[i].connect_to(&pms[(i + 1) % 4].peer_info(), tcp::Tier::T2).await;
+    }
+    for i in 0..4 {
+        tracing::info!(target:"test", "wait for {} routing table", ids[i]);
+        wait_for_routing_tables(&pms[i], &ring_routing_table(&ids, i)).await;
+    }
+    clock.advance(STORED_CONNECTIONS_MIN_DURATION);
+    for pm in &pms {
+        pm.update_connection_store(&clock.clock()).await;
+    }
+
+    tracing::info!(target:"test", "partition the network");
+    // Nodes 1 and 3 lose their outbound connections.
+    let mut pm_events: Vec<_> = [1, 3].iter().map(|i| pms[*i].events.from_now()).collect();
+    assert_eq!(scenario.update(chain.height()).len(), 1);
+    for events in &mut pm_events {
+        events
+            .recv_until(|ev| match ev {
+                Event::PeerManager(PME::ConnectionClosed(_)) => Some(()),
+                _ => None,
+            })
+            .await;
+    }
+    for (i, j) in [(0, 1), (1, 0), (2, 3), (3, 2)] {
+        tracing::info!(target:"test", "wait for {} routing table", ids[i]);
+        wait_for_routing_tables(&pms[i], &[(ids[j].clone(), vec![ids[j].clone()])]).await;
+    }
+
+    tracing::info!(target:"test", "heal the network, wait for the nodes to reconnect");
+    clock.advance(time::Duration::seconds(1));
+    assert_eq!(scenario.update(chain.height()), vec![Action::Heal]);
+    assert!(scenario.is_done());
+    clock.advance(POLL_CONNECTION_STORE_INTERVAL + RECONNECT_ATTEMPT_INTERVAL);
+    for i in 0..4 {
+        tracing::info!(target:"test", "wait for {} routing table", ids[i]);
+        wait_for_routing_tables(&pms[i], &ring_routing_table(&ids, i)).await;
+    }
+}
@@ -0,0 +1,116 @@
+use crate::config_json::TransportEncryption;
+use crate::network_protocol::testonly as data;
+use crate::network_protocol::Pong;
+use crate::peer::peer_actor::ClosingReason;
+use crate::peer_manager::testonly::start as start_pm;
+use crate::peer_manager::testonly::ActorHandler;
+use crate::peer_manager::tests::routing::wait_for_pong;
+use crate::tcp;
+use crate::testonly::make_rng;
+use near_async::time;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::network::PeerId;
+use near_store::db::TestDB;
+use std::sync::Arc;
+
+async fn is_encrypted(pm: &ActorHandler, peer_id: PeerId) -> bool {
+    pm.with_state(move |s| async move { s.tier2.load().ready.get(&peer_id).unwrap().encrypted })
+        .await
+}
+
+#[tokio::test]
+async fn encrypted_connection() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let pm0 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let pm1 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let id0 = pm0.cfg.node_id();
+    let id1 = pm1.cfg.node_id();
+
+    tracing::info!(target:"test", "connect pm0 to pm1");
+    pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    assert!(is_encrypted(&pm0, id1.clone()).await);
+    assert!(is_encrypted(&pm1, id0.clone()).await);
+
+    tracing::info!(target:"test", "exchange messages over the encrypted connection");
+    pm0.wait_for_routing_table(&[(id1.clone(), vec![id1.clone()])]).await;
+    let mut pm0_ev = pm0.events.from_now();
+    pm0.send_ping(&clock.clock(), 0, id1.clone()).await;
+    wait_for_pong(&mut pm0_ev, Pong { nonce: 0, source: id1.clone() }).await;
+}
+
+#[tokio::test]
+async fn legacy_cleartext_peers() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let pm = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+
+    tracing::info!(target:"test", "accept inbound connection from a peer without encryption");
+    let mut cfg = chain.make_config(rng);
+    cfg.transport_encryption = TransportEncryption::Disabled;
+    let peer_id = cfg.node_id();
+    let conn = pm.start_inbound(chain.clone(), cfg).await;
+    let _peer = conn.handshake(&clock.clock()).await;
+    assert!(!is_encrypted(&pm, peer_id).await);
+
+    tracing::info!(target:"test", "establish outbound connection to a peer without encryption");
+    let mut cfg = chain.make_config(rng);
+    cfg.transport_encryption = TransportEncryption::Disabled;
+    let peer_id = cfg.node_id();
+    let conn = pm.start_outbound(chain.clone(), cfg, tcp::Tier::T2).await;
+    let _peer = conn.handshake(&clock.clock()).await;
+    assert!(!is_encrypted(&pm, peer_id).await);
+
+    tracing::info!(target:"test", "peers supporting encryption still get encrypted connections");
+    let cfg = chain.make_config(rng);
+    let peer_id = cfg.node_id();
+    let conn = pm.start_inbound(chain.clone(), cfg).await;
+    let _peer = conn.handshake(&clock.clock()).await;
+    assert!(is_encrypted(&pm, peer_id).await);
+}
+
+#[tokio::test]
+async fn encryption_required() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut cfg = chain.make_config(rng);
+    cfg.transport_encryption = TransportEncryption::Required;
+    let pm = start_pm(clock.clock(), TestDB::new(), cfg, chain.clone()).await;
+
+    tracing::info!(target:"test", "reject inbound connection from a peer without encryption");
+    let mut cfg = chain.make_config(rng);
+    cfg.transport_encryption = TransportEncryption::Disabled;
+    let conn = pm.start_inbound(chain.clone(), cfg).await;
+    assert_eq!(
+        ClosingReason::EncryptionRequired,
+        conn.manager_fail_handshake(&clock.clock()).await
+    );
+
+    tracing::info!(target:"test", "reject outbound connection to a peer without encryption");
+    let mut cfg = chain.make_config(rng);
+    cfg.transport_encryption = TransportEncryption::Disabled;
+    let conn = pm.start_outbound(chain.clone(), cfg, tcp::Tier::T2).await;
+    assert_eq!(
+        ClosingReason::EncryptionRequired,
+        conn.manager_fail_handshake(&clock.clock()).await
+    );
+
+    tracing::info!(target:"test", "accept connection from a peer with encryption");
+    let cfg = chain.make_config(rng);
+    let peer_id = cfg.node_id();
+    let conn = pm.start_inbound(chain.clone(), cfg).await;
+    let _peer = conn.handshake(&clock.clock()).await;
+    assert!(is_encrypted(&pm, peer_id).await);
+}
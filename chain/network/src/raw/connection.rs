@@ -214,6 +214,9 @@ fn new_handshake(
         },
         partial_edge_info: PartialEdgeInfo::new(my_peer_id, target_peer_id, nonce, secret_key),
         owned_account: None,
+        noise: None,
+        compression: vec![],
+        chunked_frames: false,
     })
 }
 
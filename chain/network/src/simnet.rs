@@ -0,0 +1,664 @@
+//! Simulated network, which allows to test many nodes under adverse network conditions.
+//!
+//! Nodes of a `SimNetwork` are identified by their index. Connections between them
+//! are `tcp::Stream`s backed by in-memory pipes rather than TCP sockets, so they can be
+//! passed to the PeerManagerActor just like the real ones: a node configured with
+//! `tcp::Dialer::Simulated` listens and dials over the simulated network.
+//! Delivery of the data is driven by the (fake) clock of the network:
+//! * `Link::latency` delays every write by a constant.
+//! * `Link::bandwidth` limits the rate at which the data is transmitted.
+//! * `Link::loss` is the probability that a write is lost. Just like in TCP, a lost
+//!   write is retransmitted, so the data is only delayed, and the delay blocks the
+//!   data written after it.
+//! * A partition closes all the connections between the nodes in different groups and
+//!   rejects the new ones, until it is healed.
+//!
+//! `Scenario` allows to script the changes of the network conditions,
+//! for example "partition validators 0-3 from 4-7 at height 100, heal at 150".
+use crate::tcp;
+use near_async::time;
+use near_primitives::network::PeerId;
+use near_primitives::types::BlockHeight;
+use rand::Rng as _;
+use rand_xorshift::XorShiftRng;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+
+/// Index of a node in the simulated network.
+pub type NodeIdx = usize;
+
+/// Port on which every simulated node is listening.
+const LISTENER_PORT: u16 = 24567;
+/// Delay after which a lost write is retransmitted.
+/// It is the minimal TCP retransmission timeout used by linux.
+const RETRANSMISSION_TIMEOUT: time::Duration = time::Duration::milliseconds(200);
+
+/// Conditions of the link between 2 nodes. They apply to both directions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Link {
+    pub latency: time::Duration,
+    /// Probability in [0,1) that a write has to be retransmitted.
+    pub loss: f64,
+    /// Bytes per second. None means unlimited.
+    pub bandwidth: Option<u64>,
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self { latency: time::Duration::ZERO, loss: 0., bandwidth: None }
+    }
+}
+
+impl Link {
+    fn verify(&self) {
+        assert!(self.latency >= time::Duration::ZERO, "negative latency: {self:?}");
+        assert!((0. ..1.).contains(&self.loss), "loss out of range: {self:?}");
+        assert!(self.bandwidth != Some(0), "zero bandwidth: {self:?}");
+    }
+}
+
+struct Segment {
+    deliver_at: time::Instant,
+    data: Vec<u8>,
+    /// Number of bytes of `data` which have been already read.
+    read: usize,
+}
+
+/// One direction of a connection.
+struct Pipe {
+    /// Data in flight, in the order of delivery.
+    segments: VecDeque<Segment>,
+    /// Time until which the link is busy transmitting the data already written.
+    busy_until: time::Instant,
+    writer_closed: bool,
+    reader_closed: bool,
+    /// Connection has been broken by a network partition.
+    broken: bool,
+    reader_waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn new(now: time::Instant) -> Self {
+        Self {
+            segments: VecDeque::new(),
+            busy_until: now,
+            writer_closed: false,
+            reader_closed: false,
+            broken: false,
+            reader_waker: None,
+        }
+    }
+
+    fn wake_reader(&mut self) {
+        if let Some(waker) = self.reader_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct ConnInfo {
+    nodes: (NodeIdx, NodeIdx),
+    pipes: [Weak<Mutex<Pipe>>; 2],
+}
+
+struct Inner {
+    clock: time::Clock,
+    rng: XorShiftRng,
+    default_link: Link,
+    /// Links with non-default conditions, keyed by (smaller,larger) node index.
+    links: HashMap<(NodeIdx, NodeIdx), Link>,
+    /// Group of every node in the current partition, if any.
+    /// Nodes which don't belong to any group can communicate only with each other.
+    groups: Option<HashMap<NodeIdx, usize>>,
+    conns: Vec<ConnInfo>,
+    /// Listening nodes, keyed by their address.
+    listeners: HashMap<SocketAddr, (NodeIdx, mpsc::UnboundedSender<tcp::Stream>)>,
+    next_port: u16,
+}
+
+impl Inner {
+    fn link(&self, a: NodeIdx, b: NodeIdx) -> Link {
+        *self.links.get(&(a.min(b), a.max(b))).unwrap_or(&self.default_link)
+    }
+
+    fn is_reachable(&self, a: NodeIdx, b: NodeIdx) -> bool {
+        match &self.groups {
+            None => true,
+            Some(groups) => groups.get(&a) == groups.get(&b),
+        }
+    }
+
+    /// Breaks the connections between the nodes which are no longer reachable,
+    /// and forgets the connections which have been dropped.
+    fn break_unreachable(&mut self) {
+        for conn in std::mem::take(&mut self.conns) {
+            let pipes: Vec<_> = conn.pipes.iter().filter_map(|p| p.upgrade()).collect();
+            if pipes.is_empty() {
+                continue;
+            }
+            if self.is_reachable(conn.nodes.0, conn.nodes.1) {
+                self.conns.push(conn);
+                continue;
+            }
+            for pipe in pipes {
+                let mut pipe = pipe.lock().unwrap();
+                pipe.broken = true;
+                pipe.wake_reader();
+            }
+        }
+    }
+}
+
+/// Simulated network. Cloning it yields a handle to the same network.
+#[derive(Clone)]
+pub struct SimNetwork(Arc<Mutex<Inner>>);
+
+impl SimNetwork {
+    pub fn new(clock: time::Clock, rng: XorShiftRng) -> Self {
+        Self(Arc::new(Mutex::new(Inner {
+            clock,
+            rng,
+            default_link: Link::default(),
+            links: HashMap::new(),
+            groups: None,
+            conns: vec![],
+            listeners: HashMap::new(),
+            next_port: LISTENER_PORT + 1,
+        })))
+    }
+
+    /// Address at which the given node is listening.
+    pub fn addr(node: NodeIdx) -> SocketAddr {
+        let ip = Ipv4Addr::new(10, (node >> 16) as u8, (node >> 8) as u8, node as u8);
+        SocketAddr::new(ip.into(), LISTENER_PORT)
+    }
+
+    /// Sets the conditions of all the links without explicitly set conditions.
+    pub fn set_default_link(&self, link: Link) {
+        link.verify();
+        self.0.lock().unwrap().default_link = link;
+    }
+
+    /// Sets the conditions of the link between `a` and `b`.
+    /// It affects the data written after the call, including the existing connections.
+    pub fn set_link(&self, a: NodeIdx, b: NodeIdx, link: Link) {
+        link.verify();
+        self.0.lock().unwrap().links.insert((a.min(b), a.max(b)), link);
+    }
+
+    /// Partitions the network into the given groups of nodes.
+    /// Connections between the nodes in different groups are closed.
+    pub fn partition(&self, groups: Vec<Vec<NodeIdx>>) {
+        let mut inner = self.0.lock().unwrap();
+        inner.groups = Some(
+            groups
+                .into_iter()
+                .enumerate()
+                .flat_map(|(group, nodes)| nodes.into_iter().map(move |node| (node, group)))
+                .collect(),
+        );
+        inner.break_unreachable();
+    }
+
+    /// Removes the partition. Closed connections are not restored.
+    pub fn heal(&self) {
+        self.0.lock().unwrap().groups = None;
+    }
+
+    pub fn is_reachable(&self, a: NodeIdx, b: NodeIdx) -> bool {
+        self.0.lock().unwrap().is_reachable(a, b)
+    }
+
+    /// Current conditions of the link between `a` and `b`.
+    pub fn link(&self, a: NodeIdx, b: NodeIdx) -> Link {
+        self.0.lock().unwrap().link(a, b)
+    }
+
+    pub fn apply(&self, action: &Action) {
+        match action {
+            Action::Partition(groups) => self.partition(groups.clone()),
+            Action::Heal => self.heal(),
+            Action::SetLink(a, b, link) => self.set_link(*a, *b, *link),
+            Action::SetDefaultLink(link) => self.set_default_link(*link),
+        }
+    }
+
+    /// Starts listening for the connections to the given node, replacing its previous listener.
+    pub(crate) fn listen(&self, node: NodeIdx) -> tcp::Listener {
+        let (send, recv) = mpsc::unbounded_channel();
+        self.0.lock().unwrap().listeners.insert(Self::addr(node), (node, send));
+        tcp::Listener::Simulated(recv)
+    }
+
+    /// Establishes a connection from node `from` to the node listening at `addr`,
+    /// which has id `peer_id`. Returns the outbound stream, while the inbound one
+    /// is passed to the listener.
+    pub(crate) fn dial(
+        &self,
+        from: NodeIdx,
+        addr: SocketAddr,
+        peer_id: PeerId,
+        tier: tcp::Tier,
+    ) -> io::Result<tcp::Stream> {
+        let listener = self.0.lock().unwrap().listeners.get(&addr).cloned();
+        let Some((to, listener)) = listener else {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "no listener"));
+        };
+        let (outbound, inbound) = self.connect(from, to, peer_id, tier)?;
+        listener
+            .send(inbound)
+            .map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, "listener closed"))?;
+        Ok(outbound)
+    }
+
+    /// Establishes a connection from node `from` to node `to`, which has id `peer_id`.
+    /// Returns a pair of streams: (outbound,inbound).
+    /// Fails if the nodes are not reachable from each other.
+    pub fn connect(
+        &self,
+        from: NodeIdx,
+        to: NodeIdx,
+        peer_id: PeerId,
+        tier: tcp::Tier,
+    ) -> io::Result<(tcp::Stream, tcp::Stream)> {
+        let mut inner = self.0.lock().unwrap();
+        if !inner.is_reachable(from, to) {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "network partition"));
+        }
+        let now = inner.clock.now();
+        let forward = Arc::new(Mutex::new(Pipe::new(now)));
+        let backward = Arc::new(Mutex::new(Pipe::new(now)));
+        inner.conns.push(ConnInfo {
+            nodes: (from, to),
+            pipes: [Arc::downgrade(&forward), Arc::downgrade(&backward)],
+        });
+        let port = inner.next_port;
+        inner.next_port += 1;
+        let outbound_addr = SocketAddr::new(Self::addr(from).ip(), port);
+        let inbound_addr = Self::addr(to);
+        let conn = |local, remote, send, recv| Conn {
+            net: self.clone(),
+            clock: inner.clock.clone(),
+            local,
+            remote,
+            send,
+            recv,
+            sleep: Mutex::new(None),
+        };
+        Ok((
+            tcp::Stream::simulated(
+                conn(from, to, forward.clone(), backward.clone()),
+                tcp::StreamType::Outbound { peer_id, tier },
+                outbound_addr,
+                inbound_addr,
+            ),
+            tcp::Stream::simulated(
+                conn(to, from, backward, forward),
+                tcp::StreamType::Inbound,
+                inbound_addr,
+                outbound_addr,
+            ),
+        ))
+    }
+}
+
+type Sleep = Pin<Box<dyn Send + Future<Output = ()>>>;
+
+/// An endpoint of a simulated connection.
+pub struct Conn {
+    net: SimNetwork,
+    clock: time::Clock,
+    local: NodeIdx,
+    remote: NodeIdx,
+    send: Arc<Mutex<Pipe>>,
+    recv: Arc<Mutex<Pipe>>,
+    /// Awaits the delivery time of the first segment of `recv`.
+    /// Wrapped in a Mutex just to make Conn Sync.
+    sleep: Mutex<Option<(time::Instant, Sleep)>>,
+}
+
+impl std::fmt::Debug for Conn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Conn").field("local", &self.local).field("remote", &self.remote).finish()
+    }
+}
+
+impl AsyncRead for Conn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            let now = this.clock.now();
+            let mut pipe = this.recv.lock().unwrap();
+            if pipe.broken {
+                return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+            }
+            let deliver_at = match pipe.segments.front() {
+                Some(s) => s.deliver_at,
+                None if pipe.writer_closed => return Poll::Ready(Ok(())),
+                None => {
+                    pipe.reader_waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            };
+            if deliver_at <= now {
+                while buf.remaining() > 0 {
+                    let Some(s) = pipe.segments.front_mut() else { break };
+                    if s.deliver_at > now {
+                        break;
+                    }
+                    let n = buf.remaining().min(s.data.len() - s.read);
+                    buf.put_slice(&s.data[s.read..s.read + n]);
+                    s.read += n;
+                    if s.read == s.data.len() {
+                        pipe.segments.pop_front();
+                    }
+                }
+                return Poll::Ready(Ok(()));
+            }
+            // Wait for the delivery time. Also wait for the pipe to get broken.
+            pipe.reader_waker = Some(cx.waker().clone());
+            drop(pipe);
+            let sleep = this.sleep.get_mut().unwrap();
+            if !matches!(sleep, Some((t, _)) if *t == deliver_at) {
+                let clock = this.clock.clone();
+                *sleep = Some((
+                    deliver_at,
+                    Box::pin(async move { clock.sleep_until(deliver_at).await }),
+                ));
+            }
+            if sleep.as_mut().unwrap().1.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            *sleep = None;
+        }
+    }
+}
+
+impl AsyncWrite for Conn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut inner = self.net.0.lock().unwrap();
+        let mut pipe = self.send.lock().unwrap();
+        if pipe.broken {
+            return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+        }
+        if pipe.reader_closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let link = inner.link(self.local, self.remote);
+        let transmission = match link.bandwidth {
+            Some(bandwidth) => time::Duration::seconds_f64(buf.len() as f64 / bandwidth as f64),
+            None => time::Duration::ZERO,
+        };
+        pipe.busy_until = pipe.busy_until.max(self.clock.now()) + transmission;
+        let mut deliver_at = pipe.busy_until + link.latency;
+        while inner.rng.gen_bool(link.loss) {
+            deliver_at += RETRANSMISSION_TIMEOUT;
+        }
+        // The data is delivered in order.
+        if let Some(last) = pipe.segments.back() {
+            deliver_at = deliver_at.max(last.deliver_at);
+        }
+        pipe.segments.push_back(Segment { deliver_at, data: buf.to_vec(), read: 0 });
+        pipe.wake_reader();
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut pipe = self.send.lock().unwrap();
+        pipe.writer_closed = true;
+        pipe.wake_reader();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Conn {
+    fn drop(&mut self) {
+        let mut send = self.send.lock().unwrap();
+        send.writer_closed = true;
+        send.wake_reader();
+        drop(send);
+        self.recv.lock().unwrap().reader_closed = true;
+    }
+}
+
+/// Condition triggering a step of a `Scenario`.
+#[derive(Clone, Debug)]
+pub enum Trigger {
+    /// Chain has reached the given height.
+    Height(BlockHeight),
+    /// Given time has passed since the start of the scenario.
+    After(time::Duration),
+}
+
+/// Change of the network conditions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Partition(Vec<Vec<NodeIdx>>),
+    Heal,
+    SetLink(NodeIdx, NodeIdx, Link),
+    SetDefaultLink(Link),
+}
+
+/// Script of the network conditions changes. For example:
+/// ```ignore
+/// let mut scenario = Scenario::new(&net)
+///     .at_height(100, Action::Partition(vec![(0..4).collect(), (4..8).collect()]))
+///     .at_height(150, Action::Heal);
+/// ```
+/// The test is expected to call `update` whenever the chain height changes
+/// or the clock advances.
+pub struct Scenario {
+    net: SimNetwork,
+    start: time::Instant,
+    steps: Vec<(Trigger, Action)>,
+}
+
+impl Scenario {
+    pub fn new(net: &SimNetwork) -> Self {
+        let start = net.0.lock().unwrap().clock.now();
+        Self { net: net.clone(), start, steps: vec![] }
+    }
+
+    pub fn at_height(mut self, height: BlockHeight, action: Action) -> Self {
+        self.steps.push((Trigger::Height(height), action));
+        self
+    }
+
+    pub fn after(mut self, d: time::Duration, action: Action) -> Self {
+        self.steps.push((Trigger::After(d), action));
+        self
+    }
+
+    /// Applies the steps triggered at the given height and the current time,
+    /// in the order in which they were added. Returns the applied actions.
+    pub fn update(&mut self, height: BlockHeight) -> Vec<Action> {
+        let elapsed = self.net.0.lock().unwrap().clock.now() - self.start;
+        let mut applied = vec![];
+        self.steps.retain(|(trigger, action)| {
+            let triggered = match trigger {
+                Trigger::Height(h) => height >= *h,
+                Trigger::After(d) => elapsed >= *d,
+            };
+            if triggered {
+                self.net.apply(action);
+                applied.push(action.clone());
+            }
+            !triggered
+        });
+        applied
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testonly::make_rng;
+    use futures::FutureExt as _;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    /// Reads whatever has been delivered, without waiting.
+    fn read_ready(stream: &mut tcp::Stream) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; 1000];
+        match stream.stream.read(&mut buf).now_or_never() {
+            Some(n) => Ok(buf[..n?].to_vec()),
+            None => Ok(vec![]),
+        }
+    }
+
+    #[tokio::test]
+    async fn latency_and_bandwidth() {
+        let clock = time::FakeClock::default();
+        let net = SimNetwork::new(clock.clock(), make_rng(8917232));
+        net.set_link(
+            0,
+            1,
+            Link { latency: time::Duration::milliseconds(100), loss: 0., bandwidth: Some(1000) },
+        );
+        let (mut outbound, mut inbound) =
+            net.connect(0, 1, PeerId::random(), tcp::Tier::T2).unwrap();
+        assert_eq!(outbound.peer_addr, SimNetwork::addr(1));
+        assert_eq!(outbound.id(), inbound.id());
+
+        // 500 bytes take 0.5s to transmit, and then 0.1s to arrive.
+        outbound.stream.write_all(&[1; 500]).await.unwrap();
+        clock.advance(time::Duration::milliseconds(599));
+        assert_eq!(read_ready(&mut inbound).unwrap(), vec![]);
+        clock.advance(time::Duration::milliseconds(1));
+        assert_eq!(read_ready(&mut inbound).unwrap(), vec![1; 500]);
+
+        // Links of other nodes are not affected.
+        let (mut outbound, mut inbound) =
+            net.connect(0, 2, PeerId::random(), tcp::Tier::T2).unwrap();
+        inbound.stream.write_all(&[2; 500]).await.unwrap();
+        assert_eq!(read_ready(&mut outbound).unwrap(), vec![2; 500]);
+    }
+
+    #[tokio::test]
+    async fn loss_delays_data_in_order() {
+        let clock = time::FakeClock::default();
+        let net = SimNetwork::new(clock.clock(), make_rng(8917232));
+        net.set_default_link(Link { loss: 0.5, ..Link::default() });
+        let (mut outbound, mut inbound) =
+            net.connect(0, 1, PeerId::random(), tcp::Tier::T2).unwrap();
+        let want: Vec<u8> = (0..100).collect();
+        for b in &want {
+            outbound.stream.write_all(&[*b]).await.unwrap();
+        }
+        // Some of the writes had to be retransmitted.
+        let mut got = read_ready(&mut inbound).unwrap();
+        assert!(got.len() < want.len());
+        while got.len() < want.len() {
+            clock.advance(RETRANSMISSION_TIMEOUT);
+            got.extend(read_ready(&mut inbound).unwrap());
+        }
+        assert_eq!(got, want);
+    }
+
+    #[tokio::test]
+    async fn partition() {
+        let clock = time::FakeClock::default();
+        let net = SimNetwork::new(clock.clock(), make_rng(8917232));
+        let (mut s01, mut s10) = net.connect(0, 1, PeerId::random(), tcp::Tier::T2).unwrap();
+        let (mut s02, mut s20) = net.connect(0, 2, PeerId::random(), tcp::Tier::T2).unwrap();
+        net.partition(vec![vec![0, 1], vec![2]]);
+
+        // Connections within a group are not affected.
+        s01.stream.write_all(&[1]).await.unwrap();
+        assert_eq!(read_ready(&mut s10).unwrap(), vec![1]);
+        // Connections between the groups are broken.
+        assert!(s02.stream.write_all(&[2]).await.is_err());
+        assert!(read_ready(&mut s20).is_err());
+        assert!(net.connect(2, 0, PeerId::random(), tcp::Tier::T2).is_err());
+        // Nodes outside of all the groups can only reach each other.
+        assert!(net.connect(3, 4, PeerId::random(), tcp::Tier::T2).is_ok());
+        assert!(net.connect(3, 0, PeerId::random(), tcp::Tier::T2).is_err());
+
+        net.heal();
+        assert!(net.connect(2, 0, PeerId::random(), tcp::Tier::T2).is_ok());
+    }
+
+    #[tokio::test]
+    async fn dial() {
+        let clock = time::FakeClock::default();
+        let net = SimNetwork::new(clock.clock(), make_rng(8917232));
+        let addr = SimNetwork::addr(1);
+        // Nobody is listening yet.
+        assert!(net.dial(0, addr, PeerId::random(), tcp::Tier::T2).is_err());
+
+        let mut listener = net.listen(1);
+        let mut outbound = net.dial(0, addr, PeerId::random(), tcp::Tier::T2).unwrap();
+        let mut inbound = listener.accept().await.unwrap();
+        assert_eq!(outbound.id(), inbound.id());
+        outbound.stream.write_all(&[1]).await.unwrap();
+        assert_eq!(read_ready(&mut inbound).unwrap(), vec![1]);
+
+        net.partition(vec![vec![0], vec![1]]);
+        assert!(net.dial(0, addr, PeerId::random(), tcp::Tier::T2).is_err());
+        net.heal();
+        drop(listener);
+        assert!(net.dial(0, addr, PeerId::random(), tcp::Tier::T2).is_err());
+    }
+
+    #[tokio::test]
+    async fn closing() {
+        let clock = time::FakeClock::default();
+        let net = SimNetwork::new(clock.clock(), make_rng(8917232));
+        let (mut outbound, mut inbound) =
+            net.connect(0, 1, PeerId::random(), tcp::Tier::T2).unwrap();
+        outbound.stream.write_all(&[1, 2]).await.unwrap();
+        drop(outbound);
+        // Data written before closing is still delivered.
+        let mut got = vec![];
+        inbound.stream.read_to_end(&mut got).await.unwrap();
+        assert_eq!(got, vec![1, 2]);
+        assert!(inbound.stream.write_all(&[3]).await.is_err());
+    }
+
+    #[test]
+    fn scenario() {
+        let clock = time::FakeClock::default();
+        let net = SimNetwork::new(clock.clock(), make_rng(8917232));
+        let slow = Link { latency: time::Duration::seconds(1), ..Link::default() };
+        let mut scenario = Scenario::new(&net)
+            .at_height(100, Action::Partition(vec![(0..4).collect(), (4..8).collect()]))
+            .at_height(150, Action::Heal)
+            .after(time::Duration::seconds(10), Action::SetDefaultLink(slow));
+
+        assert_eq!(scenario.update(99), vec![]);
+        assert!(net.is_reachable(3, 4));
+        assert_eq!(scenario.update(100).len(), 1);
+        assert!(net.is_reachable(0, 3));
+        assert!(!net.is_reachable(3, 4));
+        clock.advance(time::Duration::seconds(10));
+        assert_eq!(scenario.update(120), vec![Action::SetDefaultLink(slow)]);
+        assert_eq!(scenario.update(150), vec![Action::Heal]);
+        assert!(net.is_reachable(3, 4));
+        assert!(scenario.is_done());
+    }
+}
@@ -160,6 +160,22 @@ pub(crate) static PEER_DATA_WRITE_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(||
     )
     .unwrap()
 });
+pub(crate) static PEER_SEND_QUEUE_DEPTH: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_peer_send_queue_depth",
+        "Number of messages waiting to be sent to peers, by message class",
+        &["class"],
+    )
+    .unwrap()
+});
+pub(crate) static PEER_SEND_QUEUE_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_send_queue_dropped_total",
+        "Number of messages dropped, because too many messages of their class were waiting to be sent to a peer",
+        &["class"],
+    )
+    .unwrap()
+});
 pub(crate) static PEER_MESSAGE_RECEIVED_BY_TYPE_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec(
         "near_peer_message_received_by_type_bytes",
@@ -168,6 +184,14 @@ pub(crate) static PEER_MESSAGE_RECEIVED_BY_TYPE_BYTES: Lazy<IntCounterVec> = Laz
     )
     .unwrap()
 });
+pub(crate) static PEER_MESSAGE_RECEIVED_BY_TYPE_RAW_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_message_received_by_type_raw_bytes",
+        "Total data received from peers by message types, before decompression",
+        &["type"],
+    )
+    .unwrap()
+});
 pub(crate) static PEER_MESSAGE_RECEIVED_BY_TYPE_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec(
         "near_peer_message_received_by_type_total",
@@ -184,6 +208,14 @@ pub(crate) static PEER_MESSAGE_SENT_BY_TYPE_BYTES: Lazy<IntCounterVec> = Lazy::n
     )
     .unwrap()
 });
+pub(crate) static PEER_MESSAGE_SENT_BY_TYPE_RAW_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_message_sent_by_type_raw_bytes",
+        "Total data sent to peers by message types, before compression",
+        &["type"],
+    )
+    .unwrap()
+});
 pub(crate) static PEER_MESSAGE_SENT_BY_TYPE_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec(
         "near_peer_message_sent_by_type_total",
@@ -269,6 +301,21 @@ pub(crate) static PEER_UNRELIABLE: Lazy<IntGauge> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub(crate) static PEER_REPUTATION_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_reputation_events_total",
+        "Number of events affecting the reputation scores of the peers",
+        &["event"],
+    )
+    .unwrap()
+});
+pub(crate) static PEER_REPUTATION_SCORED: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_peer_reputation_scored",
+        "Number of peers with a non-negligible reputation score",
+    )
+    .unwrap()
+});
 pub(crate) static PEER_MANAGER_TRIGGER_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     try_create_histogram_vec(
         "near_peer_manager_trigger_time",
@@ -2,7 +2,7 @@
 /// All transactions should be implemented within this module,
 /// in particular schema::StoreUpdate is not exported.
 use crate::network_protocol::Edge;
-use crate::types::ConnectionInfo;
+use crate::types::{ConnectionInfo, PeerScore};
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::types::AccountId;
 use std::collections::HashSet;
@@ -137,6 +137,19 @@ impl Store {
     }
 }
 
+// PeerStore storage.
+impl Store {
+    pub fn set_peer_reputation(&mut self, scores: &Vec<PeerScore>) -> Result<(), Error> {
+        let mut update = self.0.new_update();
+        update.set::<schema::PeerReputation>(&(), scores);
+        self.0.commit(update).map_err(Error)
+    }
+
+    pub fn get_peer_reputation(&self) -> Vec<PeerScore> {
+        self.0.get::<schema::PeerReputation>(&()).unwrap_or(Some(vec![])).unwrap_or(vec![])
+    }
+}
+
 impl From<Arc<dyn near_store::db::Database>> for Store {
     fn from(store: Arc<dyn near_store::db::Database>) -> Self {
         Self(schema::Store::from(store))
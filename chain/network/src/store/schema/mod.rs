@@ -59,6 +59,35 @@ impl BorshRepr for ConnectionInfoRepr {
     }
 }
 
+/// A Borsh representation of the primitives::PeerScore.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(super) struct PeerScoreRepr {
+    peer_id: PeerId,
+    value: f64,
+    /// UNIX timestamp in nanos.
+    updated: u64,
+}
+
+impl BorshRepr for PeerScoreRepr {
+    type T = primitives::PeerScore;
+    fn to_repr(s: &primitives::PeerScore) -> Self {
+        Self {
+            peer_id: s.peer_id.clone(),
+            value: s.value,
+            updated: s.updated.unix_timestamp_nanos() as u64,
+        }
+    }
+
+    fn from_repr(s: Self) -> Result<primitives::PeerScore, Error> {
+        Ok(primitives::PeerScore {
+            peer_id: s.peer_id,
+            value: s.value,
+            updated: time::Utc::from_unix_timestamp_nanos(s.updated as i128)
+                .map_err(invalid_data)?,
+        })
+    }
+}
+
 #[derive(BorshSerialize, BorshDeserialize)]
 pub(super) struct EdgeRepr {
     key: (PeerId, PeerId),
@@ -103,6 +132,13 @@ impl Column for RecentOutboundConnections {
     type Value = Vec<ConnectionInfoRepr>;
 }
 
+pub(super) struct PeerReputation;
+impl Column for PeerReputation {
+    const COL: DBCol = DBCol::PeerReputation;
+    type Key = Borsh<()>;
+    type Value = Vec<PeerScoreRepr>;
+}
+
 pub(super) struct PeerComponent;
 impl Column for PeerComponent {
     const COL: DBCol = DBCol::PeerComponent;
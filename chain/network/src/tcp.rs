@@ -4,7 +4,10 @@ use near_primitives::network::PeerId;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::fmt;
+use std::pin::Pin;
 use std::sync::Mutex;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 const LISTENER_BACKLOG: u32 = 128;
 
@@ -33,9 +36,62 @@ pub(crate) enum StreamType {
     Outbound { peer_id: PeerId, tier: Tier },
 }
 
+/// Byte stream underlying a `Stream`.
+#[derive(Debug)]
+pub(crate) enum Transport {
+    Tcp(tokio::net::TcpStream),
+    /// TEST-ONLY: connection of a simulated network, see `simnet`.
+    #[cfg(any(test, feature = "test_features"))]
+    Simulated(crate::simnet::Conn),
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(any(test, feature = "test_features"))]
+            Transport::Simulated(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(any(test, feature = "test_features"))]
+            Transport::Simulated(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(any(test, feature = "test_features"))]
+            Transport::Simulated(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(any(test, feature = "test_features"))]
+            Transport::Simulated(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Stream {
-    pub(crate) stream: tokio::net::TcpStream,
+    pub(crate) stream: Transport,
     pub(crate) type_: StreamType,
     /// cached stream.local_addr()
     pub(crate) local_addr: std::net::SocketAddr,
@@ -79,7 +135,23 @@ impl Socket {
 
 impl Stream {
     fn new(stream: tokio::net::TcpStream, type_: StreamType) -> std::io::Result<Self> {
-        Ok(Self { peer_addr: stream.peer_addr()?, local_addr: stream.local_addr()?, stream, type_ })
+        Ok(Self {
+            peer_addr: stream.peer_addr()?,
+            local_addr: stream.local_addr()?,
+            stream: Transport::Tcp(stream),
+            type_,
+        })
+    }
+
+    /// TEST-ONLY: wraps a connection of a simulated network.
+    #[cfg(any(test, feature = "test_features"))]
+    pub(crate) fn simulated(
+        conn: crate::simnet::Conn,
+        type_: StreamType,
+        local_addr: std::net::SocketAddr,
+        peer_addr: std::net::SocketAddr,
+    ) -> Self {
+        Self { stream: Transport::Simulated(conn), type_, local_addr, peer_addr }
     }
 
     pub async fn connect(peer_info: &PeerInfo, tier: Tier) -> anyhow::Result<Stream> {
@@ -191,7 +263,11 @@ impl ListenerAddr {
 
     /// Constructs a std::net::TcpListener, for usage outside of near_network.
     pub fn std_listener(&self) -> std::io::Result<std::net::TcpListener> {
-        self.listener()?.0.into_std()
+        match self.listener()? {
+            Listener::Tcp(listener) => listener.into_std(),
+            #[cfg(any(test, feature = "test_features"))]
+            Listener::Simulated(_) => unreachable!("ListenerAddr::listener() is always TCP"),
+        }
     }
 
     /// Constructs a Listener out of ListenerAddr.
@@ -205,15 +281,67 @@ impl ListenerAddr {
         }
         socket.set_reuseaddr(true)?;
         socket.bind(self.0)?;
-        Ok(Listener(socket.listen(LISTENER_BACKLOG)?))
+        Ok(Listener::Tcp(socket.listen(LISTENER_BACKLOG)?))
     }
 }
 
-pub(crate) struct Listener(tokio::net::TcpListener);
+pub(crate) enum Listener {
+    Tcp(tokio::net::TcpListener),
+    /// TEST-ONLY: receives the connections dialed over a simulated network.
+    #[cfg(any(test, feature = "test_features"))]
+    Simulated(tokio::sync::mpsc::UnboundedReceiver<Stream>),
+}
 
 impl Listener {
     pub async fn accept(&mut self) -> std::io::Result<Stream> {
-        let (stream, _) = self.0.accept().await?;
-        Stream::new(stream, StreamType::Inbound)
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept().await?;
+                Stream::new(stream, StreamType::Inbound)
+            }
+            #[cfg(any(test, feature = "test_features"))]
+            Listener::Simulated(recv) => {
+                recv.recv().await.ok_or(std::io::ErrorKind::ConnectionAborted.into())
+            }
+        }
+    }
+}
+
+/// Establishes the outbound connections of the PeerManagerActor and opens its listener.
+#[derive(Clone)]
+pub enum Dialer {
+    Tcp,
+    /// TEST-ONLY: connects over a simulated network, as the given node of that network.
+    #[cfg(any(test, feature = "test_features"))]
+    Simulated(crate::simnet::SimNetwork, crate::simnet::NodeIdx),
+}
+
+impl Dialer {
+    pub async fn connect(&self, peer_info: &PeerInfo, tier: Tier) -> anyhow::Result<Stream> {
+        match self {
+            Dialer::Tcp => Stream::connect(peer_info, tier).await,
+            #[cfg(any(test, feature = "test_features"))]
+            Dialer::Simulated(net, node) => {
+                let addr = peer_info
+                    .addr
+                    .ok_or(anyhow!("Trying to connect to peer with no public address"))?;
+                Ok(net.dial(*node, addr, peer_info.id.clone(), tier)?)
+            }
+        }
+    }
+
+    /// Opens a listener at `addr`, accepting the connections established by the
+    /// dialers of the same network.
+    pub(crate) fn listener(&self, addr: &ListenerAddr) -> std::io::Result<Listener> {
+        match self {
+            Dialer::Tcp => addr.listener(),
+            #[cfg(any(test, feature = "test_features"))]
+            Dialer::Simulated(net, node) => {
+                if **addr != crate::simnet::SimNetwork::addr(*node) {
+                    return Err(std::io::ErrorKind::AddrNotAvailable.into());
+                }
+                Ok(net.listen(*node))
+            }
+        }
     }
 }
@@ -124,6 +124,15 @@ pub struct ConnectionInfo {
     pub time_connected_until: time::Utc,
 }
 
+/// Reputation score of a peer, as of the last time it has been updated.
+/// See `peer_manager::peer_store::reputation`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerScore {
+    pub peer_id: PeerId,
+    pub value: f64,
+    pub updated: time::Utc,
+}
+
 impl KnownPeerStatus {
     pub fn is_banned(&self) -> bool {
         matches!(self, KnownPeerStatus::Banned(_, _))
@@ -280,6 +289,22 @@ pub struct HighestHeightPeerInfo {
     pub tracked_shards: Vec<ShardId>,
     /// Denote if a node is running in archival mode or not.
     pub archival: bool,
+    /// Whether the peer has a low reputation score, see `prefer_good_reputation`.
+    pub low_reputation: bool,
+}
+
+/// Returns the peers without a low reputation, or all the `peers` if there are no such peers,
+/// so that the peers with a low reputation are chosen only if there are no other candidates.
+pub fn prefer_good_reputation<'a>(
+    peers: impl IntoIterator<Item = &'a HighestHeightPeerInfo>,
+) -> Vec<&'a HighestHeightPeerInfo> {
+    let (low_reputation, peers): (Vec<_>, Vec<_>) =
+        peers.into_iter().partition(|p| p.low_reputation);
+    if peers.is_empty() {
+        low_reputation
+    } else {
+        peers
+    }
 }
 
 impl From<FullPeerInfo> for Option<HighestHeightPeerInfo> {
@@ -292,6 +317,7 @@ impl From<FullPeerInfo> for Option<HighestHeightPeerInfo> {
                 highest_block_hash: p.chain_info.last_block.unwrap().hash,
                 tracked_shards: p.chain_info.tracked_shards,
                 archival: p.chain_info.archival,
+                low_reputation: false,
             })
         } else {
             None
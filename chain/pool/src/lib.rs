@@ -1,5 +1,5 @@
 use std::collections::btree_map::Entry;
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::collections::{hash_map, BTreeMap, HashMap, HashSet, VecDeque};
 
 use crate::types::{PoolIterator, PoolKey, TransactionGroup};
 
@@ -30,8 +30,9 @@ pub struct TransactionPool {
     /// NOTE: It's more efficient on average to keep transactions unsorted and with potentially
     /// conflicting nonce than to create a BTreeMap for every transaction.
     transactions: BTreeMap<PoolKey, Vec<SignedTransaction>>,
-    /// Set of all hashes to quickly check if the given transaction is in the pool.
-    unique_transactions: HashSet<CryptoHash>,
+    /// Hashes of all the transactions to quickly check if the given transaction is in the pool,
+    /// mapped to the key of the group the transaction belongs to.
+    unique_transactions: HashMap<CryptoHash, PoolKey>,
     /// A uniquely generated key seed to randomize PoolKey order.
     key_seed: RngSeed,
     /// The key after which the pool iterator starts. Doesn't have to be present in the pool.
@@ -62,7 +63,7 @@ impl TransactionPool {
         Self {
             key_seed,
             transactions: BTreeMap::new(),
-            unique_transactions: HashSet::new(),
+            unique_transactions: HashMap::new(),
             last_used_key: CryptoHash::default(),
             total_transaction_size_limit,
             total_transaction_size: 0,
@@ -84,10 +85,14 @@ impl TransactionPool {
         &mut self,
         signed_transaction: SignedTransaction,
     ) -> InsertTransactionResult {
-        if !self.unique_transactions.insert(signed_transaction.get_hash()) {
+        let signer_id = &signed_transaction.transaction.signer_id;
+        let signer_public_key = &signed_transaction.transaction.public_key;
+        let key = self.key(signer_id, signer_public_key);
+        match self.unique_transactions.entry(signed_transaction.get_hash()) {
             // The hash of this transaction was already seen, skip it.
-            return InsertTransactionResult::Duplicate;
-        }
+            hash_map::Entry::Occupied(_) => return InsertTransactionResult::Duplicate,
+            hash_map::Entry::Vacant(entry) => entry.insert(key),
+        };
         // We never expect the total size to go over `u64` during real operation as that would
         // be more than 10^9 GiB of RAM consumed for transaction pool, so panicing here is intended
         // to catch a logic error in estimation of transaction size.
@@ -103,12 +108,7 @@ impl TransactionPool {
 
         // At this point transaction is accepted to the pool.
         self.total_transaction_size = new_total_transaction_size;
-        let signer_id = &signed_transaction.transaction.signer_id;
-        let signer_public_key = &signed_transaction.transaction.public_key;
-        self.transactions
-            .entry(self.key(signer_id, signer_public_key))
-            .or_insert_with(Vec::new)
-            .push(signed_transaction);
+        self.transactions.entry(key).or_insert_with(Vec::new).push(signed_transaction);
 
         self.transaction_pool_count_metric.inc();
         self.transaction_pool_size_metric.set(self.total_transaction_size as i64);
@@ -130,16 +130,10 @@ impl TransactionPool {
         let mut grouped_transactions = HashMap::new();
         for tx in transactions {
             // If transaction is not present in the pool, skip it.
-            if !self.unique_transactions.remove(&tx.get_hash()) {
+            let Some(key) = self.unique_transactions.remove(&tx.get_hash()) else {
                 continue;
-            }
-
-            let signer_id = &tx.transaction.signer_id;
-            let signer_public_key = &tx.transaction.public_key;
-            grouped_transactions
-                .entry(self.key(signer_id, signer_public_key))
-                .or_insert_with(HashSet::new)
-                .insert(tx.get_hash());
+            };
+            grouped_transactions.entry(key).or_insert_with(HashSet::new).insert(tx.get_hash());
         }
         for (key, hashes) in grouped_transactions {
             if let Entry::Occupied(mut entry) = self.transactions.entry(key) {
@@ -175,6 +169,27 @@ impl TransactionPool {
     pub fn transaction_size(&self) -> u64 {
         self.total_transaction_size
     }
+
+    /// Returns the hashes of all the transactions in the pool in no particular order.
+    pub fn transaction_hashes(&self) -> impl Iterator<Item = &CryptoHash> {
+        self.unique_transactions.keys()
+    }
+
+    /// Returns the transaction with the given hash if it is in the pool.
+    pub fn get_transaction(&self, hash: &CryptoHash) -> Option<&SignedTransaction> {
+        let key = self.unique_transactions.get(hash)?;
+        self.transactions.get(key)?.iter().find(|tx| &tx.get_hash() == hash)
+    }
+
+    /// Returns the transactions in the pool signed with the given access key, in no particular
+    /// order.
+    pub fn signer_transactions(
+        &self,
+        signer_id: &AccountId,
+        public_key: &PublicKey,
+    ) -> impl Iterator<Item = &SignedTransaction> {
+        self.transactions.get(&self.key(signer_id, public_key)).into_iter().flatten()
+    }
 }
 
 /// PoolIterator is a structure to pull transactions from the pool.
@@ -594,4 +609,61 @@ mod tests {
             }
         }
     }
+
+    /// Transactions listed and looked up in the pool are the ones which were inserted and not
+    /// yet removed.
+    #[test]
+    fn test_lookup_transactions() {
+        let mut transactions = generate_transactions("alice.near", "alice.near", 1, 5);
+        transactions.extend(generate_transactions("bob.near", "bob.near", 1, 5));
+        let mut pool = TransactionPool::new(TEST_SEED, None, "");
+        for tx in transactions.iter().cloned() {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        pool.remove_transactions(&transactions[..3]);
+
+        let mut listed: Vec<CryptoHash> = pool.transaction_hashes().copied().collect();
+        let mut expected: Vec<CryptoHash> =
+            transactions[3..].iter().map(|tx| tx.get_hash()).collect();
+        listed.sort();
+        expected.sort();
+        assert_eq!(listed, expected);
+
+        for tx in &transactions[..3] {
+            assert_eq!(pool.get_transaction(&tx.get_hash()), None);
+        }
+        for tx in &transactions[3..] {
+            assert_eq!(pool.get_transaction(&tx.get_hash()), Some(tx));
+        }
+
+        let alice = &transactions[0].transaction;
+        let mut alice_nonces: Vec<_> = pool
+            .signer_transactions(&alice.signer_id, &alice.public_key)
+            .map(|tx| tx.transaction.nonce)
+            .collect();
+        alice_nonces.sort();
+        assert_eq!(alice_nonces, vec![4, 5]);
+        let carol: AccountId = "carol.near".parse().unwrap();
+        assert_eq!(pool.signer_transactions(&carol, &alice.public_key).count(), 0);
+    }
+
+    /// Transactions pulled from the pool by the pool iterator can't be looked up anymore.
+    #[test]
+    fn test_lookup_transactions_after_iteration() {
+        let transactions = generate_transactions("alice.near", "alice.near", 1, 3);
+        let mut pool = TransactionPool::new(TEST_SEED, None, "");
+        for tx in transactions.iter().cloned() {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        let pulled = {
+            let mut pool_iter = pool.pool_iterator();
+            pool_iter.next().unwrap().next().unwrap()
+        };
+        assert_eq!(pulled.transaction.nonce, 1);
+        assert_eq!(pool.get_transaction(&pulled.get_hash()), None);
+        for tx in &transactions[1..] {
+            assert_eq!(pool.get_transaction(&tx.get_hash()), Some(tx));
+        }
+        assert_eq!(pool.transaction_hashes().count(), 2);
+    }
 }
@@ -199,6 +199,70 @@ pub(crate) async fn collect_transactions(
     }
 }
 
+/// Converts a transaction waiting in the transaction pool into a Rosetta
+/// transaction.
+///
+/// The operations are the ones of the transaction actions (see
+/// [`NearActions`]) followed by a gas prepayment charged to the signer.  The
+/// prepayment is estimated at the given gas price; the actual fee is only known
+/// once the transaction and its receipts are executed, and the unused part of
+/// the prepayment is refunded.
+pub(crate) fn convert_pool_transaction_to_transaction(
+    signed_transaction: near_primitives::transaction::SignedTransaction,
+    runtime_config: &near_primitives::runtime::config::RuntimeConfig,
+    gas_price: near_primitives::types::Balance,
+) -> crate::errors::Result<crate::models::Transaction> {
+    let transaction_identifier =
+        crate::models::TransactionIdentifier::transaction(&signed_transaction.get_hash());
+    let transaction = signed_transaction.transaction;
+    let overflow_error = |_| {
+        crate::errors::ErrorKind::InternalInvariantError(format!(
+            "Overflow while estimating the fee of transaction {}",
+            transaction_identifier.hash
+        ))
+    };
+    let cost = node_runtime::config::tx_cost(
+        runtime_config,
+        &transaction,
+        gas_price,
+        transaction.signer_id == transaction.receiver_id,
+    )
+    .map_err(overflow_error)?;
+    let deposit =
+        node_runtime::config::total_deposit(&transaction.actions).map_err(overflow_error)?;
+    let gas_prepayment = cost.total_cost - deposit;
+
+    let signer_account_identifier: crate::models::AccountIdentifier =
+        transaction.signer_id.clone().into();
+    let mut operations: Vec<crate::models::Operation> = NearActions {
+        sender_account_id: transaction.signer_id,
+        receiver_account_id: transaction.receiver_id,
+        actions: transaction.actions,
+    }
+    .into();
+    operations.push(crate::models::Operation {
+        operation_identifier: crate::models::OperationIdentifier::new(&operations),
+        related_operations: None,
+        account: signer_account_identifier,
+        amount: Some(-crate::models::Amount::from_yoctonear(gas_prepayment)),
+        type_: crate::models::OperationType::Transfer,
+        status: None,
+        metadata: Some(crate::models::OperationMetadata {
+            transfer_fee_type: Some(crate::models::OperationMetadataTransferFeeType::GasPrepayment),
+            ..Default::default()
+        }),
+    });
+
+    Ok(crate::models::Transaction {
+        transaction_identifier,
+        operations,
+        related_transactions: vec![],
+        metadata: crate::models::TransactionMetadata {
+            type_: crate::models::TransactionType::Transaction,
+        },
+    })
+}
+
 /// This is used as a common denominator for matching Rosetta Operations to
 /// and from NEAR Actions (see From and TryFrom implementations).
 ///
@@ -832,10 +896,10 @@ mod tests {
     use actix::System;
     use near_actix_test_utils::run_actix;
     use near_client::test_utils::setup_no_network;
-    use near_crypto::{KeyType, SecretKey};
+    use near_crypto::{InMemorySigner, KeyType, SecretKey};
     use near_primitives::action::delegate::{DelegateAction, SignedDelegateAction};
     use near_primitives::runtime::config::RuntimeConfig;
-    use near_primitives::transaction::{Action, TransferAction};
+    use near_primitives::transaction::{Action, SignedTransaction, TransferAction};
     use near_primitives::views::RuntimeConfigView;
 
     #[test]
@@ -1126,6 +1190,57 @@ mod tests {
         assert_eq!(converted_near_actions, original_near_actions);
     }
 
+    #[test]
+    fn test_convert_pool_transaction_to_transaction() {
+        let signer = InMemorySigner::from_seed(
+            "alice.near".parse().unwrap(),
+            KeyType::ED25519,
+            "alice.near",
+        );
+        let signed_transaction = SignedTransaction::send_money(
+            1,
+            "alice.near".parse().unwrap(),
+            "bob.near".parse().unwrap(),
+            &signer,
+            100,
+            near_primitives::hash::CryptoHash::default(),
+        );
+        let runtime_config = RuntimeConfig::test();
+        let gas_price = 100_000_000;
+
+        let transaction = convert_pool_transaction_to_transaction(
+            signed_transaction.clone(),
+            &runtime_config,
+            gas_price,
+        )
+        .unwrap();
+        assert_eq!(
+            transaction.transaction_identifier,
+            crate::models::TransactionIdentifier::transaction(&signed_transaction.get_hash())
+        );
+
+        // All but the last operation describe the actions of the transaction.
+        let (fee_operation, action_operations) = transaction.operations.split_last().unwrap();
+        let near_actions = NearActions::try_from(action_operations.to_vec()).unwrap();
+        assert_eq!(near_actions.actions, signed_transaction.transaction.actions);
+
+        let cost = node_runtime::config::tx_cost(
+            &runtime_config,
+            &signed_transaction.transaction,
+            gas_price,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            fee_operation.amount,
+            Some(-crate::models::Amount::from_yoctonear(cost.total_cost - 100))
+        );
+        assert_eq!(
+            fee_operation.metadata.as_ref().unwrap().transfer_fee_type,
+            Some(crate::models::OperationMetadataTransferFeeType::GasPrepayment)
+        );
+    }
+
     #[test]
     fn test_near_actions_invalid_transfer_no_amount() {
         let operations = vec![crate::models::Operation {
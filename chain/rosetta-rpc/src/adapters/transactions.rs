@@ -57,7 +57,10 @@ impl ExecutionToReceipts {
             }
         }
         let map = view_client_addr
-            .send(near_client::GetExecutionOutcomesForBlock { block_hash }.with_span_context())
+            .send(
+                near_client::GetExecutionOutcomesForBlock { block_hash, shard_ids: None }
+                    .with_span_context(),
+            )
             .await?;
 
         let map_hash_to_receipts = map
@@ -30,10 +30,20 @@ impl RosettaRpcConfig {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct RosettaRpcLimitsConfig {
     pub input_payload_max_size: usize,
+    /// Maximum number of transaction identifiers returned by `/mempool`.
+    #[serde(default = "default_mempool_transactions_max_count")]
+    pub mempool_transactions_max_count: usize,
+}
+
+fn default_mempool_transactions_max_count() -> usize {
+    1000
 }
 
 impl Default for RosettaRpcLimitsConfig {
     fn default() -> Self {
-        Self { input_payload_max_size: 10 * 1024 * 1024 }
+        Self {
+            input_payload_max_size: 10 * 1024 * 1024,
+            mempool_transactions_max_count: default_mempool_transactions_max_count(),
+        }
     }
 }
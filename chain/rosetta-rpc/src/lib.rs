@@ -448,20 +448,40 @@ async fn account_balance(
 }
 
 #[api_v2_operation]
-/// Get All Mempool Transactions (not implemented)
+/// Get All Mempool Transactions
 ///
 /// Get all Transaction Identifiers in the mempool
 ///
-/// NOTE: The mempool is short-lived, so it is currently not implemented.
+/// NOTE: The mempool is short-lived and each node only sees the transactions
+/// which were routed through it, so the result is not a network-wide view.
+/// At most `limits.mempool_transactions_max_count` identifiers are returned.
 async fn mempool(
-    _client_addr: web::Data<Addr<ClientActor>>,
-    _body: Json<models::NetworkRequest>,
+    client_addr: web::Data<Addr<ClientActor>>,
+    limits: web::Data<crate::config::RosettaRpcLimitsConfig>,
+    body: Json<models::NetworkRequest>,
 ) -> Result<Json<models::MempoolResponse>, models::Error> {
-    Ok(Json(models::MempoolResponse { transaction_identifiers: vec![] }))
+    let Json(models::NetworkRequest { network_identifier }) = body;
+
+    check_network_identifier(&client_addr, network_identifier).await?;
+
+    let transaction_hashes = client_addr
+        .send(
+            near_client::GetPoolTransactionHashes { limit: limits.mempool_transactions_max_count }
+                .with_span_context(),
+        )
+        .await?
+        .map_err(errors::ErrorKind::InternalError)?;
+
+    Ok(Json(models::MempoolResponse {
+        transaction_identifiers: transaction_hashes
+            .iter()
+            .map(models::TransactionIdentifier::transaction)
+            .collect(),
+    }))
 }
 
 #[api_v2_operation]
-/// Get a Mempool Transaction (not implemented)
+/// Get a Mempool Transaction
 ///
 /// Get a transaction in the mempool by its Transaction Identifier. This is a
 /// separate request than fetching a block transaction (/block/transaction)
@@ -472,13 +492,58 @@ async fn mempool(
 /// endpoint, it is ok that returned transactions are only estimates of what may
 /// actually be included in a block.
 ///
-/// NOTE: The mempool is short-lived, so this method does not make a lot of
-/// sense to be implemented.
+/// NOTE: The fee is reported as the gas prepayment at the gas price of the
+/// latest final block. The unused part of it is refunded after execution.
 async fn mempool_transaction(
-    _client_addr: web::Data<Addr<ClientActor>>,
-    _body: Json<models::MempoolTransactionRequest>,
+    genesis: web::Data<GenesisWithIdentifier>,
+    client_addr: web::Data<Addr<ClientActor>>,
+    view_client_addr: web::Data<Addr<ViewClientActor>>,
+    body: Json<models::MempoolTransactionRequest>,
 ) -> Result<Json<models::MempoolTransactionResponse>, models::Error> {
-    Err(errors::ErrorKind::InternalError("Not implemented yet".to_string()).into())
+    let Json(models::MempoolTransactionRequest { network_identifier, transaction_identifier }) =
+        body;
+
+    check_network_identifier(&client_addr, network_identifier).await?;
+
+    let tx_hash = transaction_identifier
+        .hash
+        .strip_prefix("tx:")
+        .and_then(|tx_hash| tx_hash.parse().ok())
+        .ok_or_else(|| {
+            errors::ErrorKind::InvalidInput(format!(
+                "Invalid transaction identifier {}",
+                transaction_identifier.hash
+            ))
+        })?;
+    let transaction = client_addr
+        .send(near_client::GetPoolTransaction { tx_hash }.with_span_context())
+        .await?
+        .map_err(errors::ErrorKind::InternalError)?
+        .ok_or_else(|| {
+            errors::ErrorKind::NotFound(format!(
+                "Transaction {} is not in the mempool",
+                transaction_identifier.hash
+            ))
+        })?;
+
+    let block = crate::utils::get_final_block(&view_client_addr).await?;
+    let protocol_version =
+        crate::utils::query_protocol_config(block.header.hash, &view_client_addr)
+            .await?
+            .protocol_version;
+    let runtime_config_store =
+        near_primitives::runtime::config_store::RuntimeConfigStore::for_chain_id(
+            &genesis.genesis.config.chain_id,
+        );
+    let runtime_config = runtime_config_store.get_config(protocol_version);
+
+    Ok(Json(models::MempoolTransactionResponse {
+        transaction: crate::adapters::convert_pool_transaction_to_transaction(
+            transaction,
+            runtime_config,
+            block.header.gas_price,
+        )?,
+    }))
 }
 
 #[api_v2_operation]
@@ -868,6 +933,7 @@ pub fn start_rosetta_rpc(
             .app_data(web::Data::new(client_addr.clone()))
             .app_data(web::Data::new(view_client_addr.clone()))
             .app_data(web::Data::new(currencies.clone()))
+            .app_data(web::Data::new(limits.clone()))
             .wrap(get_cors(&cors_allowed_origins))
             .wrap_api()
             .service(web::resource("/network/list").route(web::post().to(network_list)))
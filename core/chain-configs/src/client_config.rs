@@ -260,6 +260,9 @@ pub struct ClientConfig {
     /// - archive is true, cold_store is configured and migration to split_storage is finished - node
     /// working in split storage mode needs trie changes in order to do garbage collection on hot.
     pub save_trie_changes: bool,
+    /// Maintain an index of transactions and receipts which touched each
+    /// account, used by the `EXPERIMENTAL_account_activity` RPC method.
+    pub save_account_activity: bool,
     /// Number of threads for ViewClientActor pool.
     pub view_client_threads: usize,
     /// Run Epoch Sync on the start.
@@ -352,6 +355,7 @@ impl ClientConfig {
             tracked_shard_schedule: vec![],
             archive,
             save_trie_changes,
+            save_account_activity: false,
             log_summary_style: LogSummaryStyle::Colored,
             view_client_threads: 1,
             epoch_sync_enabled,
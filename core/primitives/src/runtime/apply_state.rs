@@ -42,4 +42,7 @@ pub struct ApplyState {
     pub migration_data: Arc<MigrationData>,
     /// Flags for migrations indicating whether they can be applied at this block
     pub migration_flags: MigrationFlags,
+    /// Whether signatures of transactions are verified. Only disabled when
+    /// simulating transactions which are never included in a chunk.
+    pub verify_signatures: bool,
 }
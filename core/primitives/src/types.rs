@@ -111,6 +111,25 @@ pub struct StoreValue(#[serde_as(as = "Base64")] Vec<u8>);
 #[serde(transparent)]
 pub struct FunctionArgs(#[serde_as(as = "Base64")] Vec<u8>);
 
+/// Kind of an entry in the account activity index, i.e. whether the indexed
+/// hash belongs to a transaction or to a receipt.
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountActivityKind {
+    Transaction,
+    Receipt,
+}
+
 /// A structure used to indicate the kind of state changes due to transaction/receipt processing, etc.
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
 pub enum StateChangeKind {
@@ -283,6 +302,14 @@ pub struct StateChangeWithCause {
 
 pub type StateChanges = Vec<StateChangeWithCause>;
 
+/// A state change together with the value the key had right before it.
+#[derive(Debug)]
+pub struct StateChangeWithPrevValue {
+    pub change: StateChangeWithCause,
+    /// `None` if the key did not exist before the change.
+    pub prev_value: Option<StateChangeValue>,
+}
+
 #[easy_ext::ext(StateChangesExt)]
 impl StateChanges {
     pub fn from_changes(
@@ -376,6 +403,54 @@ impl StateChanges {
 
         Ok(state_changes)
     }
+
+    /// Same as `from_changes`, but pairs every change with the previous value of the key.
+    ///
+    /// Within a block the previous value is the data of the preceding change of the same key,
+    /// while for the first change it is looked up via `initial_value`, which should read the
+    /// state the block was applied on top of.
+    pub fn from_changes_with_prev_values<E: From<std::io::Error>>(
+        raw_changes: impl Iterator<Item = Result<RawStateChangesWithTrieKey, std::io::Error>>,
+        mut initial_value: impl FnMut(&TrieKey) -> Result<Option<Vec<u8>>, E>,
+    ) -> Result<Vec<StateChangeWithPrevValue>, E> {
+        let mut state_changes = vec![];
+
+        for raw_change in raw_changes {
+            let raw_change = raw_change?;
+            let changes = Self::from_changes(std::iter::once(Ok(raw_change.clone())))?;
+            if changes.is_empty() {
+                continue;
+            }
+            let RawStateChangesWithTrieKey { trie_key, changes: key_changes } = raw_change;
+
+            // Shift the data by one change, so that the same conversion produces the previous
+            // values. A missing previous value would be converted into a deletion, so it's
+            // tracked separately.
+            let mut prev_data = initial_value(&trie_key)?;
+            let mut prev_exists = Vec::with_capacity(key_changes.len());
+            let mut prev_changes = Vec::with_capacity(key_changes.len());
+            for RawStateChange { cause, data } in key_changes {
+                prev_exists.push(prev_data.is_some());
+                prev_changes.push(RawStateChange { cause, data: prev_data });
+                prev_data = data;
+            }
+            let prev_values =
+                Self::from_changes(std::iter::once(Ok(RawStateChangesWithTrieKey {
+                    trie_key,
+                    changes: prev_changes,
+                })))?;
+
+            state_changes.extend(changes.into_iter().zip(prev_values).zip(prev_exists).map(
+                |((change, prev_value), prev_exists)| StateChangeWithPrevValue {
+                    change,
+                    prev_value: prev_exists.then_some(prev_value.value),
+                },
+            ));
+        }
+
+        Ok(state_changes)
+    }
+
     pub fn from_account_changes(
         raw_changes: impl Iterator<Item = Result<RawStateChangesWithTrieKey, std::io::Error>>,
     ) -> Result<StateChanges, std::io::Error> {
@@ -1029,3 +1104,116 @@ pub struct StateChangesForShard {
     pub shard_id: ShardId,
     pub state_changes: Vec<RawStateChangesWithTrieKey>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_key() -> TrieKey {
+        TrieKey::ContractData { account_id: "alice.near".parse().unwrap(), key: b"key".to_vec() }
+    }
+
+    fn change(tx: u8, data: Option<&[u8]>) -> RawStateChange {
+        RawStateChange {
+            cause: StateChangeCause::TransactionProcessing { tx_hash: CryptoHash([tx; 32]) },
+            data: data.map(<[u8]>::to_vec),
+        }
+    }
+
+    /// Converts a data change into the stored value, `None` for a deletion.
+    fn data(value: &StateChangeValue) -> Option<Vec<u8>> {
+        match value {
+            StateChangeValue::DataUpdate { value, .. } => Some(value.clone().into()),
+            StateChangeValue::DataDeletion { .. } => None,
+            value => panic!("unexpected state change {value:?}"),
+        }
+    }
+
+    /// Returns the (new value, previous value) pairs of every change of the key.
+    fn changes_with_prev_values(
+        initial: Option<&[u8]>,
+        changes: Vec<RawStateChange>,
+    ) -> Vec<(Option<Vec<u8>>, Option<Option<Vec<u8>>>)> {
+        let raw_changes = RawStateChangesWithTrieKey { trie_key: data_key(), changes };
+        let result = StateChanges::from_changes_with_prev_values(
+            std::iter::once(Ok(raw_changes)),
+            |trie_key| -> Result<_, std::io::Error> {
+                assert_eq!(trie_key, &data_key());
+                Ok(initial.map(<[u8]>::to_vec))
+            },
+        )
+        .unwrap();
+        result
+            .iter()
+            .map(|change| (data(&change.change.value), change.prev_value.as_ref().map(data)))
+            .collect()
+    }
+
+    #[test]
+    fn test_prev_values_create() {
+        assert_eq!(
+            changes_with_prev_values(None, vec![change(1, Some(b"a"))]),
+            vec![(Some(b"a".to_vec()), None)]
+        );
+    }
+
+    #[test]
+    fn test_prev_values_update() {
+        assert_eq!(
+            changes_with_prev_values(
+                Some(b"a"),
+                vec![change(1, Some(b"b")), change(2, Some(b"c"))]
+            ),
+            vec![
+                (Some(b"b".to_vec()), Some(Some(b"a".to_vec()))),
+                (Some(b"c".to_vec()), Some(Some(b"b".to_vec()))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prev_values_delete() {
+        assert_eq!(
+            changes_with_prev_values(Some(b"a"), vec![change(1, None)]),
+            vec![(None, Some(Some(b"a".to_vec())))]
+        );
+    }
+
+    #[test]
+    fn test_prev_values_create_and_delete() {
+        // The deletion sees the value created earlier in the same block, and the
+        // re-creation sees that the key did not exist.
+        assert_eq!(
+            changes_with_prev_values(
+                None,
+                vec![change(1, Some(b"a")), change(2, None), change(3, Some(b"b"))]
+            ),
+            vec![
+                (Some(b"a".to_vec()), None),
+                (None, Some(Some(b"a".to_vec()))),
+                (Some(b"b".to_vec()), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prev_values_cause() {
+        let raw_changes = RawStateChangesWithTrieKey {
+            trie_key: data_key(),
+            changes: vec![change(1, Some(b"a")), change(2, Some(b"b"))],
+        };
+        let result = StateChanges::from_changes_with_prev_values(
+            std::iter::once(Ok(raw_changes)),
+            |_| -> Result<_, std::io::Error> { Ok(None) },
+        )
+        .unwrap();
+        let causes: Vec<_> = result.into_iter().map(|change| change.change.cause).collect();
+        assert_eq!(
+            causes,
+            vec![
+                StateChangeCause::TransactionProcessing { tx_hash: CryptoHash([1; 32]) },
+                StateChangeCause::TransactionProcessing { tx_hash: CryptoHash([2; 32]) },
+            ]
+        );
+    }
+}
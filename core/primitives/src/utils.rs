@@ -10,8 +10,9 @@ use serde;
 
 use crate::hash::{hash, CryptoHash};
 use crate::receipt::Receipt;
+use crate::sharding::ShardChunk;
 use crate::transaction::SignedTransaction;
-use crate::types::{NumSeats, NumShards, ShardId};
+use crate::types::{AccountActivityKind, BlockHeight, NumSeats, NumShards, ShardId};
 use crate::version::{
     ProtocolVersion, CORRECT_RANDOM_VALUE_PROTOCOL_VERSION, CREATE_HASH_PROTOCOL_VERSION,
     CREATE_RECEIPT_ID_SWITCH_TO_CURRENT_BLOCK_VERSION,
@@ -221,6 +222,77 @@ pub fn get_outcome_id_block_hash_rev(key: &[u8]) -> std::io::Result<(CryptoHash,
     Ok((outcome_id, block_hash))
 }
 
+/// Separates account id from the rest of the key in the account activity
+/// index.  It can't appear in a valid account id so that the entries of
+/// `alice` don't share a prefix with the entries of `alice.near`.
+const ACCOUNT_ACTIVITY_SEPARATOR: u8 = b',';
+
+/// Returns the prefix of all account activity index keys of the given account.
+pub fn get_account_activity_prefix(account_id: &AccountId) -> Vec<u8> {
+    let mut res = Vec::with_capacity(account_id.len() + 1 + 40);
+    res.extend_from_slice(account_id.as_bytes());
+    res.push(ACCOUNT_ACTIVITY_SEPARATOR);
+    res
+}
+
+/// Returns the account activity index key.  Height is stored in big-endian so
+/// that the entries of an account are ordered by height.
+pub fn get_account_activity_key(
+    account_id: &AccountId,
+    height: BlockHeight,
+    id: &CryptoHash,
+) -> Vec<u8> {
+    let mut res = get_account_activity_prefix(account_id);
+    res.extend_from_slice(&height.to_be_bytes());
+    res.extend_from_slice(id.as_ref());
+    res
+}
+
+/// Extracts block height and transaction or receipt hash from the account
+/// activity index key.
+pub fn get_account_activity_key_rev(key: &[u8]) -> std::io::Result<(BlockHeight, CryptoHash)> {
+    if key.len() < 41 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid key length"));
+    }
+    let (height_bytes, id_bytes) = key[key.len() - 40..].split_at(8);
+    let height = BlockHeight::from_be_bytes(height_bytes.try_into().unwrap());
+    let id = CryptoHash::try_from(id_bytes).unwrap();
+    Ok((height, id))
+}
+
+/// Lists accounts touched by the transactions and receipts of the chunk, as
+/// recorded in the account activity index.  A transaction touches its signer
+/// and receiver, a receipt touches its predecessor and receiver.
+///
+/// Receipts are taken from the outgoing receipts of the previous chunk, except
+/// for local receipts: a transaction whose signer is also its receiver is
+/// converted to a receipt which is executed in the same chunk and never sent.
+/// Their ids are looked up with `local_receipt_id`, which returns the receipt
+/// id in the outcome of the transaction with the given hash, if it is known.
+pub fn get_account_activity_entries<'a>(
+    chunk: &'a ShardChunk,
+    mut local_receipt_id: impl FnMut(&CryptoHash) -> std::io::Result<Option<CryptoHash>>,
+) -> std::io::Result<Vec<(&'a AccountId, CryptoHash, AccountActivityKind)>> {
+    let mut res = vec![];
+    for transaction in chunk.transactions() {
+        let tx = &transaction.transaction;
+        let tx_hash = transaction.get_hash();
+        res.push((&tx.signer_id, tx_hash, AccountActivityKind::Transaction));
+        if tx.receiver_id != tx.signer_id {
+            res.push((&tx.receiver_id, tx_hash, AccountActivityKind::Transaction));
+        } else if let Some(receipt_id) = local_receipt_id(&tx_hash)? {
+            res.push((&tx.signer_id, receipt_id, AccountActivityKind::Receipt));
+        }
+    }
+    for receipt in chunk.prev_outgoing_receipts() {
+        res.push((&receipt.predecessor_id, receipt.receipt_id, AccountActivityKind::Receipt));
+        if receipt.receiver_id != receipt.predecessor_id {
+            res.push((&receipt.receiver_id, receipt.receipt_id, AccountActivityKind::Receipt));
+        }
+    }
+    Ok(res)
+}
+
 /// Creates a new Receipt ID from a given signed transaction and a block hash.
 /// This method is backward compatible, so it takes the current protocol version.
 pub fn create_receipt_id_from_transaction(
@@ -574,4 +646,22 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_account_activity_key() {
+        let alice: AccountId = "alice".parse().unwrap();
+        let alice_near: AccountId = "alice.near".parse().unwrap();
+        let id = hash(b"tx");
+
+        let key = get_account_activity_key(&alice, 258, &id);
+        assert!(key.starts_with(&get_account_activity_prefix(&alice)));
+        assert!(!key.starts_with(&get_account_activity_prefix(&alice_near)));
+        assert!(!get_account_activity_key(&alice_near, 1, &id)
+            .starts_with(&get_account_activity_prefix(&alice)));
+        assert_eq!(get_account_activity_key_rev(&key).unwrap(), (258, id));
+
+        // Keys of the same account are ordered by height.
+        assert!(key < get_account_activity_key(&alice, 259, &CryptoHash::default()));
+        assert!(key > get_account_activity_key(&alice, 257, &hash(b"other")));
+    }
 }
@@ -31,10 +31,10 @@ use crate::transaction::{
     SignedTransaction, StakeAction, TransferAction,
 };
 use crate::types::{
-    AccountId, AccountWithPublicKey, Balance, BlockHeight, EpochHeight, EpochId, FunctionArgs, Gas,
-    Nonce, NumBlocks, ShardId, StateChangeCause, StateChangeKind, StateChangeValue,
-    StateChangeWithCause, StateChangesRequest, StateRoot, StorageUsage, StoreKey, StoreValue,
-    ValidatorKickoutReason,
+    AccountActivityKind, AccountId, AccountWithPublicKey, Balance, BlockHeight, EpochHeight,
+    EpochId, FunctionArgs, Gas, Nonce, NumBlocks, ShardId, StateChangeCause, StateChangeKind,
+    StateChangeValue, StateChangeWithCause, StateChangeWithPrevValue, StateChangesRequest,
+    StateRoot, StorageUsage, StoreKey, StoreValue, ValidatorKickoutReason,
 };
 use crate::version::{ProtocolVersion, Version};
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -233,6 +233,29 @@ pub struct ViewStateResult {
     #[serde_as(as = "Vec<Base64>")]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub proof: Vec<Arc<[u8]>>,
+    /// Set if the page was cut short by the requested limit.  Pass it back in
+    /// `QueryRequest::ViewState` to fetch the next page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<ViewStateCursor>,
+}
+
+/// Continuation cursor for paginated `QueryRequest::ViewState` queries.
+///
+/// Clients should treat it as opaque and only pass back values returned in
+/// [`ViewStateResult::next_cursor`].  Internally it holds the contract data key
+/// the next page starts at.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(transparent)]
+pub struct ViewStateCursor(StoreKey);
+
+impl ViewStateCursor {
+    pub fn new(key: Vec<u8>) -> Self {
+        Self(key.into())
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.0
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone, Default)]
@@ -241,6 +264,48 @@ pub struct CallResult {
     pub logs: Vec<String>,
 }
 
+/// Result of a traced `call_function` view call, see `QueryRequest::TraceCall`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct CallTraceView {
+    pub result: Vec<u8>,
+    pub logs: Vec<String>,
+    /// Set if the call failed.  The rest of the trace then covers the
+    /// execution up to the failure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(with = "dec_format")]
+    pub burnt_gas: Gas,
+    /// Gas spent on executing WASM instructions.
+    #[serde(with = "dec_format")]
+    pub wasm_gas: Gas,
+    /// Gas spent in host functions, by cost.  Costs which weren't charged are
+    /// omitted.
+    pub host_functions: Vec<HostFunctionGasView>,
+    /// Contract storage keys read by the call, in order of access.
+    pub storage_reads: Vec<StoreKey>,
+    /// Promises created by the call.  They are not executed, but show what
+    /// the call would schedule.
+    pub promises: Vec<PromiseTraceView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct HostFunctionGasView {
+    pub cost: String,
+    #[serde(with = "dec_format")]
+    pub gas_used: Gas,
+    /// Number of charged units, e.g. calls for base costs and bytes for per
+    /// byte costs.
+    pub count: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PromiseTraceView {
+    pub receiver_id: AccountId,
+    pub actions: Vec<ActionView>,
+    /// Indices of the promises whose results this promise receives.
+    pub depends_on: Vec<u64>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct QueryError {
     pub error: String,
@@ -284,6 +349,15 @@ pub struct ConnectionInfoView {
     pub time_connected_until: i64,
 }
 
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
+pub struct PeerReputationInfoView {
+    pub peer_id: PeerId,
+    pub score: f64,
+    /// Whether the peer is avoided when choosing outbound connections and sync peers.
+    pub low_reputation: bool,
+}
+
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct SnapshotHostInfoView {
@@ -300,6 +374,7 @@ pub enum QueryResponseKind {
     ViewCode(ContractCodeView),
     ViewState(ViewStateResult),
     CallResult(CallResult),
+    CallTrace(CallTraceView),
     AccessKey(AccessKeyView),
     AccessKeyList(AccessKeyList),
 }
@@ -319,6 +394,20 @@ pub enum QueryRequest {
         prefix: StoreKey,
         #[serde(default, skip_serializing_if = "is_false")]
         include_proof: bool,
+        /// Only return keys greater than or equal to this one.
+        #[serde(rename = "start_key_base64", default, skip_serializing_if = "Option::is_none")]
+        start_key: Option<StoreKey>,
+        /// Only return keys strictly less than this one.
+        #[serde(rename = "end_key_base64", default, skip_serializing_if = "Option::is_none")]
+        end_key: Option<StoreKey>,
+        /// Maximum number of values to return.  If not set, all matching
+        /// values are returned at once, subject to the node's state size
+        /// limit.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<std::num::NonZeroU32>,
+        /// Cursor returned with the previous page.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cursor: Option<ViewStateCursor>,
     },
     ViewAccessKey {
         account_id: AccountId,
@@ -333,6 +422,15 @@ pub enum QueryRequest {
         #[serde(rename = "args_base64")]
         args: FunctionArgs,
     },
+    /// Like `CallFunction`, but returns a trace of the execution.  The method
+    /// is executed as if called from a transaction, so it may also write to
+    /// storage and create promises.  Changes are discarded afterwards.
+    TraceCall {
+        account_id: AccountId,
+        method_name: String,
+        #[serde(rename = "args_base64")]
+        args: FunctionArgs,
+    },
 }
 
 fn is_false(v: &bool) -> bool {
@@ -460,6 +558,11 @@ pub struct SnapshotHostsView {
     pub hosts: Vec<SnapshotHostInfoView>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct PeerReputationView {
+    pub peers: Vec<PeerReputationInfoView>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
 pub struct EdgeView {
     pub peer0: PeerId,
@@ -1673,6 +1776,46 @@ impl ExecutionOutcomeWithIdView {
         self.outcome.to_hashes(self.id)
     }
 }
+
+/// Entry of the account activity index: a transaction or a receipt which
+/// touched the account in the block at the given height.
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    PartialEq,
+    Eq,
+    Clone,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct AccountActivityView {
+    pub block_height: BlockHeight,
+    pub id: CryptoHash,
+    pub kind: AccountActivityKind,
+}
+
+/// Continuation cursor for paginated account activity queries.
+///
+/// Clients should treat it as opaque and only pass back values returned by the
+/// previous query.  Internally it holds the height and id of the last returned
+/// entry, in the order of the index key.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(transparent)]
+pub struct AccountActivityCursor(StoreKey);
+
+impl AccountActivityCursor {
+    /// Returns the cursor pointing right after the given entry.
+    pub fn after(entry: &AccountActivityView) -> Self {
+        Self([&entry.block_height.to_be_bytes()[..], entry.id.as_ref()].concat().into())
+    }
+
+    /// Returns the part of the index key following the account prefix.
+    pub fn key(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 #[derive(Clone)]
 pub struct TxStatusView {
     pub execution_outcome: Option<FinalExecutionOutcomeViewEnum>,
@@ -1788,6 +1931,39 @@ pub struct FinalExecutionOutcomeWithReceiptView {
     pub receipts: Vec<ReceiptView>,
 }
 
+/// Result of applying a transaction on top of a block without including it in
+/// the chain.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SimulateTransactionView {
+    /// Hash of the block on top of which the transaction was applied.
+    pub block_hash: CryptoHash,
+    /// Height of the block on top of which the transaction was applied.
+    pub block_height: BlockHeight,
+    /// Error of the transaction if it is invalid, otherwise error of the first
+    /// failed receipt.
+    pub failure: Option<TxExecutionError>,
+    /// Gas burnt by the transaction and by all executed receipts.
+    #[serde(with = "dec_format")]
+    pub gas_burnt: Gas,
+    /// Outcomes of the transaction and of the executed receipts in the order
+    /// of execution.
+    pub outcomes: Vec<SimulatedExecutionOutcomeView>,
+    /// Receipts which were produced but not executed, either because they are
+    /// sent to another shard or because the simulation ran out of blocks.
+    pub pending_receipts: Vec<ReceiptView>,
+    /// Changes to accounts, access keys, contract code and data.
+    pub state_changes: StateChangesView,
+}
+
+/// Outcome of a simulated transaction or receipt. Unlike
+/// [`ExecutionOutcomeWithIdView`] it has no proof, as simulated blocks are
+/// never produced.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SimulatedExecutionOutcomeView {
+    pub id: CryptoHash,
+    pub outcome: ExecutionOutcomeView,
+}
+
 pub mod validator_stake_view {
     pub use super::ValidatorStakeViewV1;
     use crate::types::validator_stake::ValidatorStake;
@@ -2205,7 +2381,7 @@ impl From<StateChangeKind> for StateChangeKindView {
 pub type StateChangesKindsView = Vec<StateChangeKindView>;
 
 /// See crate::types::StateChangeCause for details.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum StateChangeCauseView {
     NotWritableToDisk,
@@ -2250,7 +2426,7 @@ impl From<StateChangeCause> for StateChangeCauseView {
 }
 
 #[serde_as]
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type", content = "change")]
 pub enum StateChangeValueView {
     AccountUpdate {
@@ -2324,7 +2500,7 @@ impl From<StateChangeValue> for StateChangeValueView {
     }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct StateChangeWithCauseView {
     pub cause: StateChangeCauseView,
     #[serde(flatten)]
@@ -2340,6 +2516,22 @@ impl From<StateChangeWithCause> for StateChangeWithCauseView {
 
 pub type StateChangesView = Vec<StateChangeWithCauseView>;
 
+/// A state change together with the value the key had right before it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StateChangeWithPrevValueView {
+    #[serde(flatten)]
+    pub change: StateChangeWithCauseView,
+    /// `None` if the key did not exist before the change.
+    pub prev_value: Option<StateChangeValueView>,
+}
+
+impl From<StateChangeWithPrevValue> for StateChangeWithPrevValueView {
+    fn from(state_change: StateChangeWithPrevValue) -> Self {
+        let StateChangeWithPrevValue { change, prev_value } = state_change;
+        Self { change: change.into(), prev_value: prev_value.map(Into::into) }
+    }
+}
+
 /// Maintenance windows view are a vector of maintenance window.
 pub type MaintenanceWindowsView = Vec<Range<BlockHeight>>;
 
@@ -1,14 +1,20 @@
 use crate::columns::DBKeyType;
 use crate::db::{ColdDB, COLD_HEAD_KEY, HEAD_KEY};
-use crate::trie::TrieRefcountAddition;
+use crate::trie::{RawTrieNode, RawTrieNodeWithSize, TrieRefcountAddition};
 use crate::{metrics, DBCol, DBTransaction, Database, Store, TrieChanges};
 
-use borsh::BorshDeserialize;
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_fmt::StorageKey;
 use near_primitives::block::{Block, BlockHeader, Tip};
 use near_primitives::hash::CryptoHash;
-use near_primitives::shard_layout::ShardLayout;
+use near_primitives::shard_layout::{get_block_shard_uid, ShardLayout};
 use near_primitives::sharding::ShardChunk;
+use near_primitives::transaction::ExecutionOutcomeWithProof;
+use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::BlockHeight;
+use near_primitives::utils::{
+    get_account_activity_entries, get_account_activity_key, get_outcome_id_block_hash,
+};
 use std::collections::HashMap;
 use std::io;
 use strum::IntoEnumIterator;
@@ -163,7 +169,13 @@ pub fn update_cold_head(
     let block_hash_key = store.get_or_err(DBCol::BlockHeight, &height_key)?.as_slice().to_vec();
     let tip_header = &store.get_ser_or_err::<BlockHeader>(DBCol::BlockHeader, &block_hash_key)?;
     let tip = Tip::from_header(tip_header);
+    set_cold_head(cold_db, hot_store, &tip)
+}
 
+/// Sets the cold head to `tip` in the same places as [`update_cold_head`].
+/// Useful when the block header of the new cold head isn't in `hot_store`,
+/// e.g. when backfilling cold storage from [`ColdBlockData`].
+pub fn set_cold_head(cold_db: &ColdDB, hot_store: &Store, tip: &Tip) -> io::Result<()> {
     // Write HEAD to the cold db.
     {
         let mut transaction = DBTransaction::new();
@@ -184,7 +196,7 @@ pub fn update_cold_head(
         transaction.set(DBCol::BlockMisc, COLD_HEAD_KEY.to_vec(), borsh::to_vec(&tip)?);
         hot_store.storage.write(transaction)?;
 
-        crate::metrics::COLD_HEAD_HEIGHT.set(*height as i64);
+        crate::metrics::COLD_HEAD_HEIGHT.set(tip.height as i64);
     }
 
     return Ok(());
@@ -222,6 +234,303 @@ pub fn copy_all_data_to_cold(
     Ok(CopyAllDataToColdStatus::EverythingCopied)
 }
 
+/// Entry of a cold column which doesn’t match the data in hot storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColdStoreMismatch {
+    /// The key exists in hot storage but not in cold storage.
+    Missing { col: DBCol, key: StoreKey },
+    /// The value in cold storage is different from the one in hot storage.
+    Different { col: DBCol, key: StoreKey },
+}
+
+impl std::fmt::Display for ColdStoreMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { col, key } => write!(f, "{col} {}: missing", StorageKey(key)),
+            Self::Different { col, key } => write!(f, "{col} {}: different", StorageKey(key)),
+        }
+    }
+}
+
+/// Result of verifying the cold storage data of a single block.
+#[derive(Debug, Default)]
+pub struct ColdBlockVerification {
+    /// Number of keys checked in cold storage.
+    pub num_checked_keys: usize,
+    /// Whether the values were compared with the ones in hot storage, rather
+    /// than only checked to be present in cold storage.
+    pub compared_with_hot: bool,
+    pub mismatches: Vec<ColdStoreMismatch>,
+}
+
+/// Checks that cold storage holds all the data of the block at `height` that
+/// [`update_cold_db`] copies there.
+///
+/// If the block is still in hot storage, the keys of every cold column are
+/// recomputed from hot storage the same way [`update_cold_db`] computes them
+/// and the values are compared byte for byte with the ones in hot storage.
+/// Otherwise the block is read from cold storage, and the keys which can be
+/// derived from its contents, i.e. the block itself, its new chunks, their
+/// transactions and the outcomes of the block, are checked to be present.
+///
+/// Returns `None` if there is no block at `height` in either storage.  This
+/// is expected for skipped heights, but also happens if the block is missing
+/// together with its height in cold storage, which the caller can only tell
+/// by checking that the blocks it verified link to each other.
+pub fn verify_cold_db(
+    cold_db: &std::sync::Arc<ColdDB>,
+    hot_store: &Store,
+    shard_layout: &ShardLayout,
+    height: &BlockHeight,
+) -> io::Result<Option<ColdBlockVerification>> {
+    let _span = tracing::debug_span!(target: "store", "verify cold db", height = height);
+
+    let mut store_with_cache = StoreWithCache { store: hot_store, cache: StoreCache::new() };
+
+    let block_hash = match store_with_cache.get(DBCol::BlockHeight, &height.to_le_bytes())? {
+        Some(block_hash) if store_with_cache.get(DBCol::Block, &block_hash)?.is_some() => {
+            block_hash
+        }
+        _ => return verify_cold_block_from_cold(cold_db, shard_layout, height),
+    };
+
+    let mut result = ColdBlockVerification { compared_with_hot: true, ..Default::default() };
+    let key_type_to_keys = get_keys_from_store(&mut store_with_cache, shard_layout, height)?;
+    for col in DBCol::iter() {
+        if !col.is_cold() {
+            continue;
+        }
+        for key in combine_keys(&key_type_to_keys, &col.key_type()) {
+            let Some(hot_value) = store_with_cache.get(col, &key)? else {
+                continue;
+            };
+            let cold_value = if col.is_rc() {
+                cold_db.get_with_rc_stripped(col, &key)?
+            } else {
+                cold_db.get_raw_bytes(col, &key)?
+            };
+            result.num_checked_keys += 1;
+            match cold_value {
+                None => result.mismatches.push(ColdStoreMismatch::Missing { col, key }),
+                Some(cold_value) if cold_value.as_slice() != hot_value.as_slice() => {
+                    result.mismatches.push(ColdStoreMismatch::Different { col, key })
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(Some(result))
+}
+
+/// Checks that cold storage has the data which can be derived from the cold
+/// copy of the block at `height`, for blocks no longer in hot storage.
+fn verify_cold_block_from_cold(
+    cold_db: &std::sync::Arc<ColdDB>,
+    shard_layout: &ShardLayout,
+    height: &BlockHeight,
+) -> io::Result<Option<ColdBlockVerification>> {
+    let cold_store = Store::new(cold_db.clone());
+    let Some(block_hash) = cold_store.get(DBCol::BlockHeight, &height.to_le_bytes())? else {
+        return Ok(None);
+    };
+
+    let mut result = ColdBlockVerification::default();
+    let mut check = |col: DBCol, key: StoreKey| -> io::Result<bool> {
+        result.num_checked_keys += 1;
+        let exists = cold_store.exists(col, &key)?;
+        if !exists {
+            result.mismatches.push(ColdStoreMismatch::Missing { col, key });
+        }
+        Ok(exists)
+    };
+    let Some(block) = cold_store.get_ser::<Block>(DBCol::Block, &block_hash)? else {
+        check(DBCol::Block, block_hash.to_vec())?;
+        return Ok(Some(result));
+    };
+    check(DBCol::Block, block_hash.to_vec())?;
+    for chunk_header in block.chunks().iter() {
+        if chunk_header.height_included() != *height {
+            continue;
+        }
+        let chunk_hash = chunk_header.chunk_hash();
+        let Some(chunk) = cold_store.get_ser::<ShardChunk>(DBCol::Chunks, chunk_hash.as_bytes())?
+        else {
+            check(DBCol::Chunks, chunk_hash.as_bytes().to_vec())?;
+            continue;
+        };
+        check(DBCol::Chunks, chunk_hash.as_bytes().to_vec())?;
+        for transaction in chunk.transactions() {
+            check(DBCol::Transactions, transaction.get_hash().as_bytes().to_vec())?;
+        }
+    }
+    debug_assert_eq!(
+        DBCol::TransactionResultForBlock.key_type(),
+        &[DBKeyType::OutcomeId, DBKeyType::BlockHash]
+    );
+    for shard_id in 0..shard_layout.num_shards() {
+        let outcome_ids = cold_store.get_ser::<Vec<CryptoHash>>(
+            DBCol::OutcomeIds,
+            &join_two_keys(&block_hash, &shard_id.to_le_bytes()),
+        )?;
+        for outcome_id in outcome_ids.unwrap_or_default() {
+            check(
+                DBCol::TransactionResultForBlock,
+                join_two_keys(outcome_id.as_bytes(), &block_hash),
+            )?;
+        }
+    }
+    Ok(Some(result))
+}
+
+/// Data of a single height in the cold columns, exported from the store of
+/// one node to be imported into the cold storage of another one.
+///
+/// This allows bootstrapping cold storage of a split storage archival node
+/// without a hot archival database holding the full history: the data can be
+/// exported block by block with [`get_cold_block_data`] from any node which
+/// has the blocks and imported with [`write_cold_block_data`].
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ColdBlockData {
+    pub height: BlockHeight,
+    /// Tip of the block at `height` or `None` if the height was skipped.
+    pub tip: Option<Tip>,
+    /// Keys and values of every cold column, by column name.  Values of
+    /// reference counted columns don’t include the reference count.
+    pub columns: Vec<(String, Vec<(StoreKey, Vec<u8>)>)>,
+}
+
+/// Collects the data which [`update_cold_db`] would copy to cold storage for
+/// the block at `height`, using the same column mapping.
+///
+/// `store` can be any store holding the block, e.g. the hot store of an RPC
+/// or archival node or the split store of a split storage node.  Fails if the
+/// block at `height` has already been garbage collected.
+pub fn get_cold_block_data(
+    store: &Store,
+    shard_layout: &ShardLayout,
+    height: &BlockHeight,
+) -> io::Result<ColdBlockData> {
+    let _span = tracing::debug_span!(target: "store", "get cold block data", height = height);
+
+    let mut store_with_cache = StoreWithCache { store, cache: StoreCache::new() };
+
+    let Some(block_hash) = store_with_cache.get(DBCol::BlockHeight, &height.to_le_bytes())? else {
+        return Ok(ColdBlockData { height: *height, tip: None, columns: vec![] });
+    };
+    let header = store_with_cache.get_ser_or_err::<BlockHeader>(DBCol::BlockHeader, &block_hash)?;
+
+    let key_type_to_keys = get_keys_from_store(&mut store_with_cache, shard_layout, height)?;
+    let mut columns = vec![];
+    for col in DBCol::iter() {
+        if !col.is_cold() {
+            continue;
+        }
+        let mut entries = vec![];
+        for key in combine_keys(&key_type_to_keys, &col.key_type()) {
+            if let Some(value) = store_with_cache.get(col, &key)? {
+                entries.push((key, value));
+            }
+        }
+        columns.push((<&str>::from(col).to_string(), entries));
+    }
+    Ok(ColdBlockData { height: *height, tip: Some(Tip::from_header(&header)), columns })
+}
+
+/// Collects the `State` entries of the tries of all shards after the block
+/// `block_hash`, passing them to `on_batch` in batches of ~`batch_size` bytes.
+///
+/// Cold storage only gets the trie nodes inserted by every block it copies,
+/// so to read state from cold storage bootstrapped with blocks imported from
+/// [`get_cold_block_data`], it has to be seeded with the state before the
+/// first imported block first.  The batches can be written with
+/// [`write_cold_block_data`] as the `State` column.
+pub fn get_cold_state_data(
+    store: &Store,
+    shard_layout: &ShardLayout,
+    block_hash: &CryptoHash,
+    batch_size: usize,
+    mut on_batch: impl FnMut(Vec<(StoreKey, Vec<u8>)>) -> io::Result<()>,
+) -> io::Result<()> {
+    let _span = tracing::debug_span!(target: "store", "get cold state data", %block_hash);
+
+    let mut batch = vec![];
+    let mut batch_bytes = 0;
+    for shard_uid in shard_layout.get_shard_uids() {
+        let chunk_extra = store
+            .get_ser::<ChunkExtra>(DBCol::ChunkExtra, &get_block_shard_uid(block_hash, &shard_uid))?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no chunk extra of shard {shard_uid} at block {block_hash}"),
+                )
+            })?;
+        let shard_uid_key = shard_uid.to_bytes();
+        // Hashes of the trie nodes and values to read, with whether it's a node.
+        let mut stack = vec![];
+        if *chunk_extra.state_root() != CryptoHash::default() {
+            stack.push((*chunk_extra.state_root(), true));
+        }
+        while let Some((hash, is_node)) = stack.pop() {
+            let key = join_two_keys(&shard_uid_key, hash.as_bytes());
+            let value = store.get(DBCol::State, &key)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("missing state {hash} of shard {shard_uid}"),
+                )
+            })?;
+            if is_node {
+                match RawTrieNodeWithSize::try_from_slice(&value)?.node {
+                    RawTrieNode::Leaf(_, value) => stack.push((value.hash, false)),
+                    RawTrieNode::BranchNoValue(children) => {
+                        stack.extend(children.iter().map(|(_, child)| (*child, true)))
+                    }
+                    RawTrieNode::BranchWithValue(value, children) => {
+                        stack.push((value.hash, false));
+                        stack.extend(children.iter().map(|(_, child)| (*child, true)));
+                    }
+                    RawTrieNode::Extension(_, child) => stack.push((child, true)),
+                }
+            }
+            batch_bytes += key.len() + value.len();
+            batch.push((key, value.to_vec()));
+            if batch_bytes >= batch_size {
+                on_batch(std::mem::take(&mut batch))?;
+                batch_bytes = 0;
+            }
+        }
+    }
+    if !batch.is_empty() {
+        on_batch(batch)?;
+    }
+    Ok(())
+}
+
+/// Writes data exported with [`get_cold_block_data`] to cold storage, in the
+/// same way [`update_cold_db`] does.
+///
+/// Doesn’t update the cold head, see [`set_cold_head`].
+pub fn write_cold_block_data(cold_db: &ColdDB, data: ColdBlockData) -> io::Result<()> {
+    let _span =
+        tracing::debug_span!(target: "store", "write cold block data", height = data.height);
+
+    for (col_name, entries) in data.columns {
+        let col = DBCol::iter()
+            .find(|col| col.is_cold() && <&str>::from(*col) == col_name)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{col_name} is not a cold column"),
+                )
+            })?;
+        let mut transaction = DBTransaction::new();
+        for (key, value) in entries {
+            rc_aware_set(&mut transaction, col, key, value);
+        }
+        cold_db.write(transaction)?;
+    }
+    Ok(())
+}
+
 pub fn test_cold_genesis_update(cold_db: &ColdDB, hot_store: &Store) -> io::Result<()> {
     let mut store_with_cache = StoreWithCache { store: hot_store, cache: StoreCache::new() };
     for col in DBCol::iter() {
@@ -340,6 +649,30 @@ fn get_keys_from_store(
                 DBKeyType::ChunkHash => {
                     chunks.iter().map(|c| c.chunk_hash().as_bytes().to_vec()).collect()
                 }
+                DBKeyType::AccountActivity => {
+                    let mut keys = vec![];
+                    for (chunk_header, chunk) in block.chunks().iter().zip(chunks.iter()) {
+                        if chunk_header.height_included() != *height {
+                            continue;
+                        }
+                        let local_receipt_id =
+                            |tx_hash: &CryptoHash| -> io::Result<Option<CryptoHash>> {
+                                let outcome: Option<ExecutionOutcomeWithProof> = store.get_ser(
+                                    DBCol::TransactionResultForBlock,
+                                    &get_outcome_id_block_hash(tx_hash, block.hash()),
+                                )?;
+                                Ok(outcome.and_then(|outcome| {
+                                    outcome.outcome.receipt_ids.first().copied()
+                                }))
+                            };
+                        for (account_id, id, _) in
+                            get_account_activity_entries(chunk, local_receipt_id)?
+                        {
+                            keys.push(get_account_activity_key(account_id, *height, &id));
+                        }
+                    }
+                    keys
+                }
                 DBKeyType::OutcomeId => {
                     debug_assert_eq!(
                         DBCol::OutcomeIds.key_type(),
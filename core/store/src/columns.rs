@@ -277,6 +277,16 @@ pub enum DBCol {
     /// - *Rows*: arbitrary string, see `crate::db::FLAT_STATE_VALUES_INLINING_MIGRATION_STATUS_KEY` for example
     /// - *Column type*: arbitrary bytes
     Misc,
+    /// Index of transactions and receipts which touched an account.  Only
+    /// maintained when `save_account_activity` is enabled in the client config.
+    /// - *Rows*: AccountId || ',' || BlockHeight (big-endian) || transaction or receipt hash,
+    ///   see `near_primitives::utils::get_account_activity_key`
+    /// - *Column type*: AccountActivityKind
+    AccountActivity,
+    /// Reputation scores of the peers, learned from their behaviour.
+    /// - *Rows*: single row (empty row name)
+    /// - *Content type*: Vec of [network_primitives::types::PeerScore]
+    PeerReputation,
     /// Column to store data for Epoch Sync.
     /// Does not contain data for genesis epoch.
     /// - *Rows*: `epoch_id`
@@ -292,7 +302,8 @@ pub enum DBCol {
 /// Currently only used in cold storage continuous migration.
 #[derive(PartialEq, Copy, Clone, Debug, Hash, Eq, strum::EnumIter)]
 pub enum DBKeyType {
-    /// Empty row name. Used in DBCol::LastComponentNonce and DBCol::RecentOutboundConnections
+    /// Empty row name. Used in DBCol::LastComponentNonce, DBCol::RecentOutboundConnections
+    /// and DBCol::PeerReputation
     Empty,
     /// Set of predetermined strings. Used, for example, in DBCol::BlockMisc
     StringLiteral,
@@ -313,6 +324,9 @@ pub enum DBKeyType {
     ReceiptHash,
     TransactionHash,
     OutcomeId,
+    /// Whole key of DBCol::AccountActivity.  Enumerating account ids, heights
+    /// and hashes separately would produce too many combinations.
+    AccountActivity,
     ContractCacheKey,
     PartId,
     ColumnId,
@@ -428,7 +442,8 @@ impl DBCol {
             | DBCol::StateChangesForSplitStates
             | DBCol::StateHeaders
             | DBCol::TransactionResultForBlock
-            | DBCol::Transactions => true,
+            | DBCol::Transactions
+            | DBCol::AccountActivity => true,
 
             // TODO
             DBCol::ChallengedBlocks => false,
@@ -456,6 +471,7 @@ impl DBCol {
             | DBCol::BlockHeight
             | DBCol::_Peers
             | DBCol::RecentOutboundConnections
+            | DBCol::PeerReputation
             | DBCol::BlockMerkleTree
             | DBCol::AccountAnnouncements
             | DBCol::EpochLightClientBlocks
@@ -547,6 +563,8 @@ impl DBCol {
             DBCol::FlatStateChanges => &[DBKeyType::ShardUId, DBKeyType::BlockHash],
             DBCol::FlatStateDeltaMetadata => &[DBKeyType::ShardUId, DBKeyType::BlockHash],
             DBCol::FlatStorageStatus => &[DBKeyType::ShardUId],
+            DBCol::AccountActivity => &[DBKeyType::AccountActivity],
+            DBCol::PeerReputation => &[DBKeyType::Empty],
             #[cfg(feature = "new_epoch_sync")]
             DBCol::EpochSyncInfo => &[DBKeyType::EpochId],
         }
@@ -22,6 +22,11 @@ pub struct StoreConfig {
     /// database.
     pub path: Option<std::path::PathBuf>,
 
+    /// Database engine storing the data.  Existing databases are not
+    /// converted when this changes; use `neard database copy-database` to
+    /// copy the data into a database with another backend.
+    pub backend: DbBackend,
+
     /// Collect internal storage layer statistics.
     /// Minor performance impact is expected.
     pub enable_statistics: bool,
@@ -72,6 +77,28 @@ pub struct StoreConfig {
     pub load_mem_tries_for_shards: Vec<ShardUId>,
     /// If true, load mem tries for all shards; this has priority over `load_mem_tries_for_shards`.
     pub load_mem_tries_for_all_shards: bool,
+    /// Directory where snapshots of the in-memory tries are written on
+    /// graceful shutdown. On the next start, a snapshot taken at the current
+    /// flat storage head is loaded instead of rebuilding the tries from flat
+    /// storage; stale or corrupted snapshots are ignored. If relative,
+    /// resolved relative to the database directory. Snapshots are disabled
+    /// if not set.
+    pub mem_trie_snapshot_path: Option<std::path::PathBuf>,
+    /// If true, nodes created in the in-memory tries by applying chunks reuse
+    /// identical nodes created earlier, e.g. when the same chunk is applied
+    /// on several forks. Costs an index entry per such node.
+    pub mem_trie_dedup_nodes: bool,
+    /// If set, an in-memory trie is compacted once the fraction of its memory
+    /// wasted on freed nodes exceeds this value after garbage collection.
+    /// Compaction copies all live nodes into a new arena, so until it
+    /// finishes the trie takes up to twice as much memory and its garbage
+    /// collection is deferred. This should be well above the usual level of
+    /// fragmentation, e.g. 0.5. Compaction is disabled if not set.
+    pub mem_trie_compaction_threshold: Option<f64>,
+    /// Number of in-memory trie nodes copied by a compaction at each garbage
+    /// collection, i.e. once per block. Chunks of the shard can't be applied
+    /// while the nodes are copied.
+    pub mem_trie_compaction_nodes_per_step: usize,
 
     /// Path where to create RocksDB checkpoints during database migrations or
     /// `false` to disable that feature.
@@ -121,6 +148,9 @@ pub struct StoreConfig {
 
     // TODO (#9989): To be phased out in favor of state_snapshot_config
     pub state_snapshot_compaction_enabled: bool,
+
+    /// Background verification of flat storage against the trie.
+    pub flat_storage_verification: FlatStorageVerificationConfig,
 }
 
 /// Config used to control state snapshot creation. This is used for state sync and resharding.
@@ -146,6 +176,61 @@ pub enum StateSnapshotType {
     EveryEpoch,
 }
 
+/// Config of the background job which compares flat storage with the trie,
+/// see `near_store::flat::FlatStorageVerifierHandle`.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct FlatStorageVerificationConfig {
+    /// Whether to run the verification.  Disabled by default since it adds
+    /// trie reads on top of the regular block processing.
+    pub enabled: bool,
+    /// Limit on the number of keys verified per second, across all shards.
+    pub max_keys_per_second: u64,
+    /// Number of keys verified in one batch.  Flat head doesn't move while
+    /// a batch is verified.
+    pub batch_size: usize,
+    /// Number of batches verified at random positions of the state before
+    /// going through all of it in every pass.  Sampling finds a widespread
+    /// corruption much faster than going through the state in order.
+    pub num_sampled_batches: u64,
+}
+
+impl Default for FlatStorageVerificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_keys_per_second: 1000,
+            batch_size: 1000,
+            num_sampled_batches: 100,
+        }
+    }
+}
+
+/// Database engine used for the on-disk storage.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::Display,
+    strum::EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DbBackend {
+    /// RocksDB, the default.
+    #[default]
+    RocksDB,
+    /// redb, a B-tree based embedded database stored in a single file.  It
+    /// doesn’t support opening the database from more than one process at
+    /// a time, even in read-only mode.
+    Redb,
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum MigrationSnapshot {
@@ -216,6 +301,7 @@ impl Default for StoreConfig {
     fn default() -> Self {
         Self {
             path: None,
+            backend: DbBackend::RocksDB,
             enable_statistics: false,
             enable_statistics_export: true,
 
@@ -271,6 +357,10 @@ impl Default for StoreConfig {
 
             load_mem_tries_for_shards: vec![ShardUId { shard_id: 3, version: 1 }],
             load_mem_tries_for_all_shards: false,
+            mem_trie_snapshot_path: None,
+            mem_trie_dedup_nodes: false,
+            mem_trie_compaction_threshold: None,
+            mem_trie_compaction_nodes_per_step: 50_000,
 
             migration_snapshot: Default::default(),
 
@@ -293,6 +383,8 @@ impl Default for StoreConfig {
 
             // TODO: To be phased out in favor of state_snapshot_config
             state_snapshot_compaction_enabled: false,
+
+            flat_storage_verification: Default::default(),
         }
     }
 }
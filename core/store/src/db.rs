@@ -5,6 +5,7 @@ use std::io;
 pub(crate) mod rocksdb;
 
 mod colddb;
+mod redb;
 mod splitdb;
 
 pub mod refcount;
@@ -14,7 +15,8 @@ mod testdb;
 mod database_tests;
 
 pub use self::colddb::ColdDB;
-pub use self::rocksdb::RocksDB;
+pub use self::redb::RedbDB;
+pub use self::rocksdb::{RocksDB, DEFAULT_COMPRESSION_DICT_SIZE};
 pub use self::splitdb::SplitDB;
 
 pub use self::slice::DBSlice;
@@ -38,6 +40,8 @@ pub const STATE_SNAPSHOT_KEY: &[u8; 18] = b"STATE_SNAPSHOT_KEY";
 // `DBCol::Misc` keys
 pub const FLAT_STATE_VALUES_INLINING_MIGRATION_STATUS_KEY: &[u8] =
     b"FLAT_STATE_VALUES_INLINING_MIGRATION_STATUS";
+/// Followed by the `ShardUId` of the verified shard.
+pub const FLAT_STORAGE_VERIFICATION_STATUS_KEY_PREFIX: &[u8] = b"FLAT_STORAGE_VERIFICATION_STATUS";
 
 #[derive(Default, Debug)]
 pub struct DBTransaction {
@@ -6,21 +6,23 @@ mod tests {
     use std::sync::Arc;
 
     use crate::{
-        db::{DBTransaction, Database, TestDB},
-        DBCol, NodeStorage,
+        db::{DBTransaction, Database, RedbDB, TestDB},
+        DBCol, Mode, NodeStorage, StoreConfig,
     };
 
-    // Returns test & rocksDB databases.
-    fn test_and_rocksdb() -> Vec<Arc<dyn Database>> {
+    // Returns test, rocksDB & redb databases.
+    fn test_rocksdb_and_redb() -> Vec<Arc<dyn Database>> {
         let (_tmp_dir, opener) = NodeStorage::test_opener();
         let store = opener.open().unwrap().get_hot_store();
-        vec![TestDB::new(), store.storage.clone()]
+        let redb_dir = tempfile::tempdir().unwrap();
+        let redb = RedbDB::open(redb_dir.path(), &StoreConfig::test_config(), Mode::Create);
+        vec![TestDB::new(), store.storage.clone(), Arc::new(redb.unwrap())]
     }
 
     /// Tests the behavior of the iterators. Iterators don't really work over cold storage, so we're not testing it here.
     #[test]
     fn test_db_iter() {
-        for db in test_and_rocksdb() {
+        for db in test_rocksdb_and_redb() {
             let mut transaction = DBTransaction::new();
             transaction.insert(DBCol::Block, "a".into(), "val_a".into());
             transaction.insert(DBCol::Block, "aa".into(), "val_aa".into());
@@ -42,4 +44,44 @@ mod tests {
             assert_eq!(keys, vec!["aa", "aa1"]);
         }
     }
+
+    /// Tests reference counted columns and deleting ranges of keys.
+    #[test]
+    fn test_db_refcount_and_delete_range() {
+        for db in test_rocksdb_and_redb() {
+            let rc = |rc: i64| rc.to_le_bytes();
+            let mut transaction = DBTransaction::new();
+            transaction.update_refcount(
+                DBCol::State,
+                "a".into(),
+                [&b"val_a"[..], &rc(2)[..]].concat(),
+            );
+            transaction.update_refcount(
+                DBCol::State,
+                "b".into(),
+                [&b"val_b"[..], &rc(1)[..]].concat(),
+            );
+            transaction.insert(DBCol::Block, "a".into(), "val_a".into());
+            transaction.insert(DBCol::Block, "b".into(), "val_b".into());
+            transaction.insert(DBCol::Block, "c".into(), "val_c".into());
+            db.write(transaction).unwrap();
+
+            let mut transaction = DBTransaction::new();
+            transaction.update_refcount(DBCol::State, "a".into(), rc(-1).to_vec());
+            transaction.update_refcount(DBCol::State, "b".into(), rc(-1).to_vec());
+            transaction.delete_range(DBCol::Block, "a".into(), "c".into());
+            db.write(transaction).unwrap();
+
+            let get = |col, key: &str| {
+                db.get_with_rc_stripped(col, key.as_bytes()).unwrap().map(|value| value.to_vec())
+            };
+            assert_eq!(get(DBCol::State, "a"), Some(b"val_a".to_vec()));
+            assert_eq!(get(DBCol::State, "b"), None);
+            let keys: Vec<_> = db
+                .iter(DBCol::Block)
+                .map(|data| String::from_utf8(data.unwrap().0.to_vec()).unwrap())
+                .collect();
+            assert_eq!(keys, vec!["c"]);
+        }
+    }
 }
@@ -0,0 +1,387 @@
+use crate::config::Mode;
+use crate::db::{
+    refcount, DBIterator, DBIteratorItem, DBOp, DBSlice, DBTransaction, Database, StatsValue,
+};
+use crate::{metadata, metrics, DBCol, StoreConfig, StoreStatistics};
+use ::redb::{
+    Durability, ReadOnlyTable, ReadTransaction, ReadableTable, ReadableTableMetadata,
+    TableDefinition, TableError,
+};
+use std::io;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::RwLock;
+use strum::IntoEnumIterator;
+
+/// Name of the redb database file inside of the database directory.
+///
+/// redb keeps all the data in a single file.  We still treat the database
+/// path as a directory, like with RocksDB, so that the paths in the
+/// configuration mean the same for both backends.
+const DB_FILE_NAME: &str = "data.redb";
+
+/// Type of the redb tables.  Each column is stored in a separate table named
+/// after the column.
+type Table = TableDefinition<'static, &'static [u8], &'static [u8]>;
+
+/// Database backed by [redb](https://github.com/cberner/redb).
+///
+/// Every [`Database::write`] runs in a single redb write transaction.  redb
+/// has no merge operator, so reference counted columns are updated by reading
+/// the current value and writing the merged one within that transaction.
+/// Writes are only guaranteed to be on disk after [`Database::flush`], which
+/// matches how we use RocksDB without syncing the write-ahead log.
+pub struct RedbDB {
+    /// The database.  Compaction needs exclusive access to it, everything
+    /// else only uses it to start transactions.
+    db: RwLock<::redb::Database>,
+
+    /// Whether the database was opened in read-only mode.  redb has no such
+    /// mode, so writes are rejected by us.
+    read_only: bool,
+}
+
+impl RedbDB {
+    /// Opens the database.
+    ///
+    /// `path` specifies the database directory.  Like with
+    /// [`crate::db::RocksDB::open`], it’s assumed that it has been resolved
+    /// based on configuration in `store_config`.
+    ///
+    /// The database is created if it doesn’t exist unless `mode` forbids
+    /// that.  Note that redb locks the database file, so even in read-only mode
+    /// the database can’t be opened while another process has it open.
+    pub fn open(path: &Path, store_config: &StoreConfig, mode: Mode) -> io::Result<Self> {
+        let file = path.join(DB_FILE_NAME);
+        let mut builder = ::redb::Builder::new();
+        builder.set_cache_size(store_config.col_state_cache_size.as_u64() as usize);
+        let db = if mode.can_create() {
+            std::fs::create_dir_all(path)?;
+            builder.create(&file)
+        } else {
+            builder.open(&file)
+        }
+        .map_err(into_other)?;
+        Ok(Self { db: RwLock::new(db), read_only: mode.read_only() })
+    }
+
+    /// Returns metadata of the database or `None` if the db doesn’t exist.
+    pub(crate) fn get_metadata(
+        path: &Path,
+        config: &StoreConfig,
+    ) -> io::Result<Option<metadata::DbMetadata>> {
+        if !path.join(DB_FILE_NAME).is_file() {
+            return Ok(None);
+        }
+        let db = Self::open(path, config, Mode::ReadOnly)?;
+        Some(metadata::DbMetadata::read(&db)).transpose()
+    }
+
+    fn begin_read(&self) -> io::Result<ReadTransaction> {
+        self.db.read().unwrap().begin_read().map_err(into_other)
+    }
+
+    /// Opens the table of the given column for reading.
+    ///
+    /// Tables are created on first write, so a missing table is the same as
+    /// an empty one and `None` is returned for it.
+    fn open_table(
+        transaction: &ReadTransaction,
+        col: DBCol,
+    ) -> io::Result<Option<ReadOnlyTable<&'static [u8], &'static [u8]>>> {
+        match transaction.open_table(table(col)) {
+            Ok(table) => Ok(Some(table)),
+            Err(TableError::TableDoesNotExist(_)) => Ok(None),
+            Err(err) => Err(into_other(err)),
+        }
+    }
+
+    /// Iterates over raw key-value pairs of the given column within the
+    /// bounds.
+    fn iter_raw_bytes_internal<'a>(
+        &'a self,
+        col: DBCol,
+        lower_bound: Bound<&[u8]>,
+        upper_bound: Bound<&[u8]>,
+    ) -> DBIterator<'a> {
+        let range = self.begin_read().and_then(|transaction| {
+            let Some(table) = Self::open_table(&transaction, col)? else {
+                return Ok(None);
+            };
+            table.range::<&[u8]>((lower_bound, upper_bound)).map(Some).map_err(into_other)
+        });
+        match range {
+            Ok(Some(range)) => Box::new(range.map(|item| -> DBIteratorItem {
+                let (key, value) = item.map_err(into_other)?;
+                Ok((key.value().into(), value.value().into()))
+            })),
+            Ok(None) => Box::new(std::iter::empty()),
+            Err(err) => Box::new(std::iter::once(Err(err))),
+        }
+    }
+
+    /// Copies all columns into a new database at given directory.
+    ///
+    /// All columns are read from a single read transaction, so the copy is
+    /// consistent even if the database is written to in the meantime.
+    fn copy_to(&self, path: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(path)?;
+        let target = ::redb::Database::create(path.join(DB_FILE_NAME)).map_err(into_other)?;
+        let source = self.begin_read()?;
+        for col in DBCol::iter() {
+            let Some(source_table) = Self::open_table(&source, col)? else {
+                continue;
+            };
+            let transaction = target.begin_write().map_err(into_other)?;
+            {
+                let mut target_table = transaction.open_table(table(col)).map_err(into_other)?;
+                for item in source_table.iter().map_err(into_other)? {
+                    let (key, value) = item.map_err(into_other)?;
+                    target_table.insert(key.value(), value.value()).map_err(into_other)?;
+                }
+            }
+            transaction.commit().map_err(into_other)?;
+        }
+        Ok(())
+    }
+}
+
+impl Database for RedbDB {
+    fn get_raw_bytes(&self, col: DBCol, key: &[u8]) -> io::Result<Option<DBSlice<'_>>> {
+        let timer =
+            metrics::DATABASE_OP_LATENCY_HIST.with_label_values(&["get", col.into()]).start_timer();
+        let transaction = self.begin_read()?;
+        let Some(table) = Self::open_table(&transaction, col)? else {
+            return Ok(None);
+        };
+        let result = table
+            .get(key)
+            .map_err(into_other)?
+            .map(|value| DBSlice::from_vec(value.value().to_vec()));
+        timer.observe_duration();
+        Ok(result)
+    }
+
+    fn iter_raw_bytes(&self, col: DBCol) -> DBIterator {
+        self.iter_raw_bytes_internal(col, Bound::Unbounded, Bound::Unbounded)
+    }
+
+    fn iter(&self, col: DBCol) -> DBIterator {
+        refcount::iter_with_rc_logic(col, self.iter_raw_bytes(col))
+    }
+
+    fn iter_prefix<'a>(&'a self, col: DBCol, key_prefix: &'a [u8]) -> DBIterator<'a> {
+        let iter = self
+            .iter_raw_bytes_internal(col, Bound::Included(key_prefix), Bound::Unbounded)
+            .take_while(move |item| match item {
+                Ok((key, _)) => key.starts_with(key_prefix),
+                Err(_) => true,
+            });
+        refcount::iter_with_rc_logic(col, iter)
+    }
+
+    fn iter_range<'a>(
+        &'a self,
+        col: DBCol,
+        lower_bound: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> DBIterator<'a> {
+        let lower = lower_bound.map_or(Bound::Unbounded, Bound::Included);
+        let upper = upper_bound.map_or(Bound::Unbounded, Bound::Excluded);
+        refcount::iter_with_rc_logic(col, self.iter_raw_bytes_internal(col, lower, upper))
+    }
+
+    fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+        if self.read_only {
+            return Err(other_error("database is opened in read-only mode".to_string()));
+        }
+        let mut redb_transaction = self.db.read().unwrap().begin_write().map_err(into_other)?;
+        redb_transaction.set_durability(Durability::Eventual);
+        for op in transaction.ops {
+            match op {
+                DBOp::Set { col, key, value } => {
+                    let mut table = redb_transaction.open_table(table(col)).map_err(into_other)?;
+                    table.insert(key.as_slice(), value.as_slice()).map_err(into_other)?;
+                }
+                DBOp::Insert { col, key, value } => {
+                    let mut table = redb_transaction.open_table(table(col)).map_err(into_other)?;
+                    if cfg!(debug_assertions) {
+                        if let Some(old_value) = table.get(key.as_slice()).map_err(into_other)? {
+                            super::assert_no_overwrite(col, &key, &value, old_value.value())
+                        }
+                    }
+                    table.insert(key.as_slice(), value.as_slice()).map_err(into_other)?;
+                }
+                DBOp::UpdateRefcount { col, key, value } => {
+                    let mut table = redb_transaction.open_table(table(col)).map_err(into_other)?;
+                    let merged = {
+                        let existing = table.get(key.as_slice()).map_err(into_other)?;
+                        let existing = existing.as_ref().map(|existing| existing.value());
+                        refcount::refcount_merge(existing, [value.as_slice()])
+                    };
+                    if merged.is_empty() {
+                        table.remove(key.as_slice()).map_err(into_other)?;
+                    } else {
+                        debug_assert!(
+                            refcount::decode_value_with_rc(&merged).1 > 0,
+                            "Inserting value with non-positive refcount"
+                        );
+                        table.insert(key.as_slice(), merged.as_slice()).map_err(into_other)?;
+                    }
+                }
+                DBOp::Delete { col, key } => {
+                    let mut table = redb_transaction.open_table(table(col)).map_err(into_other)?;
+                    table.remove(key.as_slice()).map_err(into_other)?;
+                }
+                DBOp::DeleteAll { col } => {
+                    redb_transaction.delete_table(table(col)).map_err(into_other)?;
+                }
+                DBOp::DeleteRange { col, from, to } => {
+                    let mut table = redb_transaction.open_table(table(col)).map_err(into_other)?;
+                    table
+                        .retain_in::<&[u8], _>(from.as_slice()..to.as_slice(), |_, _| false)
+                        .map_err(into_other)?;
+                }
+            }
+        }
+        redb_transaction.commit().map_err(into_other)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        // Committing a durable transaction makes all the previously committed
+        // transactions durable as well.
+        let mut transaction = self.db.read().unwrap().begin_write().map_err(into_other)?;
+        transaction.set_durability(Durability::Immediate);
+        transaction.commit().map_err(into_other)
+    }
+
+    /// Compacts the database file, releasing free pages at its end.
+    ///
+    /// This fails if there are any live read transactions, which includes
+    /// iterators over the database.
+    fn compact(&self) -> io::Result<()> {
+        tracing::info!(target: "db", "Compact redb database");
+        self.db.write().unwrap().compact().map_err(into_other)?;
+        Ok(())
+    }
+
+    /// Returns the number of entries, the number of bytes of the stored keys
+    /// and values and the number of bytes of free space in the pages of every
+    /// column.
+    fn get_store_statistics(&self) -> Option<StoreStatistics> {
+        let mut num_entries = Vec::new();
+        let mut stored_bytes = Vec::new();
+        let mut fragmented_bytes = Vec::new();
+        let transaction = self.begin_read().ok()?;
+        for col in DBCol::iter() {
+            let Ok(Some(table)) = Self::open_table(&transaction, col) else {
+                continue;
+            };
+            if let (Ok(len), Ok(stats)) = (table.len(), table.stats()) {
+                num_entries.push(StatsValue::ColumnValue(col, len as i64));
+                stored_bytes.push(StatsValue::ColumnValue(col, stats.stored_bytes() as i64));
+                fragmented_bytes
+                    .push(StatsValue::ColumnValue(col, stats.fragmented_bytes() as i64));
+            }
+        }
+        if num_entries.is_empty() {
+            return None;
+        }
+        Some(StoreStatistics {
+            data: vec![
+                ("redb.num-entries".to_string(), num_entries),
+                ("redb.stored-bytes".to_string(), stored_bytes),
+                ("redb.fragmented-bytes".to_string(), fragmented_bytes),
+            ],
+        })
+    }
+
+    /// Copies the database into a new one in provided directory.
+    ///
+    /// Unlike RocksDB checkpoints, which hard link the immutable files, this
+    /// copies all the data.
+    fn create_checkpoint(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let _span =
+            tracing::info_span!(target: "state_snapshot", "create_checkpoint", ?path).entered();
+        if path.exists() {
+            anyhow::bail!("checkpoint path {} already exists", path.display());
+        }
+        self.copy_to(path)?;
+        Ok(())
+    }
+}
+
+fn table(col: DBCol) -> Table {
+    TableDefinition::new(<&str>::from(col))
+}
+
+fn other_error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, msg)
+}
+
+fn into_other(error: impl Into<::redb::Error>) -> io::Error {
+    other_error(error.into().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{Database, RedbDB};
+    use crate::{DBCol, DbBackend, Mode, NodeStorage, StoreConfig};
+
+    fn redb_config() -> StoreConfig {
+        StoreConfig { backend: DbBackend::Redb, ..StoreConfig::test_config() }
+    }
+
+    #[test]
+    fn test_open_and_reopen() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let config = redb_config();
+        let opener = NodeStorage::opener(tmpdir.path(), false, &config, None);
+        {
+            let store = opener.open().unwrap().get_hot_store();
+            let mut update = store.store_update();
+            update.set_raw_bytes(DBCol::BlockMisc, b"key", b"value");
+            update.increment_refcount(DBCol::State, b"node", b"data");
+            update.commit().unwrap();
+            store.flush().unwrap();
+        }
+        let store = opener.open_in_mode(Mode::ReadOnly).unwrap().get_hot_store();
+        assert_eq!(store.get(DBCol::BlockMisc, b"key").unwrap().as_deref(), Some(&b"value"[..]));
+        assert_eq!(store.get(DBCol::State, b"node").unwrap().as_deref(), Some(&b"data"[..]));
+        let mut update = store.store_update();
+        update.set_raw_bytes(DBCol::BlockMisc, b"key", b"other value");
+        assert!(update.commit().is_err());
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let config = redb_config();
+        let db = RedbDB::open(&tmpdir.path().join("data"), &config, Mode::Create).unwrap();
+        let mut transaction = crate::db::DBTransaction::new();
+        transaction.set(DBCol::Block, b"key".to_vec(), b"value".to_vec());
+        db.write(transaction).unwrap();
+
+        let checkpoint_path = tmpdir.path().join("checkpoint");
+        db.create_checkpoint(&checkpoint_path).unwrap();
+        assert!(db.create_checkpoint(&checkpoint_path).is_err());
+        db.write({
+            let mut transaction = crate::db::DBTransaction::new();
+            transaction.delete_all(DBCol::Block);
+            transaction
+        })
+        .unwrap();
+
+        let checkpoint = RedbDB::open(&checkpoint_path, &config, Mode::ReadOnly).unwrap();
+        assert_eq!(
+            checkpoint.get_raw_bytes(DBCol::Block, b"key").unwrap().as_deref(),
+            Some(&b"value"[..])
+        );
+        assert_eq!(db.get_raw_bytes(DBCol::Block, b"key").unwrap().as_deref(), None);
+        let stats = checkpoint.get_store_statistics().unwrap();
+        assert_eq!(stats.data[0].0, "redb.num-entries");
+    }
+}
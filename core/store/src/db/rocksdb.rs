@@ -117,7 +117,34 @@ impl RocksDB {
             .map_err(other_error)?;
         let (db, db_opt) = Self::open_db(path, store_config, mode, temp, columns)?;
         let cf_handles = Self::get_cf_handles(&db, columns);
-        Ok(Self { db, db_opt, cf_handles, _instance_tracker: counter })
+        let this = Self { db, db_opt, cf_handles, _instance_tracker: counter };
+        if mode.read_write() && columns.contains(&DBCol::DbVersion) {
+            this.set_trained_compression_options()?;
+        }
+        Ok(this)
+    }
+
+    /// Applies the compression dictionary sizes stored in the database
+    /// metadata to the columns they were set for.
+    ///
+    /// The sizes are stored in the database itself, so they can only be
+    /// applied once it’s open.  Compression options are mutable and only
+    /// affect newly written SST files, so it doesn’t matter that the columns
+    /// were opened with the default ones.
+    fn set_trained_compression_options(&self) -> io::Result<()> {
+        for (col, handle) in self.cf_handles() {
+            let Some(dict_size) =
+                metadata::DbMetadata::maybe_read_compression_dict_size(self, col)?
+            else {
+                continue;
+            };
+            let opts = bottommost_compression_opts(dict_size);
+            self.db
+                .set_options_cf(handle, &[("bottommost_compression_opts", opts.as_str())])
+                .map_err(into_other)?;
+            tracing::debug!(target: "db", column = %col, dict_size, "Set compression dictionary size");
+        }
+        Ok(())
     }
 
     /// Opens the database with given column families configured.
@@ -528,15 +555,33 @@ fn rocksdb_column_options(col: DBCol, store_config: &StoreConfig, temp: Temperat
     opts
 }
 
+/// Default maximum size of the zstd dictionaries used to compress the
+/// bottommost level of a column.
+///
+/// RocksDB documenation says that 16KB is a typical dictionary size.
+/// We've empirically tuned the dicionary size to twice of that 'typical' size.
+/// It can be changed per column with `neard database train-compression`.
+pub const DEFAULT_COMPRESSION_DICT_SIZE: usize = 2 * 16384;
+
+/// Having train data size x100 from dictionary size is a recommendation from RocksDB.
+/// See: https://rocksdb.org/blog/2021/05/31/dictionary-compression.html?utm_source=dbplatz
+const COMPRESSION_TRAIN_BYTES_PER_DICT_BYTE: usize = 100;
+
+/// Returns the value of the `bottommost_compression_opts` option for given
+/// dictionary size, with the same parameters as [`set_compression_options`].
+fn bottommost_compression_opts(dict_size: usize) -> String {
+    let max_train_bytes = dict_size * COMPRESSION_TRAIN_BYTES_PER_DICT_BYTE;
+    format!(
+        "{{window_bits=-14;level=32767;strategy=0;max_dict_bytes={dict_size};\
+         zstd_max_train_bytes={max_train_bytes};enabled=true}}"
+    )
+}
+
 fn set_compression_options(opts: &mut Options) {
     opts.set_compression_type(rocksdb::DBCompressionType::Lz4);
     opts.set_bottommost_compression_type(rocksdb::DBCompressionType::Zstd);
-    // RocksDB documenation says that 16KB is a typical dictionary size.
-    // We've empirically tuned the dicionary size to twice of that 'typical' size.
-    // Having train data size x100 from dictionary size is a recommendation from RocksDB.
-    // See: https://rocksdb.org/blog/2021/05/31/dictionary-compression.html?utm_source=dbplatz
-    let dict_size = 2 * 16384;
-    let max_train_bytes = dict_size * 100;
+    let dict_size = DEFAULT_COMPRESSION_DICT_SIZE as i32;
+    let max_train_bytes = dict_size * COMPRESSION_TRAIN_BYTES_PER_DICT_BYTE as i32;
     // We use default parameters of RocksDB here:
     //      window_bits is -14 and is unused (Zlib-specific parameter),
     //      compression_level is 32767 meaning the default compression level for ZSTD,
@@ -784,6 +829,57 @@ mod tests {
         }
     }
 
+    /// Returns `max_dict_bytes` of the bottommost compression options of the
+    /// column, as recorded in the latest OPTIONS file which RocksDB writes to
+    /// the database directory whenever the options change.
+    fn bottommost_max_dict_bytes(db_path: &Path, col: DBCol) -> usize {
+        let options_file = std::fs::read_dir(db_path)
+            .unwrap()
+            .filter_map(|entry| {
+                let name = entry.unwrap().file_name().into_string().unwrap();
+                let number: u64 = name.strip_prefix("OPTIONS-")?.parse().ok()?;
+                Some((number, name))
+            })
+            .max()
+            .unwrap()
+            .1;
+        let options = std::fs::read_to_string(db_path.join(options_file)).unwrap();
+        let section = format!("[CFOptions \"{}\"]", col_name(col));
+        let (_, section) = options.split_once(&section).unwrap();
+        let opts = section
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("bottommost_compression_opts="))
+            .unwrap();
+        let (_, max_dict_bytes) = opts.split_once("max_dict_bytes=").unwrap();
+        max_dict_bytes.split(|c| c == ';' || c == '}').next().unwrap().parse().unwrap()
+    }
+
+    /// Checks that the compression dictionary size stored in the metadata is
+    /// applied to the column when the database is opened again.
+    #[test]
+    fn test_trained_compression_options() {
+        let (_tmp_dir, opener) = NodeStorage::test_opener();
+        {
+            let store = opener.open().unwrap().get_hot_store();
+            assert_eq!(store.get_compression_dict_size(DBCol::State).unwrap(), None);
+            store.set_compression_dict_size(DBCol::State, 64 * 1024).unwrap();
+            store.set_compression_dict_size(DBCol::Receipts, 0).unwrap();
+        }
+        assert_eq!(
+            bottommost_max_dict_bytes(opener.path(), DBCol::State),
+            DEFAULT_COMPRESSION_DICT_SIZE
+        );
+        let store = opener.open().unwrap().get_hot_store();
+        assert_eq!(store.get_compression_dict_size(DBCol::State).unwrap(), Some(64 * 1024));
+        assert_eq!(store.get_compression_dict_size(DBCol::Receipts).unwrap(), Some(0));
+        assert_eq!(bottommost_max_dict_bytes(opener.path(), DBCol::State), 64 * 1024);
+        assert_eq!(bottommost_max_dict_bytes(opener.path(), DBCol::Receipts), 0);
+        assert_eq!(
+            bottommost_max_dict_bytes(opener.path(), DBCol::Block),
+            DEFAULT_COMPRESSION_DICT_SIZE
+        );
+    }
+
     #[test]
     fn test_parse_statistics() {
         let statistics = "rocksdb.cold.file.read.count COUNT : 999\n\
@@ -2,9 +2,10 @@ use std::io;
 
 use ::rocksdb::checkpoint::Checkpoint;
 
+use crate::db::Database;
 use crate::Temperature;
 
-/// Representation of a RocksDB checkpoint.
+/// Representation of a database checkpoint.
 ///
 /// Serves as kind of RAII type which logs information about the checkpoint when
 /// object is dropped if the checkpoint hasn’t been removed beforehand by
@@ -93,9 +94,19 @@ impl Snapshot {
             return Err(SnapshotError::AlreadyExists(snapshot_path));
         }
 
-        let db = super::RocksDB::open(db_path, config, crate::Mode::ReadWriteExisting, temp)?;
-        let cp = Checkpoint::new(&db.db).map_err(super::into_other)?;
-        cp.create_checkpoint(&snapshot_path)?;
+        match config.backend {
+            crate::DbBackend::RocksDB => {
+                let mode = crate::Mode::ReadWriteExisting;
+                let db = super::RocksDB::open(db_path, config, mode, temp)?;
+                let cp = Checkpoint::new(&db.db).map_err(super::into_other)?;
+                cp.create_checkpoint(&snapshot_path)?;
+            }
+            crate::DbBackend::Redb => {
+                let db = crate::db::RedbDB::open(db_path, config, crate::Mode::ReadWriteExisting)?;
+                db.create_checkpoint(&snapshot_path)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+            }
+        }
 
         Ok(Self(Some(snapshot_path)))
     }
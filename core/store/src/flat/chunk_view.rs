@@ -57,6 +57,12 @@ impl FlatStorageChunkView {
         self.flat_storage.get_head_hash()
     }
 
+    /// Whether the view's block is the flat storage head, i.e. whether
+    /// `DBCol::FlatState` reflects the state at the view's block.
+    pub fn is_at_head(&self) -> bool {
+        self.block_hash == self.flat_storage.get_head_hash()
+    }
+
     pub fn shard_uid(&self) -> ShardUId {
         self.flat_storage.shard_uid()
     }
@@ -35,6 +35,7 @@ pub mod store_helper;
 #[cfg(test)]
 pub mod test_utils;
 mod types;
+mod verification;
 
 pub use chunk_view::FlatStorageChunkView;
 pub use delta::{FlatStateChanges, FlatStateDelta, FlatStateDeltaMetadata};
@@ -44,8 +45,9 @@ pub use metrics::FlatStorageCreationMetrics;
 pub use storage::FlatStorage;
 pub use types::{
     BlockInfo, FetchingStateStatus, FlatStateIterator, FlatStorageCreationStatus, FlatStorageError,
-    FlatStorageReadyStatus, FlatStorageStatus,
+    FlatStorageMismatch, FlatStorageReadyStatus, FlatStorageStatus, FlatStorageVerificationStatus,
 };
+pub use verification::FlatStorageVerifierHandle;
 
 pub(crate) const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 
@@ -446,6 +446,11 @@ impl FlatStorage {
         Ok(())
     }
 
+    pub(crate) fn get_head(&self) -> BlockInfo {
+        let guard = self.0.read().expect(super::POISONED_LOCK_ERR);
+        guard.flat_head
+    }
+
     pub(crate) fn get_head_hash(&self) -> CryptoHash {
         let guard = self.0.read().expect(super::POISONED_LOCK_ERR);
         guard.flat_head.hash
@@ -3,9 +3,12 @@
 
 use super::delta::{FlatStateDelta, FlatStateDeltaMetadata};
 use super::types::{
-    FlatStateIterator, FlatStateValuesInliningMigrationStatus, FlatStorageResult, FlatStorageStatus,
+    FlatStateIterator, FlatStateValuesInliningMigrationStatus, FlatStorageResult,
+    FlatStorageStatus, FlatStorageVerificationStatus,
+};
+use crate::db::{
+    FLAT_STATE_VALUES_INLINING_MIGRATION_STATUS_KEY, FLAT_STORAGE_VERIFICATION_STATUS_KEY_PREFIX,
 };
-use crate::db::FLAT_STATE_VALUES_INLINING_MIGRATION_STATUS_KEY;
 use crate::flat::delta::{BlockWithChangesInfo, FlatStateChanges, KeyForFlatStateDelta};
 use crate::flat::types::FlatStorageError;
 use crate::flat::FlatStorageReadyStatus;
@@ -182,6 +185,40 @@ pub fn set_flat_state_values_inlining_migration_status(
     })
 }
 
+fn flat_storage_verification_status_key(shard_uid: ShardUId) -> Vec<u8> {
+    [FLAT_STORAGE_VERIFICATION_STATUS_KEY_PREFIX, &shard_uid.to_bytes()].concat()
+}
+
+pub fn get_flat_storage_verification_status(
+    store: &Store,
+    shard_uid: ShardUId,
+) -> FlatStorageResult<FlatStorageVerificationStatus> {
+    store
+        .get_ser(DBCol::Misc, &flat_storage_verification_status_key(shard_uid))
+        .map(|status| status.unwrap_or_default())
+        .map_err(|err| {
+            FlatStorageError::StorageInternalError(format!(
+                "failed to read flat storage verification status: {err}"
+            ))
+        })
+}
+
+pub fn set_flat_storage_verification_status(
+    store: &Store,
+    shard_uid: ShardUId,
+    status: &FlatStorageVerificationStatus,
+) -> FlatStorageResult<()> {
+    let mut store_update = store.store_update();
+    store_update
+        .set_ser(DBCol::Misc, &flat_storage_verification_status_key(shard_uid), status)
+        .expect("Borsh should not have failed here");
+    store_update.commit().map_err(|err| {
+        FlatStorageError::StorageInternalError(format!(
+            "failed to commit flat storage verification status: {err}"
+        ))
+    })
+}
+
 pub(crate) fn get_flat_state_value(
     store: &Store,
     shard_uid: ShardUId,
@@ -1,7 +1,7 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_primitives::errors::StorageError;
 use near_primitives::hash::CryptoHash;
-use near_primitives::state::FlatStateValue;
+use near_primitives::state::{FlatStateValue, ValueRef};
 use near_primitives::types::BlockHeight;
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq, serde::Serialize)]
@@ -52,6 +52,37 @@ pub enum FlatStateValuesInliningMigrationStatus {
     Finished,
 }
 
+/// Progress of the background verification of flat storage of a shard
+/// against the trie, see `FlatStorageVerifierHandle`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FlatStorageVerificationStatus {
+    /// Number of completed passes over the whole state.
+    pub num_passes: u64,
+    /// Number of batches verified at random positions in the current pass.
+    pub num_sampled_batches: u64,
+    /// Key at which the next batch going through the state in order starts.
+    /// `None` if the current pass hasn't got to it yet.
+    pub cursor: Option<Vec<u8>>,
+    /// Number of keys verified in the current pass.
+    pub num_verified_keys: u64,
+    /// Number of mismatches found in all passes.
+    pub num_mismatches: u64,
+    /// The most recent mismatches, the latest last.
+    pub recent_mismatches: Vec<FlatStorageMismatch>,
+    /// Flat head at which the last batch was verified.
+    pub last_flat_head: Option<BlockInfo>,
+}
+
+/// Key for which flat storage and the trie return different values.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FlatStorageMismatch {
+    pub key: Vec<u8>,
+    /// Flat head at which the values were compared.
+    pub flat_head: BlockInfo,
+    pub flat_value: Option<ValueRef>,
+    pub trie_value: Option<ValueRef>,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, serde::Serialize)]
 pub enum FlatStorageStatus {
     /// Flat Storage is not supported.
@@ -0,0 +1,397 @@
+//! Background verification of flat storage against the trie.
+//!
+//! Flat storage is maintained separately from the trie, so a bug in block
+//! processing or a disk corruption may make it diverge from the state it is
+//! supposed to mirror, which would go unnoticed until chunks are applied
+//! differently than on other nodes. The verifier compares values returned by
+//! both for all state keys, a batch at a time and at a limited rate, so that
+//! it can run alongside block processing.
+//!
+//! Every pass over the state of a shard starts with batches at random
+//! positions, which find a corruption spread over the state quickly, and then
+//! goes through the whole state in order. Progress is persisted, so a restart
+//! of the node doesn't restart the pass.
+
+use std::collections::BTreeSet;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use near_primitives::errors::StorageError;
+use near_primitives::shard_layout::get_block_shard_uid;
+use near_primitives::trie_key::col;
+use near_primitives::types::chunk_extra::ChunkExtra;
+use rand::Rng;
+use tracing::{debug, error, info, warn};
+
+use crate::config::FlatStorageVerificationConfig;
+use crate::metrics::flat_state_metrics::verification::{
+    COMPLETED_PASSES, MISMATCHES, VERIFIED_KEYS,
+};
+use crate::{DBCol, KeyLookupMode, Store, Trie, TrieDBStorage};
+
+use super::store_helper;
+use super::types::{BlockInfo, FlatStorageMismatch};
+use super::{FlatStorage, FlatStorageManager};
+
+/// Number of mismatches kept in the persisted status of a shard.
+const MAX_RECENT_MISMATCHES: usize = 20;
+
+pub struct FlatStorageVerifierHandle {
+    handle: JoinHandle<()>,
+    keep_running: Arc<AtomicBool>,
+}
+
+impl FlatStorageVerifierHandle {
+    pub fn start(
+        store: Store,
+        flat_storage_manager: FlatStorageManager,
+        config: FlatStorageVerificationConfig,
+    ) -> Self {
+        let keep_running = Arc::new(AtomicBool::new(true));
+        let keep_running_clone = keep_running.clone();
+        let handle = std::thread::spawn(move || {
+            run_verification(&store, &flat_storage_manager, &config, &keep_running)
+        });
+        Self { handle, keep_running: keep_running_clone }
+    }
+
+    pub fn stop(self) {
+        self.keep_running.store(false, Ordering::Relaxed);
+        self.handle.join().expect("join should not fail here");
+    }
+}
+
+/// Verifies batches of all shards with flat storage in turns until stopped.
+fn run_verification(
+    store: &Store,
+    flat_storage_manager: &FlatStorageManager,
+    config: &FlatStorageVerificationConfig,
+    keep_running: &AtomicBool,
+) {
+    info!(target: "store", ?config, "Starting flat storage verification");
+    let mut rng = rand::thread_rng();
+    while keep_running.load(Ordering::Relaxed) {
+        // Flat storages may be created after the node starts, so the list of
+        // shards is checked again after every round.
+        let shard_uids = flat_storage_manager.get_shard_uids();
+        if shard_uids.is_empty() {
+            sleep_while_running(Duration::from_secs(1), keep_running);
+            continue;
+        }
+        for shard_uid in shard_uids {
+            if !keep_running.load(Ordering::Relaxed) {
+                break;
+            }
+            let Some(flat_storage) = flat_storage_manager.get_flat_storage_for_shard(shard_uid)
+            else {
+                continue;
+            };
+            let start = Instant::now();
+            match verify_next_batch(store, &flat_storage, config, &mut rng) {
+                Ok(Some(num_keys)) => {
+                    let target = Duration::from_secs_f64(
+                        num_keys as f64 / config.max_keys_per_second.max(1) as f64,
+                    );
+                    sleep_while_running(target.saturating_sub(start.elapsed()), keep_running);
+                }
+                Ok(None) => {
+                    debug!(target: "store", %shard_uid, "Flat head moved during verification, will retry.");
+                    sleep_while_running(Duration::from_secs(1), keep_running);
+                }
+                Err(err) => {
+                    warn!(target: "store", %shard_uid, ?err, "Flat storage verification batch failed");
+                    sleep_while_running(Duration::from_secs(1), keep_running);
+                }
+            }
+        }
+    }
+    info!(target: "store", "Stopped flat storage verification");
+}
+
+fn sleep_while_running(duration: Duration, keep_running: &AtomicBool) {
+    let deadline = Instant::now() + duration;
+    while keep_running.load(Ordering::Relaxed) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        std::thread::sleep(remaining.min(Duration::from_millis(100)));
+    }
+}
+
+/// Verifies the next batch of the shard and updates its persisted status.
+///
+/// Returns the number of verified keys, or `None` if flat head moved while
+/// the batch was verified, in which case the batch is discarded.
+fn verify_next_batch(
+    store: &Store,
+    flat_storage: &FlatStorage,
+    config: &FlatStorageVerificationConfig,
+    rng: &mut impl Rng,
+) -> Result<Option<usize>, StorageError> {
+    let shard_uid = flat_storage.shard_uid();
+    let mut status = store_helper::get_flat_storage_verification_status(store, shard_uid)?;
+    let sampling = status.num_sampled_batches < config.num_sampled_batches;
+    let from = if sampling { random_key(rng) } else { status.cursor.clone().unwrap_or_default() };
+
+    // The state at flat head must stay the same while it is compared with
+    // the trie. Flat head updates are not disabled for that, since state
+    // snapshots rely on being the only ones disabling them, so the batch is
+    // checked not to overlap with a flat head update instead. Flat head only
+    // moves forward and its in-memory value is updated after the flat state,
+    // so an unchanged head means the flat state read is the one at that head.
+    let flat_head = flat_storage.get_head();
+    let result = verify_range(store, flat_storage, flat_head, &from, config.batch_size);
+    if flat_storage.get_head().hash != flat_head.hash {
+        return Ok(None);
+    }
+    let range = result?;
+
+    let shard_label = shard_uid.to_string();
+    VERIFIED_KEYS.with_label_values(&[&shard_label]).inc_by(range.num_keys as u64);
+    MISMATCHES.with_label_values(&[&shard_label]).inc_by(range.mismatches.len() as u64);
+    for mismatch in &range.mismatches {
+        error!(target: "store", %shard_uid, ?mismatch, "Flat storage doesn't match the trie");
+    }
+    status.num_verified_keys += range.num_keys as u64;
+    status.num_mismatches += range.mismatches.len() as u64;
+    status.recent_mismatches.extend(range.mismatches);
+    let num_dropped = status.recent_mismatches.len().saturating_sub(MAX_RECENT_MISMATCHES);
+    status.recent_mismatches.drain(..num_dropped);
+    status.last_flat_head = Some(range.flat_head);
+    if sampling {
+        status.num_sampled_batches += 1;
+    } else if range.end.is_some() {
+        status.cursor = range.end;
+    } else {
+        info!(target: "store", %shard_uid, num_verified_keys = status.num_verified_keys, num_mismatches = status.num_mismatches, "Completed flat storage verification pass");
+        status.num_passes += 1;
+        status.num_sampled_batches = 0;
+        status.num_verified_keys = 0;
+        status.cursor = None;
+    }
+    COMPLETED_PASSES.with_label_values(&[&shard_label]).set(status.num_passes as i64);
+    store_helper::set_flat_storage_verification_status(store, shard_uid, &status)?;
+    Ok(Some(range.num_keys))
+}
+
+/// Returns a random position in the state.  Positions aren't uniformly
+/// distributed over the keys, since columns have very different sizes, but
+/// they are spread enough to cover all parts of the state.
+fn random_key(rng: &mut impl Rng) -> Vec<u8> {
+    let mut key = vec![rng.gen_range(0..=col::CONTRACT_DATA)];
+    key.extend(rng.gen::<[u8; 8]>());
+    key
+}
+
+struct RangeVerification {
+    flat_head: BlockInfo,
+    num_keys: usize,
+    /// Key just after the verified range, or `None` if the range reached the
+    /// end of the state.
+    end: Option<Vec<u8>>,
+    mismatches: Vec<FlatStorageMismatch>,
+}
+
+/// Compares flat storage and the trie at flat head for the range of keys
+/// starting at `from` and containing at most `batch_size` keys of each of
+/// them.  The result is only valid if flat head stays at `flat_head` while
+/// this runs.
+fn verify_range(
+    store: &Store,
+    flat_storage: &FlatStorage,
+    flat_head: BlockInfo,
+    from: &[u8],
+    batch_size: usize,
+) -> Result<RangeVerification, StorageError> {
+    let shard_uid = flat_storage.shard_uid();
+    let chunk_extra = store
+        .get_ser::<ChunkExtra>(DBCol::ChunkExtra, &get_block_shard_uid(&flat_head.hash, &shard_uid))
+        .map_err(|err| StorageError::StorageInconsistentState(err.to_string()))?
+        .ok_or_else(|| {
+            StorageError::StorageInconsistentState(format!(
+                "no chunk extra for flat head {:?} of shard {shard_uid}",
+                flat_head.hash
+            ))
+        })?;
+    let trie = Trie::new(
+        Rc::new(TrieDBStorage::new(store.clone(), shard_uid)),
+        *chunk_extra.state_root(),
+        None,
+    );
+
+    // Keys missing in either of them must be found too, so the range covers
+    // keys of both, up to where the shorter of them ends.
+    let mut flat_keys = Vec::with_capacity(batch_size);
+    for item in
+        store_helper::iter_flat_state_entries(shard_uid, store, Some(from), None).take(batch_size)
+    {
+        flat_keys.push(item?.0);
+    }
+    let mut trie_keys = Vec::with_capacity(batch_size);
+    let mut trie_iter = trie.iter()?;
+    trie_iter.seek(from)?;
+    for item in trie_iter.take(batch_size) {
+        trie_keys.push(item?.0);
+    }
+    let last_key = [&flat_keys, &trie_keys]
+        .into_iter()
+        .filter(|keys| keys.len() == batch_size)
+        .filter_map(|keys| keys.last())
+        .min()
+        .cloned();
+    let keys: BTreeSet<Vec<u8>> = flat_keys
+        .into_iter()
+        .chain(trie_keys)
+        .filter(|key| last_key.as_ref().map_or(true, |last_key| key <= last_key))
+        .collect();
+
+    let mut mismatches = vec![];
+    for key in &keys {
+        let flat_value =
+            flat_storage.get_value(&flat_head.hash, key)?.map(|value| value.to_value_ref());
+        let trie_value = trie
+            .get_optimized_ref(key, KeyLookupMode::Trie)?
+            .map(|value_ref| value_ref.into_value_ref());
+        if flat_value != trie_value {
+            mismatches.push(FlatStorageMismatch {
+                key: key.clone(),
+                flat_head,
+                flat_value,
+                trie_value,
+            });
+        }
+    }
+    let end = last_key.map(|mut key| {
+        key.push(0);
+        key
+    });
+    Ok(RangeVerification { flat_head, num_keys: keys.len(), end, mismatches })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify_next_batch;
+    use crate::config::FlatStorageVerificationConfig;
+    use crate::flat::store_helper;
+    use crate::flat::{FlatStorage, FlatStorageVerificationStatus};
+    use crate::test_utils::{test_populate_trie, TestTriesBuilder};
+    use crate::{DBCol, Store, Trie};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
+    use near_primitives::state::{FlatStateValue, ValueRef};
+    use near_primitives::trie_key::col;
+    use near_primitives::types::chunk_extra::ChunkExtra;
+
+    /// Verifies batches until a pass over the state is completed, returning
+    /// the number of batches and the status after the pass.
+    fn run_pass(
+        store: &Store,
+        flat_storage: &FlatStorage,
+        num_sampled_batches: u64,
+    ) -> (usize, FlatStorageVerificationStatus) {
+        let config = FlatStorageVerificationConfig {
+            enabled: true,
+            max_keys_per_second: 1000,
+            batch_size: 4,
+            num_sampled_batches,
+        };
+        let shard_uid = flat_storage.shard_uid();
+        let mut rng = rand::thread_rng();
+        let num_passes = store_helper::get_flat_storage_verification_status(store, shard_uid)
+            .unwrap()
+            .num_passes;
+        for num_batches in 1..100 {
+            verify_next_batch(store, flat_storage, &config, &mut rng).unwrap().unwrap();
+            let status =
+                store_helper::get_flat_storage_verification_status(store, shard_uid).unwrap();
+            if status.num_passes > num_passes {
+                return (num_batches, status);
+            }
+        }
+        panic!("verification pass didn't complete");
+    }
+
+    #[test]
+    fn test_verification_finds_mismatches() {
+        let shard_uid = ShardUId::single_shard();
+        let tries = TestTriesBuilder::new().with_flat_storage().build();
+        let store = tries.get_store();
+        let changes: Vec<_> =
+            (0..10u8).map(|i| (vec![col::ACCOUNT, i], Some(vec![i; 10]))).collect();
+        let state_root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes.clone());
+        let mut store_update = store.store_update();
+        for (key, value) in changes {
+            store_helper::set_flat_state_value(
+                &mut store_update,
+                shard_uid,
+                key,
+                value.as_deref().map(FlatStateValue::inlined),
+            );
+        }
+        store_update
+            .set_ser(
+                DBCol::ChunkExtra,
+                &get_block_shard_uid(&CryptoHash::default(), &shard_uid),
+                &ChunkExtra::new_with_only_state_root(&state_root),
+            )
+            .unwrap();
+        store_update.commit().unwrap();
+        let flat_storage =
+            tries.get_flat_storage_manager().get_flat_storage_for_shard(shard_uid).unwrap();
+
+        // Sampled batches go first, then the 10 keys in batches of 4.
+        let (num_batches, status) = run_pass(&store, &flat_storage, 2);
+        assert_eq!(num_batches, 2 + 3);
+        assert_eq!(status.num_mismatches, 0);
+        assert_eq!(status.num_sampled_batches, 0);
+        assert_eq!(status.cursor, None);
+
+        // A missing key, a changed value and an extra key are all found.
+        let mut store_update = store.store_update();
+        store_helper::set_flat_state_value(
+            &mut store_update,
+            shard_uid,
+            vec![col::ACCOUNT, 2],
+            None,
+        );
+        store_helper::set_flat_state_value(
+            &mut store_update,
+            shard_uid,
+            vec![col::ACCOUNT, 5],
+            Some(FlatStateValue::inlined(&[0])),
+        );
+        store_helper::set_flat_state_value(
+            &mut store_update,
+            shard_uid,
+            vec![col::ACCOUNT, 20],
+            Some(FlatStateValue::inlined(&[20])),
+        );
+        store_update.commit().unwrap();
+        let (num_batches, status) = run_pass(&store, &flat_storage, 0);
+        assert_eq!(num_batches, 3);
+        assert_eq!(status.num_passes, 2);
+        assert_eq!(status.num_mismatches, 3);
+        // Verification doesn't change the flat head update mode, which state
+        // snapshots set.
+        assert!(flat_storage.set_flat_head_update_mode(false));
+        run_pass(&store, &flat_storage, 0);
+        assert!(flat_storage.set_flat_head_update_mode(true));
+        let mismatches: Vec<_> = status
+            .recent_mismatches
+            .into_iter()
+            .map(|mismatch| (mismatch.key, mismatch.flat_value, mismatch.trie_value))
+            .collect();
+        assert_eq!(
+            mismatches,
+            vec![
+                (vec![col::ACCOUNT, 2], None, Some(ValueRef::new(&[2; 10]))),
+                (vec![col::ACCOUNT, 5], Some(ValueRef::new(&[0])), Some(ValueRef::new(&[5; 10]))),
+                (vec![col::ACCOUNT, 20], Some(ValueRef::new(&[20])), None),
+            ]
+        );
+    }
+}
@@ -54,7 +54,7 @@ mod sync_utils;
 pub mod test_utils;
 pub mod trie;
 
-pub use crate::config::{Mode, StoreConfig};
+pub use crate::config::{DbBackend, Mode, StoreConfig};
 pub use crate::opener::{
     checkpoint_hot_storage_and_cleanup_columns, StoreMigrator, StoreOpener, StoreOpenerError,
 };
@@ -119,14 +119,11 @@ impl NodeStorage {
         StoreOpener::new(home_dir, archive, config, cold_config)
     }
 
-    /// Constructs new object backed by given database.
-    fn from_rocksdb(
-        hot_storage: crate::db::RocksDB,
-        cold_storage: Option<crate::db::RocksDB>,
+    /// Constructs new object backed by given on-disk databases.
+    fn from_databases(
+        hot_storage: Arc<dyn Database>,
+        cold_storage: Option<Arc<dyn Database>>,
     ) -> Self {
-        let hot_storage = Arc::new(hot_storage);
-        let cold_storage = cold_storage.map(|storage| Arc::new(storage));
-
         let cold_db = if let Some(cold_storage) = cold_storage {
             Some(Arc::new(crate::db::ColdDB::new(cold_storage)))
         } else {
@@ -410,6 +407,22 @@ impl Store {
         store_update.set(DBCol::DbVersion, KIND_KEY, <&str>::from(kind).as_bytes());
         store_update.commit()
     }
+
+    pub fn get_compression_dict_size(&self, col: DBCol) -> io::Result<Option<usize>> {
+        metadata::DbMetadata::maybe_read_compression_dict_size(self.storage.as_ref(), col)
+    }
+
+    /// Sets the size of the zstd dictionaries used to compress the column.
+    /// Takes effect the next time the database is opened.
+    pub fn set_compression_dict_size(&self, col: DBCol, size: usize) -> io::Result<()> {
+        let mut store_update = self.store_update();
+        store_update.set(
+            DBCol::DbVersion,
+            &metadata::compression_dict_size_key(col),
+            size.to_string().as_bytes(),
+        );
+        store_update.commit()
+    }
 }
 
 /// Keeps track of current changes to the database and can commit all of them to the database.
@@ -919,6 +932,35 @@ impl CompiledContractCache for StoreCompiledContractCache {
     }
 }
 
+/// Cache for compiled contracts which reads contracts cached in the store, but
+/// keeps newly compiled contracts in memory.  Used for executions which must
+/// not modify the store, e.g. transaction simulation.
+#[derive(Clone)]
+pub struct ReadOnlyStoreCompiledContractCache {
+    db: Arc<dyn Database>,
+    compiled: Arc<std::sync::Mutex<std::collections::HashMap<CryptoHash, CompiledContract>>>,
+}
+
+impl ReadOnlyStoreCompiledContractCache {
+    pub fn new(store: &Store) -> Self {
+        Self { db: store.storage.clone(), compiled: Default::default() }
+    }
+}
+
+impl CompiledContractCache for ReadOnlyStoreCompiledContractCache {
+    fn put(&self, key: &CryptoHash, value: CompiledContract) -> io::Result<()> {
+        self.compiled.lock().unwrap().insert(*key, value);
+        Ok(())
+    }
+
+    fn get(&self, key: &CryptoHash) -> io::Result<Option<CompiledContract>> {
+        if let Some(value) = self.compiled.lock().unwrap().get(key) {
+            return Ok(Some(value.clone()));
+        }
+        StoreCompiledContractCache { db: self.db.clone() }.get(key)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use near_primitives::hash::CryptoHash;
@@ -1051,6 +1093,29 @@ mod tests {
         assert_eq!(true, cache.has(&key).unwrap());
     }
 
+    /// Check that ReadOnlyStoreCompiledContractCache reads the store, but
+    /// doesn't write to it.
+    #[test]
+    fn test_read_only_store_compiled_contract_cache() {
+        use near_vm_runner::logic::{CompiledContract, CompiledContractCache};
+        use std::str::FromStr;
+
+        let store = crate::test_utils::create_test_store();
+        let store_cache = super::StoreCompiledContractCache::new(&store);
+        let cache = super::ReadOnlyStoreCompiledContractCache::new(&store);
+        let stored_key =
+            CryptoHash::from_str("75pAU4CJcp8Z9eoXcL6pSU8sRK5vn3NEpgvUrzZwQtr3").unwrap();
+        let stored_record = CompiledContract::Code(b"foo".to_vec());
+        store_cache.put(&stored_key, stored_record.clone()).unwrap();
+        assert_eq!(Some(stored_record), cache.get(&stored_key).unwrap());
+
+        let key = CryptoHash::default();
+        let record = CompiledContract::Code(b"bar".to_vec());
+        cache.put(&key, record.clone()).unwrap();
+        assert_eq!(Some(record), cache.get(&key).unwrap());
+        assert_eq!(None, store_cache.get(&key).unwrap());
+    }
+
     /// Check saving and reading columns to/from a file.
     #[test]
     fn test_save_to_file() {
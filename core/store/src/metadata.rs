@@ -22,6 +22,22 @@ pub(super) const VERSION_KEY: &[u8; 7] = b"VERSION";
 /// The key holds a [`DbKind`] value serialised to a string.
 pub(super) const KIND_KEY: &[u8; 4] = b"KIND";
 
+/// Prefix of the keys for the compression dictionary sizes in DBCol::DbVersion.
+///
+/// The key `COMPRESSION_DICT_SIZE:<column>` holds the maximum size in bytes of
+/// the zstd dictionaries used to compress the bottommost level of the column,
+/// serialised to a string.  RocksDB trains the dictionaries itself for every
+/// SST file, so only their size can be tuned.  It is chosen per column by
+/// `neard database train-compression`; columns without the key use the
+/// default size.
+const COMPRESSION_DICT_SIZE_KEY_PREFIX: &str = "COMPRESSION_DICT_SIZE:";
+
+/// Returns the key in DBCol::DbVersion holding the compression dictionary size
+/// of given column.
+pub(super) fn compression_dict_size_key(col: crate::DBCol) -> Vec<u8> {
+    format!("{COMPRESSION_DICT_SIZE_KEY_PREFIX}{col}").into_bytes()
+}
+
 /// Describes what kind the storage is.
 #[derive(
     Clone, Copy, Debug, Eq, PartialEq, strum::Display, strum::EnumString, strum::IntoStaticStr,
@@ -88,6 +104,15 @@ impl DbMetadata {
     pub(super) fn maybe_read_kind(db: &dyn crate::Database) -> std::io::Result<Option<DbKind>> {
         maybe_read("DbKind", db, KIND_KEY)
     }
+
+    /// Reads the compression dictionary size of given column from the db.
+    /// Returns None if it isn’t set and the default size should be used.
+    pub(super) fn maybe_read_compression_dict_size(
+        db: &dyn crate::Database,
+        col: crate::DBCol,
+    ) -> std::io::Result<Option<usize>> {
+        maybe_read("compression dictionary size", db, &compression_dict_size_key(col))
+    }
 }
 
 /// Reads value from DbVersion column and parses it using `FromStr`.
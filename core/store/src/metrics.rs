@@ -527,6 +527,38 @@ pub mod flat_state_metrics {
             .unwrap()
         });
     }
+
+    pub mod verification {
+        use near_o11y::metrics::{
+            try_create_int_counter_vec, try_create_int_gauge_vec, IntCounterVec, IntGaugeVec,
+        };
+        use once_cell::sync::Lazy;
+
+        pub static VERIFIED_KEYS: Lazy<IntCounterVec> = Lazy::new(|| {
+            try_create_int_counter_vec(
+                "near_flat_storage_verification_verified_keys",
+                "Number of flat storage keys compared with the trie.",
+                &["shard_uid"],
+            )
+            .unwrap()
+        });
+        pub static MISMATCHES: Lazy<IntCounterVec> = Lazy::new(|| {
+            try_create_int_counter_vec(
+                "near_flat_storage_verification_mismatches",
+                "Number of keys for which flat storage and the trie have different values.",
+                &["shard_uid"],
+            )
+            .unwrap()
+        });
+        pub static COMPLETED_PASSES: Lazy<IntGaugeVec> = Lazy::new(|| {
+            try_create_int_gauge_vec(
+                "near_flat_storage_verification_completed_passes",
+                "Number of completed verification passes over the whole state.",
+                &["shard_uid"],
+            )
+            .unwrap()
+        });
+    }
 }
 pub static COLD_STORE_MIGRATION_BATCH_WRITE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec(
@@ -1,13 +1,14 @@
 use crate::db::rocksdb::snapshot::{Snapshot, SnapshotError, SnapshotRemoveError};
 use crate::db::rocksdb::RocksDB;
+use crate::db::{Database, RedbDB};
 use crate::metadata::{DbKind, DbMetadata, DbVersion, DB_VERSION};
-use crate::{DBCol, DBTransaction, Mode, NodeStorage, Store, StoreConfig, Temperature};
+use crate::{DBCol, DBTransaction, DbBackend, Mode, NodeStorage, Store, StoreConfig, Temperature};
 use std::sync::Arc;
 use strum::IntoEnumIterator;
 
 #[derive(Debug, thiserror::Error)]
 pub enum StoreOpenerError {
-    /// I/O or RocksDB-level error while opening or accessing the database.
+    /// I/O or database-level error while opening or accessing the database.
     #[error("{0}")]
     IO(#[from] std::io::Error),
 
@@ -163,10 +164,10 @@ fn is_valid_kind_archive(kind: DbKind, archive: bool) -> bool {
 ///     .open();
 /// ```
 pub struct StoreOpener<'a> {
-    /// Opener for an instance of RPC or Hot RocksDB store.
+    /// Opener for an instance of RPC or Hot store.
     hot: DBOpener<'a>,
 
-    /// Opener for an instance of Cold RocksDB store if one was configured.
+    /// Opener for an instance of Cold store if one was configured.
     cold: Option<DBOpener<'a>>,
 
     /// Whether the opener should expect archival db or not.
@@ -177,7 +178,7 @@ pub struct StoreOpener<'a> {
     migrator: Option<&'a dyn StoreMigrator>,
 }
 
-/// Opener for a single RocksDB instance.
+/// Opener for a single database instance.
 struct DBOpener<'a> {
     /// Path to the database.
     ///
@@ -222,7 +223,7 @@ impl<'a> StoreOpener<'a> {
         self
     }
 
-    /// Returns path to the underlying RocksDB database.
+    /// Returns path to the underlying database.
     ///
     /// Does not check whether the database actually exists.
     pub fn path(&self) -> &std::path::Path {
@@ -241,7 +242,7 @@ impl<'a> StoreOpener<'a> {
         self.open_in_mode(Mode::ReadWrite)
     }
 
-    /// Opens the RocksDB database(s) for hot and cold (if configured) storages.
+    /// Opens the database(s) for hot and cold (if configured) storages.
     ///
     /// When opening in read-only mode, verifies that the database version is
     /// what the node expects and fails if it isn’t.  If database doesn’t exist,
@@ -280,7 +281,7 @@ impl<'a> StoreOpener<'a> {
             .transpose()?
             .map(|(db, _)| db);
 
-        let storage = NodeStorage::from_rocksdb(hot_db, cold_db);
+        let storage = NodeStorage::from_databases(hot_db, cold_db);
 
         hot_snapshot.remove()?;
         cold_snapshot.remove()?;
@@ -339,8 +340,7 @@ impl<'a> StoreOpener<'a> {
             None if mode.can_create() => {
                 tracing::info!(target: "db_opener", path=%opener.path.display(), "The database doesn't exist, creating it.");
 
-                let db = opener.create()?;
-                let store = Store { storage: Arc::new(db) };
+                let store = Store { storage: opener.create()? };
                 store.set_db_version(DB_VERSION)?;
                 return Ok(());
             }
@@ -468,19 +468,19 @@ impl<'a> StoreOpener<'a> {
         version: DbVersion,
     ) -> Result<Store, StoreOpenerError> {
         let (db, _) = opener.open(mode, version)?;
-        let store = Store { storage: Arc::new(db) };
+        let store = Store { storage: db };
         Ok(store)
     }
 
     fn open_store_unsafe(mode: Mode, opener: &DBOpener) -> Result<Store, StoreOpenerError> {
         let db = opener.open_unsafe(mode)?;
-        let store = Store { storage: Arc::new(db) };
+        let store = Store { storage: db };
         Ok(store)
     }
 }
 
 impl<'a> DBOpener<'a> {
-    /// Constructs new opener for a single RocksDB builder.
+    /// Constructs new opener for a single database.
     ///
     /// The path to the database is resolved based on the path in config with
     /// given home_dir as base directory for resolving relative paths.
@@ -500,7 +500,10 @@ impl<'a> DBOpener<'a> {
     /// introduced, the kind is returned as `None`.  Otherwise, it’s also
     /// fetched and if it’s not there error is returned.
     fn get_metadata(&self) -> std::io::Result<Option<DbMetadata>> {
-        RocksDB::get_metadata(&self.path, self.config)
+        match self.config.backend {
+            DbBackend::RocksDB => RocksDB::get_metadata(&self.path, self.config),
+            DbBackend::Redb => RedbDB::get_metadata(&self.path, self.config),
+        }
     }
 
     /// Opens the database in given mode checking expected version and kind.
@@ -519,9 +522,13 @@ impl<'a> DBOpener<'a> {
     /// new version.
     ///
     /// Use [`Self::create`] to create a new database.
-    fn open(&self, mode: Mode, want_version: DbVersion) -> std::io::Result<(RocksDB, DbMetadata)> {
-        let db = RocksDB::open(&self.path, &self.config, mode, self.temp)?;
-        let metadata = DbMetadata::read(&db)?;
+    fn open(
+        &self,
+        mode: Mode,
+        want_version: DbVersion,
+    ) -> std::io::Result<(Arc<dyn Database>, DbMetadata)> {
+        let db = self.open_db(mode)?;
+        let metadata = DbMetadata::read(db.as_ref())?;
         if want_version != metadata.version {
             let msg = format!("unexpected DbVersion {}; expected {want_version}", metadata.version);
             Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
@@ -534,14 +541,23 @@ impl<'a> DBOpener<'a> {
     ///
     /// This is only suitable when creating the database or setting the version
     /// and kind for the first time.
-    fn open_unsafe(&self, mode: Mode) -> std::io::Result<RocksDB> {
-        let db = RocksDB::open(&self.path, &self.config, mode, self.temp)?;
-        Ok(db)
+    fn open_unsafe(&self, mode: Mode) -> std::io::Result<Arc<dyn Database>> {
+        self.open_db(mode)
     }
 
     /// Creates a new database.
-    fn create(&self) -> std::io::Result<RocksDB> {
-        RocksDB::open(&self.path, &self.config, Mode::Create, self.temp)
+    fn create(&self) -> std::io::Result<Arc<dyn Database>> {
+        self.open_db(Mode::Create)
+    }
+
+    /// Opens the database with the configured backend.
+    fn open_db(&self, mode: Mode) -> std::io::Result<Arc<dyn Database>> {
+        Ok(match self.config.backend {
+            DbBackend::RocksDB => {
+                Arc::new(RocksDB::open(&self.path, &self.config, mode, self.temp)?)
+            }
+            DbBackend::Redb => Arc::new(RedbDB::open(&self.path, &self.config, mode)?),
+        })
     }
 
     /// Creates a new snapshot for the database.
@@ -2,6 +2,7 @@ use crate::config::TrieCacheConfig;
 use crate::StoreConfig;
 use near_primitives::shard_layout::ShardUId;
 use near_primitives::types::AccountId;
+use std::path::PathBuf;
 use std::str::FromStr;
 use tracing::error;
 
@@ -34,6 +35,14 @@ pub struct TrieConfig {
     /// List of shards we will load into memory.
     pub load_mem_tries_for_shards: Vec<ShardUId>,
     pub load_mem_tries_for_all_shards: bool,
+    /// Directory for snapshots of in-memory tries, see `StoreConfig::mem_trie_snapshot_path`.
+    pub mem_trie_snapshot_dir: Option<PathBuf>,
+    /// See `StoreConfig::mem_trie_dedup_nodes`.
+    pub mem_trie_dedup_nodes: bool,
+    /// See `StoreConfig::mem_trie_compaction_threshold`.
+    pub mem_trie_compaction_threshold: Option<f64>,
+    /// See `StoreConfig::mem_trie_compaction_nodes_per_step`.
+    pub mem_trie_compaction_nodes_per_step: usize,
 }
 
 impl TrieConfig {
@@ -59,6 +68,10 @@ impl TrieConfig {
         }
         this.load_mem_tries_for_shards = config.load_mem_tries_for_shards.clone();
         this.load_mem_tries_for_all_shards = config.load_mem_tries_for_all_shards;
+        this.mem_trie_snapshot_dir = config.mem_trie_snapshot_path.clone();
+        this.mem_trie_dedup_nodes = config.mem_trie_dedup_nodes;
+        this.mem_trie_compaction_threshold = config.mem_trie_compaction_threshold;
+        this.mem_trie_compaction_nodes_per_step = config.mem_trie_compaction_nodes_per_step;
 
         this
     }
@@ -105,6 +105,14 @@ impl<'a> TrieIterator<'a> {
         self.seek_nibble_slice(NibbleSlice::new(key.as_ref()), true).map(drop)
     }
 
+    /// Position the iterator on the first element with key >= `key`.
+    ///
+    /// Unlike [`Self::seek_prefix`], iteration isn’t limited to keys starting
+    /// with `key` and continues until the end of the trie.
+    pub fn seek<K: AsRef<[u8]>>(&mut self, key: K) -> Result<(), StorageError> {
+        self.seek_nibble_slice(NibbleSlice::new(key.as_ref()), false).map(drop)
+    }
+
     /// Configures whether the iterator should remember all the nodes its
     /// visiting.
     ///
@@ -471,9 +479,11 @@ mod tests {
                 assert_eq!(result1, result2);
             }
             test_seek_prefix(&trie, &map, &[]);
+            test_seek(&trie, &map, &[]);
 
             for (seek_key, _) in trie_changes.iter() {
                 test_seek_prefix(&trie, &map, seek_key);
+                test_seek(&trie, &map, seek_key);
             }
             for _ in 0..20 {
                 let alphabet = &b"abcdefgh"[0..rng.gen_range(2..8)];
@@ -481,6 +491,7 @@ mod tests {
                 let seek_key: Vec<u8> =
                     (0..key_length).map(|_| *alphabet.choose(&mut rng).unwrap()).collect();
                 test_seek_prefix(&trie, &map, &seek_key);
+                test_seek(&trie, &map, &seek_key);
             }
         }
     }
@@ -648,6 +659,15 @@ mod tests {
         assert_eq!(got, want);
     }
 
+    fn test_seek(trie: &Trie, map: &BTreeMap<Vec<u8>, Vec<u8>>, seek_key: &[u8]) {
+        let mut iterator = trie.iter().unwrap();
+        iterator.seek(&seek_key).unwrap();
+        let got: Vec<_> = iterator.map(Result::unwrap).collect();
+        let want: Vec<_> =
+            map.range(seek_key.to_vec()..).map(|(k, v)| (k.clone(), v.clone())).collect();
+        assert_eq!(got, want);
+    }
+
     #[test]
     fn test_has_value() {
         let mut rng = rand::thread_rng();
@@ -1,3 +1,4 @@
+use borsh::{BorshDeserialize, BorshSerialize};
 use near_o11y::metrics::IntGauge;
 
 use super::metrics::MEM_TRIE_ARENA_ACTIVE_ALLOCS_COUNT;
@@ -6,6 +7,8 @@ use crate::trie::mem::arena::metrics::{
     MEM_TRIE_ARENA_ACTIVE_ALLOCS_BYTES, MEM_TRIE_ARENA_MEMORY_USAGE_BYTES,
 };
 use crate::trie::mem::flexible_data::encoding::BorshFixedSize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 
 /// Simple bump allocator with freelists.
 ///
@@ -19,7 +22,8 @@ use crate::trie::mem::flexible_data::encoding::BorshFixedSize;
 ///
 /// As a result, the memory usage of this allocator never decreases. In
 /// practice, for in-memory tries, there is very little memory usage creep even
-/// when tested over weeks of uptime.
+/// when tested over weeks of uptime. If freed regions do pile up, the live
+/// nodes can be moved into a fresh arena with `MemTries::compact`.
 pub struct Allocator {
     /// The head of a linked list of freed allocations; one for each allocation
     /// class.
@@ -32,6 +36,9 @@ pub struct Allocator {
     /// This position would only ever move forward. De-allocating an allocation
     /// does not affect this position; it only adds an entry to a freelist.
     next_alloc_pos: ArenaPos,
+    /// If set, indices of new chunks are taken from this counter instead of
+    /// being assigned sequentially; see `Arena::new_with_shared_chunk_counter`.
+    shared_chunk_counter: Option<Arc<AtomicU32>>,
 
     // Stats. Note that keep the bytes and count locally too because the
     // gauges are process-wide, so stats-keeping directly with those may not be
@@ -46,7 +53,7 @@ pub struct Allocator {
 const MAX_ALLOC_SIZE: usize = 16 * 1024;
 const ROUND_UP_TO_8_BYTES_UNDER: usize = 256;
 const ROUND_UP_TO_64_BYTES_UNDER: usize = 1024;
-const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+pub(super) const CHUNK_SIZE: usize = 4 * 1024 * 1024;
 
 /// Calculates the allocation class (an index from 0 to NUM_ALLOCATION_CLASSES)
 /// for the given size that we wish to allocate.
@@ -74,11 +81,22 @@ const fn allocation_size(size_class: usize) -> usize {
 
 const NUM_ALLOCATION_CLASSES: usize = allocation_class(MAX_ALLOC_SIZE) + 1;
 
+/// Bookkeeping of the allocator that is persisted together with the arena
+/// memory, so that an arena restored from disk can keep allocating from it.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(super) struct AllocatorState {
+    freelists: Vec<ArenaPos>,
+    next_alloc_pos: ArenaPos,
+    active_allocs_bytes: u64,
+    active_allocs_count: u64,
+}
+
 impl Allocator {
     pub fn new(name: String) -> Self {
         Self {
             freelists: [ArenaPos::invalid(); NUM_ALLOCATION_CLASSES],
             next_alloc_pos: ArenaPos::invalid(),
+            shared_chunk_counter: None,
             active_allocs_bytes: 0,
             active_allocs_count: 0,
             active_allocs_bytes_gauge: MEM_TRIE_ARENA_ACTIVE_ALLOCS_BYTES
@@ -89,12 +107,97 @@ impl Allocator {
         }
     }
 
+    pub fn new_with_shared_chunk_counter(
+        name: String,
+        shared_chunk_counter: Arc<AtomicU32>,
+    ) -> Self {
+        Self { shared_chunk_counter: Some(shared_chunk_counter), ..Self::new(name) }
+    }
+
+    /// Merges allocators of arenas whose memory was merged into `arena`,
+    /// keeping all their active allocations and freed regions.
+    pub fn merge(name: String, allocators: Vec<Allocator>, arena: &mut ArenaMemory) -> Self {
+        let mut merged = Self::new(name);
+        for allocator in allocators {
+            merged.active_allocs_bytes += allocator.active_allocs_bytes;
+            merged.active_allocs_count += allocator.active_allocs_count;
+            for (size_class, mut pos) in allocator.freelists.into_iter().enumerate() {
+                while !pos.is_invalid() {
+                    let next = arena.ptr(pos).read_pos();
+                    arena
+                        .slice_mut(pos, ArenaPos::SERIALIZED_SIZE)
+                        .write_pos_at(0, merged.freelists[size_class]);
+                    merged.freelists[size_class] = pos;
+                    pos = next;
+                }
+            }
+        }
+        merged.active_allocs_bytes_gauge.set(merged.active_allocs_bytes as i64);
+        merged.active_allocs_count_gauge.set(merged.active_allocs_count as i64);
+        merged.memory_usage_gauge.set(arena.chunks.len() as i64 * CHUNK_SIZE as i64);
+        merged
+    }
+
+    /// Restores an allocator previously saved with `state`, checking that
+    /// the state is consistent with the given arena memory.
+    pub fn from_state(
+        name: String,
+        state: AllocatorState,
+        arena: &ArenaMemory,
+    ) -> std::io::Result<Self> {
+        let is_valid_pos = |pos: &ArenaPos| {
+            pos.is_invalid() || (pos.chunk() < arena.chunks.len() && pos.pos() < CHUNK_SIZE)
+        };
+        let freelists: [ArenaPos; NUM_ALLOCATION_CLASSES] =
+            state.freelists.try_into().map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "unexpected number of allocation classes",
+                )
+            })?;
+        if !freelists.iter().all(is_valid_pos) || !is_valid_pos(&state.next_alloc_pos) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "allocator position is out of the arena bounds",
+            ));
+        }
+        let mut allocator = Self::new(name);
+        allocator.freelists = freelists;
+        allocator.next_alloc_pos = state.next_alloc_pos;
+        allocator.active_allocs_bytes = state.active_allocs_bytes as usize;
+        allocator.active_allocs_count = state.active_allocs_count as usize;
+        allocator.active_allocs_bytes_gauge.set(allocator.active_allocs_bytes as i64);
+        allocator.active_allocs_count_gauge.set(allocator.active_allocs_count as i64);
+        allocator.memory_usage_gauge.set(arena.chunks.len() as i64 * CHUNK_SIZE as i64);
+        Ok(allocator)
+    }
+
+    /// Returns the bookkeeping needed to restore this allocator with `from_state`.
+    pub fn state(&self) -> AllocatorState {
+        AllocatorState {
+            freelists: self.freelists.to_vec(),
+            next_alloc_pos: self.next_alloc_pos,
+            active_allocs_bytes: self.active_allocs_bytes as u64,
+            active_allocs_count: self.active_allocs_count as u64,
+        }
+    }
+
+    /// Total size of the active allocations, as requested by the callers.
+    pub fn active_allocs_bytes(&self) -> usize {
+        self.active_allocs_bytes
+    }
+
     /// Adds a new chunk to the arena, and updates the next_alloc_pos to the beginning of
     /// the new chunk.
     fn new_chunk(&mut self, arena: &mut ArenaMemory) {
+        let chunk = match &self.shared_chunk_counter {
+            Some(counter) => counter.fetch_add(1, Ordering::Relaxed) as usize,
+            None => arena.chunks.len(),
+        };
+        // Chunks owned by other arenas sharing the counter are left empty.
+        arena.chunks.resize_with(chunk, Vec::new);
         arena.chunks.push(vec![0; CHUNK_SIZE]);
-        self.next_alloc_pos =
-            ArenaPos { chunk: u32::try_from(arena.chunks.len() - 1).unwrap(), pos: 0 };
+        self.next_alloc_pos = ArenaPos { chunk: u32::try_from(chunk).unwrap(), pos: 0 };
         self.memory_usage_gauge.set(arena.chunks.len() as i64 * CHUNK_SIZE as i64);
     }
 
@@ -148,6 +251,8 @@ mod test {
     use crate::trie::mem::arena::alloc::CHUNK_SIZE;
     use crate::trie::mem::arena::Arena;
     use std::mem::size_of;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
 
     #[test]
     fn test_allocate_deallocate() {
@@ -202,6 +307,38 @@ mod test {
         assert_eq!(arena.memory.chunks.len(), 4);
     }
 
+    #[test]
+    fn test_merge_arenas_with_shared_chunk_counter() {
+        let counter = Arc::new(AtomicU32::new(0));
+        let mut arenas = (0..3)
+            .map(|_| Arena::new_with_shared_chunk_counter("".to_owned(), counter.clone()))
+            .collect::<Vec<_>>();
+        let mut allocs = Vec::new();
+        for i in 0..1000 {
+            let arena = &mut arenas[i % 3];
+            let mut alloc = arena.alloc(8192);
+            alloc.raw_slice_mut().fill((i % 256) as u8);
+            allocs.push((i, alloc.pos, alloc.len));
+        }
+        // Free some allocations so that freelists have to be merged too.
+        for (i, pos, len) in allocs.drain(..30) {
+            arenas[i % 3].dealloc(pos, len);
+        }
+
+        let mut arena = Arena::merge("".to_owned(), arenas);
+        assert_eq!(arena.memory.chunks.len(), counter.load(Ordering::Relaxed) as usize);
+        assert_eq!(arena.num_active_allocs(), allocs.len());
+        for (i, pos, len) in &allocs {
+            assert!(arena.memory.raw_slice(*pos, *len).iter().all(|byte| *byte == (i % 256) as u8));
+        }
+        // Freed regions are reused before new chunks are allocated.
+        let num_chunks = arena.memory.chunks.len();
+        for _ in 0..30 {
+            arena.alloc(8192);
+        }
+        assert_eq!(arena.memory.chunks.len(), num_chunks);
+    }
+
     #[test]
     fn test_size_classes() {
         for i in 1..=MAX_ALLOC_SIZE {
@@ -1,10 +1,13 @@
 mod alloc;
 mod metrics;
-use self::alloc::Allocator;
+use self::alloc::{Allocator, AllocatorState, CHUNK_SIZE};
 use borsh::{BorshDeserialize, BorshSerialize};
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hash;
+use std::io::{Read, Write};
 use std::mem::size_of;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
 
 use super::flexible_data::encoding::BorshFixedSize;
 
@@ -122,6 +125,43 @@ impl Arena {
         Self { memory: ArenaMemory::new(), allocator: Allocator::new(name) }
     }
 
+    /// Creates an arena whose chunks are numbered by a counter shared with
+    /// other arenas. Such arenas can be filled in parallel and then combined
+    /// with `merge` without relocating anything, because no two of them ever
+    /// use the same chunk index.
+    pub fn new_with_shared_chunk_counter(name: String, chunk_counter: Arc<AtomicU32>) -> Self {
+        Self {
+            memory: ArenaMemory::new(),
+            allocator: Allocator::new_with_shared_chunk_counter(name, chunk_counter),
+        }
+    }
+
+    /// Combines arenas created with the same shared chunk counter into one
+    /// arena. All positions of the source arenas remain valid in the result.
+    pub fn merge(name: String, arenas: Vec<Arena>) -> Self {
+        let mut memory = ArenaMemory::new();
+        let mut allocators = Vec::new();
+        for arena in arenas {
+            for (index, chunk) in arena.memory.chunks.into_iter().enumerate() {
+                if chunk.is_empty() {
+                    continue;
+                }
+                if memory.chunks.len() <= index {
+                    memory.chunks.resize_with(index + 1, Vec::new);
+                }
+                assert!(memory.chunks[index].is_empty(), "Chunk {} owned by two arenas", index);
+                memory.chunks[index] = chunk;
+            }
+            allocators.push(arena.allocator);
+        }
+        assert!(
+            memory.chunks.iter().all(|chunk| !chunk.is_empty()),
+            "Not all arenas sharing the chunk counter were merged"
+        );
+        let allocator = Allocator::merge(name, allocators, &mut memory);
+        Self { memory, allocator }
+    }
+
     /// Allocates a slice of the given size in the arena.
     pub fn alloc<'a>(&'a mut self, size: usize) -> ArenaSliceMut<'a> {
         self.allocator.allocate(&mut self.memory, size)
@@ -133,12 +173,54 @@ impl Arena {
         self.allocator.deallocate(&mut self.memory, pos, len);
     }
 
+    /// Total size of the memory chunks held by the arena.
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.memory.chunks.len() * CHUNK_SIZE
+    }
+
+    /// Fraction of the arena memory that is not used by active allocations,
+    /// i.e. freed regions not reused yet and the unused tail of the last
+    /// chunk. The allocator never returns memory, so only `MemTries::compact`
+    /// brings this down.
+    pub fn fragmentation(&self) -> f64 {
+        let memory_usage = self.memory_usage_bytes();
+        if memory_usage == 0 {
+            return 0.0;
+        }
+        1.0 - self.allocator.active_allocs_bytes() as f64 / memory_usage as f64
+    }
+
     /// Number of active allocations (alloc calls minus dealloc calls).
     #[cfg(test)]
     pub fn num_active_allocs(&self) -> usize {
         self.allocator.num_active_allocs()
     }
 
+    /// Writes the whole arena memory along with the allocator state, so that
+    /// the arena can be restored with `read_from`.
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        self.allocator.state().serialize(writer)?;
+        u32::try_from(self.memory.chunks.len()).unwrap().serialize(writer)?;
+        for chunk in &self.memory.chunks {
+            writer.write_all(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Restores an arena written with `write_to`.
+    pub fn read_from(name: String, reader: &mut impl Read) -> std::io::Result<Self> {
+        let state = AllocatorState::deserialize_reader(reader)?;
+        let num_chunks = u32::deserialize_reader(reader)?;
+        let mut memory = ArenaMemory::new();
+        for _ in 0..num_chunks {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            reader.read_exact(&mut chunk)?;
+            memory.chunks.push(chunk);
+        }
+        let allocator = Allocator::from_state(name, state, &memory)?;
+        Ok(Self { memory, allocator })
+    }
+
     pub fn memory(&self) -> &ArenaMemory {
         &self.memory
     }
@@ -0,0 +1,587 @@
+use super::arena::{Arena, ArenaMemory, ArenaPos};
+use super::metrics::{
+    MEM_TRIE_ARENA_FRAGMENTATION, MEM_TRIE_COMPACTION_FREED_BYTES, MEM_TRIE_NUM_COMPACTIONS,
+};
+use super::node::{InputMemTrieNode, MemTrieNodeId, MemTrieNodeView};
+use super::MemTries;
+use near_primitives::types::{BlockHeight, StateRoot};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+use tracing::info;
+
+impl MemTries {
+    /// Fraction of the arena memory not used by live nodes; see
+    /// `Arena::fragmentation`.
+    pub fn arena_fragmentation(&self) -> f64 {
+        let fragmentation = self.arena.fragmentation();
+        MEM_TRIE_ARENA_FRAGMENTATION
+            .with_label_values(&[&self.shard_uid.to_string()])
+            .set(fragmentation);
+        fragmentation
+    }
+
+    /// Starts moving all live nodes into a fresh arena, to release the old
+    /// one once they are all copied. The copying is done in bounded steps by
+    /// `compaction_step`, and the tries can be read and updated as usual in
+    /// between.
+    ///
+    /// The arena allocator only reuses freed memory for allocations of the
+    /// same size class, so after many updates and GCs a good part of the
+    /// arena may be taken by freed regions. Copying the live nodes packs them
+    /// densely again. Nodes shared between tries stay shared, refcounts are
+    /// kept and the roots are updated to point to the new copies.
+    ///
+    /// Until the compaction finishes, the copied nodes take memory on top of
+    /// the old arena, and GC is deferred so that no node is freed while it
+    /// may still be copied.
+    pub fn start_compaction(&mut self) {
+        if self.compaction.is_some() {
+            return;
+        }
+        info!(
+            target: "memtrie",
+            shard_uid = %self.shard_uid,
+            memory_usage = self.arena.memory_usage_bytes(),
+            fragmentation = self.arena.fragmentation(),
+            "Starting compaction of in-memory trie arena"
+        );
+        self.compaction = Some(Compaction::new(self.shard_uid.to_string()));
+    }
+
+    pub fn is_compacting(&self) -> bool {
+        self.compaction.is_some()
+    }
+
+    /// Copies up to `max_nodes` nodes of a compaction started by
+    /// `start_compaction` and, once all the tries are copied, switches them
+    /// over to the new arena. Returns whether there is no compaction left
+    /// to run.
+    pub fn compaction_step(&mut self, max_nodes: usize) -> bool {
+        let Some(compaction) = self.compaction.as_mut() else {
+            return true;
+        };
+        let dedup_index = &self.dedup_index;
+        let done = compaction.copy_nodes(
+            self.arena.memory(),
+            &self.heights,
+            &self.roots,
+            |node| dedup_index.as_ref().map_or(false, |index| index.contains_node(node)),
+            max_nodes,
+        );
+        if done {
+            self.finish_compaction();
+        }
+        done
+    }
+
+    /// Compacts the arena in one go; see `start_compaction`.
+    pub fn compact(&mut self) {
+        self.start_compaction();
+        while !self.compaction_step(usize::MAX) {}
+    }
+
+    /// Defers GC until the running compaction finishes, returning whether
+    /// there is one.
+    pub(super) fn defer_gc_during_compaction(&mut self, block_height: BlockHeight) -> bool {
+        let Some(compaction) = self.compaction.as_mut() else {
+            return false;
+        };
+        compaction.deferred_gc_height = compaction.deferred_gc_height.max(Some(block_height));
+        true
+    }
+
+    fn finish_compaction(&mut self) {
+        let Some(compaction) = self.compaction.take() else {
+            return;
+        };
+        let Compaction { mut new_arena, copied_roots, moved, deferred_gc_height, start, .. } =
+            compaction;
+        let shard_uid = self.shard_uid.to_string();
+        let memory_usage_before = self.arena.memory_usage_bytes();
+        let mut new_roots = HashMap::new();
+        for (state_root, ids) in &self.roots {
+            let mut new_ids = Vec::with_capacity(ids.len());
+            for id in ids {
+                let new_id = copied_roots[&id.pos];
+                // So far the copy is only referenced by its copied parents.
+                // Add back the references held by `MemTries` itself.
+                let refcount = id.refcount(self.arena.memory());
+                for _ in new_id.refcount(new_arena.memory())..refcount {
+                    new_id.add_ref(&mut new_arena);
+                }
+                new_ids.push(new_id);
+            }
+            new_roots.insert(*state_root, new_ids);
+        }
+        if let Some(dedup_index) = &self.dedup_index {
+            self.dedup_index = Some(dedup_index.relocate(&moved));
+        }
+        self.arena = new_arena;
+        self.roots = new_roots;
+        if let Some(block_height) = deferred_gc_height {
+            self.delete_until_height(block_height);
+        }
+
+        let memory_usage_after = self.arena.memory_usage_bytes();
+        let freed_bytes = memory_usage_before.saturating_sub(memory_usage_after);
+        MEM_TRIE_NUM_COMPACTIONS.with_label_values(&[&shard_uid]).inc();
+        MEM_TRIE_COMPACTION_FREED_BYTES.with_label_values(&[&shard_uid]).inc_by(freed_bytes as u64);
+        let fragmentation = self.arena_fragmentation();
+        info!(
+            target: "memtrie",
+            %shard_uid,
+            memory_usage_before,
+            memory_usage_after,
+            fragmentation,
+            "Compacted in-memory trie arena in {:?}",
+            start.elapsed()
+        );
+    }
+}
+
+/// State of a compaction of `MemTries` that is copied in steps.
+///
+/// Roots are copied one at a time in the order of their heights, so roots
+/// inserted while the compaction runs are picked up as well. A node
+/// referenced more than once when it is copied is only copied once. A node
+/// that gains references later, e.g. a subtree kept by an update applied in
+/// between two steps, is instead matched by its hash against the node at the
+/// same path in the roots copied at the closest lower height.
+pub(super) struct Compaction {
+    new_arena: Arena,
+    /// Nodes of the subtree being copied, from the root down.
+    stack: Vec<CopyFrame>,
+    /// Height of the root being copied.
+    height: BlockHeight,
+    /// Copies of the roots copied so far, by the position of the old root.
+    copied_roots: HashMap<ArenaPos, MemTrieNodeId>,
+    /// Copies of the roots copied so far, by height.
+    copied_heights: BTreeMap<BlockHeight, Vec<MemTrieNodeId>>,
+    /// New locations of the copied nodes that were referenced more than once
+    /// when they were copied, or that are in the dedup index.
+    moved: HashMap<ArenaPos, MemTrieNodeId>,
+    /// Height up to which GC was requested while the compaction runs.
+    deferred_gc_height: Option<BlockHeight>,
+    start: Instant,
+}
+
+/// A node being copied, waiting for the copies of its children.
+struct CopyFrame {
+    old: MemTrieNodeId,
+    /// The children of the node, each with the nodes at the same path in the
+    /// already copied tries.
+    children: Vec<(MemTrieNodeId, Vec<MemTrieNodeId>)>,
+    /// Copies of the first children.
+    copies: Vec<MemTrieNodeId>,
+}
+
+impl Compaction {
+    fn new(arena_name: String) -> Self {
+        Self {
+            new_arena: Arena::new(arena_name),
+            stack: Vec::new(),
+            height: 0,
+            copied_roots: HashMap::new(),
+            copied_heights: BTreeMap::new(),
+            moved: HashMap::new(),
+            deferred_gc_height: None,
+            start: Instant::now(),
+        }
+    }
+
+    /// Copies up to `max_nodes` nodes, returning whether all the roots have
+    /// been copied. The refcount of a copied root is 0, while every node
+    /// below it has the refcount equal to the number of its copied parents.
+    fn copy_nodes(
+        &mut self,
+        old_memory: &ArenaMemory,
+        heights: &BTreeMap<BlockHeight, Vec<StateRoot>>,
+        roots: &HashMap<StateRoot, Vec<MemTrieNodeId>>,
+        keep_track_of: impl Fn(MemTrieNodeId) -> bool,
+        mut max_nodes: usize,
+    ) -> bool {
+        while max_nodes > 0 {
+            let Some(frame) = self.stack.last() else {
+                let Some((height, root)) = self.next_root(heights, roots) else {
+                    return true;
+                };
+                let references = match self.copied_heights.range(..height).next_back() {
+                    Some((_, copied_roots)) => copied_roots.clone(),
+                    None => Vec::new(),
+                };
+                let frame = self.new_frame(old_memory, root, &references);
+                self.height = height;
+                self.stack.push(frame);
+                continue;
+            };
+            if let Some((child, references)) = frame.children.get(frame.copies.len()).cloned() {
+                match self.find_copy(old_memory, child, &references, &keep_track_of) {
+                    Some(copy) => self.stack.last_mut().unwrap().copies.push(copy),
+                    None => {
+                        let frame = self.new_frame(old_memory, child, &references);
+                        self.stack.push(frame);
+                    }
+                }
+                continue;
+            }
+            let frame = self.stack.pop().unwrap();
+            let old = frame.old;
+            let copy = self.copy_node(old_memory, frame);
+            max_nodes -= 1;
+            if old.refcount(old_memory) > 1 || keep_track_of(old) {
+                self.moved.insert(old.pos, copy);
+            }
+            match self.stack.last_mut() {
+                Some(parent) => parent.copies.push(copy),
+                None => {
+                    self.copied_roots.insert(old.pos, copy);
+                    self.copied_heights.entry(self.height).or_default().push(copy);
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns the lowest root that is not copied yet, with its height.
+    fn next_root(
+        &self,
+        heights: &BTreeMap<BlockHeight, Vec<StateRoot>>,
+        roots: &HashMap<StateRoot, Vec<MemTrieNodeId>>,
+    ) -> Option<(BlockHeight, MemTrieNodeId)> {
+        heights
+            .iter()
+            .flat_map(|(height, state_roots)| {
+                state_roots
+                    .iter()
+                    .flat_map(|state_root| roots.get(state_root).into_iter().flatten())
+                    .map(move |id| (*height, *id))
+            })
+            .find(|(_, id)| !self.copied_roots.contains_key(&id.pos))
+    }
+
+    /// Returns an already copied node that can be used as the copy of the
+    /// given node, if there is one.
+    fn find_copy(
+        &mut self,
+        old_memory: &ArenaMemory,
+        old: MemTrieNodeId,
+        references: &[MemTrieNodeId],
+        keep_track_of: impl Fn(MemTrieNodeId) -> bool,
+    ) -> Option<MemTrieNodeId> {
+        if let Some(copy) = self.moved.get(&old.pos) {
+            return Some(*copy);
+        }
+        if references.is_empty() {
+            return None;
+        }
+        let hash = old.as_ptr(old_memory).view().node_hash();
+        let new_memory = self.new_arena.memory();
+        let copy = references
+            .iter()
+            .copied()
+            .find(|reference| reference.as_ptr(new_memory).view().node_hash() == hash)?;
+        if keep_track_of(old) {
+            self.moved.insert(old.pos, copy);
+        }
+        Some(copy)
+    }
+
+    fn new_frame(
+        &self,
+        old_memory: &ArenaMemory,
+        old: MemTrieNodeId,
+        references: &[MemTrieNodeId],
+    ) -> CopyFrame {
+        let new_memory = self.new_arena.memory();
+        let children = match old.as_ptr(old_memory).view() {
+            MemTrieNodeView::Leaf { .. } => Vec::new(),
+            MemTrieNodeView::Extension { extension, child, .. } => {
+                let child_references = references
+                    .iter()
+                    .filter_map(|reference| match reference.as_ptr(new_memory).view() {
+                        MemTrieNodeView::Extension {
+                            extension: reference_extension,
+                            child,
+                            ..
+                        } if reference_extension.raw_slice() == extension.raw_slice() => {
+                            Some(child.id())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                vec![(child.id(), child_references)]
+            }
+            MemTrieNodeView::Branch { children, .. }
+            | MemTrieNodeView::BranchWithValue { children, .. } => (0..16)
+                .filter_map(|i| {
+                    let child = children.get(i)?;
+                    let child_references = references
+                        .iter()
+                        .filter_map(|reference| match reference.as_ptr(new_memory).view() {
+                            MemTrieNodeView::Branch { children, .. }
+                            | MemTrieNodeView::BranchWithValue { children, .. } => {
+                                children.get(i).map(|child| child.id())
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    Some((child.id(), child_references))
+                })
+                .collect(),
+        };
+        let copies = Vec::with_capacity(children.len());
+        CopyFrame { old, children, copies }
+    }
+
+    /// Creates the copy of the node once all its children are copied.
+    fn copy_node(&mut self, old_memory: &ArenaMemory, frame: CopyFrame) -> MemTrieNodeId {
+        let mut copies = frame.copies.into_iter();
+        let (input, hash) = match frame.old.as_ptr(old_memory).view() {
+            MemTrieNodeView::Leaf { extension, value } => (
+                InputMemTrieNode::Leaf {
+                    value: value.to_flat_value(),
+                    extension: extension.raw_slice().to_vec().into_boxed_slice(),
+                },
+                None,
+            ),
+            MemTrieNodeView::Extension { hash, extension, .. } => (
+                InputMemTrieNode::Extension {
+                    extension: extension.raw_slice().to_vec().into_boxed_slice(),
+                    child: copies.next().unwrap(),
+                },
+                Some(hash),
+            ),
+            MemTrieNodeView::Branch { hash, children, .. } => {
+                let mut new_children = [None; 16];
+                for i in 0..16 {
+                    if children.get(i).is_some() {
+                        new_children[i] = copies.next();
+                    }
+                }
+                (InputMemTrieNode::Branch { children: new_children }, Some(hash))
+            }
+            MemTrieNodeView::BranchWithValue { hash, children, value, .. } => {
+                let mut new_children = [None; 16];
+                for i in 0..16 {
+                    if children.get(i).is_some() {
+                        new_children[i] = copies.next();
+                    }
+                }
+                (
+                    InputMemTrieNode::BranchWithValue {
+                        children: new_children,
+                        value: value.to_flat_value(),
+                    },
+                    Some(hash),
+                )
+            }
+        };
+        match hash {
+            Some(hash) => MemTrieNodeId::new_with_hash(&mut self.new_arena, input, hash),
+            None => MemTrieNodeId::new(&mut self.new_arena, input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trie::mem::node::{MemTrieNodeId, MemTrieNodeView};
+    use crate::trie::mem::updating::{apply_memtrie_changes, TrackingMode};
+    use crate::trie::mem::MemTries;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::state::FlatStateValue;
+    use near_primitives::types::{BlockHeight, StateRoot};
+    use rand::Rng;
+
+    /// Applies a sequence of random updates, each based on the previous one.
+    /// All keys start with a zero nibble.
+    fn apply_random_updates(
+        tries: &mut MemTries,
+        mut state_root: StateRoot,
+        first_height: BlockHeight,
+        num_updates: u64,
+    ) -> Vec<StateRoot> {
+        let mut rng = rand::thread_rng();
+        let mut state_roots = Vec::new();
+        for height in first_height..first_height + num_updates {
+            let mut update = tries.update(state_root, TrackingMode::None).unwrap();
+            for _ in 0..50 {
+                let key = vec![rng.gen_range(0..8), rng.gen_range(0..8), rng.gen()];
+                if rng.gen_bool(0.3) {
+                    update.delete(&key);
+                } else {
+                    update.insert_memtrie_only(&key, FlatStateValue::Inlined(vec![rng.gen(); 20]));
+                }
+            }
+            let changes = update.to_mem_trie_changes_only();
+            state_root = apply_memtrie_changes(tries, &changes, height);
+            state_roots.push(state_root);
+        }
+        state_roots
+    }
+
+    /// Inserts a key starting with a non-zero nibble on top of the given trie.
+    fn insert_key(tries: &mut MemTries, state_root: StateRoot, height: BlockHeight) -> StateRoot {
+        let mut update = tries.update(state_root, TrackingMode::None).unwrap();
+        update.insert_memtrie_only(b"key", FlatStateValue::Inlined(b"value".to_vec()));
+        let changes = update.to_mem_trie_changes_only();
+        apply_memtrie_changes(tries, &changes, height)
+    }
+
+    fn set_keys(
+        tries: &mut MemTries,
+        state_root: StateRoot,
+        height: BlockHeight,
+        entries: &[(&[u8], &[u8])],
+    ) -> StateRoot {
+        let mut update = tries.update(state_root, TrackingMode::None).unwrap();
+        for (key, value) in entries {
+            update.insert_memtrie_only(key, FlatStateValue::Inlined(value.to_vec()));
+        }
+        let changes = update.to_mem_trie_changes_only();
+        apply_memtrie_changes(tries, &changes, height)
+    }
+
+    fn root_child(tries: &MemTries, state_root: &StateRoot, nibble: usize) -> MemTrieNodeId {
+        match tries.get_root(state_root).unwrap().view() {
+            MemTrieNodeView::Branch { children, .. } => children.get(nibble).unwrap().id(),
+            view => panic!("Expected a branch, got {:?}", view),
+        }
+    }
+
+    fn check_tries(tries: &MemTries, state_roots: &[StateRoot]) {
+        for state_root in state_roots {
+            if *state_root != CryptoHash::default() {
+                assert_eq!(tries.get_root(state_root).unwrap().view().node_hash(), *state_root);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compaction_keeps_tries_and_releases_memory() {
+        let mut tries = MemTries::new(ShardUId::single_shard());
+        let state_roots = apply_random_updates(&mut tries, CryptoHash::default(), 0, 200);
+        tries.delete_until_height(190);
+        let fragmentation = tries.arena_fragmentation();
+        let memory_usage = tries.arena.memory_usage_bytes();
+        let num_allocs = tries.arena.num_active_allocs();
+
+        tries.compact();
+        assert!(tries.arena_fragmentation() <= fragmentation);
+        assert!(tries.arena.memory_usage_bytes() <= memory_usage);
+        assert_eq!(tries.arena.num_active_allocs(), num_allocs);
+        assert_eq!(tries.num_roots(), 10);
+        check_tries(&tries, &state_roots[190..]);
+
+        // The compacted tries must keep working for updates and GC, and
+        // releasing all roots must release all nodes.
+        let more_state_roots = apply_random_updates(&mut tries, state_roots[199], 200, 50);
+        check_tries(&tries, &more_state_roots);
+        tries.delete_until_height(250);
+        assert_eq!(tries.num_roots(), 0);
+        assert_eq!(tries.arena.num_active_allocs(), 0);
+    }
+
+    #[test]
+    fn test_incremental_compaction() {
+        let mut tries = MemTries::new(ShardUId::single_shard());
+        tries.enable_node_deduplication();
+        let mut state_roots = apply_random_updates(&mut tries, CryptoHash::default(), 0, 100);
+        tries.delete_until_height(90);
+        let memory_usage = tries.arena.memory_usage_bytes();
+
+        // The tries are updated and garbage collected in between the steps,
+        // like after every block.
+        tries.start_compaction();
+        let mut height = 100;
+        while !tries.compaction_step(50) {
+            let state_root = *state_roots.last().unwrap();
+            state_roots.extend(apply_random_updates(&mut tries, state_root, height, 1));
+            height += 1;
+            tries.delete_until_height(height - 10);
+            check_tries(&tries, &state_roots[state_roots.len() - 10..]);
+        }
+        assert!(height > 101);
+        assert!(!tries.is_compacting());
+        // GC deferred during the compaction has been done.
+        assert_eq!(tries.num_roots(), 10);
+        check_tries(&tries, &state_roots[state_roots.len() - 10..]);
+        assert!(tries.arena.memory_usage_bytes() <= memory_usage);
+
+        // All the nodes in the new arena are reachable from the roots, so
+        // compacting it again copies all of them.
+        let num_allocs = tries.arena.num_active_allocs();
+        tries.compact();
+        assert_eq!(tries.arena.num_active_allocs(), num_allocs);
+
+        let more_state_roots =
+            apply_random_updates(&mut tries, *state_roots.last().unwrap(), height, 20);
+        check_tries(&tries, &more_state_roots);
+        tries.delete_until_height(height + 20);
+        assert_eq!(tries.num_roots(), 0);
+        assert_eq!(tries.arena.num_active_allocs(), 0);
+        assert_eq!(tries.dedup_index.as_ref().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_node_deduplication() {
+        let mut tries = MemTries::new(ShardUId::single_shard());
+        tries.enable_node_deduplication();
+        let state_roots = apply_random_updates(&mut tries, CryptoHash::default(), 0, 20);
+
+        // The new leaf hangs right below the root in both tries, so it is the
+        // same node in both.
+        let first = insert_key(&mut tries, state_roots[18], 20);
+        let second = insert_key(&mut tries, state_roots[19], 21);
+        assert_eq!(root_child(&tries, &first, 6), root_child(&tries, &second, 6));
+
+        // Applying the same change again, like on another fork, reuses the
+        // whole trie.
+        let num_allocs = tries.arena.num_active_allocs();
+        assert_eq!(insert_key(&mut tries, state_roots[18], 22), first);
+        assert_eq!(tries.arena.num_active_allocs(), num_allocs);
+
+        // Compaction keeps the nodes shared and the index usable.
+        tries.compact();
+        check_tries(&tries, &state_roots);
+        assert_eq!(root_child(&tries, &first, 6), root_child(&tries, &second, 6));
+        let third = insert_key(&mut tries, state_roots[17], 23);
+        assert_eq!(root_child(&tries, &first, 6), root_child(&tries, &third, 6));
+
+        // Once all the tries are gone, so are all the nodes in the index.
+        tries.delete_until_height(24);
+        assert_eq!(tries.num_roots(), 0);
+        assert_eq!(tries.arena.num_active_allocs(), 0);
+        assert_eq!(tries.dedup_index.as_ref().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_node_deduplication_after_revert() {
+        let (c, d, y): (&[u8], &[u8], &[u8]) = (&[0x10, 0], &[0x11, 0], &[0x20, 0]);
+        let mut tries = MemTries::new(ShardUId::single_shard());
+        // The nodes of the initial trie are not indexed, like the ones loaded
+        // from flat storage.
+        let base =
+            set_keys(&mut tries, CryptoHash::default(), 0, &[(c, b"0"), (d, b"0"), (y, b"0")]);
+        tries.enable_node_deduplication();
+
+        // The first update changes `d`, the second one changes `c` and the
+        // third one reverts it, so the leaf of `c` is recreated with the hash
+        // of the unindexed leaf of the initial trie, while its parent has the
+        // hash of the indexed parent in the first trie.
+        let first = set_keys(&mut tries, base, 1, &[(d, b"1")]);
+        let second = set_keys(&mut tries, first, 2, &[(c, b"1")]);
+        let num_allocs = tries.arena.num_active_allocs();
+        let third = set_keys(&mut tries, second, 3, &[(c, b"0"), (y, b"1")]);
+        assert_eq!(root_child(&tries, &first, 1), root_child(&tries, &third, 1));
+        // Only the new root and the new leaf of `y` are allocated.
+        assert_eq!(tries.arena.num_active_allocs(), num_allocs + 2);
+
+        // Nothing is left behind once all the tries are gone.
+        tries.delete_until_height(4);
+        assert_eq!(tries.num_roots(), 0);
+        assert_eq!(tries.arena.num_active_allocs(), 0);
+        assert_eq!(tries.dedup_index.as_ref().unwrap().len(), 0);
+    }
+}
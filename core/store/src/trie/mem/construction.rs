@@ -160,7 +160,13 @@ impl<'a> TrieConstructor<'a> {
     /// Adds a leaf to the trie. The key must be greater than all previous keys
     /// inserted.
     pub fn add_leaf(&mut self, key: &[u8], value: FlatStateValue) {
-        let mut nibbles = NibbleSlice::new(key);
+        self.add_leaf_nibbles(NibbleSlice::new(key), value)
+    }
+
+    /// Like `add_leaf`, but the key is given as nibbles. This allows building
+    /// the subtree below some nibble prefix, by passing the keys with that
+    /// prefix stripped.
+    pub fn add_leaf_nibbles(&mut self, mut nibbles: NibbleSlice, value: FlatStateValue) {
         let mut i = 0;
         // We'll go down the segments to find where our nibbles deviate.
         // If the deviation happens in the middle of a segment, we would split
@@ -0,0 +1,61 @@
+use super::arena::ArenaPos;
+use super::node::MemTrieNodeId;
+use near_primitives::hash::CryptoHash;
+use std::collections::HashMap;
+
+/// Index of in-memory trie nodes by hash, used to share identical nodes
+/// between tries instead of allocating them again.
+///
+/// Only nodes created by applying updates are indexed: those are the nodes
+/// that get recreated when the same chunk is applied on different forks or
+/// when a change is reverted, while indexing the whole loaded trie would
+/// take as much memory as a good part of the trie itself. Roots are never
+/// indexed, so that the refcount of a root node only counts the references
+/// held by `MemTries`.
+///
+/// An indexed node must be removed from the index when it is deallocated.
+#[derive(Default)]
+pub(crate) struct NodeDedupIndex {
+    by_hash: HashMap<CryptoHash, MemTrieNodeId>,
+    /// Reverse mapping, so that deallocated nodes can be removed without
+    /// recomputing their hashes.
+    by_pos: HashMap<ArenaPos, CryptoHash>,
+}
+
+impl NodeDedupIndex {
+    pub fn get(&self, hash: &CryptoHash) -> Option<MemTrieNodeId> {
+        self.by_hash.get(hash).copied()
+    }
+
+    pub fn insert(&mut self, hash: CryptoHash, node: MemTrieNodeId) {
+        if let Some(old_node) = self.by_hash.insert(hash, node) {
+            self.by_pos.remove(&old_node.pos);
+        }
+        self.by_pos.insert(node.pos, hash);
+    }
+
+    /// Removes the node from the index, if it is there.
+    pub fn remove_node(&mut self, node: MemTrieNodeId) {
+        if let Some(hash) = self.by_pos.remove(&node.pos) {
+            self.by_hash.remove(&hash);
+        }
+    }
+
+    pub fn contains_node(&self, node: MemTrieNodeId) -> bool {
+        self.by_pos.contains_key(&node.pos)
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_hash.len()
+    }
+
+    /// Builds the index for nodes moved to a different arena, given where
+    /// each indexed node was moved.
+    pub fn relocate(&self, moved: &HashMap<ArenaPos, MemTrieNodeId>) -> Self {
+        let mut relocated = Self::default();
+        for (pos, hash) in &self.by_pos {
+            relocated.insert(*hash, moved[pos]);
+        }
+        relocated
+    }
+}
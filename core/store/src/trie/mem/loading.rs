@@ -1,88 +1,44 @@
 use super::node::MemTrieNodeId;
+use super::parallel_loader::ParallelMemTrieLoader;
 use super::MemTries;
 use crate::flat::store_helper::{
-    decode_flat_state_db_key, get_all_deltas_metadata, get_delta_changes, get_flat_storage_status,
+    get_all_deltas_metadata, get_delta_changes, get_flat_storage_status,
 };
-use crate::flat::{FlatStorageError, FlatStorageStatus};
-use crate::trie::mem::construction::TrieConstructor;
-use crate::trie::mem::updating::apply_memtrie_changes;
+use crate::flat::{BlockInfo, FlatStorageStatus};
+use crate::trie::mem::snapshot::load_mem_tries_snapshot;
+use crate::trie::mem::updating::{apply_memtrie_changes, TrackingMode};
 use crate::{DBCol, Store};
 use near_primitives::errors::StorageError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
-use near_primitives::state::FlatStateValue;
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::BlockHeight;
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use std::collections::BTreeSet;
+use std::path::Path;
 use std::time::Instant;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// Loads a trie from the FlatState column. The returned `MemTries` contains
-/// exactly one trie root.
+/// exactly one trie root, unless the trie is empty. The loading is spread
+/// over the threads of the current rayon pool, see `ParallelMemTrieLoader`.
 pub fn load_trie_from_flat_state(
     store: &Store,
     shard_uid: ShardUId,
     state_root: CryptoHash,
     block_height: BlockHeight,
 ) -> Result<MemTries, StorageError> {
-    let mut tries = MemTries::new(shard_uid);
-
-    tries.construct_root(block_height, |arena| -> Result<Option<MemTrieNodeId>, StorageError> {
-        info!(target: "memtrie", shard_uid=%shard_uid, "Loading trie from flat state...");
-        let load_start = Instant::now();
-        let mut recon = TrieConstructor::new(arena);
-        let mut num_keys_loaded = 0;
-        for item in store
-            .iter_prefix_ser::<FlatStateValue>(DBCol::FlatState, &borsh::to_vec(&shard_uid).unwrap())
-        {
-            let (key, value) = item.map_err(|err| {
-                FlatStorageError::StorageInternalError(format!("Error iterating over FlatState: {err}"))
-            })?;
-            let (_, key) = decode_flat_state_db_key(&key).map_err(|err| {
-                FlatStorageError::StorageInternalError(format!(
-                    "invalid FlatState key format: {err}"
-                ))})?;
-            recon.add_leaf(&key, value);
-            num_keys_loaded += 1;
-            if num_keys_loaded % 1000000 == 0 {
-                debug!(
-                    target: "memtrie",
-                    %shard_uid,
-                    "Loaded {} keys, current key: {}",
-                    num_keys_loaded,
-                    hex::encode(&key)
-                );
-            }
-        }
-        let root_id = match recon.finalize() {
-            Some(root_id) => root_id,
-            None => {
-                info!(target: "memtrie", shard_uid=%shard_uid, "No keys loaded, trie is empty");
-                return Ok(None);
-            }
-        };
-
-        debug!(
-            target: "memtrie",
-            %shard_uid,
-            "Loaded {} keys; computing hash and memory usage...",
-            num_keys_loaded
-        );
-        let mut subtrees = Vec::new();
-        root_id.as_ptr_mut(arena.memory_mut()).take_small_subtrees(1024 * 1024, &mut subtrees);
-        subtrees.into_par_iter().for_each(|mut subtree| {
-            subtree.compute_hash_recursively();
-        });
-        root_id.as_ptr_mut(arena.memory_mut()).compute_hash_recursively();
-        info!(target: "memtrie", shard_uid=%shard_uid, "Done loading trie from flat state, took {:?}", load_start.elapsed());
-
-        let root = root_id.as_ptr(arena.memory());
-        assert_eq!(
-            root.view().node_hash(), state_root,
-            "In-memory trie for shard {} has incorrect state root", shard_uid);
-        Ok(Some(root.id()))
+    info!(target: "memtrie", shard_uid=%shard_uid, "Loading trie from flat state...");
+    let load_start = Instant::now();
+    let (arena, root_id) =
+        ParallelMemTrieLoader::new(store.clone(), shard_uid, state_root).load()?;
+    if root_id.is_none() {
+        info!(target: "memtrie", shard_uid=%shard_uid, "Trie is empty");
+    }
+    let mut tries = MemTries::new_from_arena(shard_uid, arena);
+    tries.construct_root(block_height, |_| -> Result<Option<MemTrieNodeId>, StorageError> {
+        Ok(root_id)
     })?;
+    info!(target: "memtrie", shard_uid=%shard_uid, "Done loading trie from flat state, took {:?}", load_start.elapsed());
     Ok(tries)
 }
 
@@ -108,14 +64,49 @@ fn get_state_root(
     Ok(*chunk_extra.state_root())
 }
 
+/// Loads the tries from the snapshot in `snapshot_dir` if it was taken at the
+/// given flat head, otherwise rebuilds the trie from the FlatState column.
+fn load_trie_from_snapshot_or_flat_state(
+    store: &Store,
+    snapshot_dir: &Path,
+    shard_uid: ShardUId,
+    flat_head: &BlockInfo,
+) -> Result<MemTries, StorageError> {
+    let load_start = Instant::now();
+    match load_mem_tries_snapshot(snapshot_dir, shard_uid, flat_head.hash) {
+        Ok(Some(mem_tries)) => {
+            info!(target: "memtrie", %shard_uid, "Loaded memtrie snapshot, took {:?}", load_start.elapsed());
+            return Ok(mem_tries);
+        }
+        Ok(None) => {
+            info!(target: "memtrie", %shard_uid, "No memtrie snapshot found");
+        }
+        Err(err) => {
+            warn!(target: "memtrie", %shard_uid, %err, "Cannot use memtrie snapshot, rebuilding from flat state");
+        }
+    }
+    load_trie_from_flat_state(
+        store,
+        shard_uid,
+        get_state_root(store, flat_head.hash, shard_uid)?,
+        flat_head.height,
+    )
+}
+
 /// Constructs in-memory tries for the given shard, so that they represent the
 /// same information as the flat storage, including the final state and the
 /// deltas. The returned tries would contain a root for each block that the
 /// flat storage currently has, i.e. one for the final block, and one for each
 /// block that flat storage has a delta for, possibly in more than one fork.
+///
+/// If `snapshot_dir` is given and contains a snapshot of the tries taken at
+/// the current flat head, the tries are restored from it instead of being
+/// rebuilt from flat storage, and only the deltas missing from the snapshot
+/// are applied.
 pub fn load_trie_from_flat_state_and_delta(
     store: &Store,
     shard_uid: ShardUId,
+    snapshot_dir: Option<&Path>,
 ) -> Result<MemTries, StorageError> {
     debug!(target: "memtrie", %shard_uid, "Loading base trie from flat state...");
     let flat_head = match get_flat_storage_status(&store, shard_uid)? {
@@ -128,13 +119,18 @@ pub fn load_trie_from_flat_state_and_delta(
         }
     };
 
-    let mut mem_tries = load_trie_from_flat_state(
-        &store,
-        shard_uid,
-        get_state_root(store, flat_head.hash, shard_uid)?,
-        flat_head.height,
-    )
-    .unwrap();
+    let mut mem_tries = match snapshot_dir {
+        Some(snapshot_dir) => {
+            load_trie_from_snapshot_or_flat_state(store, snapshot_dir, shard_uid, &flat_head)?
+        }
+        None => load_trie_from_flat_state(
+            &store,
+            shard_uid,
+            get_state_root(store, flat_head.hash, shard_uid)?,
+            flat_head.height,
+        )
+        .unwrap(),
+    };
 
     debug!(target: "memtrie", %shard_uid, "Loading flat state deltas...");
     // We load the deltas in order of height, so that we always have the previous state root
@@ -150,8 +146,12 @@ pub fn load_trie_from_flat_state_and_delta(
         if let Some(changes) = delta {
             let old_state_root = get_state_root(store, prev_hash, shard_uid)?;
             let new_state_root = get_state_root(store, hash, shard_uid)?;
+            if mem_tries.has_root_at_height(height, &new_state_root) {
+                // Already restored from the snapshot.
+                continue;
+            }
 
-            let mut trie_update = mem_tries.update(old_state_root, false)?;
+            let mut trie_update = mem_tries.update(old_state_root, TrackingMode::None)?;
             for (key, value) in changes.0 {
                 match value {
                     Some(value) => {
@@ -184,6 +184,7 @@ mod tests {
     };
     use crate::trie::mem::loading::load_trie_from_flat_state;
     use crate::trie::mem::lookup::memtrie_lookup;
+    use crate::trie::mem::snapshot::save_mem_tries_snapshot;
     use crate::trie::OptimizedValueRef;
     use crate::{DBCol, KeyLookupMode, NibbleSlice, ShardTries, Store, Trie, TrieUpdate};
     use near_primitives::hash::CryptoHash;
@@ -215,6 +216,18 @@ mod tests {
                 .unwrap();
         eprintln!("In memory trie loaded");
 
+        // The loaded trie must not depend on how the loading is split between
+        // threads; loading checks the hashes of all the parts it stitches.
+        for num_threads in [1, 4] {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
+            let tries = pool
+                .install(|| {
+                    load_trie_from_flat_state(&shard_tries.get_store(), shard_uid, state_root, 123)
+                })
+                .unwrap();
+            assert_eq!(tries.num_roots(), in_memory_trie.num_roots());
+        }
+
         if keys.is_empty() {
             assert_eq!(in_memory_trie.num_roots(), 0);
             return;
@@ -462,7 +475,7 @@ mod tests {
         // Load into memory. It should load the base flat state (block 0), plus all
         // four deltas. We'll check against the state roots at each block; they should
         // all exist in the loaded memtrie.
-        let mem_tries = load_trie_from_flat_state_and_delta(&store, shard_uid).unwrap();
+        let mem_tries = load_trie_from_flat_state_and_delta(&store, shard_uid, None).unwrap();
 
         assert_eq!(
             memtrie_lookup(mem_tries.get_root(&state_root_0).unwrap(), &test_key.to_vec(), None),
@@ -484,6 +497,18 @@ mod tests {
             memtrie_lookup(mem_tries.get_root(&state_root_4).unwrap(), &test_key.to_vec(), None),
             Some(FlatStateValue::inlined(&test_val4))
         );
+
+        // Restoring from a snapshot taken at the flat head must yield the same
+        // roots, without applying the deltas already present in the snapshot.
+        let snapshot_dir = tempfile::tempdir().unwrap();
+        save_mem_tries_snapshot(snapshot_dir.path(), &mem_tries, chain.get_block(0).hash).unwrap();
+        let restored_mem_tries =
+            load_trie_from_flat_state_and_delta(&store, shard_uid, Some(snapshot_dir.path()))
+                .unwrap();
+        assert_eq!(restored_mem_tries.num_roots(), mem_tries.num_roots());
+        for state_root in [state_root_0, state_root_1, state_root_2, state_root_3, state_root_4] {
+            assert!(restored_mem_tries.get_root(&state_root).is_some());
+        }
     }
 
     /// Makes the given changes to both the trie and flat storage.
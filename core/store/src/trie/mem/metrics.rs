@@ -1,6 +1,6 @@
 use near_o11y::metrics::{
-    try_create_int_counter, try_create_int_counter_vec, try_create_int_gauge_vec, IntCounter,
-    IntCounterVec, IntGaugeVec,
+    try_create_gauge_vec, try_create_int_counter, try_create_int_counter_vec,
+    try_create_int_gauge_vec, GaugeVec, IntCounter, IntCounterVec, IntGaugeVec,
 };
 use once_cell::sync::Lazy;
 
@@ -29,3 +29,75 @@ pub static MEM_TRIE_NUM_LOOKUPS: Lazy<IntCounter> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+pub static MEM_TRIE_LOADING_NUM_KEYS_LOADED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_mem_trie_loading_num_keys_loaded",
+        "Number of keys loaded from flat storage so far while loading the in-memory trie",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub static MEM_TRIE_LOADING_NUM_SUBTREES_TOTAL: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_mem_trie_loading_num_subtrees_total",
+        "Number of subtrees the in-memory trie is split into for parallel loading",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub static MEM_TRIE_LOADING_NUM_SUBTREES_LOADED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_mem_trie_loading_num_subtrees_loaded",
+        "Number of subtrees of the in-memory trie loaded so far",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub static MEM_TRIE_NUM_NODES_DEDUPLICATED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_mem_trie_num_nodes_deduplicated",
+        "Number of trie nodes from updates that reused an identical existing node",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub static MEM_TRIE_DEDUP_INDEX_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_mem_trie_dedup_index_size",
+        "Number of nodes in the index used to deduplicate in-memory trie nodes",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub static MEM_TRIE_NUM_COMPACTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_mem_trie_num_compactions",
+        "Number of times the in-memory trie was moved into a fresh arena",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub static MEM_TRIE_COMPACTION_FREED_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_mem_trie_compaction_freed_bytes",
+        "Arena memory released by compactions of the in-memory trie",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub static MEM_TRIE_ARENA_FRAGMENTATION: Lazy<GaugeVec> = Lazy::new(|| {
+    try_create_gauge_vec(
+        "near_mem_trie_arena_fragmentation",
+        "Fraction of the in-memory trie arena not used by live nodes",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
@@ -1,7 +1,9 @@
 use self::arena::Arena;
-use self::metrics::MEM_TRIE_NUM_ROOTS;
+use self::compaction::Compaction;
+use self::dedup::NodeDedupIndex;
+use self::metrics::{MEM_TRIE_DEDUP_INDEX_SIZE, MEM_TRIE_NUM_ROOTS};
 use self::node::{MemTrieNodeId, MemTrieNodePtr};
-use self::updating::MemTrieUpdate;
+use self::updating::{MemTrieUpdate, TrackingMode};
 use near_primitives::errors::StorageError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::ShardUId;
@@ -9,12 +11,16 @@ use near_primitives::types::{BlockHeight, StateRoot};
 use std::collections::{BTreeMap, HashMap};
 
 mod arena;
+mod compaction;
 mod construction;
+mod dedup;
 mod flexible_data;
 pub mod loading;
 pub mod lookup;
 pub mod metrics;
 pub mod node;
+pub mod parallel_loader;
+pub mod snapshot;
 pub mod updating;
 
 /// Check this, because in the code we conveniently assume usize is 8 bytes.
@@ -27,19 +33,28 @@ compile_error!("In-memory trie requires a 64 bit platform");
 /// refcounting works is very similar to as if each node held a Rc of
 /// its children nodes. The `roots` field of this struct logically
 /// holds an Rc of the root of each trie.
+///
+/// By default nodes are only shared where an update keeps a subtree of the
+/// old trie, so identical nodes created by different updates are stored
+/// multiple times. `enable_node_deduplication` makes updates reuse such
+/// nodes instead, see `NodeDedupIndex`.
 pub struct MemTries {
     arena: Arena,
     /// Maps a state root to a list of nodes that have the same root hash.
-    /// The reason why this is a list is because we do not have a node
-    /// deduplication mechanism so we can't guarantee that nodes of the
-    /// same hash are unique. During lookup, any of these nodes can be provided
-    /// as they all logically represent the same trie.
+    /// The reason why this is a list is because without node deduplication
+    /// we can't guarantee that nodes of the same hash are unique. During
+    /// lookup, any of these nodes can be provided as they all logically
+    /// represent the same trie.
     roots: HashMap<StateRoot, Vec<MemTrieNodeId>>,
     /// Maps a block height to a list of state roots present at that height.
     /// This is used for GC. The invariant is that for any state root, the
     /// number of times the state root appears in this map is equal to the
     /// sum of the refcounts of each `MemTrieNodeId`s in `roots[state hash]`.
     heights: BTreeMap<BlockHeight, Vec<StateRoot>>,
+    /// Index of the nodes created by updates, if node deduplication is enabled.
+    dedup_index: Option<NodeDedupIndex>,
+    /// Compaction of the arena in progress, see `start_compaction`.
+    compaction: Option<Compaction>,
     /// Shard UID, for exporting metrics only.
     shard_uid: ShardUId,
 }
@@ -50,10 +65,34 @@ impl MemTries {
             arena: Arena::new(shard_uid.to_string()),
             roots: HashMap::new(),
             heights: Default::default(),
+            dedup_index: None,
+            compaction: None,
             shard_uid,
         }
     }
 
+    /// Creates `MemTries` owning the given arena, which may already contain
+    /// nodes that are not yet referenced by any root.
+    pub(crate) fn new_from_arena(shard_uid: ShardUId, arena: Arena) -> Self {
+        Self {
+            arena,
+            roots: HashMap::new(),
+            heights: Default::default(),
+            dedup_index: None,
+            compaction: None,
+            shard_uid,
+        }
+    }
+
+    /// Makes updates applied from now on reuse identical nodes created by
+    /// earlier updates instead of allocating them again. Nodes that already
+    /// exist, e.g. the ones loaded from flat storage, are not deduplicated.
+    pub fn enable_node_deduplication(&mut self) {
+        if self.dedup_index.is_none() {
+            self.dedup_index = Some(NodeDedupIndex::default());
+        }
+    }
+
     /// Inserts a new root into the trie. The given function should perform
     /// the entire construction of the new trie, possibly based on some existing
     /// trie nodes. This internally takes care of refcounting.
@@ -100,7 +139,13 @@ impl MemTries {
     /// `block_height`. This internally manages refcounts. If a trie root
     /// is expired but is still used at a higher height, it will still be
     /// valid until all references to that root expires.
+    ///
+    /// While the arena is being compacted, the roots are only deleted once
+    /// the compaction finishes.
     pub fn delete_until_height(&mut self, block_height: BlockHeight) {
+        if self.defer_gc_during_compaction(block_height) {
+            return;
+        }
         let mut to_delete = vec![];
         self.heights.retain(|height, state_roots| {
             if *height < block_height {
@@ -120,7 +165,13 @@ impl MemTries {
     fn delete_root(&mut self, state_root: &CryptoHash) {
         if let Some(ids) = self.roots.get_mut(state_root) {
             let last_id = ids.last().unwrap();
-            let new_ref = last_id.remove_ref(&mut self.arena);
+            let new_ref = match self.dedup_index.as_mut() {
+                Some(dedup_index) => last_id
+                    .remove_ref_with_dealloc_hook(&mut self.arena, &mut |node| {
+                        dedup_index.remove_node(node)
+                    }),
+                None => last_id.remove_ref(&mut self.arena),
+            };
             if new_ref == 0 {
                 ids.pop();
                 if ids.is_empty() {
@@ -133,6 +184,23 @@ impl MemTries {
         MEM_TRIE_NUM_ROOTS
             .with_label_values(&[&self.shard_uid.to_string()])
             .set(self.roots.len() as i64);
+        self.update_dedup_index_size_metric();
+    }
+
+    fn update_dedup_index_size_metric(&self) {
+        if let Some(dedup_index) = &self.dedup_index {
+            MEM_TRIE_DEDUP_INDEX_SIZE
+                .with_label_values(&[&self.shard_uid.to_string()])
+                .set(dedup_index.len() as i64);
+        }
+    }
+
+    /// Returns whether the given state root was inserted at the given height
+    /// and was not expired yet.
+    pub fn has_root_at_height(&self, block_height: BlockHeight, state_root: &StateRoot) -> bool {
+        self.heights
+            .get(&block_height)
+            .map_or(false, |state_roots| state_roots.contains(state_root))
     }
 
     /// Used for unit testing and integration testing.
@@ -143,7 +211,7 @@ impl MemTries {
     pub fn update(
         &self,
         root: CryptoHash,
-        track_disk_changes: bool,
+        mode: TrackingMode,
     ) -> Result<MemTrieUpdate, StorageError> {
         let root_id = if root == CryptoHash::default() {
             None
@@ -159,12 +227,7 @@ impl MemTries {
                 .id();
             Some(root_id)
         };
-        Ok(MemTrieUpdate::new(
-            root_id,
-            &self.arena.memory(),
-            self.shard_uid.to_string(),
-            track_disk_changes,
-        ))
+        Ok(MemTrieUpdate::new(root_id, &self.arena.memory(), self.shard_uid.to_string(), mode))
     }
 }
 
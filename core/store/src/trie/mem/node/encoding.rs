@@ -1,5 +1,5 @@
 use super::{InputMemTrieNode, MemTrieNodeId, MemTrieNodePtr, MemTrieNodeView};
-use crate::trie::mem::arena::{Arena, ArenaPos};
+use crate::trie::mem::arena::{Arena, ArenaMemory, ArenaPos};
 use crate::trie::mem::flexible_data::children::EncodedChildrenHeader;
 use crate::trie::mem::flexible_data::encoding::{BorshFixedSize, RawDecoder, RawEncoder};
 use crate::trie::mem::flexible_data::extension::EncodedExtensionHeader;
@@ -246,9 +246,24 @@ impl MemTrieNodeId {
         new_refcount
     }
 
+    /// Returns the current refcount.
+    pub(crate) fn refcount(&self, arena: &ArenaMemory) -> u32 {
+        self.as_ptr(arena).decoder().peek::<CommonHeader>().refcount
+    }
+
     /// Decrements the refcount, deallocating the node if it reaches zero.
     /// Returns the new refcount.
     pub(crate) fn remove_ref(&self, arena: &mut Arena) -> u32 {
+        self.remove_ref_with_dealloc_hook(arena, &mut |_| {})
+    }
+
+    /// Like `remove_ref`, but also calls `on_dealloc` for every node that gets
+    /// deallocated as a result, including descendants of this node.
+    pub(crate) fn remove_ref_with_dealloc_hook(
+        &self,
+        arena: &mut Arena,
+        on_dealloc: &mut impl FnMut(MemTrieNodeId),
+    ) -> u32 {
         let mut ptr = self.as_ptr_mut(arena.memory_mut());
         let mut decoder = ptr.decoder_mut();
         let mut header = decoder.peek::<CommonHeader>();
@@ -263,8 +278,9 @@ impl MemTrieNodeId {
             }
             let alloc_size = node_ptr.size_of_allocation();
             arena.dealloc(self.pos, alloc_size);
+            on_dealloc(*self);
             for child in children_to_unref.iter() {
-                MemTrieNodeId { pos: *child }.remove_ref(arena);
+                MemTrieNodeId { pos: *child }.remove_ref_with_dealloc_hook(arena, on_dealloc);
             }
         }
         new_refcount
@@ -15,28 +15,12 @@ impl<'a> MemTrieNodePtrMut<'a> {
         RawDecoderMut::new(self.ptr.ptr_mut())
     }
 
-    /// Obtains a list of mutable references to the children of this node,
-    /// destroying this mutable reference.
+    /// Obtains a list of mutable references to the children of this node.
     ///
     /// Despite being implemented with unsafe code, this is a safe operation
     /// because the children subtrees are disjoint (even if there are multiple
-    /// roots). It is very similar to `split_at_mut` on mutable slices.
-    fn split_children_mut(mut self) -> Vec<MemTrieNodePtrMut<'a>> {
-        let arena_mut = self.ptr.arena_mut() as *mut ArenaMemory;
-        let mut result = Vec::new();
-        let view = self.as_const().view();
-        for child in view.iter_children() {
-            let child_id = child.id();
-            let arena_mut_ref = unsafe { &mut *arena_mut };
-            result.push(child_id.as_ptr_mut(arena_mut_ref));
-        }
-        result
-    }
-
-    /// Like `split_children_mut`, but does not destroy the reference itself.
-    /// This is possible because of the returned references can only be used
-    /// while this reference is being mutably held, but it does result in a
-    /// different lifetime.
+    /// roots). The returned references can only be used while this reference
+    /// is being mutably held.
     fn children_mut<'b>(&'b mut self) -> Vec<MemTrieNodePtrMut<'b>> {
         let arena_mut = self.ptr.arena_mut() as *mut ArenaMemory;
         let mut result = Vec::new();
@@ -84,22 +68,4 @@ impl<'a> MemTrieNodePtrMut<'a> {
         }
         self.compute_hash();
     }
-
-    /// Recursively expand the current subtree until we arrive at subtrees
-    /// that are small enough (by memory usage); we store these subtrees in
-    /// the provided vector. The returned subtrees cover all leaves but are
-    /// disjoint.
-    pub(crate) fn take_small_subtrees(
-        self,
-        threshold_memory_usage: u64,
-        trees: &mut Vec<MemTrieNodePtrMut<'a>>,
-    ) {
-        if self.as_const().view().memory_usage() < threshold_memory_usage {
-            trees.push(self);
-        } else {
-            for child in self.split_children_mut() {
-                child.take_small_subtrees(threshold_memory_usage, trees);
-            }
-        }
-    }
 }
@@ -0,0 +1,352 @@
+use super::arena::Arena;
+use super::construction::TrieConstructor;
+use super::metrics::{
+    MEM_TRIE_LOADING_NUM_KEYS_LOADED, MEM_TRIE_LOADING_NUM_SUBTREES_LOADED,
+    MEM_TRIE_LOADING_NUM_SUBTREES_TOTAL,
+};
+use super::node::{InputMemTrieNode, MemTrieNodeId};
+use crate::flat::store_helper::{decode_flat_state_db_key, encode_flat_state_db_key};
+use crate::trie::Children;
+use crate::{DBCol, NibbleSlice, RawTrieNode, RawTrieNodeWithSize, Store, TrieCachingStorage};
+use borsh::BorshDeserialize;
+use near_o11y::metrics::IntGauge;
+use near_primitives::errors::StorageError;
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::state::FlatStateValue;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::debug;
+
+/// How many subtrees per thread the trie is split into. Subtrees are only
+/// roughly equal in size, so having more of them than threads keeps all the
+/// threads busy until the end.
+const NUM_SUBTREES_PER_THREAD: u64 = 16;
+
+/// Loads a trie from the FlatState column using all threads of the current
+/// rayon pool.
+///
+/// The top of the trie is first read from the State column, descending until
+/// the remaining subtrees are small enough by memory usage. Each such subtree
+/// contains exactly the FlatState keys starting with its nibble path, so it
+/// can be built from a single range of the column. Each thread builds the
+/// subtrees it picks up into its own arena; the arenas share a chunk counter
+/// so they can be merged without relocating any node. The top nodes are then
+/// constructed in the merged arena on top of the loaded subtrees.
+///
+/// Every subtree and the final root are checked against the hashes from the
+/// State column, so an inconsistency between flat storage and the trie is
+/// reported as an error.
+pub struct ParallelMemTrieLoader {
+    store: Store,
+    shard_uid: ShardUId,
+    root: CryptoHash,
+}
+
+/// The part of the trie above the subtrees that are loaded in parallel.
+enum TrieLoadingPlanNode {
+    Branch {
+        children: Vec<(u8, TrieLoadingPlanNode)>,
+        value: Option<FlatStateValue>,
+    },
+    Extension {
+        extension: Box<[u8]>,
+        child: Box<TrieLoadingPlanNode>,
+    },
+    /// Index of the subtree in the list of subtrees to load.
+    Subtree(usize),
+}
+
+struct SubtreeToLoad {
+    /// Nibbles of the path from the root to the subtree.
+    path: Vec<u8>,
+    hash: CryptoHash,
+    memory_usage: u64,
+}
+
+impl ParallelMemTrieLoader {
+    pub fn new(store: Store, shard_uid: ShardUId, root: CryptoHash) -> Self {
+        Self { store, shard_uid, root }
+    }
+
+    /// Loads the trie, returning the arena holding it and its root node, or
+    /// `None` if the trie is empty. The root node has a 0 refcount.
+    pub fn load(&self) -> Result<(Arena, Option<MemTrieNodeId>), StorageError> {
+        let arena_name = self.shard_uid.to_string();
+        if self.root == CryptoHash::default() {
+            return Ok((Arena::new(arena_name), None));
+        }
+        let num_threads = rayon::current_num_threads();
+        let threshold_memory_usage = self.read_node(&self.root)?.memory_usage
+            / (num_threads as u64 * NUM_SUBTREES_PER_THREAD);
+        let mut subtrees = Vec::new();
+        let plan = self.make_plan(self.root, Vec::new(), threshold_memory_usage, &mut subtrees)?;
+        debug!(
+            target: "memtrie",
+            shard_uid = %self.shard_uid,
+            num_subtrees = subtrees.len(),
+            num_threads,
+            "Loading subtrees in parallel..."
+        );
+
+        let label = [arena_name.as_str()];
+        let keys_loaded_gauge = MEM_TRIE_LOADING_NUM_KEYS_LOADED.with_label_values(&label);
+        let subtrees_loaded_gauge = MEM_TRIE_LOADING_NUM_SUBTREES_LOADED.with_label_values(&label);
+        keys_loaded_gauge.set(0);
+        subtrees_loaded_gauge.set(0);
+        MEM_TRIE_LOADING_NUM_SUBTREES_TOTAL.with_label_values(&label).set(subtrees.len() as i64);
+
+        // Start with the largest subtrees so that the tail of the loading is
+        // made of small ones.
+        let mut order = (0..subtrees.len()).collect::<Vec<_>>();
+        order.sort_by_key(|index| std::cmp::Reverse(subtrees[*index].memory_usage));
+        let next_subtree = AtomicUsize::new(0);
+        let chunk_counter = Arc::new(AtomicU32::new(0));
+        let results = (0..num_threads)
+            .into_par_iter()
+            .map(|_| -> Result<(Arena, Vec<(usize, MemTrieNodeId)>), StorageError> {
+                let mut arena =
+                    Arena::new_with_shared_chunk_counter(arena_name.clone(), chunk_counter.clone());
+                let mut roots = Vec::new();
+                while let Some(index) = order.get(next_subtree.fetch_add(1, Ordering::Relaxed)) {
+                    let root =
+                        self.load_subtree(&mut arena, &subtrees[*index], &keys_loaded_gauge)?;
+                    roots.push((*index, root));
+                    subtrees_loaded_gauge.inc();
+                }
+                Ok((arena, roots))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut subtree_roots = vec![None; subtrees.len()];
+        let mut arenas = Vec::new();
+        for (arena, roots) in results {
+            arenas.push(arena);
+            for (index, root) in roots {
+                subtree_roots[index] = Some(root);
+            }
+        }
+        let mut arena = Arena::merge(arena_name, arenas);
+        let root = Self::construct_top(&mut arena, plan, &subtree_roots);
+        root.as_ptr_mut(arena.memory_mut()).compute_hash_recursively();
+        let root_hash = root.as_ptr(arena.memory()).view().node_hash();
+        if root_hash != self.root {
+            return Err(StorageError::MemTrieLoadingError(format!(
+                "In-memory trie for shard {} has incorrect state root {}, expected {}",
+                self.shard_uid, root_hash, self.root
+            )));
+        }
+        Ok((arena, Some(root)))
+    }
+
+    /// Reads the top of the trie from the State column, collecting the
+    /// subtrees below `threshold_memory_usage` to be loaded from flat storage.
+    fn make_plan(
+        &self,
+        hash: CryptoHash,
+        path: Vec<u8>,
+        threshold_memory_usage: u64,
+        subtrees: &mut Vec<SubtreeToLoad>,
+    ) -> Result<TrieLoadingPlanNode, StorageError> {
+        let node = self.read_node(&hash)?;
+        if node.memory_usage <= threshold_memory_usage || matches!(node.node, RawTrieNode::Leaf(..))
+        {
+            subtrees.push(SubtreeToLoad { path, hash, memory_usage: node.memory_usage });
+            return Ok(TrieLoadingPlanNode::Subtree(subtrees.len() - 1));
+        }
+        match node.node {
+            RawTrieNode::Leaf(..) => unreachable!(),
+            RawTrieNode::BranchNoValue(children) => Ok(TrieLoadingPlanNode::Branch {
+                children: self.make_children_plan(
+                    children,
+                    &path,
+                    threshold_memory_usage,
+                    subtrees,
+                )?,
+                value: None,
+            }),
+            RawTrieNode::BranchWithValue(_, children) => {
+                let value = self.read_flat_state_value(&path)?;
+                Ok(TrieLoadingPlanNode::Branch {
+                    children: self.make_children_plan(
+                        children,
+                        &path,
+                        threshold_memory_usage,
+                        subtrees,
+                    )?,
+                    value: Some(value),
+                })
+            }
+            RawTrieNode::Extension(extension, child) => {
+                let mut child_path = path;
+                child_path.extend(NibbleSlice::from_encoded(&extension).0.iter());
+                let child = self.make_plan(child, child_path, threshold_memory_usage, subtrees)?;
+                Ok(TrieLoadingPlanNode::Extension {
+                    extension: extension.into_boxed_slice(),
+                    child: Box::new(child),
+                })
+            }
+        }
+    }
+
+    fn make_children_plan(
+        &self,
+        children: Children,
+        path: &[u8],
+        threshold_memory_usage: u64,
+        subtrees: &mut Vec<SubtreeToLoad>,
+    ) -> Result<Vec<(u8, TrieLoadingPlanNode)>, StorageError> {
+        let mut result = Vec::new();
+        for (nibble, child) in children.iter() {
+            let mut child_path = path.to_vec();
+            child_path.push(nibble);
+            result.push((
+                nibble,
+                self.make_plan(*child, child_path, threshold_memory_usage, subtrees)?,
+            ));
+        }
+        Ok(result)
+    }
+
+    /// Builds the subtree from all FlatState keys starting with its path.
+    fn load_subtree(
+        &self,
+        arena: &mut Arena,
+        subtree: &SubtreeToLoad,
+        keys_loaded_gauge: &IntGauge,
+    ) -> Result<MemTrieNodeId, StorageError> {
+        // The smallest key with the given nibble prefix is the prefix itself,
+        // padded with a zero nibble if its length is odd.
+        let lower_bound =
+            encode_flat_state_db_key(self.shard_uid, &nibbles_to_bytes(&subtree.path));
+        let mut recon = TrieConstructor::new(arena);
+        let mut num_keys_loaded = 0;
+        for item in self.store.iter_range(DBCol::FlatState, Some(&lower_bound), None) {
+            let (key, value) = item.map_err(|err| {
+                StorageError::MemTrieLoadingError(format!("Error iterating over FlatState: {err}"))
+            })?;
+            let (shard_uid, key) = decode_flat_state_db_key(&key).map_err(|err| {
+                StorageError::MemTrieLoadingError(format!("invalid FlatState key format: {err}"))
+            })?;
+            let nibbles = NibbleSlice::new(&key);
+            if shard_uid != self.shard_uid || !has_nibble_prefix(&nibbles, &subtree.path) {
+                break;
+            }
+            let value = FlatStateValue::try_from_slice(&value).map_err(|err| {
+                StorageError::MemTrieLoadingError(format!("invalid FlatState value format: {err}"))
+            })?;
+            recon.add_leaf_nibbles(nibbles.mid(subtree.path.len()), value);
+            num_keys_loaded += 1;
+            if num_keys_loaded % 10000 == 0 {
+                keys_loaded_gauge.add(10000);
+            }
+        }
+        keys_loaded_gauge.add(num_keys_loaded % 10000);
+        let root = recon.finalize().ok_or_else(|| {
+            StorageError::MemTrieLoadingError(format!(
+                "No keys in flat storage of shard {} for trie node {}",
+                self.shard_uid, subtree.hash
+            ))
+        })?;
+        root.as_ptr_mut(arena.memory_mut()).compute_hash_recursively();
+        let hash = root.as_ptr(arena.memory()).view().node_hash();
+        if hash != subtree.hash {
+            return Err(StorageError::MemTrieLoadingError(format!(
+                "Subtree of shard {} loaded from flat storage has hash {}, expected {}",
+                self.shard_uid, hash, subtree.hash
+            )));
+        }
+        Ok(root)
+    }
+
+    /// Constructs the top nodes of the trie above the loaded subtrees.
+    fn construct_top(
+        arena: &mut Arena,
+        plan: TrieLoadingPlanNode,
+        subtree_roots: &[Option<MemTrieNodeId>],
+    ) -> MemTrieNodeId {
+        match plan {
+            TrieLoadingPlanNode::Subtree(index) => subtree_roots[index].unwrap(),
+            TrieLoadingPlanNode::Branch { children: plan_children, value } => {
+                let mut children = [None; 16];
+                for (nibble, child) in plan_children {
+                    children[nibble as usize] =
+                        Some(Self::construct_top(arena, child, subtree_roots));
+                }
+                let node = match value {
+                    Some(value) => InputMemTrieNode::BranchWithValue { children, value },
+                    None => InputMemTrieNode::Branch { children },
+                };
+                MemTrieNodeId::new(arena, node)
+            }
+            TrieLoadingPlanNode::Extension { extension, child } => {
+                let child = Self::construct_top(arena, *child, subtree_roots);
+                MemTrieNodeId::new(arena, InputMemTrieNode::Extension { extension, child })
+            }
+        }
+    }
+
+    fn read_node(&self, hash: &CryptoHash) -> Result<RawTrieNodeWithSize, StorageError> {
+        let key = TrieCachingStorage::get_key_from_shard_uid_and_hash(self.shard_uid, hash);
+        let bytes = self
+            .store
+            .get(DBCol::State, &key)
+            .map_err(|err| {
+                StorageError::MemTrieLoadingError(format!("Cannot read trie node {hash}: {err}"))
+            })?
+            .ok_or_else(|| {
+                StorageError::MemTrieLoadingError(format!(
+                    "Trie node {hash} of shard {} not found",
+                    self.shard_uid
+                ))
+            })?;
+        RawTrieNodeWithSize::try_from_slice(&bytes).map_err(|err| {
+            StorageError::MemTrieLoadingError(format!("Cannot decode trie node {hash}: {err}"))
+        })
+    }
+
+    fn read_flat_state_value(&self, path: &[u8]) -> Result<FlatStateValue, StorageError> {
+        if path.len() % 2 != 0 {
+            return Err(StorageError::MemTrieLoadingError(format!(
+                "Trie node with a value at an odd number of nibbles in shard {}",
+                self.shard_uid
+            )));
+        }
+        let key = encode_flat_state_db_key(self.shard_uid, &nibbles_to_bytes(path));
+        self.store
+            .get_ser::<FlatStateValue>(DBCol::FlatState, &key)
+            .map_err(|err| {
+                StorageError::MemTrieLoadingError(format!("Cannot read FlatState value: {err}"))
+            })?
+            .ok_or_else(|| {
+                StorageError::MemTrieLoadingError(format!(
+                    "Missing FlatState value for key {} in shard {}",
+                    hex::encode(nibbles_to_bytes(path)),
+                    self.shard_uid
+                ))
+            })
+    }
+}
+
+/// Packs nibbles into bytes, padding an odd number of nibbles with a zero.
+fn nibbles_to_bytes(nibbles: &[u8]) -> Vec<u8> {
+    nibbles.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)).collect()
+}
+
+fn has_nibble_prefix(nibbles: &NibbleSlice, prefix: &[u8]) -> bool {
+    nibbles.len() >= prefix.len()
+        && prefix.iter().enumerate().all(|(i, nibble)| nibbles.at(i) == *nibble)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::nibbles_to_bytes;
+
+    #[test]
+    fn test_nibbles_to_bytes() {
+        assert_eq!(nibbles_to_bytes(&[]), Vec::<u8>::new());
+        assert_eq!(nibbles_to_bytes(&[0x1, 0x2]), vec![0x12]);
+        assert_eq!(nibbles_to_bytes(&[0x1, 0x2, 0xf]), vec![0x12, 0xf0]);
+    }
+}
@@ -0,0 +1,311 @@
+use super::arena::{Arena, ArenaPos};
+use super::metrics::MEM_TRIE_NUM_ROOTS;
+use super::node::MemTrieNodeId;
+use super::MemTries;
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::types::{BlockHeight, StateRoot};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Identifies a memtrie snapshot file.
+const SNAPSHOT_MAGIC: [u8; 8] = *b"MEMTRIE\0";
+/// Bumped whenever the layout of the snapshot or of the arena memory changes.
+/// Snapshots of other versions are ignored and the tries are rebuilt.
+const SNAPSHOT_VERSION: u32 = 1;
+const SNAPSHOT_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum MemTrieSnapshotError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("unsupported snapshot version {0}")]
+    UnsupportedVersion(u32),
+    #[error("snapshot was taken for shard {shard_uid} at flat head {flat_head_hash}")]
+    Stale { shard_uid: ShardUId, flat_head_hash: CryptoHash },
+    #[error("snapshot is corrupted: {0}")]
+    Corrupted(String),
+}
+
+/// The snapshot starts with this header, followed by the borsh serialized
+/// roots and heights of the `MemTries`, then by the raw arena memory. The
+/// file ends with the SHA-256 checksum of everything before it.
+#[derive(BorshSerialize, BorshDeserialize)]
+struct SnapshotHeader {
+    magic: [u8; 8],
+    version: u32,
+    shard_uid: ShardUId,
+    /// Flat head the tries were consistent with when the snapshot was taken.
+    flat_head_hash: CryptoHash,
+}
+
+/// Returns the path of the snapshot file of the given shard.
+pub fn mem_tries_snapshot_path(dir: &Path, shard_uid: ShardUId) -> PathBuf {
+    dir.join(format!("{}.memtrie", shard_uid))
+}
+
+/// Writes the whole `MemTries`, including all roots, to a snapshot file in
+/// `dir`. `flat_head_hash` must be the flat storage head of the shard, so
+/// that the tries are consistent with flat storage plus its deltas.
+///
+/// The snapshot is written to a temporary file first and then atomically
+/// renamed, so a crash never leaves a partially written snapshot behind.
+pub fn save_mem_tries_snapshot(
+    dir: &Path,
+    tries: &MemTries,
+    flat_head_hash: CryptoHash,
+) -> Result<(), MemTrieSnapshotError> {
+    std::fs::create_dir_all(dir)?;
+    let path = mem_tries_snapshot_path(dir, tries.shard_uid);
+    let tmp_path = path.with_extension("memtrie.tmp");
+    let file = File::create(&tmp_path)?;
+    let mut writer = ChecksumWriter::new(BufWriter::with_capacity(SNAPSHOT_BUFFER_SIZE, file));
+
+    let header = SnapshotHeader {
+        magic: SNAPSHOT_MAGIC,
+        version: SNAPSHOT_VERSION,
+        shard_uid: tries.shard_uid,
+        flat_head_hash,
+    };
+    header.serialize(&mut writer)?;
+    let roots = tries
+        .roots
+        .iter()
+        .map(|(state_root, ids)| (*state_root, ids.iter().map(|id| id.pos).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    roots.serialize(&mut writer)?;
+    let heights = tries.heights.iter().collect::<Vec<_>>();
+    heights.serialize(&mut writer)?;
+    tries.arena.write_to(&mut writer)?;
+
+    let (mut writer, checksum) = writer.finish();
+    writer.write_all(&checksum)?;
+    let file = writer.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// Loads the `MemTries` of the given shard from the snapshot file in `dir`.
+///
+/// Returns `Ok(None)` if there is no snapshot, and an error if the snapshot
+/// cannot be used because it was taken at a different flat head or is
+/// corrupted; in both cases the caller should rebuild the tries from flat
+/// storage instead.
+pub fn load_mem_tries_snapshot(
+    dir: &Path,
+    shard_uid: ShardUId,
+    flat_head_hash: CryptoHash,
+) -> Result<Option<MemTries>, MemTrieSnapshotError> {
+    let file = match File::open(mem_tries_snapshot_path(dir, shard_uid)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut reader = ChecksumReader::new(BufReader::with_capacity(SNAPSHOT_BUFFER_SIZE, file));
+
+    let header = SnapshotHeader::deserialize_reader(&mut reader)?;
+    if header.magic != SNAPSHOT_MAGIC {
+        return Err(MemTrieSnapshotError::Corrupted("invalid magic bytes".to_string()));
+    }
+    if header.version != SNAPSHOT_VERSION {
+        return Err(MemTrieSnapshotError::UnsupportedVersion(header.version));
+    }
+    if header.shard_uid != shard_uid || header.flat_head_hash != flat_head_hash {
+        return Err(MemTrieSnapshotError::Stale {
+            shard_uid: header.shard_uid,
+            flat_head_hash: header.flat_head_hash,
+        });
+    }
+    let roots = Vec::<(StateRoot, Vec<ArenaPos>)>::deserialize_reader(&mut reader)?;
+    let heights = Vec::<(BlockHeight, Vec<StateRoot>)>::deserialize_reader(&mut reader)?;
+    let arena = Arena::read_from(shard_uid.to_string(), &mut reader)?;
+
+    let (mut reader, checksum) = reader.finish();
+    let mut expected_checksum = [0; 32];
+    reader.read_exact(&mut expected_checksum)?;
+    if checksum != expected_checksum {
+        return Err(MemTrieSnapshotError::Corrupted("checksum mismatch".to_string()));
+    }
+    if reader.read(&mut [0])? != 0 {
+        return Err(MemTrieSnapshotError::Corrupted("trailing data".to_string()));
+    }
+
+    let tries = MemTries {
+        arena,
+        roots: roots
+            .into_iter()
+            .map(|(state_root, positions)| {
+                (state_root, positions.into_iter().map(|pos| MemTrieNodeId { pos }).collect())
+            })
+            .collect(),
+        heights: heights.into_iter().collect(),
+        dedup_index: None,
+        shard_uid,
+    };
+    for (state_root, ids) in &tries.roots {
+        for id in ids {
+            let node_hash = id.as_ptr(tries.arena.memory()).view().node_hash();
+            if node_hash != *state_root {
+                return Err(MemTrieSnapshotError::Corrupted(format!(
+                    "root {} has hash {}",
+                    state_root, node_hash
+                )));
+            }
+        }
+    }
+    MEM_TRIE_NUM_ROOTS.with_label_values(&[&shard_uid.to_string()]).set(tries.roots.len() as i64);
+    Ok(Some(tries))
+}
+
+/// Computes the checksum of everything written through it.
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new() }
+    }
+
+    fn finish(self) -> (W, [u8; 32]) {
+        (self.inner, self.hasher.finalize().into())
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Computes the checksum of everything read through it.
+struct ChecksumReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, hasher: Sha256::new() }
+    }
+
+    fn finish(self) -> (R, [u8; 32]) {
+        (self.inner, self.hasher.finalize().into())
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_mem_tries_snapshot, save_mem_tries_snapshot, MemTrieSnapshotError};
+    use crate::trie::mem::node::{InputMemTrieNode, MemTrieNodeId};
+    use crate::trie::mem::snapshot::mem_tries_snapshot_path;
+    use crate::trie::mem::MemTries;
+    use crate::NibbleSlice;
+    use assert_matches::assert_matches;
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::state::FlatStateValue;
+
+    fn construct_leaf(tries: &mut MemTries, height: u64) -> CryptoHash {
+        tries
+            .construct_root(height, |arena| -> Result<Option<MemTrieNodeId>, ()> {
+                let root = MemTrieNodeId::new(
+                    arena,
+                    InputMemTrieNode::Leaf {
+                        value: FlatStateValue::Inlined(format!("{}", height).into_bytes()),
+                        extension: NibbleSlice::new(&[]).encoded(true).to_vec().into_boxed_slice(),
+                    },
+                );
+                root.as_ptr_mut(arena.memory_mut()).compute_hash_recursively();
+                Ok(Some(root))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let shard_uid = ShardUId::single_shard();
+        let flat_head_hash = hash(b"flat head");
+        let mut tries = MemTries::new(shard_uid);
+        let state_roots =
+            (1..=10).map(|height| construct_leaf(&mut tries, height)).collect::<Vec<_>>();
+        tries.delete_until_height(5);
+        save_mem_tries_snapshot(dir.path(), &tries, flat_head_hash).unwrap();
+
+        let mut loaded =
+            load_mem_tries_snapshot(dir.path(), shard_uid, flat_head_hash).unwrap().unwrap();
+        assert_eq!(loaded.num_roots(), tries.num_roots());
+        assert_eq!(loaded.arena.num_active_allocs(), tries.arena.num_active_allocs());
+        for state_root in &state_roots[4..] {
+            assert_eq!(loaded.get_root(state_root).unwrap().view().node_hash(), *state_root);
+        }
+        // The restored arena must keep working for new roots and for GC.
+        let new_root = construct_leaf(&mut loaded, 11);
+        assert!(loaded.get_root(&new_root).is_some());
+        loaded.delete_until_height(12);
+        assert_eq!(loaded.num_roots(), 0);
+        assert_eq!(loaded.arena.num_active_allocs(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_missing_or_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let shard_uid = ShardUId::single_shard();
+        assert_matches!(load_mem_tries_snapshot(dir.path(), shard_uid, hash(b"head")), Ok(None));
+
+        let mut tries = MemTries::new(shard_uid);
+        construct_leaf(&mut tries, 1);
+        save_mem_tries_snapshot(dir.path(), &tries, hash(b"head")).unwrap();
+        assert_matches!(
+            load_mem_tries_snapshot(dir.path(), shard_uid, hash(b"other head")),
+            Err(MemTrieSnapshotError::Stale { .. })
+        );
+    }
+
+    #[test]
+    fn test_snapshot_corrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let shard_uid = ShardUId::single_shard();
+        let mut tries = MemTries::new(shard_uid);
+        construct_leaf(&mut tries, 1);
+        save_mem_tries_snapshot(dir.path(), &tries, CryptoHash::default()).unwrap();
+
+        let path = mem_tries_snapshot_path(dir.path(), shard_uid);
+        let mut data = std::fs::read(&path).unwrap();
+        let last_byte = data.len() - 100;
+        data[last_byte] ^= 1;
+        std::fs::write(&path, &data).unwrap();
+        assert_matches!(
+            load_mem_tries_snapshot(dir.path(), shard_uid, CryptoHash::default()),
+            Err(MemTrieSnapshotError::Corrupted(_))
+        );
+
+        data.truncate(data.len() / 2);
+        std::fs::write(&path, &data).unwrap();
+        assert_matches!(
+            load_mem_tries_snapshot(dir.path(), shard_uid, CryptoHash::default()),
+            Err(MemTrieSnapshotError::Io(_))
+        );
+    }
+}
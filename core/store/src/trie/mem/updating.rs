@@ -1,6 +1,7 @@
 use super::arena::ArenaMemory;
+use super::dedup::NodeDedupIndex;
 use super::flexible_data::children::ChildrenView;
-use super::metrics::MEM_TRIE_NUM_NODES_CREATED_FROM_UPDATES;
+use super::metrics::{MEM_TRIE_NUM_NODES_CREATED_FROM_UPDATES, MEM_TRIE_NUM_NODES_DEDUPLICATED};
 use super::node::{InputMemTrieNode, MemTrieNodeId, MemTrieNodeView};
 use super::MemTries;
 use crate::trie::{Children, MemTrieChanges, TrieRefcountDeltaMap, TRIE_COSTS};
@@ -8,7 +9,35 @@ use crate::{NibbleSlice, RawTrieNode, RawTrieNodeWithSize, TrieChanges};
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::state::FlatStateValue;
 use near_primitives::types::BlockHeight;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// What a `MemTrieUpdate` keeps track of in addition to the changes to the
+/// in-memory trie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackingMode {
+    /// Only the in-memory trie changes are computed.
+    None,
+    /// Refcount changes to on-disk trie nodes and values are computed too,
+    /// so that the update can be turned into `TrieChanges`.
+    Refcounts,
+    /// In addition to refcounts, the trie nodes and values read by the update
+    /// are recorded, see `TrieAccesses`.
+    RefcountsAndAccesses,
+}
+
+/// Trie nodes and values read while building an update. These are exactly
+/// what the on-disk trie reads when applying the same changes, so recording
+/// them produces the same state proof.
+#[derive(Debug, Default)]
+pub struct TrieAccesses {
+    /// Accessed nodes by hash, serialized as `RawTrieNodeWithSize` just like
+    /// in the State column.
+    pub nodes: HashMap<CryptoHash, Arc<[u8]>>,
+    /// Values that were overwritten or deleted. The in-memory trie may only
+    /// hold references to them, so the caller has to read them from storage.
+    pub values: Vec<FlatStateValue>,
+}
 
 /// An old node means a node in the current in-memory trie. An updated node means a
 /// node we're going to store in the in-memory trie but have not constructed there yet.
@@ -55,6 +84,8 @@ pub struct MemTrieUpdate<'a> {
     pub updated_nodes: Vec<Option<UpdatedMemTrieNode>>,
     /// Refcount changes to on-disk trie nodes.
     pub trie_refcount_changes: Option<TrieRefcountDeltaMap>,
+    /// Nodes and values read by the update, if they are to be recorded.
+    accesses: Option<TrieAccesses>,
 }
 
 impl UpdatedMemTrieNode {
@@ -96,17 +127,22 @@ impl<'a> MemTrieUpdate<'a> {
         root: Option<MemTrieNodeId>,
         arena: &'a ArenaMemory,
         shard_uid: String,
-        track_disk_changes: bool,
+        mode: TrackingMode,
     ) -> Self {
         let mut trie_update = Self {
             root,
             arena,
             shard_uid,
             updated_nodes: vec![],
-            trie_refcount_changes: if track_disk_changes {
-                Some(TrieRefcountDeltaMap::new())
-            } else {
-                None
+            trie_refcount_changes: match mode {
+                TrackingMode::None => None,
+                TrackingMode::Refcounts | TrackingMode::RefcountsAndAccesses => {
+                    Some(TrieRefcountDeltaMap::new())
+                }
+            },
+            accesses: match mode {
+                TrackingMode::RefcountsAndAccesses => Some(TrieAccesses::default()),
+                TrackingMode::None | TrackingMode::Refcounts => None,
             },
         };
         assert_eq!(trie_update.convert_existing_to_updated(root), 0usize);
@@ -144,12 +180,16 @@ impl<'a> MemTrieUpdate<'a> {
         match node {
             None => self.new_updated_node(UpdatedMemTrieNode::Empty),
             Some(node) => {
+                let view = node.as_ptr(self.arena).view();
                 if let Some(trie_refcount_changes) = self.trie_refcount_changes.as_mut() {
-                    trie_refcount_changes.subtract(node.as_ptr(self.arena).view().node_hash(), 1);
+                    trie_refcount_changes.subtract(view.node_hash(), 1);
+                }
+                if let Some(accesses) = self.accesses.as_mut() {
+                    let raw_node_serialized =
+                        borsh::to_vec(&view.to_raw_trie_node_with_size()).unwrap();
+                    accesses.nodes.insert(view.node_hash(), raw_node_serialized.into());
                 }
-                self.new_updated_node(UpdatedMemTrieNode::from_existing_node_view(
-                    node.as_ptr(self.arena).view(),
-                ))
+                self.new_updated_node(UpdatedMemTrieNode::from_existing_node_view(view))
             }
         }
     }
@@ -168,12 +208,23 @@ impl<'a> MemTrieUpdate<'a> {
         }
     }
 
-    fn subtract_refcount_for_value(&mut self, hash: CryptoHash) {
+    fn subtract_refcount_for_value(&mut self, value: FlatStateValue) {
         if let Some(trie_refcount_changes) = self.trie_refcount_changes.as_mut() {
-            trie_refcount_changes.subtract(hash, 1);
+            trie_refcount_changes.subtract(value.to_value_ref().hash, 1);
+        }
+        // The on-disk trie reads the value it dereferences, so it ends up in
+        // the recorded proof as well.
+        if let Some(accesses) = self.accesses.as_mut() {
+            accesses.values.push(value);
         }
     }
 
+    /// Returns the nodes and values read by the update so far. Only available
+    /// with `TrackingMode::RefcountsAndAccesses`.
+    pub fn take_accesses(&mut self) -> Option<TrieAccesses> {
+        self.accesses.take()
+    }
+
     /// Inserts the given key value pair into the trie.
     pub fn insert(&mut self, key: &[u8], value: Vec<u8>) {
         self.insert_impl(key, FlatStateValue::on_disk(&value), Some(value));
@@ -218,7 +269,7 @@ impl<'a> MemTrieUpdate<'a> {
                     if partial.is_empty() {
                         // This branch node is exactly where the value should be added.
                         if let Some(value) = old_value {
-                            self.subtract_refcount_for_value(value.to_value_ref().hash);
+                            self.subtract_refcount_for_value(value);
                         }
                         self.place_node(
                             node_id,
@@ -249,7 +300,7 @@ impl<'a> MemTrieUpdate<'a> {
                     let common_prefix = partial.common_prefix(&existing_key);
                     if common_prefix == existing_key.len() && common_prefix == partial.len() {
                         // We're at the exact leaf. Rewrite the value at this leaf.
-                        self.subtract_refcount_for_value(old_value.to_value_ref().hash);
+                        self.subtract_refcount_for_value(old_value);
                         self.place_node(
                             node_id,
                             UpdatedMemTrieNode::Leaf { extension, value: flat_value },
@@ -388,7 +439,7 @@ impl<'a> MemTrieUpdate<'a> {
                 }
                 UpdatedMemTrieNode::Leaf { extension, value } => {
                     if NibbleSlice::from_encoded(&extension).0 == partial {
-                        self.subtract_refcount_for_value(value.to_value_ref().hash);
+                        self.subtract_refcount_for_value(value);
                         self.place_node(node_id, UpdatedMemTrieNode::Empty);
                         break;
                     } else {
@@ -407,7 +458,7 @@ impl<'a> MemTrieUpdate<'a> {
                             );
                             return;
                         };
-                        self.subtract_refcount_for_value(value.unwrap().to_value_ref().hash);
+                        self.subtract_refcount_for_value(value.unwrap());
                         self.place_node(
                             node_id,
                             UpdatedMemTrieNode::Branch { children: old_children, value: None },
@@ -779,7 +830,7 @@ impl<'a> MemTrieUpdate<'a> {
 
     /// Converts the updates to trie changes as well as memtrie changes.
     pub fn to_trie_changes(self) -> TrieChanges {
-        let Self { root, arena, shard_uid, trie_refcount_changes, updated_nodes } = self;
+        let Self { root, arena, shard_uid, trie_refcount_changes, updated_nodes, .. } = self;
         let mut trie_refcount_changes =
             trie_refcount_changes.expect("Cannot to_trie_changes for memtrie changes only");
         let (mem_trie_changes, hashes_and_serialized) =
@@ -813,7 +864,26 @@ pub fn apply_memtrie_changes(
     changes: &MemTrieChanges,
     block_height: BlockHeight,
 ) -> CryptoHash {
-    memtries
+    let node_ids_with_hashes = &changes.node_ids_with_hashes;
+    if memtries.dedup_index.is_some() {
+        // An update resulting in a trie we already have just takes another
+        // reference to its root.
+        if let Some((_, root_hash)) = node_ids_with_hashes.last() {
+            if let Some(ids) = memtries.roots.get(root_hash) {
+                let root_id = ids[0];
+                memtries.insert_root(*root_hash, root_id, block_height);
+                MEM_TRIE_NUM_NODES_DEDUPLICATED
+                    .with_label_values(&[&memtries.shard_uid.to_string()])
+                    .inc_by(node_ids_with_hashes.len() as u64);
+                return *root_hash;
+            }
+        }
+    }
+
+    let mut dedup_index = memtries.dedup_index.take();
+    let reuse_plan = dedup_index.as_ref().map(|dedup_index| plan_node_reuse(changes, dedup_index));
+    let mut num_deduplicated = 0;
+    let state_root = memtries
         .construct_root(block_height, |arena| {
             let mut last_node_id: Option<MemTrieNodeId> = None;
             let map_to_new_node_id = |node_id: OldOrUpdatedNodeId,
@@ -830,8 +900,21 @@ pub fn apply_memtrie_changes(
 
             let mut updated_to_new_map = HashMap::<UpdatedMemTrieNodeId, MemTrieNodeId>::new();
             let updated_nodes = &changes.updated_nodes;
-            let node_ids_with_hashes = &changes.node_ids_with_hashes;
-            for (node_id, node_hash) in node_ids_with_hashes.iter() {
+            for (index, (node_id, node_hash)) in node_ids_with_hashes.iter().enumerate() {
+                let is_root = index + 1 == node_ids_with_hashes.len();
+                if let Some(reuse_plan) = &reuse_plan {
+                    if let Some(existing_node_id) = reuse_plan.reused.get(node_id) {
+                        num_deduplicated += 1;
+                        updated_to_new_map.insert(*node_id, *existing_node_id);
+                        last_node_id = Some(*existing_node_id);
+                        continue;
+                    }
+                    if !reuse_plan.needed.contains(node_id) {
+                        // Below a reused node, so it would never be referenced.
+                        num_deduplicated += 1;
+                        continue;
+                    }
+                }
                 let node = updated_nodes.get(*node_id).unwrap().clone().unwrap();
                 let node = match node {
                     UpdatedMemTrieNode::Empty => unreachable!(),
@@ -861,20 +944,83 @@ pub fn apply_memtrie_changes(
                     }
                 };
                 let mem_node_id = MemTrieNodeId::new_with_hash(arena, node, *node_hash);
+                if let Some(dedup_index) = dedup_index.as_mut() {
+                    if !is_root {
+                        dedup_index.insert(*node_hash, mem_node_id);
+                    }
+                }
                 updated_to_new_map.insert(*node_id, mem_node_id);
                 last_node_id = Some(mem_node_id);
             }
 
             Ok::<Option<MemTrieNodeId>, ()>(last_node_id)
         })
-        .unwrap() // cannot fail
+        .unwrap(); // cannot fail
+    if dedup_index.is_some() {
+        memtries.dedup_index = dedup_index;
+        MEM_TRIE_NUM_NODES_DEDUPLICATED
+            .with_label_values(&[&memtries.shard_uid.to_string()])
+            .inc_by(num_deduplicated);
+        memtries.update_dedup_index_size_metric();
+    }
+    state_root
+}
+
+/// Which of the updated nodes of `MemTrieChanges` are taken from the dedup
+/// index, and which have to be allocated.
+struct NodeReusePlan {
+    /// Nodes found in the index.
+    reused: HashMap<UpdatedMemTrieNodeId, MemTrieNodeId>,
+    /// Nodes to allocate: the root and the nodes referenced by allocated
+    /// nodes which aren't in the index.
+    needed: HashSet<UpdatedMemTrieNodeId>,
+}
+
+/// The changes list the nodes children first, but whether a node is taken from
+/// the index has to be decided before its children are allocated: if a child
+/// isn't in the index, e.g. because it matches a node loaded from disk, while
+/// its parent is, the allocated child would never be referenced and stay in
+/// the arena until the next compaction.  So the plan is made from the root down,
+/// and nodes below a reused node are neither reused nor allocated.
+fn plan_node_reuse(changes: &MemTrieChanges, dedup_index: &NodeDedupIndex) -> NodeReusePlan {
+    let mut plan = NodeReusePlan { reused: HashMap::new(), needed: HashSet::new() };
+    let node_ids_with_hashes = &changes.node_ids_with_hashes;
+    for (index, (node_id, node_hash)) in node_ids_with_hashes.iter().enumerate().rev() {
+        // The root is never taken from the index; see `NodeDedupIndex`.
+        let is_root = index + 1 == node_ids_with_hashes.len();
+        if !is_root {
+            if !plan.needed.contains(node_id) {
+                continue;
+            }
+            if let Some(existing_node_id) = dedup_index.get(node_hash) {
+                plan.needed.remove(node_id);
+                plan.reused.insert(*node_id, existing_node_id);
+                continue;
+            }
+        }
+        plan.needed.insert(*node_id);
+        match changes.updated_nodes.get(*node_id).unwrap().as_ref().unwrap() {
+            UpdatedMemTrieNode::Branch { children, .. } => {
+                for child in children.iter().flatten() {
+                    if let OldOrUpdatedNodeId::Updated(child) = child {
+                        plan.needed.insert(*child);
+                    }
+                }
+            }
+            UpdatedMemTrieNode::Extension { child: OldOrUpdatedNodeId::Updated(child), .. } => {
+                plan.needed.insert(*child);
+            }
+            _ => {}
+        }
+    }
+    plan
 }
 
 #[cfg(test)]
 mod tests {
     use crate::test_utils::TestTriesBuilder;
     use crate::trie::mem::lookup::memtrie_lookup;
-    use crate::trie::mem::updating::apply_memtrie_changes;
+    use crate::trie::mem::updating::{apply_memtrie_changes, TrackingMode};
     use crate::trie::mem::MemTries;
     use crate::trie::MemTrieChanges;
     use crate::{KeyLookupMode, ShardTries, TrieChanges};
@@ -906,10 +1052,9 @@ mod tests {
         }
 
         fn make_all_changes(&mut self, changes: Vec<(Vec<u8>, Option<Vec<u8>>)>) -> TrieChanges {
-            let mut update = self.mem.update(self.state_root, true).expect(&format!(
-                "Trying to update root {:?} but it's not in memtries",
-                self.state_root
-            ));
+            let mut update = self.mem.update(self.state_root, TrackingMode::Refcounts).expect(
+                &format!("Trying to update root {:?} but it's not in memtries", self.state_root),
+            );
             for (key, value) in changes {
                 if let Some(value) = value {
                     update.insert(&key, value);
@@ -924,7 +1069,7 @@ mod tests {
             &mut self,
             changes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
         ) -> MemTrieChanges {
-            let mut update = self.mem.update(self.state_root, false).expect(&format!(
+            let mut update = self.mem.update(self.state_root, TrackingMode::None).expect(&format!(
                 "Trying to update root {:?} but it's not in memtries",
                 self.state_root
             ));
@@ -1,9 +1,9 @@
 use self::accounting_cache::TrieAccountingCache;
 use self::mem::lookup::memtrie_lookup;
-use self::mem::updating::{UpdatedMemTrieNode, UpdatedMemTrieNodeId};
+use self::mem::updating::{TrackingMode, TrieAccesses, UpdatedMemTrieNode, UpdatedMemTrieNodeId};
 use self::mem::MemTries;
 use self::trie_recording::TrieRecorder;
-use self::trie_storage::TrieMemoryPartialStorage;
+use self::trie_storage::{TrieMemoryPartialStorage, TrieOverlayStorage};
 use crate::flat::{FlatStateChanges, FlatStorageChunkView};
 pub use crate::trie::config::TrieConfig;
 pub(crate) use crate::trie::config::{
@@ -688,6 +688,29 @@ impl Trie {
         trie
     }
 
+    /// Constructs a Trie over the state after `changes` are applied to this
+    /// trie one after another, without writing them to the storage. Nodes and
+    /// values inserted by `changes` are kept in memory, everything else is read
+    /// from the storage of this trie.
+    ///
+    /// Flat storage and in-memory tries do not include the changes, so the
+    /// returned trie uses neither. It charges gas for trie node access the same
+    /// way this trie does though, so that costs don't change between the two.
+    pub fn with_uncommitted_changes(&self, changes: &[TrieChanges]) -> Self {
+        let overlay = changes
+            .iter()
+            .flat_map(|changes| changes.insertions.iter())
+            .map(|addition| {
+                (addition.trie_node_or_value_hash, addition.trie_node_or_value.as_slice().into())
+            })
+            .collect();
+        let root = changes.last().map_or(self.root, |changes| changes.new_root);
+        let storage = Rc::new(TrieOverlayStorage { base: self.storage.clone(), overlay });
+        let mut trie = Self::new(storage, root, None);
+        trie.charge_gas_for_trie_node_access = self.charge_gas_for_trie_node_access;
+        trie
+    }
+
     pub fn get_root(&self) -> &StateRoot {
         &self.root
     }
@@ -696,6 +719,10 @@ impl Trie {
         self.flat_storage_chunk_view.is_some()
     }
 
+    pub fn has_memtries(&self) -> bool {
+        self.memtries.is_some()
+    }
+
     pub fn internal_get_storage_as_caching_storage(&self) -> Option<&TrieCachingStorage> {
         self.storage.as_caching_storage()
     }
@@ -1425,7 +1452,12 @@ impl Trie {
                 // If we have in-memory tries, use it to construct the the changes entirely (for
                 // both in-memory and on-disk updates) because it's much faster.
                 let guard = memtries.read().unwrap();
-                let mut trie_update = guard.update(self.root, true)?;
+                let mode = if self.recorder.is_some() {
+                    TrackingMode::RefcountsAndAccesses
+                } else {
+                    TrackingMode::Refcounts
+                };
+                let mut trie_update = guard.update(self.root, mode)?;
                 for (key, value) in changes {
                     match value {
                         Some(arr) => {
@@ -1434,6 +1466,9 @@ impl Trie {
                         None => trie_update.delete(&key),
                     }
                 }
+                if let Some(accesses) = trie_update.take_accesses() {
+                    self.record_memtrie_accesses(accesses)?;
+                }
                 Ok(trie_update.to_trie_changes())
             }
             None => {
@@ -1456,10 +1491,86 @@ impl Trie {
         }
     }
 
+    /// Records the nodes and values read by an in-memory trie update, so that
+    /// the recorded storage is the same as if the update was applied to the
+    /// on-disk trie.
+    fn record_memtrie_accesses(&self, accesses: TrieAccesses) -> Result<(), StorageError> {
+        let Some(recorder) = &self.recorder else {
+            return Ok(());
+        };
+        for (node_hash, serialized_node) in accesses.nodes {
+            recorder.borrow_mut().record(&node_hash, serialized_node);
+        }
+        for value in accesses.values {
+            match value {
+                FlatStateValue::Inlined(value) => {
+                    recorder.borrow_mut().record(&hash(&value), value.into());
+                }
+                FlatStateValue::Ref(value_ref) => {
+                    // Values not inlined in the in-memory trie live on disk;
+                    // reading them records them.
+                    self.internal_retrieve_trie_node(&value_ref.hash, false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the serialized trie nodes on the path to `key`. Together they
+    /// prove either the value reference stored under the key or its absence.
+    ///
+    /// The nodes are the same regardless of whether the trie is backed by
+    /// in-memory tries or by the State column.
+    pub fn get_trie_nodes_for_key(&self, key: &[u8]) -> Result<PartialState, StorageError> {
+        self.get_trie_nodes_for_keys([key])
+    }
+
+    /// Returns the serialized trie nodes on the paths to all the given keys,
+    /// deduplicated and sorted.  See [`Self::get_trie_nodes_for_key`].
+    pub fn get_trie_nodes_for_keys<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k [u8]>,
+    ) -> Result<PartialState, StorageError> {
+        let trie = self.recording_reads();
+        for key in keys {
+            trie.get_optimized_ref(key, KeyLookupMode::Trie)?;
+        }
+        Ok(trie.recorded_storage().unwrap().nodes)
+    }
+
     pub fn iter<'a>(&'a self) -> Result<TrieIterator<'a>, StorageError> {
         TrieIterator::new(self, None)
     }
 
+    /// Returns an iterator over key-value pairs with keys in `[from, to)`
+    /// read from flat storage, or `None` if the trie has no flat storage
+    /// chunk view or its block is not the flat storage head.
+    ///
+    /// `DBCol::FlatState` only reflects the state at the flat storage head,
+    /// so for any other block callers need to fall back to iterating the trie.
+    pub fn iter_flat_state_at_head<'a>(
+        &'a self,
+        from: Option<&[u8]>,
+        to: Option<&[u8]>,
+    ) -> Option<impl Iterator<Item = Result<(Vec<u8>, Vec<u8>), StorageError>> + 'a> {
+        let chunk_view = self.flat_storage_chunk_view.as_ref()?;
+        if !chunk_view.is_at_head() {
+            return None;
+        }
+        let iter = chunk_view.iter_flat_state_entries(from, to);
+        // The head only moves forward, so if it is at our block both before
+        // and after the iterator was created, the iterator observes the state
+        // at our block.
+        if !chunk_view.is_at_head() {
+            return None;
+        }
+        Some(iter.map(move |item| {
+            let (key, value) = item?;
+            let value = self.deref_optimized(&OptimizedValueRef::from_flat_value(value))?;
+            Ok((key, value))
+        }))
+    }
+
     pub fn iter_with_max_depth<'a>(
         &'a self,
         max_depth: usize,
@@ -1603,6 +1714,35 @@ mod tests {
         assert_eq!(other_iter.next(), None);
     }
 
+    #[test]
+    fn test_trie_with_uncommitted_changes() {
+        let tries = TestTriesBuilder::new().with_shard_layout(SHARD_VERSION, 2).build();
+        let shard_uid = ShardUId { version: SHARD_VERSION, shard_id: 0 };
+        let changes = vec![
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+        ];
+        let root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes);
+        let trie = tries.get_trie_for_shard(shard_uid, root);
+        let first_changes = trie
+            .update(vec![(b"dog".to_vec(), None), (b"doge".to_vec(), Some(b"coin".to_vec()))])
+            .unwrap();
+        let second_changes = trie
+            .with_uncommitted_changes(std::slice::from_ref(&first_changes))
+            .update(vec![(b"horse".to_vec(), Some(b"pony".to_vec()))])
+            .unwrap();
+
+        let updated = trie.with_uncommitted_changes(&[first_changes, second_changes]);
+        assert_eq!(updated.get(b"dog"), Ok(None));
+        assert_eq!(updated.get(b"doge"), Ok(Some(b"coin".to_vec())));
+        assert_eq!(updated.get(b"horse"), Ok(Some(b"pony".to_vec())));
+
+        // Nothing was written to the store.
+        let from_store = tries.get_trie_for_shard(shard_uid, *updated.get_root());
+        assert_matches!(from_store.get(b"doge"), Err(StorageError::MissingTrieValue(_, _)));
+        assert_eq!(trie.get(b"dog"), Ok(Some(b"puppy".to_vec())));
+    }
+
     #[test]
     fn test_trie_leaf_into_branch() {
         let tries = TestTriesBuilder::new().with_shard_layout(SHARD_VERSION, 2).build();
@@ -1,16 +1,18 @@
 use super::mem::MemTries;
 use super::state_snapshot::{StateSnapshot, StateSnapshotConfig};
 use super::TrieRefcountSubtraction;
-use crate::flat::store_helper::remove_all_state_values;
+use crate::flat::store_helper::{get_flat_storage_status, remove_all_state_values};
 use crate::flat::{FlatStorageManager, FlatStorageStatus};
 use crate::trie::config::TrieConfig;
 use crate::trie::mem::loading::load_trie_from_flat_state_and_delta;
+use crate::trie::mem::snapshot::save_mem_tries_snapshot;
 use crate::trie::mem::updating::apply_memtrie_changes;
 use crate::trie::prefetching_trie_storage::PrefetchingThreadsHandle;
 use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
 use crate::trie::{TrieRefcountAddition, POISONED_LOCK_ERR};
 use crate::{metrics, DBCol, PrefetchApi};
 use crate::{Store, StoreUpdate, Trie, TrieChanges, TrieUpdate};
+use anyhow::Context;
 use near_primitives::errors::StorageError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::{self, ShardUId};
@@ -398,7 +400,14 @@ impl ShardTries {
         shard_uids_to_load
             .par_iter()
             .map(|shard_uid| -> Result<(), StorageError> {
-                let mem_tries = load_trie_from_flat_state_and_delta(&store, *shard_uid)?;
+                let mut mem_tries = load_trie_from_flat_state_and_delta(
+                    &store,
+                    *shard_uid,
+                    trie_config.mem_trie_snapshot_dir.as_deref(),
+                )?;
+                if trie_config.mem_trie_dedup_nodes {
+                    mem_tries.enable_node_deduplication();
+                }
                 self.0
                     .mem_tries
                     .write()
@@ -414,6 +423,35 @@ impl ShardTries {
         Ok(())
     }
 
+    /// Writes snapshots of all loaded in-memory tries, so that the next start
+    /// can restore them instead of rebuilding them from flat storage. Does
+    /// nothing if `mem_trie_snapshot_dir` is not configured.
+    ///
+    /// Should be called on shutdown, once blocks are no longer processed;
+    /// the snapshots are only usable as long as the flat head does not move.
+    pub fn save_mem_tries_snapshots(&self) -> anyhow::Result<()> {
+        let Some(snapshot_dir) = &self.0.trie_config.mem_trie_snapshot_dir else {
+            return Ok(());
+        };
+        let mem_tries = self.0.mem_tries.read().unwrap().clone();
+        for (shard_uid, mem_tries) in mem_tries {
+            let flat_head = match get_flat_storage_status(&self.0.store, shard_uid)? {
+                FlatStorageStatus::Ready(status) => status.flat_head,
+                other => anyhow::bail!(
+                    "flat storage is not ready for shard {}, actual status: {:?}",
+                    shard_uid,
+                    other
+                ),
+            };
+            info!(target: "memtrie", %shard_uid, flat_head = ?flat_head.hash, "Saving memtrie snapshot...");
+            save_mem_tries_snapshot(snapshot_dir, &mem_tries.read().unwrap(), flat_head.hash)
+                .with_context(|| {
+                    format!("failed to save memtrie snapshot for shard {}", shard_uid)
+                })?;
+        }
+        Ok(())
+    }
+
     /// Retrieves the in-memory tries for the shard.
     pub fn get_mem_tries(&self, shard_uid: ShardUId) -> Option<Arc<RwLock<MemTries>>> {
         let guard = self.0.mem_tries.write().unwrap();
@@ -421,10 +459,19 @@ impl ShardTries {
     }
 
     /// Garbage collects the in-memory tries for the shard up to (and including) the given
-    /// height.
+    /// height. Starts a compaction of the tries if `mem_trie_compaction_threshold` is reached,
+    /// and runs the next step of the compaction in progress, if any.
     pub fn delete_memtrie_roots_up_to_height(&self, shard_uid: ShardUId, height: BlockHeight) {
         if let Some(memtries) = self.get_mem_tries(shard_uid) {
-            memtries.write().unwrap().delete_until_height(height);
+            let mut memtries = memtries.write().unwrap();
+            memtries.delete_until_height(height);
+            let Some(threshold) = self.0.trie_config.mem_trie_compaction_threshold else {
+                return;
+            };
+            if !memtries.is_compacting() && memtries.arena_fragmentation() > threshold {
+                memtries.start_compaction();
+            }
+            memtries.compaction_step(self.0.trie_config.mem_trie_compaction_nodes_per_step);
         }
     }
 }
@@ -703,6 +750,10 @@ mod test {
             sweat_prefetch_senders: Vec::new(),
             load_mem_tries_for_shards: Vec::new(),
             load_mem_tries_for_all_shards: false,
+            mem_trie_snapshot_dir: None,
+            mem_trie_dedup_nodes: false,
+            mem_trie_compaction_threshold: None,
+            mem_trie_compaction_nodes_per_step: 0,
         };
         let shard_uids = Vec::from([ShardUId::single_shard()]);
         ShardTries::new(
@@ -823,6 +874,10 @@ mod test {
             sweat_prefetch_senders: Vec::new(),
             load_mem_tries_for_shards: Vec::new(),
             load_mem_tries_for_all_shards: false,
+            mem_trie_snapshot_dir: None,
+            mem_trie_dedup_nodes: false,
+            mem_trie_compaction_threshold: None,
+            mem_trie_compaction_nodes_per_step: 0,
         };
         let shard_uids = Vec::from([ShardUId { shard_id: 0, version: 0 }]);
         let shard_uid = *shard_uids.first().unwrap();
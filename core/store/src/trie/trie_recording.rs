@@ -33,7 +33,7 @@ mod trie_recording_tests {
         TestTriesBuilder,
     };
     use crate::trie::mem::metrics::MEM_TRIE_NUM_LOOKUPS;
-    use crate::{DBCol, Store, Trie};
+    use crate::{DBCol, PartialStorage, Store, Trie};
     use near_primitives::hash::{hash, CryptoHash};
     use near_primitives::shard_layout::{get_block_shard_uid, get_block_shard_uid_rev, ShardUId};
     use near_primitives::state::ValueRef;
@@ -451,4 +451,103 @@ mod trie_recording_tests {
     ) {
         test_trie_recording_consistency_with_flat_storage(true, true, true);
     }
+
+    /// Verifies that the proof of a key produced from in-memory tries is
+    /// exactly the same as the one produced from the on-disk trie, and that
+    /// it is enough to look the key up.
+    fn test_memtrie_trie_nodes_for_key(use_missing_keys: bool) {
+        for _ in 0..NUM_ITERATIONS_PER_TEST {
+            let PreparedTrie {
+                store,
+                shard_uid,
+                data_in_trie,
+                keys_to_get,
+                keys_to_get_ref,
+                state_root,
+            } = prepare_trie(use_missing_keys);
+            let disk_tries = TestTriesBuilder::new().with_store(store.clone()).build();
+            let mem_tries =
+                TestTriesBuilder::new().with_store(store).with_in_memory_tries().build();
+            let mem_trie_lookup_counts_before = MEM_TRIE_NUM_LOOKUPS.get();
+
+            let disk_trie = disk_tries.get_trie_for_shard(shard_uid, state_root);
+            let mem_trie = mem_tries.get_trie_for_shard(shard_uid, state_root);
+            for key in keys_to_get.iter().chain(&keys_to_get_ref) {
+                let proof = mem_trie.get_trie_nodes_for_key(key).unwrap();
+                assert_eq!(proof, disk_trie.get_trie_nodes_for_key(key).unwrap());
+
+                let trie =
+                    Trie::from_recorded_storage(PartialStorage { nodes: proof }, state_root, false);
+                assert_eq!(
+                    trie.get_optimized_ref(key, crate::KeyLookupMode::Trie)
+                        .unwrap()
+                        .map(|value| value.into_value_ref()),
+                    data_in_trie.get(key).map(|value| ValueRef::new(&value))
+                );
+            }
+            assert!(MEM_TRIE_NUM_LOOKUPS.get() > mem_trie_lookup_counts_before);
+        }
+    }
+
+    #[test]
+    fn test_memtrie_trie_nodes_for_key_consistency() {
+        test_memtrie_trie_nodes_for_key(false);
+    }
+
+    #[test]
+    fn test_memtrie_trie_nodes_for_key_consistency_with_missing_keys() {
+        test_memtrie_trie_nodes_for_key(true);
+    }
+
+    /// Verifies that updating in-memory tries while recording produces the
+    /// same partial storage as updating the on-disk trie, and that the
+    /// recorded storage is enough to apply the update again.
+    #[test]
+    fn test_memtrie_update_recording_consistency() {
+        let mut rng = thread_rng();
+        for _ in 0..NUM_ITERATIONS_PER_TEST {
+            let PreparedTrie { store, shard_uid, data_in_trie, state_root, .. } =
+                prepare_trie(false);
+            let disk_tries = TestTriesBuilder::new().with_store(store.clone()).build();
+            let mem_tries =
+                TestTriesBuilder::new().with_store(store).with_in_memory_tries().build();
+
+            // Delete or overwrite some of the existing keys, with values both
+            // small enough to be inlined and not, delete some missing keys and
+            // insert some new ones.
+            let mut changes = Vec::new();
+            for key in data_in_trie.keys() {
+                match rng.gen_range(0..4) {
+                    0 => changes.push((key.clone(), None)),
+                    1 => {
+                        let value_length = rng.gen_range(1..5000);
+                        changes.push((key.clone(), Some(vec![rng.gen::<u8>(); value_length])));
+                    }
+                    2 => {
+                        let mut missing_key = key.clone();
+                        missing_key.push(100);
+                        changes.push((missing_key, None));
+                    }
+                    _ => {}
+                }
+            }
+            changes.extend(
+                gen_larger_changes(&mut rng, 10)
+                    .into_iter()
+                    .filter(|(key, value)| value.is_some() && !data_in_trie.contains_key(key)),
+            );
+
+            let disk_trie = disk_tries.get_trie_for_shard(shard_uid, state_root).recording_reads();
+            let mem_trie = mem_tries.get_trie_for_shard(shard_uid, state_root).recording_reads();
+            let disk_trie_changes = disk_trie.update(changes.clone()).unwrap();
+            let mem_trie_changes = mem_trie.update(changes.clone()).unwrap();
+            assert_eq!(mem_trie_changes.new_root, disk_trie_changes.new_root);
+
+            let partial_storage = mem_trie.recorded_storage().unwrap();
+            assert_eq!(partial_storage.nodes, disk_trie.recorded_storage().unwrap().nodes);
+
+            let trie = Trie::from_recorded_storage(partial_storage, state_root, false);
+            assert_eq!(trie.update(changes).unwrap().new_root, disk_trie_changes.new_root);
+        }
+    }
 }
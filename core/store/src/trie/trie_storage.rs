@@ -12,6 +12,7 @@ use near_primitives::shard_layout::ShardUId;
 use near_primitives::types::ShardId;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 pub(crate) struct BoundedQueue<T> {
@@ -322,6 +323,23 @@ impl TrieStorage for TrieMemoryPartialStorage {
     }
 }
 
+/// Storage which serves trie nodes and values of changes that were not
+/// committed, falling back to the underlying storage for everything else.
+/// Used to chain state transitions which must not be written to the database.
+pub(crate) struct TrieOverlayStorage {
+    pub(crate) base: Rc<dyn TrieStorage>,
+    pub(crate) overlay: HashMap<CryptoHash, Arc<[u8]>>,
+}
+
+impl TrieStorage for TrieOverlayStorage {
+    fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
+        match self.overlay.get(hash) {
+            Some(value) => Ok(value.clone()),
+            None => self.base.retrieve_raw_bytes(hash),
+        }
+    }
+}
+
 impl TrieMemoryPartialStorage {
     pub fn new(recorded_storage: HashMap<CryptoHash, Arc<[u8]>>) -> Self {
         Self { recorded_storage, visited_nodes: Default::default() }
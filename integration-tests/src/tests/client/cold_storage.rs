@@ -7,23 +7,28 @@ use near_crypto::{InMemorySigner, KeyType};
 use near_epoch_manager::EpochManager;
 use near_o11y::testonly::init_test_logger;
 use near_primitives::block::Tip;
+use near_primitives::shard_layout::get_block_shard_uid;
 use near_primitives::sharding::{PartialEncodedChunk, ShardChunk};
 use near_primitives::transaction::{
     Action, DeployContractAction, FunctionCallAction, SignedTransaction,
 };
+use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives_core::types::AccountId;
 use near_store::cold_storage::{
-    copy_all_data_to_cold, test_cold_genesis_update, test_get_store_initial_writes,
-    test_get_store_reads, update_cold_db, update_cold_head,
+    copy_all_data_to_cold, get_cold_block_data, get_cold_state_data, set_cold_head,
+    test_cold_genesis_update, test_get_store_initial_writes, test_get_store_reads, update_cold_db,
+    update_cold_head, verify_cold_db, write_cold_block_data, ColdBlockData, ColdStoreMismatch,
 };
+use near_store::db::{DBTransaction, Database};
 use near_store::metadata::DbKind;
 use near_store::metadata::DB_VERSION;
-use near_store::test_utils::create_test_node_storage_with_cold;
-use near_store::{DBCol, Store, COLD_HEAD_KEY, HEAD_KEY};
+use near_store::test_utils::{create_test_node_storage_with_cold, create_test_store};
+use near_store::{DBCol, Store, Trie, TrieDBStorage, COLD_HEAD_KEY, HEAD_KEY};
 use nearcore::config::GenesisExt;
 use nearcore::test_utils::TestEnvNightshadeSetupExt;
 use nearcore::{cold_storage::spawn_cold_store_loop, NearConfig};
 use std::collections::HashSet;
+use std::rc::Rc;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
 
@@ -85,6 +90,7 @@ fn test_storage_after_commit_of_cold_update() {
         .real_epoch_managers(&genesis.config)
         .nightshade_runtimes(&genesis)
         .build();
+    env.clients[0].chain.mut_store().set_save_account_activity(true);
 
     let (store, ..) = create_test_node_storage_with_cold(DB_VERSION, DbKind::Hot);
 
@@ -280,6 +286,7 @@ fn test_cold_db_copy_with_height_skips() {
         .real_epoch_managers(&genesis.config)
         .nightshade_runtimes(&genesis)
         .build();
+    env.clients[0].chain.mut_store().set_save_account_activity(true);
 
     let (storage, ..) = create_test_node_storage_with_cold(DB_VERSION, DbKind::Hot);
 
@@ -377,6 +384,215 @@ fn test_cold_db_copy_with_height_skips() {
     }
 }
 
+/// Copying blocks to cold storage one by one and then checking that
+/// `verify_cold_db` finds them all complete, and that it reports a value
+/// changed in cold storage afterwards.
+#[test]
+fn test_verify_cold_db() {
+    init_test_logger();
+
+    let epoch_length = 5;
+    let max_height = epoch_length * 4;
+
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(chain_genesis)
+        .real_epoch_managers(&genesis.config)
+        .nightshade_runtimes(&genesis)
+        .build();
+
+    let (storage, ..) = create_test_node_storage_with_cold(DB_VERSION, DbKind::Hot);
+    let cold_db = storage.cold_db().unwrap();
+    let hot_store = env.clients[0].runtime_adapter.store().clone();
+
+    let mut last_hash = *env.clients[0].chain.genesis().hash();
+    let mut shard_layouts = vec![];
+    for h in 1..max_height {
+        let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+        // See `test_storage_after_commit_of_cold_update` for why there are no
+        // transactions in the last two blocks.
+        if h + 2 < max_height {
+            let tx = SignedTransaction::send_money(
+                h,
+                "test0".parse().unwrap(),
+                "test1".parse().unwrap(),
+                &signer,
+                1,
+                last_hash,
+            );
+            assert_eq!(env.clients[0].process_tx(tx, false, false), ProcessTxResponse::ValidTx);
+        }
+
+        let block = env.clients[0].produce_block(h).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+
+        let epoch_manager = &env.clients[0].epoch_manager;
+        let shard_layout = epoch_manager
+            .get_shard_layout(&epoch_manager.get_epoch_id_from_prev_block(&last_hash).unwrap())
+            .unwrap();
+        update_cold_db(cold_db, &hot_store, &shard_layout, &h).unwrap();
+        shard_layouts.push(shard_layout);
+
+        last_hash = *block.hash();
+    }
+
+    for (h, shard_layout) in (1..max_height).zip(&shard_layouts) {
+        let verification = verify_cold_db(cold_db, &hot_store, shard_layout, &h).unwrap().unwrap();
+        assert!(verification.compared_with_hot);
+        assert!(verification.num_checked_keys > 0);
+        assert_eq!(verification.mismatches, vec![]);
+    }
+
+    // Blocks which are no longer in hot storage are checked using their
+    // copies in cold storage.
+    let empty_hot_store = create_test_store();
+    for (h, shard_layout) in (1..max_height).zip(&shard_layouts) {
+        let verification =
+            verify_cold_db(cold_db, &empty_hot_store, shard_layout, &h).unwrap().unwrap();
+        assert!(!verification.compared_with_hot);
+        assert!(verification.num_checked_keys > 0);
+        assert_eq!(verification.mismatches, vec![]);
+    }
+
+    // Heights without blocks can't be verified.
+    assert!(verify_cold_db(cold_db, &hot_store, &shard_layouts[0], &max_height).unwrap().is_none());
+
+    let height = 3;
+    let block_hash = hot_store.get(DBCol::BlockHeight, &height.to_le_bytes()).unwrap().unwrap();
+    let mut transaction = DBTransaction::new();
+    transaction.set(DBCol::Block, block_hash.to_vec(), vec![1, 2, 3]);
+    cold_db.write(transaction).unwrap();
+    let shard_layout = &shard_layouts[height as usize - 1];
+    let verification = verify_cold_db(cold_db, &hot_store, shard_layout, &height).unwrap().unwrap();
+    assert_eq!(
+        verification.mismatches,
+        vec![ColdStoreMismatch::Different { col: DBCol::Block, key: block_hash.to_vec() }]
+    );
+}
+
+/// Producing 4 epochs of blocks with some transactions.
+/// Exports the cold data of every block, imports it into an empty cold storage
+/// seeded with the genesis state and checks with `verify_cold_db` that it has
+/// all the data of the blocks and that the latest state can be read from it.
+#[test]
+fn test_backfill_cold_db_from_exported_blocks() {
+    init_test_logger();
+
+    let epoch_length = 5;
+    let max_height = epoch_length * 4;
+
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(chain_genesis)
+        .real_epoch_managers(&genesis.config)
+        .nightshade_runtimes(&genesis)
+        .build();
+
+    let hot_store = env.clients[0].runtime_adapter.store().clone();
+
+    let mut last_hash = *env.clients[0].chain.genesis().hash();
+    let mut exported = vec![];
+    for h in 1..max_height {
+        let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+        if h + 2 < max_height {
+            let tx = SignedTransaction::send_money(
+                h,
+                "test0".parse().unwrap(),
+                "test1".parse().unwrap(),
+                &signer,
+                1,
+                last_hash,
+            );
+            assert_eq!(env.clients[0].process_tx(tx, false, false), ProcessTxResponse::ValidTx);
+        }
+
+        let block = env.clients[0].produce_block(h).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+
+        let epoch_manager = &env.clients[0].epoch_manager;
+        let shard_layout = epoch_manager
+            .get_shard_layout(&epoch_manager.get_epoch_id_from_prev_block(&last_hash).unwrap())
+            .unwrap();
+        let data = get_cold_block_data(&hot_store, &shard_layout, &h).unwrap();
+        assert_eq!(data.tip.as_ref().unwrap().last_block_hash, *block.hash());
+        exported.push((borsh::to_vec(&data).unwrap(), shard_layout));
+
+        last_hash = *block.hash();
+    }
+
+    // Import into cold storage which has never seen the hot store, starting
+    // with the state the first block is applied to.
+    let (storage, ..) = create_test_node_storage_with_cold(DB_VERSION, DbKind::Hot);
+    let cold_db = storage.cold_db().unwrap();
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    get_cold_state_data(&hot_store, &exported[0].1, &genesis_hash, 1000, |entries| {
+        let columns = vec![("State".to_string(), entries)];
+        write_cold_block_data(cold_db, ColdBlockData { height: 1, tip: None, columns })
+    })
+    .unwrap();
+    for (bytes, _) in &exported {
+        let data = ColdBlockData::try_from_slice(bytes).unwrap();
+        let tip = data.tip.clone().unwrap();
+        write_cold_block_data(cold_db, data).unwrap();
+        set_cold_head(cold_db, &storage.get_hot_store(), &tip).unwrap();
+    }
+
+    for (h, (_, shard_layout)) in (1..max_height).zip(&exported) {
+        let verification = verify_cold_db(cold_db, &hot_store, shard_layout, &h).unwrap().unwrap();
+        assert!(verification.num_checked_keys > 0);
+        assert_eq!(verification.mismatches, vec![]);
+    }
+    let cold_head = storage
+        .get_cold_store()
+        .unwrap()
+        .get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(cold_head.height, max_height - 1);
+
+    let cold_store = storage.get_cold_store().unwrap();
+    for shard_uid in exported.last().unwrap().1.get_shard_uids() {
+        let chunk_extra = hot_store
+            .get_ser::<ChunkExtra>(DBCol::ChunkExtra, &get_block_shard_uid(&last_hash, &shard_uid))
+            .unwrap()
+            .unwrap();
+        let read_state = |store: &Store| -> Vec<_> {
+            let storage = Rc::new(TrieDBStorage::new(store.clone(), shard_uid));
+            let trie = Trie::new(storage, *chunk_extra.state_root(), None);
+            trie.iter().unwrap().map(Result::unwrap).collect()
+        };
+        let state = read_state(&hot_store);
+        assert!(!state.is_empty());
+        assert_eq!(read_state(&cold_store), state);
+    }
+
+    // Heights without blocks are exported without data.
+    let data = get_cold_block_data(&hot_store, &exported[0].1, &max_height).unwrap();
+    assert_eq!(data, ColdBlockData { height: max_height, tip: None, columns: vec![] });
+
+    // A chunk missing in cold storage is reported without the block in hot
+    // storage too.
+    let (storage, ..) = create_test_node_storage_with_cold(DB_VERSION, DbKind::Hot);
+    let cold_db = storage.cold_db().unwrap();
+    let (bytes, shard_layout) = &exported[1];
+    let mut data = ColdBlockData::try_from_slice(bytes).unwrap();
+    let (_, chunks) = data.columns.iter_mut().find(|(col, _)| col == "Chunks").unwrap();
+    let (chunk_hash, _) = chunks.pop().unwrap();
+    write_cold_block_data(cold_db, data).unwrap();
+    let verification =
+        verify_cold_db(cold_db, &create_test_store(), shard_layout, &2).unwrap().unwrap();
+    assert_eq!(
+        verification.mismatches,
+        vec![ColdStoreMismatch::Missing { col: DBCol::Chunks, key: chunk_hash }]
+    );
+}
+
 /// Producing 4 epochs of blocks with some transactions.
 /// Call copying full contents of cold columns to cold storage in batches of specified max_size.
 /// Checks COLD_STORE_MIGRATION_BATCH_WRITE_COUNT metric for some batch_sizes:
@@ -398,6 +614,7 @@ fn test_initial_copy_to_cold(batch_size: usize) {
         .real_epoch_managers(&genesis.config)
         .nightshade_runtimes(&genesis)
         .build();
+    env.clients[0].chain.mut_store().set_save_account_activity(true);
 
     let (store, ..) = create_test_node_storage_with_cold(DB_VERSION, DbKind::Archive);
 
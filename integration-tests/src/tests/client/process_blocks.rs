@@ -996,6 +996,7 @@ fn client_sync_headers() {
                     highest_block_hash: hash(&[5]),
                     tracked_shards: vec![],
                     archival: false,
+                    low_reputation: false,
                 }],
                 sent_bytes_per_sec: 0,
                 received_bytes_per_sec: 0,
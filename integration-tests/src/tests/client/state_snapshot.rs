@@ -47,6 +47,10 @@ impl StateSnaptshotTestEnv {
             sweat_prefetch_senders: Vec::new(),
             load_mem_tries_for_shards: Vec::new(),
             load_mem_tries_for_all_shards: false,
+            mem_trie_snapshot_dir: None,
+            mem_trie_dedup_nodes: false,
+            mem_trie_compaction_threshold: None,
+            mem_trie_compaction_nodes_per_step: 0,
         };
         let flat_storage_manager = FlatStorageManager::new(store.clone());
         let shard_uids = [ShardUId::single_shard()];
@@ -3,6 +3,8 @@ use std::{collections::HashMap, io, sync::Arc};
 use borsh::BorshDeserialize;
 
 use crate::runtime_utils::{get_runtime_and_trie, get_test_trie_viewer, TEST_SHARD_UID};
+use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
+use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::{
     account::Account,
     hash::hash as sha256,
@@ -10,7 +12,7 @@ use near_primitives::{
     serialize::to_base64,
     trie_key::trie_key_parsers,
     types::{AccountId, StateRoot},
-    views::{StateItem, ViewApplyState},
+    views::{StateItem, ViewApplyState, ViewStateCursor},
 };
 use near_primitives::{
     test_utils::MockEpochInfoProvider,
@@ -18,7 +20,10 @@ use near_primitives::{
     types::{EpochId, StateChangeCause},
     version::PROTOCOL_VERSION,
 };
-use near_store::{set_account, NibbleSlice, RawTrieNode, RawTrieNodeWithSize};
+use near_store::test_utils::{test_populate_flat_storage, test_populate_trie, TestTriesBuilder};
+use near_store::{
+    set_account, DBCol, NibbleSlice, RawTrieNode, RawTrieNodeWithSize, Trie, TrieUpdate,
+};
 use node_runtime::state_viewer::errors;
 use node_runtime::state_viewer::*;
 use testlib::runtime_utils::alice_account;
@@ -161,6 +166,90 @@ fn test_view_call_try_changing_storage() {
     );
 }
 
+#[test]
+fn test_trace_call() {
+    let (viewer, root) = get_test_trie_viewer();
+    let view_state = ViewApplyState {
+        block_height: 1,
+        prev_block_hash: CryptoHash::default(),
+        block_hash: CryptoHash::default(),
+        epoch_id: EpochId::default(),
+        epoch_height: 0,
+        block_timestamp: 1,
+        current_protocol_version: PROTOCOL_VERSION,
+        cache: None,
+    };
+    let key = 42u64.to_le_bytes();
+    let trace = viewer
+        .trace_call(
+            root,
+            view_state,
+            &"test.contract".parse().unwrap(),
+            "read_value",
+            &key,
+            &MockEpochInfoProvider::default(),
+        )
+        .unwrap();
+    assert_eq!(trace.error, None);
+    assert_eq!(trace.storage_reads, vec![key.to_vec().into()]);
+    let count = |cost: &str| {
+        trace.host_functions.iter().find(|host_function| host_function.cost == cost).unwrap().count
+    };
+    assert_eq!(count("STORAGE_READ_BASE"), 1);
+    assert_eq!(count("STORAGE_READ_KEY_BYTE"), key.len() as u64);
+    assert!(trace.wasm_gas > 0);
+    assert!(trace.promises.is_empty());
+}
+
+#[test]
+fn test_trace_call_promises() {
+    let (viewer, root) = get_test_trie_viewer();
+    let view_state = ViewApplyState {
+        block_height: 1,
+        prev_block_hash: CryptoHash::default(),
+        block_hash: CryptoHash::default(),
+        epoch_id: EpochId::default(),
+        epoch_height: 0,
+        block_timestamp: 1,
+        current_protocol_version: PROTOCOL_VERSION,
+        cache: None,
+    };
+    let args = serde_json::json!([
+        {"create": {
+            "account_id": "alice.near",
+            "method_name": "first",
+            "arguments": [],
+            "amount": "0",
+            "gas": 1_000_000_000_000u64,
+        }},
+        {"then": {
+            "promise_index": 0,
+            "account_id": "bob.near",
+            "method_name": "second",
+            "arguments": [],
+            "amount": "0",
+            "gas": 1_000_000_000_000u64,
+        }},
+    ]);
+    let trace = viewer
+        .trace_call(
+            root,
+            view_state,
+            &"test.contract".parse().unwrap(),
+            "call_promise",
+            &serde_json::to_vec(&args).unwrap(),
+            &MockEpochInfoProvider::default(),
+        )
+        .unwrap();
+    assert_eq!(trace.error, None);
+    let promises = trace
+        .promises
+        .iter()
+        .map(|promise| (promise.receiver_id.as_str(), promise.depends_on.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(promises, vec![("alice.near", vec![]), ("bob.near", vec![0])]);
+}
+
 #[test]
 fn test_view_call_with_args() {
     let (viewer, root) = get_test_trie_viewer();
@@ -203,8 +292,15 @@ fn assert_view_state(
         .map(|(key, value)| StateItem { key: key.to_vec().into(), value: value.to_vec().into() })
         .collect::<Vec<_>>();
 
-    let view_state =
-        |include_proof| trie_viewer.view_state(&state_update, &alice, prefix, include_proof);
+    let view_state = |include_proof| {
+        trie_viewer.view_state(
+            &state_update,
+            &alice,
+            prefix,
+            &ViewStateRange::default(),
+            include_proof,
+        )
+    };
 
     // Test without proof
     let result = view_state(false).unwrap();
@@ -363,7 +459,13 @@ fn test_view_state_too_large() {
         &Account::new(0, 0, CryptoHash::default(), 50_001),
     );
     let trie_viewer = TrieViewer::new(Some(50_000), None);
-    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false);
+    let result = trie_viewer.view_state(
+        &state_update,
+        &alice_account(),
+        b"",
+        &ViewStateRange::default(),
+        false,
+    );
     assert!(matches!(result, Err(errors::ViewStateError::AccountStateTooLarge { .. })));
 }
 
@@ -379,10 +481,203 @@ fn test_view_state_with_large_contract() {
     );
     state_update.set(TrieKey::ContractCode { account_id: alice_account() }, contract_code);
     let trie_viewer = TrieViewer::new(Some(50_000), None);
-    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false);
+    let result = trie_viewer.view_state(
+        &state_update,
+        &alice_account(),
+        b"",
+        &ViewStateRange::default(),
+        false,
+    );
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_view_state_paginated() {
+    let (_, tries, root) = get_runtime_and_trie();
+    let mut state_update = tries.new_trie_update(TEST_SHARD_UID, root);
+    set_account(
+        &mut state_update,
+        alice_account(),
+        &Account::new(0, 0, CryptoHash::default(), 50_001),
+    );
+    let keys: [&[u8]; 5] = [b"a", b"b1", b"b2", b"b3", b"c"];
+    for key in keys {
+        state_update.set(
+            TrieKey::ContractData { account_id: alice_account(), key: key.to_vec() },
+            key.to_vec(),
+        );
+    }
+    state_update.commit(StateChangeCause::InitialState);
+    let trie_changes = state_update.finalize().unwrap().1;
+    let mut db_changes = tries.store_update();
+    let new_root = tries.apply_all(&trie_changes, TEST_SHARD_UID, &mut db_changes);
+    db_changes.commit().unwrap();
+    let state_update = tries.new_trie_update(TEST_SHARD_UID, new_root);
+
+    // The whole state is too large, but pages of it can still be viewed.
+    let trie_viewer = TrieViewer::new(Some(50_000), None);
+    let view_state = |prefix: &[u8], range: &ViewStateRange| {
+        let result = trie_viewer.view_state(&state_update, &alice_account(), prefix, range, true);
+        let result = result.unwrap();
+        let proof_verifier = ProofVerifier::new(result.proof.clone()).unwrap();
+        for item in &result.values {
+            assert!(proof_verifier.verify(
+                &new_root,
+                &alice_account(),
+                &item.key,
+                Some(&item.value[..])
+            ));
+        }
+        let keys = result.values.iter().map(|item| item.key.to_vec()).collect::<Vec<_>>();
+        (keys, result.next_cursor)
+    };
+    let result = trie_viewer.view_state(
+        &state_update,
+        &alice_account(),
+        b"",
+        &ViewStateRange::default(),
+        false,
+    );
+    assert!(matches!(result, Err(errors::ViewStateError::AccountStateTooLarge { .. })));
+
+    let mut cursor = None;
+    let mut pages = vec![];
+    loop {
+        let range =
+            ViewStateRange { limit: Some(2), cursor: cursor.as_ref(), ..Default::default() };
+        let (keys, next_cursor) = view_state(b"", &range);
+        pages.push(keys);
+        cursor = match next_cursor {
+            Some(next_cursor) => Some(next_cursor),
+            None => break,
+        };
+    }
+    assert_eq!(
+        pages,
+        vec![
+            vec![b"a".to_vec(), b"b1".to_vec()],
+            vec![b"b2".to_vec(), b"b3".to_vec()],
+            vec![b"c".to_vec()],
+        ]
+    );
+
+    // Bounds are combined with the prefix.
+    let range = ViewStateRange {
+        start_key: Some(b"b2"),
+        end_key: Some(b"c"),
+        limit: Some(10),
+        ..Default::default()
+    };
+    assert_eq!(view_state(b"", &range), (vec![b"b2".to_vec(), b"b3".to_vec()], None));
+    assert_eq!(view_state(b"b", &range), (vec![b"b2".to_vec(), b"b3".to_vec()], None));
+    let range = ViewStateRange { start_key: Some(b"a"), limit: Some(1), ..Default::default() };
+    assert_eq!(
+        view_state(b"b", &range),
+        (vec![b"b1".to_vec()], Some(ViewStateCursor::new(b"b2".to_vec())))
+    );
+}
+
+/// Proofs of view state queries served from in-memory tries are the same as the
+/// ones read from the on-disk trie.
+#[test]
+fn test_view_state_proof_with_memtries() {
+    let shard_uid = ShardUId::single_shard();
+    let account = Account::new(0, 0, CryptoHash::default(), 50_001);
+    let mut changes = vec![];
+    for account_id in [alice_account(), "alex".parse().unwrap(), "alina".parse().unwrap()] {
+        changes.push((
+            TrieKey::Account { account_id: account_id.clone() }.to_vec(),
+            Some(borsh::to_vec(&account).unwrap()),
+        ));
+        for key in [&b"a"[..], b"b1", b"b2", b"b3", b"c"] {
+            changes.push((
+                TrieKey::ContractData { account_id: account_id.clone(), key: key.to_vec() }
+                    .to_vec(),
+                Some(key.to_vec()),
+            ));
+        }
+    }
+
+    let tries = TestTriesBuilder::new().with_flat_storage().build();
+    let root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes.clone());
+    test_populate_flat_storage(
+        &tries,
+        shard_uid,
+        &CryptoHash::default(),
+        &CryptoHash::default(),
+        &changes,
+    );
+    // ChunkExtra is needed for in-memory trie loading code to query state roots.
+    let chunk_extra = ChunkExtra::new(&root, CryptoHash::default(), Vec::new(), 0, 0, 0);
+    let mut store_update = tries.store_update();
+    store_update
+        .set_ser(
+            DBCol::ChunkExtra,
+            &get_block_shard_uid(&CryptoHash::default(), &shard_uid),
+            &chunk_extra,
+        )
+        .unwrap();
+    store_update.commit().unwrap();
+    let mem_tries = TestTriesBuilder::new()
+        .with_store(tries.get_store())
+        .with_flat_storage()
+        .with_in_memory_tries()
+        .build();
+
+    let trie_viewer = TrieViewer::default();
+    let ranges: [(&[u8], ViewStateRange); 8] = [
+        (b"", ViewStateRange::default()),
+        (b"", ViewStateRange { limit: Some(2), ..Default::default() }),
+        (b"b", ViewStateRange::default()),
+        (b"xyz", ViewStateRange::default()),
+        (b"", ViewStateRange { start_key: Some(b"b2"), limit: Some(1), ..Default::default() }),
+        (b"", ViewStateRange { start_key: Some(b"b"), end_key: Some(b"c"), ..Default::default() }),
+        (b"b", ViewStateRange { start_key: Some(b"b2"), ..Default::default() }),
+        (b"", ViewStateRange { start_key: Some(b"d"), ..Default::default() }),
+    ];
+    let disk_state_update = tries.new_trie_update(shard_uid, root);
+    let disk_results = ranges
+        .iter()
+        .map(|(prefix, range)| {
+            trie_viewer.view_state(&disk_state_update, &alice_account(), prefix, range, true)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    // Remove the on-disk trie, so that the proofs can only come from in-memory
+    // tries.  All the values are small enough to be inlined.
+    let mut store_update = tries.store_update();
+    store_update.delete_all(DBCol::State);
+    store_update.commit().unwrap();
+
+    let mem_state_update = TrieUpdate::new(mem_tries.get_trie_with_block_hash_for_shard(
+        shard_uid,
+        root,
+        &CryptoHash::default(),
+        true,
+    ));
+    for ((prefix, range), disk_result) in ranges.iter().zip(disk_results) {
+        let mem_result = trie_viewer
+            .view_state(&mem_state_update, &alice_account(), prefix, range, true)
+            .unwrap();
+        assert_eq!(mem_result.values, disk_result.values, "prefix: {prefix:?}, range: {range:?}");
+        assert_eq!(mem_result.next_cursor, disk_result.next_cursor);
+        let mut disk_proof = disk_result.proof;
+        disk_proof.sort();
+        assert_eq!(mem_result.proof, disk_proof, "prefix: {prefix:?}, range: {range:?}");
+
+        let proof_verifier = ProofVerifier::new(mem_result.proof).unwrap();
+        for item in &mem_result.values {
+            assert!(proof_verifier.verify(
+                &root,
+                &alice_account(),
+                &item.key,
+                Some(&item.value[..])
+            ));
+        }
+    }
+}
+
 #[test]
 fn test_log_when_panic() {
     let (viewer, root) = get_test_trie_viewer();
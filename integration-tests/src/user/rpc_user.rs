@@ -82,6 +82,10 @@ impl User for RpcUser {
             account_id: account_id.clone(),
             prefix: prefix.to_vec().into(),
             include_proof: false,
+            start_key: None,
+            end_key: None,
+            limit: None,
+            cursor: None,
         };
         match self.query(query)?.kind {
             near_jsonrpc_primitives::types::query::QueryResponseKind::ViewState(
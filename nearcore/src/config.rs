@@ -301,6 +301,12 @@ pub struct Config {
     /// needs trie changes in order to do garbage collection on hot and populate cold State column.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub save_trie_changes: Option<bool>,
+    /// Maintain an index of transactions and receipts which touched each
+    /// account, used by the `EXPERIMENTAL_account_activity` RPC method.
+    /// Entries written while enabled are garbage collected only as long as
+    /// the index stays enabled.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub save_account_activity: bool,
     pub log_summary_style: LogSummaryStyle,
     #[serde(default = "default_log_summary_period")]
     pub log_summary_period: Duration,
@@ -371,6 +377,7 @@ impl Default for Config {
             tracked_shard_schedule: None,
             archive: false,
             save_trie_changes: None,
+            save_account_activity: false,
             log_summary_style: LogSummaryStyle::Colored,
             log_summary_period: default_log_summary_period(),
             gc: GCConfig::default(),
@@ -669,6 +676,7 @@ impl NearConfig {
                 tracked_shard_schedule: config.tracked_shard_schedule.unwrap_or(vec![]),
                 archive: config.archive,
                 save_trie_changes: config.save_trie_changes.unwrap_or(!config.archive),
+                save_account_activity: config.save_account_activity,
                 log_summary_style: config.log_summary_style,
                 gc: config.gc,
                 view_client_threads: config.view_client_threads,
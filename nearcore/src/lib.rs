@@ -25,11 +25,11 @@ use near_epoch_manager::shard_tracker::{ShardTracker, TrackedConfig};
 use near_epoch_manager::EpochManager;
 use near_network::PeerManagerActor;
 use near_primitives::block::GenesisId;
-use near_store::flat::FlatStateValuesInliningMigrationHandle;
+use near_store::flat::{FlatStateValuesInliningMigrationHandle, FlatStorageVerifierHandle};
 use near_store::genesis::initialize_genesis_state;
 use near_store::metadata::DbKind;
 use near_store::metrics::spawn_db_metrics_loop;
-use near_store::{DBCol, Mode, NodeStorage, Store, StoreOpenerError};
+use near_store::{DBCol, Mode, NodeStorage, ShardTries, Store, StoreOpenerError};
 use near_telemetry::TelemetryActor;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
@@ -213,6 +213,11 @@ pub struct NearNode {
     /// A handle to control background flat state values inlining migration.
     /// Needed temporarily, will be removed after the migration is completed.
     pub flat_state_migration_handle: FlatStateValuesInliningMigrationHandle,
+    /// A handle to the background verification of flat storage against the
+    /// trie.  Only set if `store.flat_storage_verification.enabled`.
+    pub flat_storage_verifier_handle: Option<FlatStorageVerifierHandle>,
+    /// Tries of the node, used to snapshot the in-memory tries on shutdown.
+    pub shard_tries: ShardTries,
 }
 
 pub fn start_with_config(home_dir: &Path, config: NearConfig) -> anyhow::Result<NearNode> {
@@ -367,7 +372,16 @@ pub fn start_with_config_and_synchronization(
             runtime.get_flat_storage_manager(),
             config.client_config.client_background_migration_threads,
         );
-
+    let flat_storage_verifier_handle =
+        config.config.store.flat_storage_verification.enabled.then(|| {
+            FlatStorageVerifierHandle::start(
+                storage.get_hot_store(),
+                runtime.get_flat_storage_manager(),
+                config.config.store.flat_storage_verification.clone(),
+            )
+        });
+
+    let shard_tries = runtime.get_tries();
     let state_sync_dump_handle = spawn_state_sync_dump(
         &config.client_config,
         chain_genesis,
@@ -442,6 +456,8 @@ pub fn start_with_config_and_synchronization(
         cold_store_loop_handle,
         state_sync_dump_handle,
         flat_state_migration_handle,
+        flat_storage_verifier_handle,
+        shard_tries,
     })
 }
 
@@ -6,7 +6,7 @@ use borsh::BorshDeserialize;
 use errors::FromStateViewerErrors;
 use near_chain::types::{
     ApplySplitStateResult, ApplyTransactionResult, RuntimeAdapter, RuntimeStorageConfig,
-    StorageDataSource, Tip,
+    SimulateTransactionResult, StorageDataSource, Tip,
 };
 use near_chain::Error;
 use near_chain_configs::{
@@ -34,25 +34,26 @@ use near_primitives::trie_key::TrieKey;
 use near_primitives::types::validator_stake::ValidatorStakeIter;
 use near_primitives::types::{
     AccountId, Balance, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, Gas, MerkleHash,
-    ShardId, StateChangeCause, StateChangesForSplitStates, StateRoot, StateRootNode,
+    ShardId, StateChangeCause, StateChanges, StateChangesForSplitStates, StateRoot, StateRootNode,
 };
 use near_primitives::version::ProtocolVersion;
 use near_primitives::views::{
-    AccessKeyInfoView, CallResult, QueryRequest, QueryResponse, QueryResponseKind, ViewApplyState,
-    ViewStateResult,
+    AccessKeyInfoView, CallResult, CallTraceView, QueryRequest, QueryResponse, QueryResponseKind,
+    ViewApplyState, ViewStateResult,
 };
 use near_store::config::StateSnapshotType;
 use near_store::flat::FlatStorageManager;
 use near_store::metadata::DbKind;
 use near_store::{
-    ApplyStatePartResult, DBCol, ShardTries, StateSnapshotConfig, Store,
-    StoreCompiledContractCache, Trie, TrieConfig, WrappedTrieChanges, COLD_HEAD_KEY,
+    ApplyStatePartResult, DBCol, ReadOnlyStoreCompiledContractCache, ShardTries,
+    StateSnapshotConfig, Store, StoreCompiledContractCache, Trie, TrieConfig, TrieUpdate,
+    WrappedTrieChanges, COLD_HEAD_KEY,
 };
 use near_vm_runner::logic::CompiledContractCache;
 use near_vm_runner::precompile_contract;
 use near_vm_runner::ContractCode;
 use node_runtime::adapter::ViewRuntimeAdapter;
-use node_runtime::state_viewer::TrieViewer;
+use node_runtime::state_viewer::{TrieViewer, ViewStateRange};
 use node_runtime::{
     validate_transaction, verify_and_charge_transaction, ApplyState, Runtime,
     ValidatorAccountsUpdate,
@@ -65,6 +66,10 @@ use tracing::{debug, error, info};
 
 pub mod errors;
 
+/// Maximal number of blocks in which a simulated transaction and the receipts
+/// it produces for its own shard are applied.
+const MAX_SIMULATED_BLOCKS: BlockHeight = 16;
+
 /// Defines Nightshade state transition and validator rotation.
 /// TODO: this possibly should be merged with the runtime cargo or at least reconciled on the interfaces.
 pub struct NightshadeRuntime {
@@ -96,10 +101,18 @@ impl NightshadeRuntime {
         // TODO (#9989): directly use the new state snapshot config once the migration is done.
         let compaction_enabled = config.config.store.state_snapshot_compaction_enabled
             || config.config.store.state_snapshot_config.compaction_enabled;
+        let hot_store_path = config.config.store.path.clone().unwrap_or(PathBuf::from("data"));
+        let mut trie_config = TrieConfig::from_store_config(&config.config.store);
+        trie_config.mem_trie_snapshot_dir = config
+            .config
+            .store
+            .mem_trie_snapshot_path
+            .as_ref()
+            .map(|path| home_dir.join(&hot_store_path).join(path));
         let state_snapshot_config = StateSnapshotConfig {
             state_snapshot_type,
             home_dir: home_dir.to_path_buf(),
-            hot_store_path: config.config.store.path.clone().unwrap_or(PathBuf::from("data")),
+            hot_store_path,
             state_snapshot_subdir: PathBuf::from("state_snapshot"),
             compaction_enabled,
         };
@@ -111,7 +124,7 @@ impl NightshadeRuntime {
             config.client_config.max_gas_burnt_view,
             None,
             config.config.gc.gc_num_epochs_to_keep(),
-            TrieConfig::from_store_config(&config.config.store),
+            trie_config,
             state_snapshot_config,
         )
     }
@@ -396,6 +409,7 @@ impl NightshadeRuntime {
                 is_first_block_of_version,
                 is_first_block_with_chunk_of_version,
             },
+            verify_signatures: true,
         };
 
         let instant = Instant::now();
@@ -968,13 +982,65 @@ impl RuntimeAdapter for NightshadeRuntime {
                     block_hash: *block_hash,
                 })
             }
-            QueryRequest::ViewState { account_id, prefix, include_proof } => {
+            QueryRequest::TraceCall { account_id, method_name, args } => {
+                let (epoch_height, current_protocol_version) = {
+                    let epoch_manager = self.epoch_manager.read();
+                    let epoch_info = epoch_manager.get_epoch_info(epoch_id).map_err(|err| {
+                        near_chain::near_chain_primitives::error::QueryError::from_epoch_error(
+                            err,
+                            block_height,
+                            *block_hash,
+                        )
+                    })?;
+                    (epoch_info.epoch_height(), epoch_info.protocol_version())
+                };
+
+                let call_trace = self
+                    .trace_call(
+                        &shard_uid,
+                        *state_root,
+                        block_height,
+                        block_timestamp,
+                        prev_block_hash,
+                        block_hash,
+                        epoch_height,
+                        epoch_id,
+                        account_id,
+                        method_name,
+                        args.as_ref(),
+                        self.epoch_manager.as_ref(),
+                        current_protocol_version,
+                    )
+                    .map_err(|err| near_chain::near_chain_primitives::error::QueryError::from_call_function_error(err, block_height, *block_hash))?;
+                Ok(QueryResponse {
+                    kind: QueryResponseKind::CallTrace(call_trace),
+                    block_height,
+                    block_hash: *block_hash,
+                })
+            }
+            QueryRequest::ViewState {
+                account_id,
+                prefix,
+                include_proof,
+                start_key,
+                end_key,
+                limit,
+                cursor,
+            } => {
+                let range = ViewStateRange {
+                    start_key: start_key.as_deref().map(Vec::as_slice),
+                    end_key: end_key.as_deref().map(Vec::as_slice),
+                    limit: limit.map(|limit| limit.get() as usize),
+                    cursor: cursor.as_ref(),
+                };
                 let view_state_result = self
                     .view_state(
                         &shard_uid,
                         *state_root,
+                        block_hash,
                         account_id,
                         prefix.as_ref(),
+                        &range,
                         *include_proof,
                     )
                     .map_err(|err| {
@@ -1032,6 +1098,135 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
+    fn simulate_transaction(
+        &self,
+        shard_id: ShardId,
+        state_root: StateRoot,
+        height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        gas_price: Balance,
+        random_seed: CryptoHash,
+        transaction: &SignedTransaction,
+        verify_signature: bool,
+    ) -> Result<SimulateTransactionResult, Error> {
+        let epoch_id = self.epoch_manager.get_epoch_id_from_prev_block(prev_block_hash)?;
+        let epoch_height = self.epoch_manager.get_epoch_height_from_prev_block(prev_block_hash)?;
+        let current_protocol_version = self.epoch_manager.get_epoch_protocol_version(&epoch_id)?;
+        let shard_layout = self.epoch_manager.get_shard_layout(&epoch_id)?;
+        let shard_uid = self.get_shard_uid_from_prev_hash(shard_id, prev_block_hash)?;
+        let base_trie = || {
+            self.tries.get_trie_with_block_hash_for_shard(
+                shard_uid,
+                state_root,
+                prev_block_hash,
+                true,
+            )
+        };
+
+        // Changes of all simulated blocks, which are only kept in memory.
+        let mut trie_changes = vec![];
+        // Receipts which are already delayed are not part of the simulation,
+        // so they are dropped from the queue before applying anything.
+        let mut state_update = TrieUpdate::new(base_trie());
+        let mut delayed_receipts_indices: DelayedReceiptIndices =
+            near_store::get(&state_update, &TrieKey::DelayedReceiptIndices)?.unwrap_or_default();
+        if delayed_receipts_indices.len() > 0 {
+            delayed_receipts_indices.first_index = delayed_receipts_indices.next_available_index;
+            near_store::set(
+                &mut state_update,
+                TrieKey::DelayedReceiptIndices,
+                &delayed_receipts_indices,
+            );
+            state_update.commit(StateChangeCause::UpdatedDelayedReceipts);
+            trie_changes.push(state_update.finalize()?.1);
+        }
+
+        // Contracts compiled during the simulation are only kept in memory
+        // too, so that the simulation doesn't modify the store.
+        let contract_cache = ReadOnlyStoreCompiledContractCache::new(&self.store);
+        let mut outcomes = vec![];
+        let mut raw_state_changes = vec![];
+        let mut pending_receipts = vec![];
+        let mut transactions = vec![transaction.clone()];
+        let mut receipts = vec![];
+        let mut block_hash = *prev_block_hash;
+        for height in height..height + MAX_SIMULATED_BLOCKS {
+            if transactions.is_empty() && receipts.is_empty() {
+                break;
+            }
+            let trie = if trie_changes.is_empty() {
+                base_trie()
+            } else {
+                base_trie().with_uncommitted_changes(&trie_changes)
+            };
+            // Simulated blocks don't exist, but their hashes are needed to
+            // derive receipt ids.
+            let prev_block_hash = block_hash;
+            block_hash = CryptoHash::hash_borsh((prev_block_hash, height));
+            let apply_state = ApplyState {
+                block_height: height,
+                prev_block_hash,
+                block_hash,
+                epoch_id: epoch_id.clone(),
+                epoch_height,
+                gas_price,
+                block_timestamp,
+                gas_limit: None,
+                random_seed,
+                current_protocol_version,
+                config: self.runtime_config_store.get_config(current_protocol_version).clone(),
+                cache: Some(Box::new(contract_cache.clone())),
+                is_new_chunk: true,
+                migration_data: Arc::clone(&self.migration_data),
+                migration_flags: MigrationFlags::default(),
+                verify_signatures: verify_signature,
+            };
+            let apply_result = match self.runtime.apply(
+                trie,
+                &None,
+                &apply_state,
+                &receipts,
+                &transactions,
+                self.epoch_manager.as_ref(),
+                Default::default(),
+            ) {
+                Ok(apply_result) => apply_result,
+                Err(RuntimeError::InvalidTxError(err)) => {
+                    return Ok(SimulateTransactionResult {
+                        outcomes: vec![],
+                        state_changes: vec![],
+                        pending_receipts: vec![],
+                        invalid_tx_error: Some(err),
+                    });
+                }
+                Err(RuntimeError::StorageError(err)) => return Err(Error::StorageError(err)),
+                Err(err) => return Err(Error::Other(err.to_string())),
+            };
+            outcomes.extend(apply_result.outcomes);
+            raw_state_changes.extend(apply_result.state_changes);
+            trie_changes.push(apply_result.trie_changes);
+            // Receipts for this shard are applied in the next simulated block,
+            // receipts for other shards are only reported.
+            transactions.clear();
+            let (local_receipts, other_receipts): (Vec<_>, Vec<_>) =
+                apply_result.outgoing_receipts.into_iter().partition(|receipt| {
+                    account_id_to_shard_id(&receipt.receiver_id, &shard_layout) == shard_id
+                });
+            pending_receipts.extend(other_receipts);
+            receipts = local_receipts;
+        }
+        pending_receipts.extend(receipts);
+        let state_changes = StateChanges::from_changes(raw_state_changes.into_iter().map(Ok))
+            .map_err(|err| Error::Other(err.to_string()))?;
+        Ok(SimulateTransactionResult {
+            outcomes,
+            state_changes,
+            pending_receipts,
+            invalid_tx_error: None,
+        })
+    }
+
     // Wrapper to get the metrics.
     fn obtain_state_part(
         &self,
@@ -1288,6 +1483,43 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         )
     }
 
+    fn trace_call(
+        &self,
+        shard_uid: &ShardUId,
+        state_root: MerkleHash,
+        height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
+        epoch_height: EpochHeight,
+        epoch_id: &EpochId,
+        contract_id: &AccountId,
+        method_name: &str,
+        args: &[u8],
+        epoch_info_provider: &dyn EpochInfoProvider,
+        current_protocol_version: ProtocolVersion,
+    ) -> Result<CallTraceView, node_runtime::state_viewer::errors::CallFunctionError> {
+        let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
+        let view_state = ViewApplyState {
+            block_height: height,
+            prev_block_hash: *prev_block_hash,
+            block_hash: *block_hash,
+            epoch_id: epoch_id.clone(),
+            epoch_height,
+            block_timestamp,
+            current_protocol_version,
+            cache: Some(Box::new(StoreCompiledContractCache::new(&self.tries.get_store()))),
+        };
+        self.trie_viewer.trace_call(
+            state_update,
+            view_state,
+            contract_id,
+            method_name,
+            args,
+            epoch_info_provider,
+        )
+    }
+
     fn view_access_key(
         &self,
         shard_uid: &ShardUId,
@@ -1314,12 +1546,28 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         &self,
         shard_uid: &ShardUId,
         state_root: MerkleHash,
+        block_hash: &CryptoHash,
         account_id: &AccountId,
         prefix: &[u8],
+        range: &ViewStateRange,
         include_proof: bool,
     ) -> Result<ViewStateResult, node_runtime::state_viewer::errors::ViewStateError> {
-        let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
-        self.trie_viewer.view_state(&state_update, account_id, prefix, include_proof)
+        // Flat storage can only be iterated at its head and doesn't give us
+        // proofs, so otherwise go through the trie.
+        let flat_storage_at_block = !include_proof
+            && self
+                .get_flat_storage_manager()
+                .get_flat_storage_for_shard(*shard_uid)
+                .map_or(false, |flat_storage| flat_storage.get_head_hash() == *block_hash);
+        let state_update = if flat_storage_at_block {
+            TrieUpdate::new(
+                self.tries
+                    .get_trie_with_block_hash_for_shard(*shard_uid, state_root, block_hash, true),
+            )
+        } else {
+            self.tries.new_trie_update_view(*shard_uid, state_root)
+        };
+        self.trie_viewer.view_state(&state_update, account_id, prefix, range, include_proof)
     }
 }
 
@@ -1339,13 +1587,16 @@ mod test {
 
     use crate::config::{GenesisExt, TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
     use near_chain_configs::{Genesis, DEFAULT_GC_NUM_EPOCHS_TO_KEEP};
-    use near_crypto::{InMemorySigner, KeyType, Signer};
+    use near_crypto::{InMemorySigner, KeyType, Signature, Signer};
     use near_o11y::testonly::init_test_logger;
     use near_primitives::block::Tip;
     use near_primitives::challenge::SlashedValidator;
-    use near_primitives::transaction::{Action, DeleteAccountAction, StakeAction, TransferAction};
+    use near_primitives::transaction::{
+        Action, DeleteAccountAction, DeployContractAction, ExecutionStatus, FunctionCallAction,
+        StakeAction, TransferAction,
+    };
     use near_primitives::types::{
-        BlockHeightDelta, Nonce, NumShards, ValidatorId, ValidatorInfoIdentifier,
+        BlockHeightDelta, Nonce, NumShards, StateChangeValue, ValidatorId, ValidatorInfoIdentifier,
         ValidatorKickoutReason,
     };
     use near_primitives::validator_signer::ValidatorSigner;
@@ -1356,6 +1607,7 @@ mod test {
     use near_store::{get_genesis_state_roots, NodeStorage};
 
     use super::*;
+    use strum::IntoEnumIterator;
 
     use near_primitives::account::id::AccountIdRef;
     use near_primitives::trie_key::TrieKey;
@@ -2795,6 +3047,118 @@ mod test {
         assert_eq!(state_value, view_state_value);
     }
 
+    /// Simulated transactions run their receipts to completion without
+    /// changing the stored state.
+    #[test]
+    fn test_simulate_transaction() {
+        let validators: Vec<AccountId> = vec!["test1".parse().unwrap(), "test2".parse().unwrap()];
+        let mut env = TestEnv::new(vec![validators.clone()], 4, false);
+        env.step_default(vec![]);
+        let signer = InMemorySigner::from_seed(validators[0].clone(), KeyType::ED25519, "test1");
+        let transfer_tx = SignedTransaction::from_actions(
+            1,
+            validators[0].clone(),
+            validators[1].clone(),
+            &signer as &dyn Signer,
+            vec![Action::Transfer(TransferAction { deposit: 10 })],
+            CryptoHash::default(),
+        );
+        let simulate = |transaction: &SignedTransaction, verify_signature: bool| {
+            env.runtime
+                .simulate_transaction(
+                    0,
+                    env.state_roots[0],
+                    env.head.height + 1,
+                    0,
+                    &env.head.last_block_hash,
+                    env.runtime.genesis_config.min_gas_price,
+                    CryptoHash::default(),
+                    transaction,
+                    verify_signature,
+                )
+                .unwrap()
+        };
+
+        let result = simulate(&transfer_tx, true);
+        assert_eq!(result.invalid_tx_error, None);
+        assert_eq!(result.outcomes[0].id, transfer_tx.get_hash());
+        // The transfer receipt is executed in the following simulated block.
+        assert_eq!(result.outcomes[1].id, result.outcomes[0].outcome.receipt_ids[0]);
+        assert!(result.pending_receipts.is_empty());
+        assert!(result.state_changes.iter().any(|change| matches!(
+            &change.value,
+            StateChangeValue::AccountUpdate { account_id, account }
+                if account_id == &validators[1]
+                    && account.amount() == TESTING_INIT_BALANCE - TESTING_INIT_STAKE + 10
+        )));
+        assert_eq!(
+            env.view_account(&validators[1]).amount,
+            TESTING_INIT_BALANCE - TESTING_INIT_STAKE
+        );
+
+        let mut unsigned_tx = transfer_tx;
+        unsigned_tx.signature = Signature::empty(KeyType::ED25519);
+        assert_eq!(
+            simulate(&unsigned_tx, true).invalid_tx_error,
+            Some(InvalidTxError::InvalidSignature)
+        );
+        assert_eq!(simulate(&unsigned_tx, false).invalid_tx_error, None);
+    }
+
+    /// Simulating a transaction which compiles a contract doesn't modify the
+    /// store.
+    #[test]
+    fn test_simulate_transaction_keeps_store_unchanged() {
+        let validators: Vec<AccountId> = vec!["test1".parse().unwrap(), "test2".parse().unwrap()];
+        let mut env = TestEnv::new(vec![validators.clone()], 4, false);
+        env.step_default(vec![]);
+        let signer = InMemorySigner::from_seed(validators[0].clone(), KeyType::ED25519, "test1");
+        let transaction = SignedTransaction::from_actions(
+            1,
+            validators[0].clone(),
+            validators[0].clone(),
+            &signer as &dyn Signer,
+            vec![
+                Action::DeployContract(DeployContractAction {
+                    code: near_test_contracts::rs_contract().to_vec(),
+                }),
+                Action::FunctionCall(Box::new(FunctionCallAction {
+                    method_name: "log_something".to_string(),
+                    args: vec![],
+                    gas: 100_000_000_000_000,
+                    deposit: 0,
+                })),
+            ],
+            CryptoHash::default(),
+        );
+        let dump_store = |store: &Store| {
+            DBCol::iter()
+                .map(|col| store.iter(col).map(Result::unwrap).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        let store_before = dump_store(&env.runtime.store);
+
+        let result = env
+            .runtime
+            .simulate_transaction(
+                0,
+                env.state_roots[0],
+                env.head.height + 1,
+                0,
+                &env.head.last_block_hash,
+                env.runtime.genesis_config.min_gas_price,
+                CryptoHash::default(),
+                &transaction,
+                true,
+            )
+            .unwrap();
+        assert_eq!(result.invalid_tx_error, None);
+        assert_eq!(result.outcomes[1].outcome.status, ExecutionStatus::SuccessValue(vec![]));
+        assert!(!result.outcomes[1].outcome.logs.is_empty());
+
+        assert!(dump_store(&env.runtime.store) == store_before);
+    }
+
     /// Check that mainnet genesis hash still matches, to make sure that we're still backwards compatible.
     #[test]
     fn test_genesis_hash() {
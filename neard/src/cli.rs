@@ -520,7 +520,7 @@ impl RunCmd {
             broadcast::channel::<Result<UpdateableConfigs, Arc<UpdateableConfigLoaderError>>>(16);
         let sys = actix::System::new();
 
-        sys.block_on(async move {
+        let shard_tries = sys.block_on(async move {
             // Initialize the subscriber that takes care of both logging and tracing.
             let _subscriber_guard = default_subscriber_with_opentelemetry(
                 make_env_filter(verbose_target).unwrap(),
@@ -547,6 +547,8 @@ impl RunCmd {
                 cold_store_loop_handle,
                 state_sync_dump_handle,
                 flat_state_migration_handle,
+                flat_storage_verifier_handle,
+                shard_tries,
                 ..
             } = nearcore::start_with_config_and_synchronization(
                 home_dir,
@@ -574,6 +576,9 @@ impl RunCmd {
                 handle.stop()
             }
             flat_state_migration_handle.stop();
+            if let Some(handle) = flat_storage_verifier_handle {
+                handle.stop()
+            }
             futures::future::join_all(rpc_servers.iter().map(|(name, server)| async move {
                 server.stop(true).await;
                 debug!(target: "neard", "{} server stopped", name);
@@ -583,8 +588,13 @@ impl RunCmd {
             // Disable the subscriber to properly shutdown the tracer.
             near_o11y::reload(Some("error"), None, Some(near_o11y::OpenTelemetryLevel::OFF))
                 .unwrap();
+            shard_tries
         });
         sys.run().unwrap();
+        if let Err(err) = shard_tries.save_mem_tries_snapshots() {
+            error!(target: "neard", ?err, "Failed to save memtrie snapshots");
+        }
+        drop(shard_tries);
         info!(target: "neard", "Waiting for RocksDB to gracefully shutdown");
         RocksDB::block_until_all_instances_are_dropped();
     }
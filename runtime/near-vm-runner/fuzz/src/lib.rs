@@ -48,6 +48,7 @@ pub fn create_context(input: Vec<u8>) -> VMContext {
         random_seed: vec![0, 1, 2],
         view_config: None,
         output_data_receivers: vec![],
+        count_ext_costs: false,
     }
 }
 
@@ -51,6 +51,9 @@ pub struct VMContext {
     /// How many `DataReceipt`'s should receive this execution result. This should be empty if
     /// this function call is a part of a batch and it is not the last action.
     pub output_data_receivers: Vec<AccountId>,
+    /// Whether to count the units charged for each host function cost into
+    /// `VMOutcome::ext_counts`.  Only set when tracing a call.
+    pub count_ext_costs: bool,
 }
 
 impl VMContext {
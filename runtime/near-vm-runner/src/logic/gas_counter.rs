@@ -1,6 +1,7 @@
 use super::errors::{HostError, VMLogicError};
 use super::TrieNodesCount;
 use crate::ProfileDataV3;
+use enum_map::EnumMap;
 use near_primitives_core::config::ExtCosts::read_cached_trie_node;
 use near_primitives_core::config::ExtCosts::touching_trie_node;
 use near_primitives_core::{
@@ -60,6 +61,9 @@ pub struct GasCounter {
     ext_costs_config: ExtCostsConfig,
     /// Where to store profile data, if needed.
     profile: ProfileDataV3,
+    /// Number of units charged for each host function cost, if counting was
+    /// enabled with [`Self::count_ext_costs`].
+    ext_counts: Option<Box<EnumMap<ExtCosts, u64>>>,
 }
 
 impl GasCounter {
@@ -85,9 +89,15 @@ impl GasCounter {
             prepaid_gas,
             is_view,
             profile: Default::default(),
+            ext_counts: None,
         }
     }
 
+    /// Starts counting the units charged for each host function cost.
+    pub fn count_ext_costs(&mut self) {
+        self.ext_counts.get_or_insert_with(Default::default);
+    }
+
     /// Deducts burnt and used gas.
     ///
     /// Returns an error if the `max_gax_burnt` or the `prepaid_gas` limits are
@@ -199,6 +209,9 @@ impl GasCounter {
 
     #[inline]
     fn inc_ext_costs_counter(&mut self, cost: ExtCosts, value: u64) {
+        if let Some(counts) = &mut self.ext_counts {
+            counts[cost] = counts[cost].saturating_add(value);
+        }
         with_ext_cost_counter(|cc| *cc.entry(cost).or_default() += value)
     }
 
@@ -281,6 +294,10 @@ impl GasCounter {
     pub fn profile_data(&self) -> ProfileDataV3 {
         self.profile.clone()
     }
+
+    pub fn ext_counts(&self) -> Option<Box<EnumMap<ExtCosts, u64>>> {
+        self.ext_counts.clone()
+    }
 }
 
 #[cfg(test)]
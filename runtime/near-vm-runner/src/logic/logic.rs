@@ -8,6 +8,7 @@ use super::{HostError, VMLogicError};
 use super::{StorageGetMode, ValuePtr};
 use crate::config::Config;
 use crate::ProfileDataV3;
+use enum_map::EnumMap;
 use near_crypto::Secp256K1Signature;
 use near_primitives_core::config::ExtCosts::*;
 use near_primitives_core::config::ViewConfig;
@@ -142,13 +143,16 @@ impl<'a> VMLogic<'a> {
         };
 
         let current_account_locked_balance = context.account_locked_balance;
-        let gas_counter = GasCounter::new(
+        let mut gas_counter = GasCounter::new(
             config.ext_costs.clone(),
             max_gas_burnt,
             config.regular_op_cost,
             context.prepaid_gas,
             context.is_view(),
         );
+        if context.count_ext_costs {
+            gas_counter.count_ext_costs();
+        }
         Self {
             ext,
             context,
@@ -2771,6 +2775,7 @@ impl<'a> VMLogic<'a> {
             compute_usage,
             logs: self.logs,
             profile,
+            ext_counts: self.gas_counter.ext_counts(),
             aborted: None,
         }
     }
@@ -2879,6 +2884,11 @@ pub struct VMOutcome {
     pub logs: Vec<String>,
     /// Data collected from making a contract call
     pub profile: ProfileDataV3,
+    /// Number of units charged for each host function cost, e.g. calls for
+    /// base costs and bytes for per byte costs.  Only collected if
+    /// `VMContext::count_ext_costs` is set.  Unlike `profile`, it isn't
+    /// persisted in execution outcomes.
+    pub ext_counts: Option<Box<EnumMap<ExtCosts, u64>>>,
     pub aborted: Option<FunctionCallError>,
 }
 
@@ -2911,6 +2921,7 @@ impl VMOutcome {
             compute_usage: 0,
             logs: Vec::new(),
             profile: ProfileDataV3::default(),
+            ext_counts: None,
             aborted: Some(error),
         }
     }
@@ -81,6 +81,7 @@ fn get_context() -> VMContext {
         random_seed: vec![0, 1, 2],
         view_config: None,
         output_data_receivers: vec![],
+        count_ext_costs: false,
     }
 }
 
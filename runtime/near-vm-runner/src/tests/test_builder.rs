@@ -28,6 +28,7 @@ pub(crate) fn test_builder() -> TestBuilder {
         random_seed: vec![0, 1, 2],
         view_config: None,
         output_data_receivers: vec![],
+        count_ext_costs: false,
     };
     let mut skip = HashSet::new();
     if cfg!(not(target_arch = "x86_64")) {
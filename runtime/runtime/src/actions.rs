@@ -53,6 +53,7 @@ pub(crate) fn execute_function_call(
     config: &RuntimeConfig,
     is_last_action: bool,
     view_config: Option<ViewConfig>,
+    count_ext_costs: bool,
 ) -> Result<VMOutcome, RuntimeError> {
     let account_id = runtime_ext.account_id();
     tracing::debug!(target: "runtime", %account_id, "Calling the contract");
@@ -97,6 +98,7 @@ pub(crate) fn execute_function_call(
         random_seed,
         view_config: view_config.clone(),
         output_data_receivers,
+        count_ext_costs,
     };
 
     // Enable caching chunk mode for the function call. This allows to charge for nodes touched in a chunk only once for
@@ -208,6 +210,7 @@ pub(crate) fn action_function_call(
         config,
         is_last_action,
         None,
+        false,
     )?;
 
     match &outcome.aborted {
@@ -1212,6 +1215,7 @@ mod tests {
             is_new_chunk: false,
             migration_data: Arc::default(),
             migration_flags: MigrationFlags::default(),
+            verify_signatures: true,
         }
     }
 
@@ -1,4 +1,5 @@
 use crate::near_primitives::shard_layout::ShardUId;
+use crate::state_viewer::ViewStateRange;
 use near_crypto::PublicKey;
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::hash::CryptoHash;
@@ -6,7 +7,7 @@ use near_primitives::types::{
     AccountId, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, MerkleHash,
 };
 use near_primitives::version::ProtocolVersion;
-use near_primitives::views::ViewStateResult;
+use near_primitives::views::{CallTraceView, ViewStateResult};
 use near_vm_runner::ContractCode;
 
 /// Adapter for querying runtime.
@@ -43,6 +44,23 @@ pub trait ViewRuntimeAdapter {
         current_protocol_version: ProtocolVersion,
     ) -> Result<Vec<u8>, crate::state_viewer::errors::CallFunctionError>;
 
+    fn trace_call(
+        &self,
+        shard_uid: &ShardUId,
+        state_root: MerkleHash,
+        height: BlockHeight,
+        block_timestamp: u64,
+        last_block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
+        epoch_height: EpochHeight,
+        epoch_id: &EpochId,
+        contract_id: &AccountId,
+        method_name: &str,
+        args: &[u8],
+        epoch_info_provider: &dyn EpochInfoProvider,
+        current_protocol_version: ProtocolVersion,
+    ) -> Result<CallTraceView, crate::state_viewer::errors::CallFunctionError>;
+
     fn view_access_key(
         &self,
         shard_uid: &ShardUId,
@@ -62,8 +80,10 @@ pub trait ViewRuntimeAdapter {
         &self,
         shard_uid: &ShardUId,
         state_root: MerkleHash,
+        block_hash: &CryptoHash,
         account_id: &AccountId,
         prefix: &[u8],
+        range: &ViewStateRange,
         include_proof: bool,
     ) -> Result<ViewStateResult, crate::state_viewer::errors::ViewStateError>;
 }
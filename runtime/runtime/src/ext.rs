@@ -12,6 +12,7 @@ use near_vm_runner::logic::errors::{AnyError, VMLogicError};
 use near_vm_runner::logic::types::ReceiptIndex;
 use near_vm_runner::logic::{External, StorageGetMode, ValuePtr};
 use near_vm_runner::ContractCode;
+use std::cell::RefCell;
 
 pub struct RuntimeExt<'a> {
     trie_update: &'a mut TrieUpdate,
@@ -24,6 +25,9 @@ pub struct RuntimeExt<'a> {
     last_block_hash: &'a CryptoHash,
     epoch_info_provider: &'a dyn EpochInfoProvider,
     current_protocol_version: ProtocolVersion,
+    /// Contract storage keys read so far, if enabled with
+    /// [`RuntimeExt::record_storage_reads`].
+    storage_reads: Option<RefCell<Vec<Vec<u8>>>>,
 }
 
 /// Error used by `RuntimeExt`.
@@ -77,6 +81,23 @@ impl<'a> RuntimeExt<'a> {
             last_block_hash,
             epoch_info_provider,
             current_protocol_version,
+            storage_reads: None,
+        }
+    }
+
+    /// Starts recording keys of contract storage reads, to be retrieved with
+    /// [`Self::take_storage_reads`].  Used for tracing view calls.
+    pub fn record_storage_reads(&mut self) {
+        self.storage_reads = Some(RefCell::default());
+    }
+
+    pub fn take_storage_reads(&mut self) -> Vec<Vec<u8>> {
+        self.storage_reads.take().map(RefCell::into_inner).unwrap_or_default()
+    }
+
+    fn record_storage_read(&self, key: &[u8]) {
+        if let Some(storage_reads) = &self.storage_reads {
+            storage_reads.borrow_mut().push(key.to_vec());
         }
     }
 
@@ -121,6 +142,7 @@ impl<'a> External for RuntimeExt<'a> {
         key: &[u8],
         mode: StorageGetMode,
     ) -> ExtResult<Option<Box<dyn ValuePtr + 'b>>> {
+        self.record_storage_read(key);
         let storage_key = self.create_storage_key(key);
         let mode = match mode {
             StorageGetMode::FlatStorage => KeyLookupMode::FlatStorage,
@@ -139,6 +161,7 @@ impl<'a> External for RuntimeExt<'a> {
     }
 
     fn storage_has_key(&mut self, key: &[u8], mode: StorageGetMode) -> ExtResult<bool> {
+        self.record_storage_read(key);
         let storage_key = self.create_storage_key(key);
         let mode = match mode {
             StorageGetMode::FlatStorage => KeyLookupMode::FlatStorage,
@@ -227,7 +227,7 @@ impl Runtime {
             state_update,
             apply_state.gas_price,
             signed_transaction,
-            true,
+            apply_state.verify_signatures,
             Some(apply_state.block_height),
             apply_state.current_protocol_version,
         ) {
@@ -1645,6 +1645,7 @@ mod tests {
             is_new_chunk: true,
             migration_data: Arc::new(MigrationData::default()),
             migration_flags: MigrationFlags::default(),
+            verify_signatures: true,
         };
 
         (runtime, tries, root, apply_state, signer, MockEpochInfoProvider::default())
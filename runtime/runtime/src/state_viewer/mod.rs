@@ -4,6 +4,7 @@ use crate::{actions::execute_function_call, ext::RuntimeExt};
 use near_crypto::{KeyType, PublicKey};
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::borsh::BorshDeserialize;
+use near_primitives::challenge::PartialState;
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::ActionReceipt;
 use near_primitives::runtime::apply_state::ApplyState;
@@ -12,16 +13,85 @@ use near_primitives::runtime::migration_data::{MigrationData, MigrationFlags};
 use near_primitives::transaction::FunctionCallAction;
 use near_primitives::trie_key::trie_key_parsers;
 use near_primitives::types::{AccountId, EpochInfoProvider, Gas};
-use near_primitives::views::{StateItem, ViewApplyState, ViewStateResult};
-use near_primitives_core::config::ViewConfig;
-use near_store::{get_access_key, get_account, get_code, TrieUpdate};
-use near_vm_runner::logic::ReturnData;
+use near_primitives::views::{
+    CallTraceView, HostFunctionGasView, PromiseTraceView, StateItem, ViewApplyState,
+    ViewStateCursor, ViewStateResult,
+};
+use near_primitives_core::config::{ExtCosts, ViewConfig};
+use near_store::{get_access_key, get_account, get_code, StorageError, TrieUpdate};
+use near_vm_runner::logic::{ReturnData, VMOutcome};
 use near_vm_runner::ContractCode;
 use std::{str, sync::Arc, time::Instant};
+use strum::IntoEnumIterator;
 use tracing::debug;
 
 pub mod errors;
 
+/// Key range and page size of a `view_state` query.
+///
+/// Keys are relative to the account’s contract data, i.e. the same as keys
+/// returned in [`ViewStateResult::values`].  The default value selects all
+/// keys in a single page.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ViewStateRange<'a> {
+    /// Inclusive lower bound.
+    pub start_key: Option<&'a [u8]>,
+    /// Exclusive upper bound.
+    pub end_key: Option<&'a [u8]>,
+    /// Maximum number of values in a page.
+    pub limit: Option<usize>,
+    /// Where to resume a previous query from.
+    pub cursor: Option<&'a ViewStateCursor>,
+}
+
+/// Collects a single page of contract data from a trie or flat storage
+/// iterator positioned at the first key of the page.
+struct ViewStatePage<'a> {
+    /// Raw trie key prefix all returned keys need to start with.
+    query: &'a [u8],
+    /// Length of the account and separator part of raw keys.
+    acc_sep_len: usize,
+    end_key: Option<&'a [u8]>,
+    limit: Option<usize>,
+    /// Cap on total size of keys and values in a page.
+    max_page_bytes: Option<u64>,
+}
+
+impl ViewStatePage<'_> {
+    fn collect(
+        &self,
+        iter: impl Iterator<Item = Result<(Vec<u8>, Vec<u8>), StorageError>>,
+    ) -> Result<(Vec<StateItem>, Option<ViewStateCursor>), errors::ViewStateError> {
+        let mut values = vec![];
+        let mut page_bytes = 0u64;
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(self.query) {
+                break;
+            }
+            let key = &key[self.acc_sep_len..];
+            if self.end_key.map_or(false, |end_key| key >= end_key) {
+                break;
+            }
+            page_bytes += (key.len() + value.len()) as u64;
+            let page_full = self.limit.map_or(false, |limit| values.len() >= limit)
+                || self.max_page_bytes.map_or(false, |max| page_bytes > max && !values.is_empty());
+            if page_full {
+                return Ok((values, Some(ViewStateCursor::new(key.to_vec()))));
+            }
+            values.push(StateItem { key: key.to_vec().into(), value: value.into() });
+        }
+        Ok((values, None))
+    }
+}
+
+/// Outcome of a view call together with the data needed to trace it.
+struct ViewCallExecution {
+    outcome: VMOutcome,
+    receipt_manager: ReceiptManager,
+    storage_reads: Vec<Vec<u8>>,
+}
+
 pub struct TrieViewer {
     /// Upper bound of the byte size of contract state that is still viewable. None is no limit
     state_size_limit: Option<u64>,
@@ -116,6 +186,7 @@ impl TrieViewer {
         state_update: &TrieUpdate,
         account_id: &AccountId,
         prefix: &[u8],
+        range: &ViewStateRange,
         include_proof: bool,
     ) -> Result<ViewStateResult, errors::ViewStateError> {
         match get_account(state_update, account_id)? {
@@ -123,7 +194,8 @@ impl TrieViewer {
                 let code_len = get_code(state_update, account_id, Some(account.code_hash()))?
                     .map(|c| c.code().len() as u64)
                     .unwrap_or_default();
-                if let Some(limit) = self.state_size_limit {
+                // Paginated queries are bounded by the page size instead.
+                if let (Some(limit), None) = (self.state_size_limit, range.limit) {
                     if account.storage_usage().saturating_sub(code_len) > limit {
                         return Err(errors::ViewStateError::AccountStateTooLarge {
                             requested_account_id: account_id.clone(),
@@ -138,23 +210,72 @@ impl TrieViewer {
             }
         };
 
-        let mut values = vec![];
         let query = trie_key_parsers::get_raw_prefix_for_contract_data(account_id, prefix);
         let acc_sep_len = query.len() - prefix.len();
+        // Keys below `prefix` can’t match, so the effective lower bound is the
+        // largest of the prefix, the start key and the cursor.
+        let start = [range.start_key, range.cursor.map(ViewStateCursor::key)]
+            .into_iter()
+            .flatten()
+            .max()
+            .filter(|start| *start > prefix);
+        let raw_key = |key: &[u8]| [&query[..acc_sep_len], key].concat();
+        let page = ViewStatePage {
+            query: &query,
+            acc_sep_len,
+            end_key: range.end_key,
+            limit: range.limit,
+            max_page_bytes: range.limit.and(self.state_size_limit),
+        };
+
+        // With in-memory tries the proof is built from the paths to the keys
+        // the page has read, which are exactly the nodes the on-disk trie
+        // iterator would visit.  That includes the key the page stopped at, so
+        // the iteration isn't bounded by the end key then.
+        if !include_proof || state_update.trie().has_memtries() {
+            let from = raw_key(start.unwrap_or(prefix));
+            let to = if include_proof { None } else { range.end_key.map(raw_key) };
+            if let Some(iter) =
+                state_update.trie().iter_flat_state_at_head(Some(&from), to.as_deref())
+            {
+                let mut read_keys = vec![];
+                let (values, next_cursor) = page.collect(iter.inspect(|item| match item {
+                    Ok((key, _)) if include_proof => read_keys.push(key.clone()),
+                    _ => {}
+                }))?;
+                let proof = if include_proof {
+                    // Without a start key the on-disk iterator is limited to
+                    // the prefix, so it never reaches the keys past it.
+                    let read_keys = read_keys
+                        .iter()
+                        .filter(|key| start.is_some() || key.starts_with(&query))
+                        .map(Vec::as_slice);
+                    let PartialState::TrieValues(nodes) =
+                        state_update.trie().get_trie_nodes_for_keys(
+                            std::iter::once(from.as_slice()).chain(read_keys),
+                        )?;
+                    nodes
+                } else {
+                    vec![]
+                };
+                return Ok(ViewStateResult { values, proof, next_cursor });
+            }
+        }
+
         let mut iter = state_update.trie().iter()?;
         iter.remember_visited_nodes(include_proof);
-        iter.seek_prefix(&query)?;
-        for item in &mut iter {
-            let (key, value) = item?;
-            values.push(StateItem { key: key[acc_sep_len..].to_vec().into(), value: value.into() });
+        match start {
+            Some(start) => iter.seek(raw_key(start))?,
+            None => iter.seek_prefix(&query)?,
         }
+        let (values, next_cursor) = page.collect(&mut iter)?;
         let proof = iter.into_visited_nodes();
-        Ok(ViewStateResult { values, proof })
+        Ok(ViewStateResult { values, proof, next_cursor })
     }
 
     pub fn call_function(
         &self,
-        mut state_update: TrieUpdate,
+        state_update: TrieUpdate,
         view_state: ViewApplyState,
         contract_id: &AccountId,
         method_name: &str,
@@ -163,6 +284,129 @@ impl TrieViewer {
         epoch_info_provider: &dyn EpochInfoProvider,
     ) -> Result<Vec<u8>, errors::CallFunctionError> {
         let now = Instant::now();
+        let ViewCallExecution { outcome, .. } = self.execute_view_call(
+            state_update,
+            view_state,
+            contract_id,
+            method_name,
+            args,
+            epoch_info_provider,
+            false,
+        )?;
+        let elapsed = now.elapsed();
+        let time_ms =
+            (elapsed.as_secs() as f64 / 1_000.0) + f64::from(elapsed.subsec_nanos()) / 1_000_000.0;
+        let time_str = format!("{:.*}ms", 2, time_ms);
+
+        if let Some(err) = outcome.aborted {
+            logs.extend(outcome.logs);
+            let message = format!("wasm execution failed with error: {:?}", err);
+            debug!(target: "runtime", "(exec time {}) {}", time_str, message);
+            Err(errors::CallFunctionError::VMError { error_message: message })
+        } else {
+            debug!(target: "runtime", "(exec time {}) result of execution: {:?}", time_str, outcome);
+            logs.extend(outcome.logs);
+            let result = match outcome.return_data {
+                ReturnData::Value(buf) => buf,
+                ReturnData::ReceiptIndex(_) | ReturnData::None => vec![],
+            };
+            Ok(result)
+        }
+    }
+
+    /// Executes a function call on top of the given state and returns its gas
+    /// profile, the storage keys it read and the promises it created.
+    ///
+    /// Unlike [`Self::call_function`], the method isn’t restricted to view
+    /// host functions, so it may write to storage and create promises, as it
+    /// would when called from a transaction.  The call is given
+    /// `max_gas_burnt_view` of prepaid gas.  All changes are discarded
+    /// together with `state_update` and the promises aren’t executed.
+    /// Failures of the contract itself are reported in the trace rather than
+    /// as an error.
+    pub fn trace_call(
+        &self,
+        state_update: TrieUpdate,
+        view_state: ViewApplyState,
+        contract_id: &AccountId,
+        method_name: &str,
+        args: &[u8],
+        epoch_info_provider: &dyn EpochInfoProvider,
+    ) -> Result<CallTraceView, errors::CallFunctionError> {
+        let ViewCallExecution { outcome, receipt_manager, storage_reads } = self
+            .execute_view_call(
+                state_update,
+                view_state,
+                contract_id,
+                method_name,
+                args,
+                epoch_info_provider,
+                true,
+            )?;
+
+        let ext_counts = outcome.ext_counts.unwrap_or_default();
+        let host_functions = ExtCosts::iter()
+            .filter_map(|cost| {
+                let gas_used = outcome.profile.get_ext_cost(cost);
+                (gas_used > 0).then(|| HostFunctionGasView {
+                    cost: format!("{:?}", cost).to_ascii_uppercase(),
+                    gas_used,
+                    count: ext_counts[cost],
+                })
+            })
+            .collect();
+
+        let receipts = &receipt_manager.action_receipts;
+        let promises = receipts
+            .iter()
+            .map(|(receiver_id, receipt)| {
+                let depends_on = receipts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, other))| {
+                        other
+                            .output_data_receivers
+                            .iter()
+                            .any(|receiver| receipt.input_data_ids.contains(&receiver.data_id))
+                    })
+                    .map(|(index, _)| index as u64)
+                    .collect();
+                PromiseTraceView {
+                    receiver_id: receiver_id.clone(),
+                    actions: receipt.actions.iter().cloned().map(Into::into).collect(),
+                    depends_on,
+                }
+            })
+            .collect();
+
+        let result = match outcome.return_data {
+            ReturnData::Value(buf) => buf,
+            ReturnData::ReceiptIndex(_) | ReturnData::None => vec![],
+        };
+        Ok(CallTraceView {
+            result,
+            logs: outcome.logs,
+            error: outcome
+                .aborted
+                .map(|err| format!("wasm execution failed with error: {:?}", err)),
+            burnt_gas: outcome.burnt_gas,
+            wasm_gas: outcome.profile.get_wasm_cost(),
+            host_functions,
+            storage_reads: storage_reads.into_iter().map(Into::into).collect(),
+            promises,
+        })
+    }
+
+    fn execute_view_call(
+        &self,
+        mut state_update: TrieUpdate,
+        view_state: ViewApplyState,
+        contract_id: &AccountId,
+        method_name: &str,
+        args: &[u8],
+        epoch_info_provider: &dyn EpochInfoProvider,
+        trace: bool,
+    ) -> Result<ViewCallExecution, errors::CallFunctionError> {
         let root = *state_update.get_root();
         let mut account = get_account(&state_update, contract_id)?.ok_or_else(|| {
             errors::CallFunctionError::AccountDoesNotExist {
@@ -185,6 +429,9 @@ impl TrieViewer {
             epoch_info_provider,
             view_state.current_protocol_version,
         );
+        if trace {
+            runtime_ext.record_storage_reads();
+        }
         let config_store = RuntimeConfigStore::new(None);
         let config = config_store.get_config(PROTOCOL_VERSION);
         let apply_state = ApplyState {
@@ -204,6 +451,7 @@ impl TrieViewer {
             is_new_chunk: false,
             migration_data: Arc::new(MigrationData::default()),
             migration_flags: MigrationFlags::default(),
+            verify_signatures: true,
         };
         let action_receipt = ActionReceipt {
             signer_id: originator_id.clone(),
@@ -230,27 +478,11 @@ impl TrieViewer {
             &empty_hash,
             config,
             true,
-            Some(ViewConfig { max_gas_burnt: self.max_gas_burnt_view }),
+            (!trace).then(|| ViewConfig { max_gas_burnt: self.max_gas_burnt_view }),
+            trace,
         )
         .map_err(|e| errors::CallFunctionError::InternalError { error_message: e.to_string() })?;
-        let elapsed = now.elapsed();
-        let time_ms =
-            (elapsed.as_secs() as f64 / 1_000.0) + f64::from(elapsed.subsec_nanos()) / 1_000_000.0;
-        let time_str = format!("{:.*}ms", 2, time_ms);
-
-        if let Some(err) = outcome.aborted {
-            logs.extend(outcome.logs);
-            let message = format!("wasm execution failed with error: {:?}", err);
-            debug!(target: "runtime", "(exec time {}) {}", time_str, message);
-            Err(errors::CallFunctionError::VMError { error_message: message })
-        } else {
-            debug!(target: "runtime", "(exec time {}) result of execution: {:?}", time_str, outcome);
-            logs.extend(outcome.logs);
-            let result = match outcome.return_data {
-                ReturnData::Value(buf) => buf,
-                ReturnData::ReceiptIndex(_) | ReturnData::None => vec![],
-            };
-            Ok(result)
-        }
+        let storage_reads = runtime_ext.take_storage_reads();
+        Ok(ViewCallExecution { outcome, receipt_manager, storage_reads })
     }
 }
@@ -4,17 +4,22 @@ use anyhow::Context;
 use borsh::BorshDeserialize;
 use clap;
 use near_epoch_manager::{EpochManager, EpochManagerAdapter, EpochManagerHandle};
-use near_primitives::block::Tip;
+use near_primitives::block::{Block, BlockHeader, Tip};
 use near_primitives::epoch_manager::block_info::BlockInfo;
 use near_primitives::hash::CryptoHash;
-use near_store::cold_storage::{copy_all_data_to_cold, update_cold_db, update_cold_head};
+use near_primitives::types::BlockHeight;
+use near_store::cold_storage::{
+    copy_all_data_to_cold, get_cold_block_data, get_cold_state_data, set_cold_head, update_cold_db,
+    update_cold_head, verify_cold_db, write_cold_block_data, ColdBlockData,
+};
+use near_store::db::ColdDB;
 use near_store::metadata::DbKind;
 use near_store::{DBCol, NodeStorage, Store, StoreOpener};
 use near_store::{COLD_HEAD_KEY, FINAL_HEAD_KEY, HEAD_KEY, TAIL_KEY};
 use nearcore::NearConfig;
 use rand::seq::SliceRandom;
 use std::io::Result;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use strum::IntoEnumIterator;
 
 #[derive(clap::Parser)]
@@ -53,6 +58,25 @@ enum SubCommand {
     /// You can provide maximum depth and/or maximum number of vertices to traverse for each root.
     /// Trie is traversed using DFS with randomly shuffled kids for every node.
     CheckStateRoot(CheckStateRootCmd),
+    /// Check that cold storage has all the data of the blocks in a height
+    /// range and that it matches the data in hot storage.
+    /// Blocks which are no longer in hot storage are only checked for the
+    /// data that can be derived from their cold copies.
+    /// Can run on a live node: it is rate limited and, given a checkpoint
+    /// file, resumes from where the previous run stopped.
+    Verify(VerifyCmd),
+    /// Export the data which would be copied to cold storage for the blocks
+    /// in a height range into a directory, one file per height.
+    /// The blocks are read from the split store if cold storage is
+    /// configured and from the hot store otherwise.
+    ExportBlocks(ExportBlocksCmd),
+    /// Import blocks exported with `export-blocks` into cold storage and
+    /// update cold HEAD. This allows creating the cold storage of a split
+    /// storage archival node without a hot archival database.
+    /// Heights are imported in order, starting right after cold HEAD, until
+    /// the first missing file. Cold storage without cold HEAD is first seeded
+    /// with the state exported by `export-blocks --with-state`.
+    ImportBlocks(ImportBlocksCmd),
 }
 
 impl ColdStoreCommand {
@@ -87,6 +111,9 @@ impl ColdStoreCommand {
             }
             SubCommand::PrepareHot(cmd) => cmd.run(&storage, &home_dir, &near_config),
             SubCommand::CheckStateRoot(cmd) => cmd.run(&storage),
+            SubCommand::Verify(cmd) => cmd.run(&storage, epoch_manager.as_ref()),
+            SubCommand::ExportBlocks(cmd) => cmd.run(&storage, epoch_manager.as_ref()),
+            SubCommand::ImportBlocks(cmd) => cmd.run(&storage),
         }
     }
 
@@ -651,3 +678,397 @@ impl CheckStateRootCmd {
         store.get(DBCol::State, &cold_state_key)
     }
 }
+
+#[derive(clap::Args)]
+struct VerifyCmd {
+    /// First height to verify.
+    #[clap(long)]
+    from_height: BlockHeight,
+    /// Last height to verify, inclusive. Defaults to the cold head.
+    #[clap(long)]
+    to_height: Option<BlockHeight>,
+    /// File holding the last verified height, updated after every block.
+    /// If it exists, verification continues after the height stored in it.
+    #[clap(long)]
+    checkpoint_file: Option<PathBuf>,
+    /// Maximum number of keys read from each of the databases per second.
+    /// 0 means no limit.
+    #[clap(long, default_value_t = 10_000)]
+    max_keys_per_second: u64,
+}
+
+impl VerifyCmd {
+    pub fn run(
+        self,
+        storage: &NodeStorage,
+        epoch_manager: &EpochManagerHandle,
+    ) -> anyhow::Result<()> {
+        let cold_db = storage.cold_db().ok_or(anyhow::anyhow!("Cold storage is not configured"))?;
+        let hot_store = storage.get_hot_store();
+        let cold_store = storage.get_cold_store().unwrap();
+
+        let to_height = match self.to_height {
+            Some(height) => height,
+            None => {
+                let cold_head = cold_store
+                    .get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?
+                    .ok_or(anyhow::anyhow!("The cold head is missing"))?;
+                cold_head.height
+            }
+        };
+        let from_height = match self.read_checkpoint()? {
+            Some(height) => {
+                println!("Resuming after height {height} from the checkpoint");
+                std::cmp::max(self.from_height, height + 1)
+            }
+            None => self.from_height,
+        };
+
+        let start = std::time::Instant::now();
+        let mut num_checked_keys = 0;
+        let mut num_checked_blocks = 0;
+        let mut num_blocks_not_in_hot = 0;
+        let mut num_mismatches = 0;
+        // Hash of the last block found, to detect blocks missing together with
+        // their heights, which look like skipped heights otherwise.
+        let mut last_block_hash = None;
+        for height in from_height..=to_height {
+            if let Some(header) = Self::get_header(&hot_store, &cold_store, height)? {
+                if last_block_hash.is_some_and(|hash| hash != *header.prev_hash()) {
+                    println!("Height {height}: previous block {} is missing", header.prev_hash());
+                    num_mismatches += 1;
+                }
+                last_block_hash = Some(*header.hash());
+
+                let shard_layout = epoch_manager.get_shard_layout(header.epoch_id())?;
+                match verify_cold_db(cold_db, &hot_store, &shard_layout, &height)? {
+                    Some(verification) => {
+                        for mismatch in &verification.mismatches {
+                            println!("Height {height}: {mismatch}");
+                        }
+                        num_checked_keys += verification.num_checked_keys as u64;
+                        num_mismatches += verification.mismatches.len();
+                        num_checked_blocks += 1;
+                        if !verification.compared_with_hot {
+                            num_blocks_not_in_hot += 1;
+                        }
+                    }
+                    None => {
+                        println!("Height {height}: block {} is missing", header.hash());
+                        num_mismatches += 1;
+                    }
+                }
+            }
+            self.write_checkpoint(height)?;
+            self.throttle(start, num_checked_keys);
+        }
+
+        println!(
+            "Verified {num_checked_blocks} blocks between heights {from_height} and {to_height}: \
+             {num_checked_keys} keys checked, {num_mismatches} mismatches found; \
+             {num_blocks_not_in_hot} blocks were no longer in hot storage and were only checked \
+             for completeness"
+        );
+        if num_mismatches > 0 {
+            return Err(anyhow::anyhow!("Cold storage doesn't match hot storage"));
+        }
+        Ok(())
+    }
+
+    /// Returns the header of the block at `height` from hot storage or, if it
+    /// has been garbage collected there, from cold storage.
+    fn get_header(
+        hot_store: &Store,
+        cold_store: &Store,
+        height: BlockHeight,
+    ) -> anyhow::Result<Option<BlockHeader>> {
+        let height_key = height.to_le_bytes();
+        if let Some(block_hash) =
+            hot_store.get_ser::<CryptoHash>(DBCol::BlockHeight, &height_key)?
+        {
+            if let Some(header) =
+                hot_store.get_ser::<BlockHeader>(DBCol::BlockHeader, block_hash.as_ref())?
+            {
+                return Ok(Some(header));
+            }
+        }
+        let Some(block_hash) = cold_store.get_ser::<CryptoHash>(DBCol::BlockHeight, &height_key)?
+        else {
+            return Ok(None);
+        };
+        let block = cold_store.get_ser::<Block>(DBCol::Block, block_hash.as_ref())?.ok_or(
+            anyhow::anyhow!("Block {block_hash} at height {height} is missing in cold storage"),
+        )?;
+        Ok(Some(block.header().clone()))
+    }
+
+    fn read_checkpoint(&self) -> anyhow::Result<Option<BlockHeight>> {
+        let Some(path) = &self.checkpoint_file else {
+            return Ok(None);
+        };
+        match std::fs::read_to_string(path) {
+            Ok(content) => {
+                Ok(Some(content.trim().parse().with_context(|| {
+                    format!("Failed to parse checkpoint file {}", path.display())
+                })?))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write_checkpoint(&self, height: BlockHeight) -> anyhow::Result<()> {
+        if let Some(path) = &self.checkpoint_file {
+            std::fs::write(path, height.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Sleeps for as long as needed to keep the number of checked keys
+    /// within the limit.
+    fn throttle(&self, start: std::time::Instant, num_checked_keys: u64) {
+        if self.max_keys_per_second == 0 {
+            return;
+        }
+        let min_duration = std::time::Duration::from_secs_f64(
+            num_checked_keys as f64 / self.max_keys_per_second as f64,
+        );
+        if let Some(delay) = min_duration.checked_sub(start.elapsed()) {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+/// Path of the file holding the exported data of the given height.
+fn exported_block_path(dir: &Path, height: BlockHeight) -> PathBuf {
+    dir.join(format!("{height}.cold"))
+}
+
+fn exported_state_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("state-{index}.cold"))
+}
+
+/// Amount of state exported into a single file.
+const EXPORTED_STATE_BATCH_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(clap::Args)]
+struct ExportBlocksCmd {
+    /// First height to export.
+    #[clap(long)]
+    from_height: BlockHeight,
+    /// Last height to export, inclusive.
+    #[clap(long)]
+    to_height: BlockHeight,
+    /// Directory to write the exported blocks to.
+    #[clap(long)]
+    destination: PathBuf,
+    /// Also export the state before the block at `from_height`, which is
+    /// needed to import the blocks into empty cold storage.
+    #[clap(long)]
+    with_state: bool,
+}
+
+impl ExportBlocksCmd {
+    pub fn run(
+        self,
+        storage: &NodeStorage,
+        epoch_manager: &EpochManagerHandle,
+    ) -> anyhow::Result<()> {
+        let store = storage.get_split_store().unwrap_or_else(|| storage.get_hot_store());
+        std::fs::create_dir_all(&self.destination)?;
+        if self.with_state {
+            self.export_state(&store, epoch_manager)?;
+        }
+
+        let mut num_blocks = 0;
+        for height in self.from_height..=self.to_height {
+            // Skipped heights are exported as well, so that the importer can
+            // tell them apart from the heights which haven't been exported.
+            let data =
+                match store.get_ser::<CryptoHash>(DBCol::BlockHeight, &height.to_le_bytes())? {
+                    Some(block_hash) => {
+                        let header = store
+                            .get_ser::<BlockHeader>(DBCol::BlockHeader, block_hash.as_ref())?
+                            .ok_or(anyhow::anyhow!("Failed to find block header {block_hash}"))?;
+                        let shard_layout = epoch_manager.get_shard_layout(header.epoch_id())?;
+                        num_blocks += 1;
+                        get_cold_block_data(&store, &shard_layout, &height)
+                            .with_context(|| format!("Failed to export block at height {height}"))?
+                    }
+                    None => ColdBlockData { height, tip: None, columns: vec![] },
+                };
+            std::fs::write(exported_block_path(&self.destination, height), borsh::to_vec(&data)?)?;
+        }
+
+        println!(
+            "Exported {num_blocks} blocks between heights {} and {} to {}",
+            self.from_height,
+            self.to_height,
+            self.destination.display()
+        );
+        Ok(())
+    }
+
+    /// Exports the state after the block preceding the one at `from_height`,
+    /// or the genesis state if that's the genesis block.
+    fn export_state(
+        &self,
+        store: &Store,
+        epoch_manager: &EpochManagerHandle,
+    ) -> anyhow::Result<()> {
+        let block_hash = store
+            .get_ser::<CryptoHash>(DBCol::BlockHeight, &self.from_height.to_le_bytes())?
+            .ok_or(anyhow::anyhow!(
+                "No block at height {}, the state can only be exported before a block",
+                self.from_height
+            ))?;
+        let header = store
+            .get_ser::<BlockHeader>(DBCol::BlockHeader, block_hash.as_ref())?
+            .ok_or(anyhow::anyhow!("Failed to find block header {block_hash}"))?;
+        let state_header = if *header.prev_hash() == CryptoHash::default() {
+            header
+        } else {
+            store
+                .get_ser::<BlockHeader>(DBCol::BlockHeader, header.prev_hash().as_ref())?
+                .ok_or(anyhow::anyhow!("Failed to find block header {}", header.prev_hash()))?
+        };
+        let shard_layout = epoch_manager.get_shard_layout(state_header.epoch_id())?;
+
+        let mut index = 0;
+        get_cold_state_data(
+            store,
+            &shard_layout,
+            state_header.hash(),
+            EXPORTED_STATE_BATCH_SIZE,
+            |entries| {
+                let data = ColdBlockData {
+                    height: self.from_height,
+                    tip: None,
+                    columns: vec![(<&str>::from(DBCol::State).to_string(), entries)],
+                };
+                std::fs::write(
+                    exported_state_path(&self.destination, index),
+                    borsh::to_vec(&data)?,
+                )?;
+                index += 1;
+                Ok(())
+            },
+        )
+        .with_context(|| format!("Failed to export the state at block {}", state_header.hash()))?;
+        println!("Exported the state at block {} in {index} files", state_header.hash());
+        Ok(())
+    }
+}
+
+#[derive(clap::Args)]
+struct ImportBlocksCmd {
+    /// Directory with blocks exported with `export-blocks`.
+    #[clap(long)]
+    source: PathBuf,
+    /// First height to import. Required if cold HEAD is not set yet,
+    /// otherwise the import starts right after cold HEAD.
+    #[clap(long)]
+    from_height: Option<BlockHeight>,
+}
+
+impl ImportBlocksCmd {
+    pub fn run(self, storage: &NodeStorage) -> anyhow::Result<()> {
+        let cold_db = storage.cold_db().ok_or(anyhow::anyhow!("Cold storage is not configured"))?;
+        let hot_store = storage.get_hot_store();
+
+        let cold_head =
+            storage.get_cold_store().unwrap().get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?;
+        let from_height = match (self.from_height, &cold_head) {
+            (Some(height), Some(cold_head)) if height > cold_head.height + 1 => {
+                return Err(anyhow::anyhow!(
+                    "Cold HEAD is at {}, importing from {height} would leave a gap",
+                    cold_head.height
+                ))
+            }
+            (Some(height), _) => height,
+            (None, Some(cold_head)) => cold_head.height + 1,
+            (None, None) => {
+                return Err(anyhow::anyhow!("Cold HEAD is not set, --from-height is required"))
+            }
+        };
+        // Cold storage without cold HEAD has no state, which the blocks only
+        // add the trie nodes they create to.
+        if cold_head.is_none() {
+            self.import_state(cold_db, from_height)?;
+        }
+
+        let mut new_cold_head = None;
+        let mut height = from_height;
+        loop {
+            let path = exported_block_path(&self.source, height);
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => break,
+                Err(err) => return Err(err.into()),
+            };
+            let data = ColdBlockData::try_from_slice(&bytes)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            anyhow::ensure!(
+                data.height == height,
+                "{} holds height {} instead of {height}",
+                path.display(),
+                data.height
+            );
+            if data.tip.is_some() {
+                new_cold_head = data.tip.clone();
+            }
+            write_cold_block_data(cold_db, data)?;
+            height += 1;
+        }
+
+        // Cold HEAD is only moved once all the blocks up to it are imported,
+        // same as when copying blocks from hot storage, and never backwards.
+        let new_cold_head = new_cold_head
+            .filter(|tip| cold_head.as_ref().map_or(true, |head| tip.height > head.height));
+        match new_cold_head {
+            Some(tip) => {
+                set_cold_head(cold_db, &hot_store, &tip)?;
+                println!(
+                    "Imported {} heights starting at {from_height}, cold HEAD is at {}",
+                    height - from_height,
+                    tip.height
+                );
+            }
+            None => println!(
+                "Imported {} heights starting at {from_height}, cold HEAD unchanged",
+                height - from_height
+            ),
+        }
+        Ok(())
+    }
+
+    fn import_state(&self, cold_db: &ColdDB, from_height: BlockHeight) -> anyhow::Result<()> {
+        let mut index = 0;
+        loop {
+            let path = exported_state_path(&self.source, index);
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => break,
+                Err(err) => return Err(err.into()),
+            };
+            let data = ColdBlockData::try_from_slice(&bytes)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            anyhow::ensure!(
+                data.height == from_height,
+                "{} holds the state before height {} instead of {from_height}",
+                path.display(),
+                data.height
+            );
+            write_cold_block_data(cold_db, data)?;
+            index += 1;
+        }
+        anyhow::ensure!(
+            index > 0,
+            "Cold storage is empty and {} has no state to seed it with, \
+             export the blocks with --with-state",
+            self.source.display()
+        );
+        println!("Imported the state before height {from_height} from {index} files");
+        Ok(())
+    }
+}
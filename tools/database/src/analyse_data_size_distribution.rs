@@ -27,7 +27,7 @@ struct ColumnFamilyCountAndSize {
     size: usize,
 }
 
-struct DataSizeDistribution {
+pub(crate) struct DataSizeDistribution {
     key_sizes: Vec<(usize, usize)>,
     value_sizes: Vec<(usize, usize)>,
     total_num_of_pairs: usize,
@@ -61,7 +61,7 @@ impl DataSizeDistribution {
         self.print_sizes_count(&self.value_sizes, "Value", top_k);
     }
 
-    fn print_column_family_data(&self) {
+    pub(crate) fn print_column_family_data(&self) {
         for (column_family_name, column_family_data) in self.column_families_data.iter() {
             println!(
                 "Column family {} has {} number of pairs and {} bytes size",
@@ -120,7 +120,7 @@ impl DataSizeDistribution {
     }
 }
 
-fn read_all_pairs(db: &RocksDB, col_families: &Vec<DBCol>) -> DataSizeDistribution {
+pub(crate) fn read_all_pairs(db: &RocksDB, col_families: &Vec<DBCol>) -> DataSizeDistribution {
     // Initialize counters
     let key_sizes: Arc<Mutex<HashMap<usize, usize>>> = Arc::new(Mutex::new(HashMap::new()));
     let value_sizes: Arc<Mutex<HashMap<usize, usize>>> = Arc::new(Mutex::new(HashMap::new()));
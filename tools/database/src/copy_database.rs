@@ -0,0 +1,138 @@
+use near_store::db::{DBTransaction, Database};
+use near_store::{DBCol, DbBackend, Mode, NodeStorage, StoreConfig, Temperature};
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
+
+/// Amount of data written to the destination database in a single transaction.
+const BATCH_SIZE_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(clap::Args)]
+pub(crate) struct CopyDatabaseCommand {
+    /// Directory of the new database.  Must not contain a database yet.
+    #[clap(long)]
+    destination: PathBuf,
+    /// Backend of the new database.
+    #[clap(long)]
+    backend: DbBackend,
+}
+
+impl CopyDatabaseCommand {
+    /// Copies all the columns of the hot database into a new database with
+    /// the requested backend.
+    ///
+    /// Values are copied as they are stored, including reference counts, so
+    /// the new database can replace the old one after setting `store.backend`
+    /// and `store.path` in the config.
+    pub(crate) fn run(
+        &self,
+        home_dir: &Path,
+        archive: bool,
+        store_config: &StoreConfig,
+    ) -> anyhow::Result<()> {
+        // Copying into an existing database would silently merge the two.
+        let destination_path = home_dir.join(&self.destination);
+        if destination_path.exists() && destination_path.read_dir()?.next().is_some() {
+            anyhow::bail!("destination {} is not empty", destination_path.display());
+        }
+
+        let source = NodeStorage::opener(home_dir, archive, store_config, None)
+            .open_in_mode(Mode::ReadOnly)?
+            .into_inner(Temperature::Hot);
+
+        let destination_config = StoreConfig {
+            path: Some(self.destination.clone()),
+            backend: self.backend,
+            ..store_config.clone()
+        };
+        let destination = NodeStorage::opener(home_dir, archive, &destination_config, None)
+            .open_in_mode(Mode::Create)?
+            .into_inner(Temperature::Hot);
+
+        for col in DBCol::iter() {
+            let num_keys = copy_column(source.as_ref(), destination.as_ref(), col)?;
+            eprintln!("Copied {num_keys} keys of column {col}");
+        }
+        destination.flush()?;
+        eprintln!("Database copied to {}", self.destination.display());
+        Ok(())
+    }
+}
+
+/// Copies the raw contents of a column, returning the number of keys copied.
+fn copy_column(
+    source: &dyn Database,
+    destination: &dyn Database,
+    col: DBCol,
+) -> anyhow::Result<usize> {
+    let mut num_keys = 0;
+    let mut batch_size = 0;
+    let mut transaction = DBTransaction::new();
+    for item in source.iter_raw_bytes(col) {
+        let (key, value) = item?;
+        batch_size += key.len() + value.len();
+        transaction.set(col, key.into_vec(), value.into_vec());
+        num_keys += 1;
+        if batch_size >= BATCH_SIZE_BYTES {
+            destination.write(std::mem::take(&mut transaction))?;
+            batch_size = 0;
+        }
+    }
+    destination.write(transaction)?;
+    Ok(num_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::copy_database::CopyDatabaseCommand;
+    use near_store::{DBCol, DbBackend, Mode, NodeStorage, StoreConfig};
+
+    /// Copies a RocksDB database into a redb one and checks that the copy
+    /// has the same data.
+    #[test]
+    fn test_copy_to_redb() {
+        let home_dir = tempfile::tempdir().unwrap();
+        let store_config = StoreConfig::test_config();
+        {
+            let node_storage =
+                NodeStorage::opener(home_dir.path(), false, &store_config, None).open().unwrap();
+            let mut store_update = node_storage.get_hot_store().store_update();
+            store_update.insert(DBCol::Block, vec![1], vec![42]);
+            store_update.increment_refcount_by(
+                DBCol::State,
+                &[2],
+                &[43],
+                std::num::NonZeroU32::new(2).unwrap(),
+            );
+            store_update.commit().unwrap();
+        }
+
+        let destination = home_dir.path().join("redb-data");
+        let cmd =
+            CopyDatabaseCommand { destination: destination.clone(), backend: DbBackend::Redb };
+        cmd.run(home_dir.path(), false, &store_config).unwrap();
+
+        let redb_config =
+            StoreConfig { path: Some(destination), backend: DbBackend::Redb, ..store_config };
+        let store = NodeStorage::opener(home_dir.path(), false, &redb_config, None)
+            .open_in_mode(Mode::ReadOnly)
+            .unwrap()
+            .get_hot_store();
+        assert_eq!(store.get(DBCol::Block, &[1]).unwrap().as_deref(), Some(&[42][..]));
+        assert_eq!(store.get(DBCol::State, &[2]).unwrap().as_deref(), Some(&[43][..]));
+        assert_eq!(store.get_db_version().unwrap(), Some(near_store::metadata::DB_VERSION));
+    }
+
+    /// Copying into a directory that already has data must fail rather than
+    /// merge the two databases.
+    #[test]
+    fn test_copy_to_non_empty_destination() {
+        let home_dir = tempfile::tempdir().unwrap();
+        let store_config = StoreConfig::test_config();
+        NodeStorage::opener(home_dir.path(), false, &store_config, None).open().unwrap();
+
+        let destination = home_dir.path().join("redb-data");
+        let cmd = CopyDatabaseCommand { destination, backend: DbBackend::Redb };
+        cmd.run(home_dir.path(), false, &store_config).unwrap();
+        assert!(cmd.run(home_dir.path(), false, &store_config).is_err());
+    }
+}
@@ -2,8 +2,10 @@ mod adjust_database;
 mod analyse_data_size_distribution;
 pub mod commands;
 mod compact;
+mod copy_database;
 mod make_snapshot;
 mod memtrie;
 mod run_migrations;
 mod state_perf;
+mod train_compression;
 mod utils;
@@ -8,13 +8,17 @@ use near_store::{DBCol, ShardUId, HEAD_KEY};
 use nearcore::NearConfig;
 use std::path::Path;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Command to load an in-memory trie for research purposes.
 #[derive(clap::Parser)]
 pub struct LoadMemTrieCommand {
     #[clap(long)]
     shard_id: ShardId,
+    /// Number of threads used to load the trie. Defaults to the number of
+    /// CPUs.
+    #[clap(long)]
+    num_threads: Option<usize>,
 }
 
 impl LoadMemTrieCommand {
@@ -37,10 +41,21 @@ impl LoadMemTrieCommand {
         let state_root = flat_head_state_root(&store, &shard_uid);
         let flat_head_height = flat_head(&store, &shard_uid).height;
 
-        let _trie = load_trie_from_flat_state(&store, shard_uid, state_root, flat_head_height)?;
+        let mut pool_builder = rayon::ThreadPoolBuilder::new();
+        if let Some(num_threads) = self.num_threads {
+            pool_builder = pool_builder.num_threads(num_threads);
+        }
+        let pool = pool_builder.build()?;
+        let load_start = Instant::now();
+        let _trie = pool.install(|| {
+            load_trie_from_flat_state(&store, shard_uid, state_root, flat_head_height)
+        })?;
         println!(
-            "Loaded trie for shard {} at height {}, press Ctrl-C to exit.",
-            self.shard_id, flat_head_height
+            "Loaded trie for shard {} at height {} in {:?} using {} threads, press Ctrl-C to exit.",
+            self.shard_id,
+            flat_head_height,
+            load_start.elapsed(),
+            pool.current_num_threads()
         );
         std::thread::sleep(Duration::from_secs(10000000000));
         Ok(())
@@ -0,0 +1,199 @@
+use crate::analyse_data_size_distribution::read_all_pairs;
+use crate::utils::{open_rocksdb, resolve_column};
+use clap::Parser;
+use near_store::db::{Database, RocksDB, StatsValue, DEFAULT_COMPRESSION_DICT_SIZE};
+use near_store::{DBCol, NodeStorage};
+use rand::Rng;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Compression level RocksDB uses for zstd by default.
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Parser)]
+pub(crate) struct TrainCompressionCommand {
+    /// Column to train the compression dictionaries for.
+    #[arg(short, long)]
+    column: String,
+
+    /// Number of values sampled from the column.
+    #[arg(long, default_value_t = 100_000)]
+    num_samples: usize,
+
+    /// Dictionary sizes to compare, in bytes.  0 means no dictionary.
+    #[arg(long, value_delimiter = ',', default_values_t = [0, 16384, 32768, 65536, 131072])]
+    dict_sizes: Vec<usize>,
+
+    /// Size of the compressed blocks.  Should match `store.block_size`.
+    #[arg(long, default_value_t = 16384)]
+    block_size: usize,
+
+    /// Store the best dictionary size in the database metadata, so that it
+    /// is used for the column from now on.
+    #[arg(long)]
+    apply: bool,
+
+    /// Compact the column after applying the dictionary size, so that all
+    /// of it is compressed with it, and report the size after compaction.
+    #[arg(long, requires = "apply")]
+    compact: bool,
+}
+
+impl TrainCompressionCommand {
+    /// Trains zstd dictionaries of every size on values sampled from the
+    /// column and compares how well they compress another part of the sample.
+    ///
+    /// RocksDB trains the dictionaries itself, separately for every SST file,
+    /// from the data of the file, so a trained dictionary can’t be given to
+    /// it.  What can be tuned is the dictionary size, which is what gets
+    /// stored in the metadata with `--apply`.
+    pub(crate) fn run(&self, home: &PathBuf) -> anyhow::Result<()> {
+        let col = resolve_column(&self.column)?;
+        let db = Arc::new(open_rocksdb(home, near_store::Mode::ReadOnly)?);
+        let store = NodeStorage::new(db.clone()).get_hot_store();
+        print_column_size(&db, col, "before");
+
+        let current_dict_size =
+            store.get_compression_dict_size(col)?.unwrap_or(DEFAULT_COMPRESSION_DICT_SIZE);
+        let samples = sample_values(&db, col, self.num_samples)?;
+        drop((store, db));
+        anyhow::ensure!(samples.len() >= 10, "column {col} has too few values to train on");
+
+        // Dictionaries trained and evaluated on the same values would look
+        // better than they are, so keep a fifth of the values for evaluation.
+        let (eval_samples, train_samples) = samples.split_at(samples.len() / 5);
+        let blocks = make_blocks(eval_samples, self.block_size);
+        let raw_size = blocks.iter().map(Vec::len).sum::<usize>();
+        println!(
+            "Sampled {} values of {col}, evaluating on {raw_size} bytes in {} blocks",
+            samples.len(),
+            blocks.len()
+        );
+
+        let mut best = None;
+        for &dict_size in &self.dict_sizes {
+            let dict = if dict_size == 0 {
+                vec![]
+            } else {
+                match zstd::dict::from_samples(train_samples, dict_size) {
+                    Ok(dict) => dict,
+                    Err(err) => {
+                        println!("Dictionary size {dict_size}: training failed: {err}");
+                        continue;
+                    }
+                }
+            };
+            let compressed_size = compressed_size(&blocks, &dict)?;
+            println!(
+                "Dictionary size {dict_size}: {compressed_size} bytes, ratio {:.3}{}",
+                raw_size as f64 / compressed_size as f64,
+                if dict_size == current_dict_size { " (current)" } else { "" }
+            );
+            if best.map_or(true, |(_, best_size)| compressed_size < best_size) {
+                best = Some((dict_size, compressed_size));
+            }
+        }
+        let (best_dict_size, _) = best.ok_or(anyhow::anyhow!("no dictionary could be trained"))?;
+        println!("Best dictionary size for {col}: {best_dict_size}");
+
+        if !self.apply {
+            return Ok(());
+        }
+        let db = open_rocksdb(home, near_store::Mode::ReadWrite)?;
+        NodeStorage::new(Arc::new(db))
+            .get_hot_store()
+            .set_compression_dict_size(col, best_dict_size)?;
+        println!("Stored dictionary size {best_dict_size} for {col} in the database metadata");
+
+        if self.compact {
+            // Reopen the database for the new size to be applied.
+            let db = open_rocksdb(home, near_store::Mode::ReadWrite)?;
+            db.compact_column(col)?;
+            print_column_size(&db, col, "after");
+        }
+        Ok(())
+    }
+}
+
+/// Prints the number of entries and the on-disk size of the column.
+fn print_column_size(db: &RocksDB, col: DBCol, when: &str) {
+    println!("Column {col} {when}:");
+    read_all_pairs(db, &vec![col]).print_column_family_data();
+    let on_disk_size = db.get_store_statistics().and_then(|stats| {
+        let (_, values) =
+            stats.data.into_iter().find(|(name, _)| name == "rocksdb.live-sst-files-size")?;
+        values.into_iter().find_map(|value| match value {
+            StatsValue::ColumnValue(value_col, size) if value_col == col => Some(size),
+            _ => None,
+        })
+    });
+    match on_disk_size {
+        Some(size) => println!("Column {col} takes {size} bytes on disk"),
+        None => println!("Column {col} on-disk size is unknown"),
+    }
+}
+
+/// Samples values of the column uniformly using reservoir sampling.
+fn sample_values(db: &RocksDB, col: DBCol, num_samples: usize) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut rng = rand::thread_rng();
+    let mut samples = Vec::with_capacity(num_samples);
+    for (index, item) in db.iter_raw_bytes(col).enumerate() {
+        let (_, value) = item?;
+        if samples.len() < num_samples {
+            samples.push(value.into_vec());
+        } else {
+            let pos = rng.gen_range(0..=index);
+            if pos < num_samples {
+                samples[pos] = value.into_vec();
+            }
+        }
+    }
+    Ok(samples)
+}
+
+/// Concatenates the values into blocks of about `block_size` bytes, which is
+/// the unit RocksDB compresses.
+fn make_blocks(values: &[Vec<u8>], block_size: usize) -> Vec<Vec<u8>> {
+    let mut blocks = vec![];
+    let mut block = Vec::with_capacity(block_size);
+    for value in values {
+        block.extend_from_slice(value);
+        if block.len() >= block_size {
+            blocks.push(std::mem::replace(&mut block, Vec::with_capacity(block_size)));
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Returns the total size of the blocks compressed with the dictionary.
+fn compressed_size(blocks: &[Vec<u8>], dict: &[u8]) -> anyhow::Result<usize> {
+    let mut compressor = zstd::bulk::Compressor::with_dictionary(ZSTD_LEVEL, dict)?;
+    let mut size = 0;
+    for block in blocks {
+        size += compressor.compress(block)?.len();
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::train_compression::{compressed_size, make_blocks};
+
+    #[test]
+    fn test_dictionary_helps_with_repetitive_values() {
+        let values: Vec<Vec<u8>> = (0..2000u32)
+            .map(|i| {
+                let mut value = b"some repetitive borsh payload with an account id ".to_vec();
+                value.extend_from_slice(&i.to_le_bytes());
+                value
+            })
+            .collect();
+        let blocks = make_blocks(&values[..400], 256);
+        assert!(blocks.iter().all(|block| block.len() < 256 + 60));
+        let dict = zstd::dict::from_samples(&values[400..], 4096).unwrap();
+        assert!(compressed_size(&blocks, &dict).unwrap() < compressed_size(&blocks, &[]).unwrap());
+    }
+}
@@ -6,6 +6,7 @@ import { ConnectionStorageView } from './ConnectionStorageView';
 import { Tier1View } from './Tier1View';
 import { RoutingTableView } from './RoutingTableView';
 import { SnapshotHostsView } from './SnapshotHostsView';
+import { PeerReputationView } from './PeerReputationView';
 
 type NetworkInfoViewProps = {
     addr: string;
@@ -33,6 +34,9 @@ export const NetworkInfoView = ({ addr }: NetworkInfoViewProps) => {
                 <NavLink to="snapshot_hosts" className={navLinkClassName}>
                     Snapshot Hosts
                 </NavLink>
+                <NavLink to="peer_reputation" className={navLinkClassName}>
+                    Peer Reputation
+                </NavLink>
             </div>
             <Routes>
                 <Route path="" element={<Navigate to="current" />} />
@@ -42,6 +46,7 @@ export const NetworkInfoView = ({ addr }: NetworkInfoViewProps) => {
                 <Route path="tier1" element={<Tier1View addr={addr} />} />
                 <Route path="routing_table" element={<RoutingTableView addr={addr} />} />
                 <Route path="snapshot_hosts" element={<SnapshotHostsView addr={addr} />} />
+                <Route path="peer_reputation" element={<PeerReputationView addr={addr} />} />
             </Routes>
         </div>
     );
@@ -0,0 +1,48 @@
+import { useQuery } from 'react-query';
+import { fetchPeerReputation } from './api';
+import './PeerReputationView.scss';
+
+type PeerReputationViewProps = {
+    addr: string;
+};
+
+export const PeerReputationView = ({ addr }: PeerReputationViewProps) => {
+    const {
+        data: peerReputation,
+        error,
+        isLoading,
+    } = useQuery(['peerReputation', addr], () => fetchPeerReputation(addr));
+
+    if (isLoading) {
+        return <div>Loading...</div>;
+    } else if (error) {
+        return <div className="error">{(error as Error).stack}</div>;
+    }
+
+    const peer_reputation = peerReputation!.status_response.PeerReputation;
+
+    return (
+        <div className="peer-reputation-view">
+            <table>
+                <thead>
+                    <th>Peer ID</th>
+                    <th>Score</th>
+                    <th>Avoided</th>
+                </thead>
+                <tbody>
+                    {peer_reputation.peers.map((peer) => {
+                        return (
+                            <tr key={peer.peer_id}>
+                                <td>{peer.peer_id}</td>
+                                <td>{peer.score.toFixed(2)}</td>
+                                <td className={peer.low_reputation ? 'low-reputation' : ''}>
+                                    {peer.low_reputation ? 'Yes' : 'No'}
+                                </td>
+                            </tr>
+                        );
+                    })}
+                </tbody>
+            </table>
+        </div>
+    );
+};
@@ -347,6 +347,22 @@ export interface SnapshotHostsResponse {
     };
 }
 
+export interface PeerReputationInfoView {
+    peer_id: string,
+    score: number,
+    low_reputation: boolean,
+}
+
+export interface PeerReputationView {
+    peers: PeerReputationInfoView[],
+}
+
+export interface PeerReputationResponse {
+    status_response: {
+        PeerReputation: PeerReputationView;
+    };
+}
+
 export type DroppedReason = 'HeightProcessed' | 'TooManyProcessingBlocks';
 
 export type BlockProcessingStatus =
@@ -465,6 +481,13 @@ export async function fetchSnapshotHosts(
     return await response.json();
 }
 
+export async function fetchPeerReputation(
+    addr: string
+): Promise<PeerReputationResponse> {
+    const response = await fetch(`http://${addr}/debug/api/peer_reputation`);
+    return await response.json();
+}
+
 export async function fetchChainProcessingStatus(
     addr: string
 ): Promise<ChainProcessingStatusResponse> {
@@ -242,6 +242,7 @@ fn load_snapshot(load_cmd: LoadCmd) {
         DoomslugThresholdMode::TwoThirds,
         ChainConfig {
             save_trie_changes: config.client_config.save_trie_changes,
+            save_account_activity: config.client_config.save_account_activity,
             background_migration_threads: 1,
             state_split_config: StateSplitConfig::default(),
         },
@@ -33,6 +33,7 @@ use near_primitives::trie_key::col::NON_DELAYED_RECEIPT_COLUMNS;
 use near_primitives::trie_key::TrieKey;
 use near_primitives::types::{chunk_extra::ChunkExtra, BlockHeight, ShardId, StateRoot};
 use near_primitives_core::types::Gas;
+use near_store::flat::FlatStateIterator;
 use near_store::flat::FlatStorageChunkView;
 use near_store::flat::FlatStorageManager;
 use near_store::test_utils::create_test_store;
@@ -926,7 +927,7 @@ enum LoadTrieMode {
     LastFinalFromHeight(BlockHeight),
 }
 
-fn load_trie(
+pub(crate) fn load_trie(
     store: Store,
     home_dir: &Path,
     near_config: &NearConfig,
@@ -1089,9 +1090,8 @@ fn print_state_stats_for_shard_uid(
     block_hash: CryptoHash,
     shard_uid: ShardUId,
 ) {
-    flat_storage_manager.create_flat_storage_for_shard(shard_uid).unwrap();
     let trie_storage = TrieDBStorage::new(store.clone(), shard_uid);
-    let chunk_view = flat_storage_manager.chunk_view(shard_uid, block_hash).unwrap();
+    let chunk_view = flat_storage_chunk_view(flat_storage_manager, block_hash, shard_uid);
 
     let mut state_stats = StateStats::default();
 
@@ -1118,6 +1118,30 @@ fn print_state_stats_for_shard_uid(
     tracing::info!(target: "state_viewer", "{state_stats:#?}");
 }
 
+/// Creates the flat storage for a single shard and returns its view at the
+/// given block.
+///
+/// Note that iterating the view reads `DBCol::FlatState` directly, so the
+/// entries reflect the state at the flat storage head, which may lag behind
+/// `block_hash`, see `FlatStorageChunkView::get_head_hash`.
+pub(crate) fn flat_storage_chunk_view(
+    flat_storage_manager: &FlatStorageManager,
+    block_hash: CryptoHash,
+    shard_uid: ShardUId,
+) -> FlatStorageChunkView {
+    flat_storage_manager.create_flat_storage_for_shard(shard_uid).unwrap();
+    flat_storage_manager.chunk_view(shard_uid, block_hash).unwrap()
+}
+
+/// Gets the flat state iterator over the entries of a single trie column,
+/// identified by its type byte, see `NON_DELAYED_RECEIPT_COLUMNS`.
+pub(crate) fn iter_flat_state_column(
+    chunk_view: &FlatStorageChunkView,
+    type_byte: u8,
+) -> FlatStateIterator<'_> {
+    chunk_view.iter_flat_state_entries(Some(&[type_byte]), Some(&[type_byte + 1]))
+}
+
 /// Gets the flat state iterator from the chunk view, rearranges it to be sorted
 /// by the account id, rather than type, account id and finally groups the
 /// records by account id while collecting aggregate statistics.
@@ -1134,9 +1158,7 @@ fn get_state_stats_group_by<'a>(
     // the account id.
     let type_iters = NON_DELAYED_RECEIPT_COLUMNS
         .iter()
-        .map(|(type_byte, _)| {
-            chunk_view.iter_flat_state_entries(Some(&[*type_byte]), Some(&[*type_byte + 1]))
-        })
+        .map(|(type_byte, _)| iter_flat_state_column(chunk_view, *type_byte))
         .into_iter();
 
     // Filter out any errors.
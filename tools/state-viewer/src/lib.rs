@@ -13,5 +13,6 @@ mod state_dump;
 mod state_parts;
 mod trie_iteration_benchmark;
 mod tx_dump;
+mod value_dedup;
 
 pub use cli::StateViewerSubCommand;
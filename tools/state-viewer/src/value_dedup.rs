@@ -0,0 +1,210 @@
+//! State viewer command reporting how much state could be saved by storing
+//! identical values once, e.g. contract code deployed to many accounts.
+
+use crate::commands::{flat_storage_chunk_view, iter_flat_state_column, load_trie};
+use near_epoch_manager::EpochManagerAdapter;
+use near_primitives::hash::CryptoHash;
+use near_primitives::state::ValueRef;
+use near_primitives::trie_key::col::NON_DELAYED_RECEIPT_COLUMNS;
+use near_primitives::trie_key::trie_key_parsers::parse_account_id_from_raw_key;
+use near_primitives::types::AccountId;
+use near_store::Store;
+use nearcore::NearConfig;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(clap::Parser)]
+pub struct ValueDedupCmd {
+    /// Trie columns to analyse, e.g. `ContractCode,ContractData`.  All
+    /// columns but the delayed receipts by default.
+    ///
+    /// Memory use grows with the number of distinct values, so analysing
+    /// `ContractData` of a large shard takes a lot of it.
+    #[clap(long, use_value_delimiter = true)]
+    columns: Option<Vec<String>>,
+    /// Number of the most repeated values to list for every column.
+    #[clap(long, default_value_t = 20)]
+    top_k: usize,
+}
+
+impl ValueDedupCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        let columns = match &self.columns {
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    NON_DELAYED_RECEIPT_COLUMNS
+                        .iter()
+                        .find(|(_, col_name)| *col_name == name.as_str())
+                        .copied()
+                        .unwrap_or_else(|| panic!("Unknown trie column {name}"))
+                })
+                .collect(),
+            None => NON_DELAYED_RECEIPT_COLUMNS.to_vec(),
+        };
+        let report = value_dedup_report(home_dir, store, near_config, &columns, self.top_k);
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+}
+
+/// Deduplication report of the state of all shards.
+#[derive(serde::Serialize)]
+struct ValueDedupReport {
+    /// Block at which each shard's state was analysed.
+    shards: Vec<ShardDedupHead>,
+    columns: BTreeMap<&'static str, ColumnDedupReport>,
+}
+
+/// The values are read from flat storage, so the state of every shard is the
+/// one at its flat storage head, which may lag behind the chain head and
+/// differ between shards.
+#[derive(serde::Serialize)]
+struct ShardDedupHead {
+    shard_uid: String,
+    flat_head_hash: CryptoHash,
+}
+
+/// Deduplication statistics of the values of a single trie column.
+#[derive(serde::Serialize, Debug, PartialEq)]
+struct ColumnDedupReport {
+    /// Number of values in the column and their total size.
+    num_values: u64,
+    total_bytes: u64,
+    /// Number of distinct values and their total size, i.e. what would be
+    /// stored if every value was stored once.
+    num_unique_values: u64,
+    unique_bytes: u64,
+    /// `total_bytes - unique_bytes`.
+    savings_bytes: u64,
+    /// The most repeated values, by the number of repetitions.
+    top_values: Vec<RepeatedValue>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+struct RepeatedValue {
+    hash: CryptoHash,
+    length: u32,
+    count: u64,
+    /// One of the accounts holding the value.
+    example_account: Option<AccountId>,
+}
+
+/// Counts occurrences of values by their hash.
+#[derive(Default)]
+struct ValueCounter {
+    values: HashMap<CryptoHash, RepeatedValue>,
+}
+
+impl ValueCounter {
+    fn push(&mut self, value_ref: ValueRef, account_id: impl FnOnce() -> Option<AccountId>) {
+        self.values
+            .entry(value_ref.hash)
+            .or_insert_with(|| RepeatedValue {
+                hash: value_ref.hash,
+                length: value_ref.length,
+                count: 0,
+                example_account: account_id(),
+            })
+            .count += 1;
+    }
+
+    fn report(&self, top_k: usize) -> ColumnDedupReport {
+        let mut report = ColumnDedupReport {
+            num_values: 0,
+            total_bytes: 0,
+            num_unique_values: self.values.len() as u64,
+            unique_bytes: 0,
+            savings_bytes: 0,
+            top_values: vec![],
+        };
+        for value in self.values.values() {
+            report.num_values += value.count;
+            report.total_bytes += value.count * value.length as u64;
+            report.unique_bytes += value.length as u64;
+        }
+        report.savings_bytes = report.total_bytes - report.unique_bytes;
+
+        let mut repeated: Vec<_> = self.values.values().filter(|value| value.count > 1).collect();
+        // Ties are broken by hash for the output to be deterministic.
+        repeated.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.hash.cmp(&b.hash)));
+        report.top_values = repeated.into_iter().take(top_k).cloned().collect();
+        report
+    }
+}
+
+/// Counts the values of the given trie columns across all shards, using
+/// flat storage so that large values don’t have to be read to be hashed.
+fn value_dedup_report(
+    home_dir: &Path,
+    store: Store,
+    near_config: NearConfig,
+    columns: &[(u8, &'static str)],
+    top_k: usize,
+) -> ValueDedupReport {
+    let (epoch_manager, runtime, _, block_header) =
+        load_trie(store.clone(), home_dir, &near_config);
+    let block_hash = *block_header.hash();
+    let shard_layout = epoch_manager.get_shard_layout_from_prev_block(&block_hash).unwrap();
+    let flat_storage_manager = runtime.get_flat_storage_manager();
+
+    let mut shards = vec![];
+    let mut counters: BTreeMap<&'static str, ValueCounter> = BTreeMap::new();
+    for shard_uid in shard_layout.get_shard_uids() {
+        eprintln!("Starting shard {shard_uid}");
+        let chunk_view = flat_storage_chunk_view(&flat_storage_manager, block_hash, shard_uid);
+        shards.push(ShardDedupHead {
+            shard_uid: shard_uid.to_string(),
+            flat_head_hash: chunk_view.get_head_hash(),
+        });
+        for &(type_byte, name) in columns {
+            let counter = counters.entry(name).or_default();
+            for item in iter_flat_state_column(&chunk_view, type_byte) {
+                let (key, value) = item.unwrap();
+                counter.push(value.to_value_ref(), || {
+                    parse_account_id_from_raw_key(&key).ok().flatten()
+                });
+            }
+        }
+    }
+
+    ValueDedupReport {
+        shards,
+        columns: counters.iter().map(|(name, counter)| (*name, counter.report(top_k))).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RepeatedValue, ValueCounter};
+    use near_primitives::state::ValueRef;
+    use near_primitives::types::AccountId;
+
+    #[test]
+    fn test_value_counter() {
+        let code = vec![1; 1000];
+        let other_code = vec![2; 500];
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let mut counter = ValueCounter::default();
+        for account in ["alice.near", "bob.near", "carol.near"] {
+            counter.push(ValueRef::new(&code), || account.parse().ok());
+        }
+        counter.push(ValueRef::new(&other_code), || "dave.near".parse().ok());
+
+        let report = counter.report(10);
+        assert_eq!(report.num_values, 4);
+        assert_eq!(report.total_bytes, 3500);
+        assert_eq!(report.num_unique_values, 2);
+        assert_eq!(report.unique_bytes, 1500);
+        assert_eq!(report.savings_bytes, 2000);
+        assert_eq!(
+            report.top_values,
+            vec![RepeatedValue {
+                hash: ValueRef::new(&code).hash,
+                length: 1000,
+                count: 3,
+                example_account: Some(alice),
+            }]
+        );
+        assert_eq!(counter.report(0).top_values, vec![]);
+    }
+}